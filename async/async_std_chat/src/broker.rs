@@ -0,0 +1,323 @@
+use std::collections::{BTreeSet, HashMap, VecDeque};
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use async_std::net::TcpStream;
+use futures::channel::mpsc;
+use futures::sink::SinkExt;
+use futures::{select, FutureExt, StreamExt};
+
+use crate::protocol::{valid_name, Command, ParseError, Reply, Target};
+use crate::{connection_writer_loop, spawn_and_log_error, Receiver, Sender};
+
+/// Messages a room keeps to replay to whoever joins it
+pub const HISTORY: usize = 20;
+
+/// Peers are told apart by address until they have a name - and after, since they
+/// can change it
+pub(crate) type PeerId = SocketAddr;
+
+#[derive(Debug)]
+pub(crate) enum Event {
+    NewPeer {
+        id: PeerId,
+        stream: Arc<TcpStream>,
+    },
+    Command {
+        from: PeerId,
+        command: Result<Command, ParseError>,
+    },
+}
+
+struct Peer {
+    name: Option<String>,
+    sender: Sender<String>,
+    /// in the order they were joined
+    rooms: Vec<String>,
+    /// where lines that aren't commands go
+    current: Option<String>,
+}
+
+#[derive(Default)]
+struct Room {
+    members: BTreeSet<PeerId>,
+    history: VecDeque<String>,
+}
+
+/// Everything the broker knows. Dropping a peer drops its sender, which ends its
+/// writer once it has written what's queued.
+#[derive(Default)]
+struct Broker {
+    peers: HashMap<PeerId, Peer>,
+    names: HashMap<String, PeerId>,
+    rooms: HashMap<String, Room>,
+}
+
+pub(crate) async fn broker_loop(events: Receiver<Event>) {
+    let (disconnect_sender, mut disconnect_receiver) = // 1
+        mpsc::unbounded::<(PeerId, Receiver<String>)>();
+    let mut broker = Broker::default();
+    let mut events = events.fuse();
+
+    loop {
+        let event = select! {
+            event = events.next().fuse() => match event {
+                None => break, // 2
+                Some(event) => event,
+            },
+            disconnect = disconnect_receiver.next().fuse() => {
+                // a writer that failed takes its peer with it; one that finished
+                // after a /quit has nothing left to take
+                if let Some((id, _pending_messages)) = disconnect {
+                    broker.leave(id, Some("connection lost"));
+                }
+                continue;
+            },
+        };
+        match event {
+            Event::NewPeer { id, stream } => {
+                let (client_sender, mut client_receiver) = mpsc::unbounded();
+                broker.peers.insert(
+                    id,
+                    Peer {
+                        name: None,
+                        sender: client_sender,
+                        rooms: vec![],
+                        current: None,
+                    },
+                );
+                let mut disconnect_sender = disconnect_sender.clone();
+                spawn_and_log_error(async move {
+                    let res = connection_writer_loop(&mut client_receiver, stream).await;
+                    // gone already if the server is shutting down
+                    let _ = disconnect_sender.send((id, client_receiver)).await;
+                    res
+                });
+            }
+            Event::Command { from, command } => broker.command(from, command),
+        }
+    }
+    drop(broker); // 3
+    drop(disconnect_sender); // 6
+    while let Some((_id, _pending_messages)) = disconnect_receiver.next().await {}
+}
+
+impl Broker {
+    fn command(&mut self, from: PeerId, command: Result<Command, ParseError>) {
+        let Some(peer) = self.peers.get(&from) else {
+            return;
+        };
+        let command = match command {
+            Ok(command) => command,
+            Err(ParseError(error)) => return self.tell(from, Reply::Error(error)),
+        };
+        let Some(name) = peer.name.clone() else {
+            // nothing but a name until there is one
+            return match command {
+                Command::Nick(name) => self.nick(from, name),
+                Command::Say(line) => match valid_name(line.trim()) {
+                    Ok(name) => self.nick(from, name.to_string()),
+                    Err(ParseError(error)) => self.tell(from, Reply::Error(error)),
+                },
+                Command::Quit(reason) => self.quit(from, reason),
+                _ => self.tell(
+                    from,
+                    Reply::Error("pick a name first: /nick <name>".to_string()),
+                ),
+            };
+        };
+        match command {
+            Command::Nick(new) => self.nick(from, new),
+            Command::Join(room) => self.join(from, &name, room),
+            Command::Part(room) => self.part(from, &name, &room),
+            Command::Msg {
+                to: Target::Room(room),
+                text,
+            } => self.say(from, &name, room, text),
+            Command::Msg {
+                to: Target::Peer(to),
+                text,
+            } => self.whisper(from, &name, &to, text),
+            Command::Say(text) if text.trim().is_empty() => {}
+            Command::Say(text) => match peer.current.clone() {
+                Some(room) => self.say(from, &name, room, text),
+                None => self.tell(
+                    from,
+                    Reply::Error("join a room first: /join #<room>".to_string()),
+                ),
+            },
+            Command::Who(room) => self.who(from, room),
+            Command::Quit(reason) => self.quit(from, reason),
+        }
+    }
+
+    fn nick(&mut self, from: PeerId, new: String) {
+        match self.names.get(&new) {
+            Some(&id) if id == from => return,
+            Some(_) => return self.tell(from, Reply::Error(format!("{new} is taken"))),
+            None => {}
+        }
+        let Some(peer) = self.peers.get_mut(&from) else {
+            return;
+        };
+        let old = peer.name.replace(new.clone());
+        self.names.insert(new.clone(), from);
+        let Some(old) = old else {
+            return self.tell(from, Reply::Notice(format!("welcome, {new}")));
+        };
+        self.names.remove(&old);
+        self.tell(from, Reply::Notice(format!("you are now {new}")));
+        // once each, however many rooms they share
+        let neighbours: BTreeSet<PeerId> = self.peers[&from]
+            .rooms
+            .iter()
+            .flat_map(|room| &self.rooms[room].members)
+            .copied()
+            .filter(|&id| id != from)
+            .collect();
+        for id in neighbours {
+            self.tell(id, Reply::Notice(format!("{old} is now {new}")));
+        }
+    }
+
+    fn join(&mut self, from: PeerId, name: &str, room: String) {
+        let Some(peer) = self.peers.get_mut(&from) else {
+            return;
+        };
+        if peer.rooms.contains(&room) {
+            peer.current = Some(room.clone());
+            return self.tell(from, Reply::Notice(format!("talking in {room}")));
+        }
+        peer.rooms.push(room.clone());
+        peer.current = Some(room.clone());
+        let entry = self.rooms.entry(room.clone()).or_default();
+        for line in &entry.history {
+            let _ = peer.sender.unbounded_send(format!("{line}\n"));
+        }
+        entry.members.insert(from);
+        self.broadcast(&room, Reply::Notice(format!("{name} joined {room}")), None);
+    }
+
+    fn part(&mut self, from: PeerId, name: &str, room: &str) {
+        let Some(peer) = self.peers.get_mut(&from) else {
+            return;
+        };
+        let Some(index) = peer.rooms.iter().position(|joined| joined == room) else {
+            return self.tell(from, Reply::Error(format!("you're not in {room}")));
+        };
+        peer.rooms.remove(index);
+        // back to the room joined most recently of those left
+        if peer.current.as_deref() == Some(room) {
+            peer.current = peer.rooms.last().cloned();
+        }
+        self.tell(from, Reply::Notice(format!("you left {room}")));
+        self.remove_member(from, name, room, None);
+    }
+
+    fn say(&mut self, from: PeerId, name: &str, room: String, text: String) {
+        let Some(members) = self.rooms.get_mut(&room).map(|room| &room.members) else {
+            return self.tell(from, Reply::Error(format!("join {room} first")));
+        };
+        if !members.contains(&from) {
+            return self.tell(from, Reply::Error(format!("join {room} first")));
+        }
+        let message = Reply::Message {
+            room: Some(room.clone()),
+            from: name.to_string(),
+            text,
+        };
+        let history = &mut self.rooms.get_mut(&room).expect("checked above").history;
+        history.push_back(message.to_string());
+        while history.len() > HISTORY {
+            history.pop_front();
+        }
+        self.broadcast(&room, message, Some(from));
+    }
+
+    fn whisper(&mut self, from: PeerId, name: &str, to: &str, text: String) {
+        match self.names.get(to) {
+            Some(&id) => self.tell(
+                id,
+                Reply::Message {
+                    room: None,
+                    from: name.to_string(),
+                    text,
+                },
+            ),
+            None => self.tell(from, Reply::Error(format!("no one called {to}"))),
+        }
+    }
+
+    fn who(&mut self, from: PeerId, room: Option<String>) {
+        let ids: Vec<PeerId> = match &room {
+            Some(name) => match self.rooms.get(name) {
+                Some(room) if !room.members.is_empty() => room.members.iter().copied().collect(),
+                _ => return self.tell(from, Reply::Error(format!("no one is in {name}"))),
+            },
+            None => self.names.values().copied().collect(),
+        };
+        let mut names: Vec<String> = ids
+            .iter()
+            .filter_map(|id| self.peers.get(id)?.name.clone())
+            .collect();
+        names.sort();
+        self.tell(from, Reply::Names { room, names });
+    }
+
+    fn quit(&mut self, from: PeerId, reason: Option<String>) {
+        self.tell(from, Reply::Notice("bye".to_string()));
+        self.leave(from, reason.as_deref());
+    }
+
+    /// Forgets the peer, telling its rooms why it went
+    fn leave(&mut self, id: PeerId, reason: Option<&str>) {
+        let Some(peer) = self.peers.remove(&id) else {
+            return;
+        };
+        let Some(name) = peer.name else {
+            return;
+        };
+        self.names.remove(&name);
+        for room in &peer.rooms {
+            self.remove_member(id, &name, room, reason);
+        }
+    }
+
+    fn remove_member(&mut self, id: PeerId, name: &str, room: &str, reason: Option<&str>) {
+        let Some(members) = self.rooms.get_mut(room).map(|room| &mut room.members) else {
+            return;
+        };
+        members.remove(&id);
+        if members.is_empty() {
+            // the history stays for whoever joins next; a room with none of
+            // that to keep has nothing left to remember
+            if self.rooms[room].history.is_empty() {
+                self.rooms.remove(room);
+            }
+            return;
+        }
+        let notice = match reason {
+            Some(reason) => format!("{name} left {room} ({reason})"),
+            None => format!("{name} left {room}"),
+        };
+        self.broadcast(room, Reply::Notice(notice), None);
+    }
+
+    fn tell(&self, id: PeerId, reply: Reply) {
+        if let Some(peer) = self.peers.get(&id) {
+            // a peer whose writer has gone is about to be disconnected anyway
+            let _ = peer.sender.unbounded_send(format!("{reply}\n"));
+        }
+    }
+
+    fn broadcast(&self, room: &str, reply: Reply, except: Option<PeerId>) {
+        let line = format!("{reply}\n");
+        for id in &self.rooms[room].members {
+            if Some(*id) != except {
+                if let Some(peer) = self.peers.get(id) {
+                    let _ = peer.sender.unbounded_send(line.clone());
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,107 @@
+//! The async-std book's chat server, grown a [protocol] of commands and rooms.
+//! One task accepts connections, one reads each client's lines and one writes each
+//! client's replies; the [broker] task in the middle owns every name and room, so
+//! none of them need a lock.
+
+use async_std::{
+    io::BufReader,
+    net::{TcpListener, TcpStream, ToSocketAddrs},
+    prelude::*,
+    task,
+};
+use futures::channel::mpsc; // 1
+use futures::sink::SinkExt;
+use std::sync::Arc;
+
+pub mod broker;
+pub mod protocol;
+
+use broker::{broker_loop, Event};
+use protocol::Command;
+
+pub type Result<T> = std::result::Result<T, Box<dyn std::error::Error + Send + Sync>>;
+
+type Sender<T> = mpsc::UnboundedSender<T>; // 2
+type Receiver<T> = mpsc::UnboundedReceiver<T>;
+
+pub async fn accept_loop(addr: impl ToSocketAddrs) -> Result<()> {
+    serve(TcpListener::bind(addr).await?).await
+}
+
+/// Chats with whoever connects to `listener`, until it fails
+pub async fn serve(listener: TcpListener) -> Result<()> {
+    let (broker_sender, broker_receiver) = mpsc::unbounded(); // 1
+    let broker_handle = task::spawn(broker_loop(broker_receiver));
+    let mut incoming = listener.incoming();
+    while let Some(stream) = incoming.next().await {
+        let stream = stream?;
+        println!("Accepting from: {}", stream.peer_addr()?);
+        spawn_and_log_error(connection_loop(broker_sender.clone(), stream));
+    }
+    drop(broker_sender); // 1
+    broker_handle.await; // 5
+    Ok(())
+}
+
+async fn connection_loop(mut broker: Sender<Event>, stream: TcpStream) -> Result<()> {
+    let id = stream.peer_addr()?;
+    let stream = Arc::new(stream);
+    let reader = BufReader::new(&*stream); // 2
+    let mut lines = reader.lines();
+
+    broker
+        .send(Event::NewPeer {
+            id,
+            stream: Arc::clone(&stream),
+        })
+        .await?;
+
+    let read = async {
+        while let Some(line) = lines.next().await {
+            let command = Command::parse(&line?);
+            let quit = matches!(command, Ok(Command::Quit(_)));
+            broker.send(Event::Command { from: id, command }).await?;
+            if quit {
+                return Ok(true);
+            }
+        }
+        Ok::<_, Box<dyn std::error::Error + Send + Sync>>(false)
+    };
+    let read = read.await;
+    if !matches!(read, Ok(true)) {
+        // hung up, or sent something unreadable: as good as a /quit
+        let quit = Ok(Command::Quit(None));
+        broker
+            .send(Event::Command {
+                from: id,
+                command: quit,
+            })
+            .await?;
+    }
+    read.map(drop)
+}
+
+/// Writes replies until the broker forgets the peer, then hangs up
+pub(crate) async fn connection_writer_loop(
+    messages: &mut Receiver<String>,
+    stream: Arc<TcpStream>,
+) -> Result<()> {
+    let mut stream = &*stream;
+    while let Some(msg) = messages.next().await {
+        stream.write_all(msg.as_bytes()).await?;
+    }
+    // the reader may still be waiting on the client - unless the client went first
+    let _ = stream.shutdown(std::net::Shutdown::Both);
+    Ok(())
+}
+
+fn spawn_and_log_error<F>(fut: F) -> task::JoinHandle<()>
+where
+    F: Future<Output = Result<()>> + Send + 'static,
+{
+    task::spawn(async move {
+        if let Err(e) = fut.await {
+            eprintln!("{}", e)
+        }
+    })
+}
@@ -0,0 +1,263 @@
+//! The wire protocol: one command per line from a client, one reply per line back.
+//!
+//! ```text
+//! alice                   the first line names you, the same as /nick alice
+//! /nick bob               rename
+//! /join #rust             join (or open) a room, replaying its recent history
+//! /part #rust             leave a room
+//! /msg #rust hello        say something in a room
+//! /msg bob hello          ... or to one person
+//! hello                   say something in the room joined last
+//! /who [#rust]            who's in a room, or who's online
+//! /quit [reason]          leave
+//! ```
+//!
+//! Replies start with what they are: `#rust <alice> hello` in a room, `<alice> hello`
+//! just for you, `* ...` for news, `= ...` for names and `! ...` for errors.
+
+use std::fmt;
+
+/// Longest name or room, in characters
+pub const MAX_NAME: usize = 32;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Command {
+    Nick(String),
+    Join(String),
+    Part(String),
+    Msg {
+        to: Target,
+        text: String,
+    },
+    /// to the room joined last
+    Say(String),
+    Who(Option<String>),
+    Quit(Option<String>),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Target {
+    Room(String),
+    Peer(String),
+}
+
+/// A line the server doesn't understand - reported to whoever sent it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError(pub String);
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+impl Command {
+    /// A line without its newline. Anything not starting with `/` is `Say` - the
+    /// broker takes it as a name until there is one.
+    pub fn parse(line: &str) -> Result<Command, ParseError> {
+        let line = line.trim_end_matches('\r');
+        let Some(command) = line.strip_prefix('/') else {
+            return Ok(Command::Say(line.to_string()));
+        };
+        let (verb, rest) = match command.split_once(char::is_whitespace) {
+            Some((verb, rest)) => (verb, rest.trim()),
+            None => (command, ""),
+        };
+        let error = |message: &str| Err(ParseError(message.to_string()));
+        match verb {
+            "nick" => match single(rest) {
+                Some(name) => valid_name(name).map(|name| Command::Nick(name.to_string())),
+                None => error("usage: /nick <name>"),
+            },
+            "join" => match single(rest) {
+                Some(room) => valid_room(room).map(|room| Command::Join(room.to_string())),
+                None => error("usage: /join #<room>"),
+            },
+            "part" => match single(rest) {
+                Some(room) => valid_room(room).map(|room| Command::Part(room.to_string())),
+                None => error("usage: /part #<room>"),
+            },
+            "msg" => {
+                let Some((to, text)) = rest.split_once(char::is_whitespace) else {
+                    return error("usage: /msg <#room or name> <text>");
+                };
+                let to = if to.starts_with('#') {
+                    Target::Room(valid_room(to)?.to_string())
+                } else {
+                    Target::Peer(valid_name(to)?.to_string())
+                };
+                Ok(Command::Msg {
+                    to,
+                    text: text.trim_start().to_string(),
+                })
+            }
+            "who" => match rest {
+                "" => Ok(Command::Who(None)),
+                room => match single(room) {
+                    Some(room) => valid_room(room).map(|room| Command::Who(Some(room.to_string()))),
+                    None => error("usage: /who [#<room>]"),
+                },
+            },
+            "quit" => Ok(Command::Quit((!rest.is_empty()).then(|| rest.to_string()))),
+            _ => Err(ParseError(format!("unknown command /{verb}"))),
+        }
+    }
+}
+
+/// One word, no more
+fn single(rest: &str) -> Option<&str> {
+    (!rest.is_empty() && !rest.contains(char::is_whitespace)).then_some(rest)
+}
+
+/// Names can't look like rooms or commands
+pub fn valid_name(name: &str) -> Result<&str, ParseError> {
+    if name.is_empty()
+        || name.chars().count() > MAX_NAME
+        || name.starts_with(['#', '/', '*', '!', '=', '<'])
+        || name.contains(|c: char| c.is_whitespace() || c.is_control() || c == ',')
+    {
+        Err(ParseError(format!(
+            "{name:?} isn't a name - up to {MAX_NAME} characters, no spaces, not starting with # or /"
+        )))
+    } else {
+        Ok(name)
+    }
+}
+
+pub fn valid_room(room: &str) -> Result<&str, ParseError> {
+    match room.strip_prefix('#') {
+        Some(rest)
+            if !rest.is_empty()
+                && room.chars().count() <= MAX_NAME
+                && !rest.contains(|c: char| c.is_whitespace() || c.is_control() || c == ',') =>
+        {
+            Ok(room)
+        }
+        _ => Err(ParseError(format!(
+            "{room:?} isn't a room - # and up to {} more characters, no spaces",
+            MAX_NAME - 1
+        ))),
+    }
+}
+
+/// A line from the server
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Reply {
+    /// `#room <from> text`, or `<from> text` when it's just for you
+    Message {
+        room: Option<String>,
+        from: String,
+        text: String,
+    },
+    /// `* text`
+    Notice(String),
+    /// `= #room a b c`, or `= a b c` for everyone online
+    Names {
+        room: Option<String>,
+        names: Vec<String>,
+    },
+    /// `! text`
+    Error(String),
+}
+
+impl fmt::Display for Reply {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Reply::Message {
+                room: Some(room),
+                from,
+                text,
+            } => write!(f, "{room} <{from}> {text}"),
+            Reply::Message {
+                room: None,
+                from,
+                text,
+            } => write!(f, "<{from}> {text}"),
+            Reply::Notice(text) => write!(f, "* {text}"),
+            Reply::Names { room, names } => {
+                f.write_str("=")?;
+                if let Some(room) = room {
+                    write!(f, " {room}")?;
+                }
+                for name in names {
+                    write!(f, " {name}")?;
+                }
+                Ok(())
+            }
+            Reply::Error(text) => write!(f, "! {text}"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_commands() {
+        let parse = |line| Command::parse(line).unwrap();
+        assert_eq!(parse("/nick alice"), Command::Nick("alice".into()));
+        assert_eq!(parse("/join #rust\r"), Command::Join("#rust".into()));
+        assert_eq!(
+            parse("/msg #rust  hello there "),
+            Command::Msg {
+                to: Target::Room("#rust".into()),
+                text: "hello there".into()
+            }
+        );
+        assert_eq!(
+            parse("/msg bob hi"),
+            Command::Msg {
+                to: Target::Peer("bob".into()),
+                text: "hi".into()
+            }
+        );
+        assert_eq!(parse("/who"), Command::Who(None));
+        assert_eq!(parse("/who #rust"), Command::Who(Some("#rust".into())));
+        assert_eq!(parse("/quit"), Command::Quit(None));
+        assert_eq!(
+            parse("/quit gone fishing"),
+            Command::Quit(Some("gone fishing".into()))
+        );
+        assert_eq!(parse("just talking"), Command::Say("just talking".into()));
+    }
+
+    #[test]
+    fn rejects_what_it_cant_parse() {
+        for line in [
+            "/nick",
+            "/nick two words",
+            "/nick #room",
+            "/join rust",
+            "/join #",
+            "/msg bob",
+            "/who rust",
+            "/dance",
+        ] {
+            assert!(Command::parse(line).is_err(), "{line}");
+        }
+        assert!(valid_name(&"x".repeat(MAX_NAME + 1)).is_err());
+    }
+
+    #[test]
+    fn formats_replies() {
+        let message = |room: Option<&str>| Reply::Message {
+            room: room.map(String::from),
+            from: "alice".into(),
+            text: "hi".into(),
+        };
+        assert_eq!(message(Some("#rust")).to_string(), "#rust <alice> hi");
+        assert_eq!(message(None).to_string(), "<alice> hi");
+        assert_eq!(
+            Reply::Names {
+                room: Some("#rust".into()),
+                names: vec!["alice".into(), "bob".into()]
+            }
+            .to_string(),
+            "= #rust alice bob"
+        );
+        assert_eq!(Reply::Error("no".into()).to_string(), "! no");
+    }
+}
@@ -0,0 +1,247 @@
+//! Several clients talking to a server on localhost
+
+use std::net::SocketAddr;
+use std::time::Duration;
+
+use async_std::io::{BufReader, Lines};
+use async_std::net::{TcpListener, TcpStream};
+use async_std::{future, prelude::*, task};
+use async_std_chat::{broker::HISTORY, serve};
+
+async fn server() -> SocketAddr {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    task::spawn(serve(listener));
+    addr
+}
+
+struct Client {
+    stream: TcpStream,
+    lines: Lines<BufReader<TcpStream>>,
+}
+
+impl Client {
+    async fn connect(addr: SocketAddr) -> Client {
+        let stream = TcpStream::connect(addr).await.unwrap();
+        let lines = BufReader::new(stream.clone()).lines();
+        Client { stream, lines }
+    }
+
+    async fn named(addr: SocketAddr, name: &str) -> Client {
+        let mut client = Client::connect(addr).await;
+        client.send(name).await;
+        client.expect(&format!("* welcome, {name}")).await;
+        client
+    }
+
+    async fn send(&mut self, line: &str) {
+        self.stream
+            .write_all(format!("{line}\n").as_bytes())
+            .await
+            .unwrap();
+    }
+
+    /// The next line, or `None` once the server hangs up
+    async fn next(&mut self) -> Option<String> {
+        future::timeout(Duration::from_secs(5), self.lines.next())
+            .await
+            .expect("a line in time")
+            .map(Result::unwrap)
+    }
+
+    async fn expect(&mut self, line: &str) {
+        assert_eq!(self.next().await.as_deref(), Some(line));
+    }
+
+    /// Asserts nothing else has arrived: replies come in order, so anything sent
+    /// before would come before the answer to a `/who`
+    async fn expect_nothing(&mut self) {
+        self.send("/who").await;
+        let line = self.next().await.unwrap();
+        assert!(line.starts_with('='), "unexpected {line:?}");
+    }
+}
+
+#[async_std::test]
+async fn rejects_names_that_are_taken() {
+    let addr = server().await;
+    let mut alice = Client::named(addr, "alice").await;
+
+    let mut other = Client::connect(addr).await;
+    other.send("alice").await;
+    other.expect("! alice is taken").await;
+    other.send("/join #rust").await;
+    other.expect("! pick a name first: /nick <name>").await;
+    other.send("two words").await;
+    assert!(other
+        .next()
+        .await
+        .unwrap()
+        .starts_with("! \"two words\" isn't a name"));
+    other.send("/nick bob").await;
+    other.expect("* welcome, bob").await;
+
+    other.send("/nick alice").await;
+    other.expect("! alice is taken").await;
+    alice.send("/nick carol").await;
+    alice.expect("* you are now carol").await;
+    // alice is free again
+    other.send("/nick alice").await;
+    other.expect("* you are now alice").await;
+    other.send("/who").await;
+    other.expect("= alice carol").await;
+}
+
+#[async_std::test]
+async fn broadcasts_to_rooms_and_replays_their_history() {
+    let addr = server().await;
+    let mut alice = Client::named(addr, "alice").await;
+    let mut bob = Client::named(addr, "bob").await;
+    let mut carol = Client::named(addr, "carol").await;
+
+    alice.send("/join #rust").await;
+    alice.expect("* alice joined #rust").await;
+    for i in 0..HISTORY + 5 {
+        alice.send(&format!("message {i}")).await;
+    }
+    alice.expect_nothing().await;
+
+    // only the last HISTORY, oldest first
+    bob.send("/join #rust").await;
+    for i in 5..HISTORY + 5 {
+        bob.expect(&format!("#rust <alice> message {i}")).await;
+    }
+    bob.expect("* bob joined #rust").await;
+    alice.expect("* bob joined #rust").await;
+
+    bob.send("/msg #rust hi alice").await;
+    alice.expect("#rust <bob> hi alice").await;
+    carol.send("/msg #rust let me in").await;
+    carol.expect("! join #rust first").await;
+    carol.expect_nothing().await;
+
+    // a nick change reaches each neighbour once, however many rooms they share
+    bob.send("/join #async").await;
+    bob.expect("* bob joined #async").await;
+    alice.send("/join #async").await;
+    alice.expect("* alice joined #async").await;
+    bob.expect("* alice joined #async").await;
+    alice.send("/nick ally").await;
+    alice.expect("* you are now ally").await;
+    bob.expect("* alice is now ally").await;
+    bob.expect_nothing().await;
+
+    bob.send("/who #rust").await;
+    bob.expect("= #rust ally bob").await;
+    bob.send("/part #rust").await;
+    bob.expect("* you left #rust").await;
+    alice.expect("* bob left #rust").await;
+    // lines without a command go to the room joined last
+    alice.send("still here").await;
+    bob.expect("#async <ally> still here").await;
+}
+
+#[async_std::test]
+async fn keeps_history_once_everyone_has_left() {
+    let addr = server().await;
+    let mut alice = Client::named(addr, "alice").await;
+    let mut bob = Client::named(addr, "bob").await;
+
+    alice.send("/join #rust").await;
+    alice.expect("* alice joined #rust").await;
+    alice.send("anyone?").await;
+    alice.send("/part #rust").await;
+    alice.expect("* you left #rust").await;
+    bob.send("/who #rust").await;
+    bob.expect("! no one is in #rust").await;
+
+    bob.send("/join #rust").await;
+    bob.expect("#rust <alice> anyone?").await;
+    bob.expect("* bob joined #rust").await;
+    bob.send("/quit").await;
+    bob.expect("* bye").await;
+
+    alice.send("/join #rust").await;
+    alice.expect("#rust <alice> anyone?").await;
+    alice.expect("* alice joined #rust").await;
+}
+
+#[async_std::test]
+async fn falls_back_to_the_room_joined_last() {
+    let addr = server().await;
+    let mut alice = Client::named(addr, "alice").await;
+    let mut bob = Client::named(addr, "bob").await;
+
+    bob.send("/join #a").await;
+    bob.expect("* bob joined #a").await;
+    alice.send("/join #c").await;
+    alice.expect("* alice joined #c").await;
+    alice.send("/join #b").await;
+    alice.expect("* alice joined #b").await;
+    alice.send("/join #a").await;
+    alice.expect("* alice joined #a").await;
+    bob.expect("* alice joined #a").await;
+    alice.send("/part #a").await;
+    alice.expect("* you left #a").await;
+    bob.expect("* alice left #a").await;
+
+    // #b was joined after #c, though #c sorts last
+    bob.send("/join #b").await;
+    bob.expect("* bob joined #b").await;
+    alice.expect("* bob joined #b").await;
+    alice.send("still here").await;
+    bob.expect("#b <alice> still here").await;
+}
+
+#[async_std::test]
+async fn sends_private_messages() {
+    let addr = server().await;
+    let mut alice = Client::named(addr, "alice").await;
+    let mut bob = Client::named(addr, "bob").await;
+
+    alice.send("/msg bob psst").await;
+    bob.expect("<alice> psst").await;
+    alice.send("/msg dave psst").await;
+    alice.expect("! no one called dave").await;
+    alice.send("hello?").await;
+    alice.expect("! join a room first: /join #<room>").await;
+    alice.send("/dance").await;
+    alice.expect("! unknown command /dance").await;
+    alice.send("/who").await;
+    alice.expect("= alice bob").await;
+    alice.send("/who #nowhere").await;
+    alice.expect("! no one is in #nowhere").await;
+    bob.expect_nothing().await;
+}
+
+#[async_std::test]
+async fn says_goodbye_to_those_who_quit_or_hang_up() {
+    let addr = server().await;
+    let mut alice = Client::named(addr, "alice").await;
+    let mut bob = Client::named(addr, "bob").await;
+    let mut carol = Client::named(addr, "carol").await;
+    for (client, name) in [
+        (&mut alice, "alice"),
+        (&mut bob, "bob"),
+        (&mut carol, "carol"),
+    ] {
+        client.send("/join #rust").await;
+        client.expect(&format!("* {name} joined #rust")).await;
+    }
+    alice.expect("* bob joined #rust").await;
+    alice.expect("* carol joined #rust").await;
+    bob.expect("* carol joined #rust").await;
+
+    bob.send("/quit lunch").await;
+    bob.expect("* bye").await;
+    assert_eq!(bob.next().await, None);
+    alice.expect("* bob left #rust (lunch)").await;
+
+    drop(carol);
+    alice.expect("* carol left #rust").await;
+    alice.send("/who").await;
+    alice.expect("= alice").await;
+
+    // their names are free again
+    Client::named(addr, "bob").await;
+}
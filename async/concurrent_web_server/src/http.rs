@@ -0,0 +1,732 @@
+//! Just enough HTTP/1.1: requests are parsed as their bytes arrive, bodies are
+//! read on demand (with `Content-Length` or `Transfer-Encoding: chunked`), and
+//! responses are written with a `Content-Length` so the connection can be kept
+//! alive for the next request.
+
+use std::{
+    cmp::min,
+    fmt, io,
+    path::Path,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use async_std::{
+    fs::File,
+    io::{BufRead, Read, Write},
+};
+use futures::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt};
+
+/// Longest request line plus headers
+pub const MAX_HEAD: usize = 8 * 1024;
+pub const MAX_HEADERS: usize = 100;
+/// Longest chunk-size or trailer line in a chunked body
+const MAX_CHUNK_LINE: usize = 1024;
+
+#[derive(Debug)]
+pub enum Error {
+    /// The connection failed, or the client went away mid-request
+    Io(io::Error),
+    BadRequest(&'static str),
+    HeadTooLarge,
+    PayloadTooLarge,
+    /// A `Transfer-Encoding` other than chunked
+    NotImplemented,
+    VersionNotSupported,
+}
+
+impl Error {
+    /// What to tell the client, if it can still be told anything. The
+    /// connection is closed afterwards either way.
+    pub fn response(&self) -> Option<Response> {
+        let (status, detail) = match self {
+            Error::Io(error) if error.kind() == io::ErrorKind::InvalidData => {
+                (Status::BAD_REQUEST, error.to_string())
+            }
+            Error::Io(_) => return None,
+            Error::BadRequest(detail) => (Status::BAD_REQUEST, detail.to_string()),
+            Error::HeadTooLarge => (
+                Status::HEADERS_TOO_LARGE,
+                format!("request line and headers must fit in {MAX_HEAD} bytes"),
+            ),
+            Error::PayloadTooLarge => (Status::PAYLOAD_TOO_LARGE, "body too large".to_string()),
+            Error::NotImplemented => (
+                Status::NOT_IMPLEMENTED,
+                "only chunked transfer encoding is supported".to_string(),
+            ),
+            Error::VersionNotSupported => (
+                Status::VERSION_NOT_SUPPORTED,
+                "only HTTP/1.0 and HTTP/1.1 are supported".to_string(),
+            ),
+        };
+        Some(Response::text(status, format!("{detail}\n")))
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Io(error) => write!(f, "{error}"),
+            Error::BadRequest(detail) => write!(f, "bad request: {detail}"),
+            Error::HeadTooLarge => f.write_str("request head too large"),
+            Error::PayloadTooLarge => f.write_str("request body too large"),
+            Error::NotImplemented => f.write_str("unsupported transfer encoding"),
+            Error::VersionNotSupported => f.write_str("unsupported HTTP version"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<io::Error> for Error {
+    fn from(error: io::Error) -> Error {
+        Error::Io(error)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Version {
+    Http10,
+    Http11,
+}
+
+/// What the body is read from: the connection, minus the head already parsed.
+pub type Reader<'c> = dyn BufRead + Unpin + Send + 'c;
+
+pub struct Request<'c> {
+    pub method: String,
+    /// The target without its query string
+    pub path: String,
+    pub query: Option<String>,
+    pub version: Version,
+    pub headers: Vec<(String, String)>,
+    /// Filled in by the router from `:name` and `*name` segments
+    pub params: Vec<(String, String)>,
+    pub body: Body<'c>,
+}
+
+impl Request<'_> {
+    /// The first header called `name`, ignoring case
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    pub fn param(&self, name: &str) -> Option<&str> {
+        self.params
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// HTTP/1.1 keeps connections open unless told to close, HTTP/1.0 the
+    /// other way round
+    pub fn keep_alive(&self) -> bool {
+        let has = |option: &str| {
+            self.header("connection").is_some_and(|value| {
+                value
+                    .split(',')
+                    .any(|token| token.trim().eq_ignore_ascii_case(option))
+            })
+        };
+        match self.version {
+            Version::Http11 => !has("close"),
+            Version::Http10 => has("keep-alive"),
+        }
+    }
+}
+
+impl fmt::Debug for Request<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Request")
+            .field("method", &self.method)
+            .field("path", &self.path)
+            .field("query", &self.query)
+            .field("version", &self.version)
+            .field("headers", &self.headers)
+            .field("params", &self.params)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Reads the next request's head off `reader`, a line at a time, leaving its
+/// body to be read through [`Request::body`]. `None` when the client closed the
+/// connection between requests.
+pub async fn read_request<'c>(reader: &'c mut Reader<'c>) -> Result<Option<Request<'c>>, Error> {
+    let mut budget = MAX_HEAD;
+    let mut line = Vec::new();
+    // a stray empty line or two before a request is allowed
+    loop {
+        if !read_line(reader, &mut line, &mut budget).await? {
+            return match line.is_empty() {
+                true => Ok(None),
+                false => Err(Error::Io(io::ErrorKind::UnexpectedEof.into())),
+            };
+        }
+        if !trim_newline(&line).is_empty() {
+            break;
+        }
+        line.clear();
+    }
+    let (method, target, version) = parse_request_line(trim_newline(&line))?;
+    let (path, query) = match target.split_once('?') {
+        Some((path, query)) => (path.to_string(), Some(query.to_string())),
+        None => (target.to_string(), None),
+    };
+    let method = method.to_string();
+
+    let mut headers = Vec::new();
+    loop {
+        line.clear();
+        if !read_line(reader, &mut line, &mut budget).await? {
+            return Err(Error::Io(io::ErrorKind::UnexpectedEof.into()));
+        }
+        let header = trim_newline(&line);
+        if header.is_empty() {
+            break;
+        }
+        if headers.len() == MAX_HEADERS {
+            return Err(Error::HeadTooLarge);
+        }
+        headers.push(parse_header(header)?);
+    }
+
+    let mut request = Request {
+        method,
+        path,
+        query,
+        version,
+        headers,
+        params: Vec::new(),
+        body: Body {
+            reader,
+            state: State::Done,
+        },
+    };
+    request.body.state = body_state(&request)?;
+    Ok(Some(request))
+}
+
+/// Appends a line, newline included, to `line`. False at end of stream.
+async fn read_line(
+    reader: &mut Reader<'_>,
+    line: &mut Vec<u8>,
+    budget: &mut usize,
+) -> Result<bool, Error> {
+    loop {
+        let available = reader.fill_buf().await?;
+        if available.is_empty() {
+            return Ok(false);
+        }
+        let (take, done) = match available.iter().position(|&b| b == b'\n') {
+            Some(newline) => (newline + 1, true),
+            None => (available.len(), false),
+        };
+        if take > *budget {
+            return Err(Error::HeadTooLarge);
+        }
+        *budget -= take;
+        line.extend_from_slice(&available[..take]);
+        reader.consume_unpin(take);
+        if done {
+            return Ok(true);
+        }
+    }
+}
+
+fn trim_newline(line: &[u8]) -> &[u8] {
+    let line = line.strip_suffix(b"\n").unwrap_or(line);
+    line.strip_suffix(b"\r").unwrap_or(line)
+}
+
+fn parse_request_line(line: &[u8]) -> Result<(&str, &str, Version), Error> {
+    let line =
+        std::str::from_utf8(line).map_err(|_| Error::BadRequest("request line isn't UTF-8"))?;
+    let mut parts = line.split(' ');
+    let (Some(method), Some(target), Some(version), None) =
+        (parts.next(), parts.next(), parts.next(), parts.next())
+    else {
+        return Err(Error::BadRequest(
+            "request line isn't METHOD TARGET VERSION",
+        ));
+    };
+    if method.is_empty() || !method.bytes().all(is_token) {
+        return Err(Error::BadRequest("bad method"));
+    }
+    if !target.starts_with('/') {
+        return Err(Error::BadRequest("target must be a path"));
+    }
+    let version = match version {
+        "HTTP/1.1" => Version::Http11,
+        "HTTP/1.0" => Version::Http10,
+        version if version.starts_with("HTTP/") => return Err(Error::VersionNotSupported),
+        _ => return Err(Error::BadRequest("bad HTTP version")),
+    };
+    Ok((method, target, version))
+}
+
+fn parse_header(line: &[u8]) -> Result<(String, String), Error> {
+    let line = std::str::from_utf8(line).map_err(|_| Error::BadRequest("header isn't UTF-8"))?;
+    let Some((name, value)) = line.split_once(':') else {
+        return Err(Error::BadRequest("header without a colon"));
+    };
+    // this also rules out folded lines, which start with whitespace
+    if name.is_empty() || !name.bytes().all(is_token) {
+        return Err(Error::BadRequest("bad header name"));
+    }
+    Ok((name.to_string(), value.trim().to_string()))
+}
+
+/// Characters allowed in methods and header names
+fn is_token(b: u8) -> bool {
+    b.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(&b)
+}
+
+fn body_state(request: &Request) -> Result<State, Error> {
+    let lengths: Vec<&str> = request
+        .headers
+        .iter()
+        .filter(|(name, _)| name.eq_ignore_ascii_case("content-length"))
+        .map(|(_, value)| value.as_str())
+        .collect();
+    if let Some(encoding) = request.header("transfer-encoding") {
+        // a length as well could be read two ways, which is how requests get smuggled
+        if !lengths.is_empty() {
+            return Err(Error::BadRequest(
+                "both Content-Length and Transfer-Encoding",
+            ));
+        }
+        let last = encoding.rsplit(',').next().unwrap_or_default().trim();
+        return match last.eq_ignore_ascii_case("chunked") {
+            true => Ok(State::Chunked(Chunked::Size(Vec::new()))),
+            false => Err(Error::NotImplemented),
+        };
+    }
+    match lengths.as_slice() {
+        [] => Ok(State::Done),
+        [first, rest @ ..] if rest.iter().all(|length| length == first) => {
+            match first.parse::<u64>() {
+                Ok(0) => Ok(State::Done),
+                Ok(length) => Ok(State::Length(length)),
+                Err(_) => Err(Error::BadRequest("bad Content-Length")),
+            }
+        }
+        _ => Err(Error::BadRequest("conflicting Content-Length headers")),
+    }
+}
+
+/// A request body, read straight off the connection as the handler asks for
+/// it. Whatever the handler leaves unread is drained before the next request.
+pub struct Body<'c> {
+    reader: &'c mut Reader<'c>,
+    state: State,
+}
+
+#[derive(Debug)]
+enum State {
+    Length(u64),
+    Chunked(Chunked),
+    Done,
+}
+
+#[derive(Debug)]
+enum Chunked {
+    /// Reading a chunk-size line
+    Size(Vec<u8>),
+    Data(u64),
+    /// Reading the line break after a chunk's data
+    DataEnd(Vec<u8>),
+    /// Reading trailer lines up to the empty one
+    Trailer(Vec<u8>),
+}
+
+impl Body<'_> {
+    pub fn is_done(&self) -> bool {
+        matches!(self.state, State::Done)
+    }
+
+    /// Reads the rest of the body, refusing one longer than `limit`.
+    pub async fn read_to_end(&mut self, limit: usize) -> Result<Vec<u8>, Error> {
+        let mut body = Vec::new();
+        let mut chunk = [0; 8 * 1024];
+        loop {
+            let n = self.read(&mut chunk).await?;
+            if n == 0 {
+                return Ok(body);
+            }
+            if body.len() + n > limit {
+                return Err(Error::PayloadTooLarge);
+            }
+            body.extend_from_slice(&chunk[..n]);
+        }
+    }
+
+    /// Skips what's left of the body; false if that's more than `limit` and
+    /// the connection is better closed.
+    pub async fn drain(&mut self, limit: u64) -> io::Result<bool> {
+        let mut skipped = 0;
+        let mut chunk = [0; 8 * 1024];
+        while !self.is_done() {
+            if skipped > limit {
+                return Ok(false);
+            }
+            skipped += self.read(&mut chunk).await? as u64;
+        }
+        Ok(true)
+    }
+
+    /// Copies the next bytes of data, at most `max`, into `buf`
+    fn poll_data(
+        reader: &mut Reader<'_>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+        max: u64,
+    ) -> Poll<io::Result<usize>> {
+        let available = match Pin::new(&mut *reader).poll_fill_buf(cx) {
+            Poll::Ready(Ok(available)) => available,
+            Poll::Ready(Err(error)) => return Poll::Ready(Err(error)),
+            Poll::Pending => return Poll::Pending,
+        };
+        if available.is_empty() {
+            return Poll::Ready(Err(io::ErrorKind::UnexpectedEof.into()));
+        }
+        let n = min(min(available.len(), buf.len()) as u64, max) as usize;
+        buf[..n].copy_from_slice(&available[..n]);
+        Pin::new(reader).consume(n);
+        Poll::Ready(Ok(n))
+    }
+}
+
+/// Reads into `line` until it ends with a newline.
+fn poll_line(
+    reader: &mut Reader<'_>,
+    cx: &mut Context<'_>,
+    line: &mut Vec<u8>,
+) -> Poll<io::Result<()>> {
+    loop {
+        let available = match Pin::new(&mut *reader).poll_fill_buf(cx) {
+            Poll::Ready(Ok(available)) => available,
+            Poll::Ready(Err(error)) => return Poll::Ready(Err(error)),
+            Poll::Pending => return Poll::Pending,
+        };
+        if available.is_empty() {
+            return Poll::Ready(Err(io::ErrorKind::UnexpectedEof.into()));
+        }
+        let (take, done) = match available.iter().position(|&b| b == b'\n') {
+            Some(newline) => (newline + 1, true),
+            None => (available.len(), false),
+        };
+        if line.len() + take > MAX_CHUNK_LINE {
+            return Poll::Ready(Err(invalid("chunk line too long")));
+        }
+        line.extend_from_slice(&available[..take]);
+        Pin::new(&mut *reader).consume(take);
+        if done {
+            return Poll::Ready(Ok(()));
+        }
+    }
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+impl Read for Body<'_> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        let Body { reader, state } = self.get_mut();
+        if buf.is_empty() {
+            return Poll::Ready(Ok(0));
+        }
+        loop {
+            let (State::Length(remaining) | State::Chunked(Chunked::Data(remaining))) = state
+            else {
+                match state {
+                    State::Done => return Poll::Ready(Ok(0)),
+                    State::Chunked(
+                        Chunked::Size(line) | Chunked::DataEnd(line) | Chunked::Trailer(line),
+                    ) => match poll_line(reader, cx, line) {
+                        Poll::Ready(Ok(())) => {}
+                        Poll::Ready(Err(error)) => return Poll::Ready(Err(error)),
+                        Poll::Pending => return Poll::Pending,
+                    },
+                    _ => unreachable!("data states are handled below"),
+                }
+                *state = next_chunk_state(state)?;
+                continue;
+            };
+            let n = match Body::poll_data(reader, cx, buf, *remaining) {
+                Poll::Ready(Ok(n)) => n,
+                other => return other,
+            };
+            *remaining -= n as u64;
+            if *remaining == 0 {
+                *state = match state {
+                    State::Length(_) => State::Done,
+                    _ => State::Chunked(Chunked::DataEnd(Vec::new())),
+                };
+            }
+            return Poll::Ready(Ok(n));
+        }
+    }
+}
+
+/// Where a chunked body goes after a complete line
+fn next_chunk_state(state: &State) -> io::Result<State> {
+    let State::Chunked(chunked) = state else {
+        unreachable!("only chunked bodies read lines")
+    };
+    Ok(match chunked {
+        Chunked::Size(line) => {
+            let line = trim_newline(line);
+            // chunk extensions after a `;` are allowed, and ignored
+            let size = line.split(|&b| b == b';').next().unwrap_or_default();
+            let size = std::str::from_utf8(size)
+                .ok()
+                .and_then(|size| u64::from_str_radix(size.trim(), 16).ok())
+                .ok_or_else(|| invalid("bad chunk size"))?;
+            match size {
+                0 => State::Chunked(Chunked::Trailer(Vec::new())),
+                size => State::Chunked(Chunked::Data(size)),
+            }
+        }
+        Chunked::DataEnd(line) if trim_newline(line).is_empty() => {
+            State::Chunked(Chunked::Size(Vec::new()))
+        }
+        Chunked::DataEnd(_) => return Err(invalid("chunk longer than its size")),
+        Chunked::Trailer(line) if trim_newline(line).is_empty() => State::Done,
+        // trailers are read and thrown away
+        Chunked::Trailer(_) => State::Chunked(Chunked::Trailer(Vec::new())),
+        Chunked::Data(_) => unreachable!("data isn't read a line at a time"),
+    })
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Status(pub u16, pub &'static str);
+
+impl Status {
+    pub const OK: Status = Status(200, "OK");
+    pub const BAD_REQUEST: Status = Status(400, "Bad Request");
+    pub const NOT_FOUND: Status = Status(404, "Not Found");
+    pub const METHOD_NOT_ALLOWED: Status = Status(405, "Method Not Allowed");
+    pub const PAYLOAD_TOO_LARGE: Status = Status(413, "Payload Too Large");
+    pub const HEADERS_TOO_LARGE: Status = Status(431, "Request Header Fields Too Large");
+    pub const INTERNAL_SERVER_ERROR: Status = Status(500, "Internal Server Error");
+    pub const NOT_IMPLEMENTED: Status = Status(501, "Not Implemented");
+    pub const VERSION_NOT_SUPPORTED: Status = Status(505, "HTTP Version Not Supported");
+}
+
+#[derive(Debug)]
+pub struct Response {
+    pub status: Status,
+    pub headers: Vec<(String, String)>,
+    pub body: ResponseBody,
+}
+
+#[derive(Debug)]
+pub enum ResponseBody {
+    Empty,
+    Bytes(Vec<u8>),
+    /// Streamed from disk as it's written out
+    File {
+        file: File,
+        len: u64,
+    },
+}
+
+impl Response {
+    pub fn new(status: Status) -> Response {
+        Response {
+            status,
+            headers: Vec::new(),
+            body: ResponseBody::Empty,
+        }
+    }
+
+    pub fn with_header(mut self, name: &str, value: impl Into<String>) -> Response {
+        self.headers.push((name.to_string(), value.into()));
+        self
+    }
+
+    pub fn with_body(mut self, content_type: &str, body: impl Into<Vec<u8>>) -> Response {
+        self.body = ResponseBody::Bytes(body.into());
+        self.with_header("Content-Type", content_type)
+    }
+
+    pub fn text(status: Status, text: impl Into<String>) -> Response {
+        Response::new(status).with_body("text/plain; charset=utf-8", text.into())
+    }
+
+    /// Serves the file at `path`, typed by its extension. Fails like
+    /// `File::open` does, including for directories.
+    pub async fn file(status: Status, path: impl AsRef<Path>) -> io::Result<Response> {
+        let path = path.as_ref();
+        let file = File::open(path).await?;
+        let metadata = file.metadata().await?;
+        if !metadata.is_file() {
+            return Err(io::ErrorKind::NotFound.into());
+        }
+        let response = Response {
+            status,
+            headers: Vec::new(),
+            body: ResponseBody::File {
+                file,
+                len: metadata.len(),
+            },
+        };
+        Ok(response.with_header("Content-Type", content_type(path)))
+    }
+}
+
+fn content_type(path: &Path) -> &'static str {
+    match path.extension().and_then(|extension| extension.to_str()) {
+        Some("html" | "htm") => "text/html; charset=utf-8",
+        Some("txt" | "md") => "text/plain; charset=utf-8",
+        Some("css") => "text/css",
+        Some("js") => "text/javascript",
+        Some("json") => "application/json",
+        Some("png") => "image/png",
+        Some("jpg" | "jpeg") => "image/jpeg",
+        Some("svg") => "image/svg+xml",
+        _ => "application/octet-stream",
+    }
+}
+
+/// Writes `response`, with a `Content-Length` and a `Connection` header saying
+/// whether another request may follow. `head_only` answers a HEAD request:
+/// the same headers, no body.
+pub async fn write_response(
+    writer: &mut (impl Write + Unpin),
+    response: Response,
+    keep_alive: bool,
+    head_only: bool,
+) -> io::Result<()> {
+    let Response {
+        status: Status(code, reason),
+        headers,
+        body,
+    } = response;
+    let len = match &body {
+        ResponseBody::Empty => 0,
+        ResponseBody::Bytes(bytes) => bytes.len() as u64,
+        ResponseBody::File { len, .. } => *len,
+    };
+    let mut head = format!("HTTP/1.1 {code} {reason}\r\n");
+    for (name, value) in &headers {
+        head.push_str(&format!("{name}: {value}\r\n"));
+    }
+    head.push_str(&format!("Content-Length: {len}\r\n"));
+    let connection = if keep_alive { "keep-alive" } else { "close" };
+    head.push_str(&format!("Connection: {connection}\r\n\r\n"));
+    writer.write_all(head.as_bytes()).await?;
+    if !head_only {
+        match body {
+            ResponseBody::Empty => {}
+            ResponseBody::Bytes(bytes) => writer.write_all(&bytes).await?,
+            ResponseBody::File { file, len } => {
+                // the length is promised already, so stop there even if the file grew
+                let copied = futures::io::copy(file.take(len), writer).await?;
+                if copied < len {
+                    return Err(io::ErrorKind::UnexpectedEof.into());
+                }
+            }
+        }
+    }
+    writer.flush().await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test::MockTcpStream;
+    use async_std::io::BufReader;
+
+    async fn parse(bytes: &[u8]) -> Result<(String, String, Vec<u8>), Error> {
+        let mut reader = BufReader::new(MockTcpStream::new(bytes, 3));
+        let mut request = read_request(&mut reader).await?.expect("a request");
+        let body = request.body.read_to_end(1024).await?;
+        Ok((request.method.clone(), request.path.clone(), body))
+    }
+
+    #[async_std::test]
+    async fn reads_bodies_by_length_or_in_chunks() {
+        let (method, path, body) =
+            parse(b"POST /echo HTTP/1.1\r\nContent-Length: 5\r\n\r\nhelloEXTRA")
+                .await
+                .unwrap();
+        assert_eq!((method.as_str(), path.as_str()), ("POST", "/echo"));
+        assert_eq!(body, b"hello");
+
+        let chunked = b"POST /echo HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n\
+            5;ext=1\r\nhello\r\n7\r\n, world\r\n0\r\nTrailer: yes\r\n\r\n";
+        assert_eq!(parse(chunked).await.unwrap().2, b"hello, world");
+    }
+
+    #[async_std::test]
+    async fn rejects_malformed_heads_and_bodies() {
+        for (request, expected) in [
+            (&b"GET /\r\n\r\n"[..], 400),
+            (b"GET / HTTP/2.0\r\n\r\n", 505),
+            (b"GET / HTTP/1.1\r\nno colon\r\n\r\n", 400),
+            (b"GET / HTTP/1.1\r\n folded: header\r\n\r\n", 400),
+            (b"POST / HTTP/1.1\r\nContent-Length: x\r\n\r\n", 400),
+            (
+                b"POST / HTTP/1.1\r\nContent-Length: 1\r\nContent-Length: 2\r\n\r\n",
+                400,
+            ),
+            (
+                b"POST / HTTP/1.1\r\nContent-Length: 1\r\nTransfer-Encoding: chunked\r\n\r\n",
+                400,
+            ),
+            (b"POST / HTTP/1.1\r\nTransfer-Encoding: gzip\r\n\r\n", 501),
+            (
+                b"POST / HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\nzz\r\n",
+                400,
+            ),
+            (
+                b"POST / HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n1\r\nab\r\n0\r\n\r\n",
+                400,
+            ),
+        ] {
+            let error = parse(request).await.unwrap_err();
+            let Status(code, _) = error.response().expect("an answer").status;
+            assert_eq!(code, expected, "{}", String::from_utf8_lossy(request));
+        }
+
+        let long = format!(
+            "POST / HTTP/1.1\r\nContent-Length: 2000\r\n\r\n{}",
+            "x".repeat(2000)
+        );
+        assert!(matches!(
+            parse(long.as_bytes()).await,
+            Err(Error::PayloadTooLarge)
+        ));
+        let huge = format!("GET / HTTP/1.1\r\nX: {}\r\n\r\n", "x".repeat(MAX_HEAD));
+        assert!(matches!(
+            parse(huge.as_bytes()).await,
+            Err(Error::HeadTooLarge)
+        ));
+        // cut off part way: nothing to answer, just hang up
+        let error = parse(b"GET / HTTP/1.1\r\nHost:").await.unwrap_err();
+        assert!(error.response().is_none());
+    }
+
+    #[async_std::test]
+    async fn keep_alive_depends_on_the_version() {
+        for (head, keep_alive) in [
+            (&b"GET / HTTP/1.1\r\n\r\n"[..], true),
+            (b"GET / HTTP/1.1\r\nConnection: Close\r\n\r\n", false),
+            (b"GET / HTTP/1.0\r\n\r\n", false),
+            (b"GET / HTTP/1.0\r\nConnection: keep-alive\r\n\r\n", true),
+        ] {
+            let mut reader = BufReader::new(MockTcpStream::new(head, 1024));
+            let request = read_request(&mut reader).await.unwrap().unwrap();
+            assert_eq!(request.keep_alive(), keep_alive);
+        }
+    }
+}
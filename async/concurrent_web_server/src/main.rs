@@ -1,77 +1,184 @@
-use std::{
-    fs,
-    // io::prelude::*,
-    // net::{TcpListener, TcpStream },
-    time::Duration,
-};
-use async_std::net::{TcpListener, /*TcpStream*/};
-use async_std::prelude::*;
-use async_std::task::{self, spawn};
-use async_std::io::{Read, Write};
+use std::{io, sync::Arc, time::Duration};
+
+use async_std::io::{BufReader, Read, Write};
+use async_std::net::TcpListener;
+use async_std::{future, task};
+use futures::future::{BoxFuture, FutureExt};
+use futures::io::AsyncWriteExt;
 use futures::stream::StreamExt;
 
+mod http;
+mod router;
+
+use http::{Request, Response, Status};
+use router::{Router, StaticFiles};
+
+/// How long an idle keep-alive connection may take to send its next request
+const KEEP_ALIVE: Duration = Duration::from_secs(5);
+/// Most of an unread request body skipped to keep the connection open
+const MAX_DRAIN: u64 = 64 * 1024;
+const MAX_ECHO: usize = 1024 * 1024;
+
 #[async_std::main]
 async fn main() {
     let listener = TcpListener::bind("127.0.0.1:7878").await.unwrap();
+    let router = Arc::new(routes());
     listener
         .incoming()
-        .for_each_concurrent(/* limit */ None, |tcpstream| async move {
-            let tcpstream = tcpstream.unwrap();
-            // handle_connection(tcpstream).await;
-            spawn(handle_connection(tcpstream));
+        .for_each_concurrent(/* limit */ None, |tcpstream| {
+            let router = Arc::clone(&router);
+            async move {
+                let tcpstream = match tcpstream {
+                    Ok(tcpstream) => tcpstream,
+                    Err(error) => return eprintln!("accept failed: {error}"),
+                };
+                task::spawn(async move {
+                    if let Err(error) = handle_connection(tcpstream, &router).await {
+                        eprintln!("connection failed: {error}");
+                    }
+                });
+            }
         })
         .await;
     println!("Shutting down.");
 }
 
-async fn handle_connection(mut stream: impl Read + Write + Unpin) { // TcpStream
-        // Read the first 1024 bytes of data from the stream
-        let mut buffer = [0; 1024];
-        stream.read(&mut buffer).await.unwrap();
-    
-        let get = b"GET / HTTP/1.1\r\n";
-        let sleep = b"GET /sleep HTTP/1.1\r\n";
-    
-        // Respond with greetings or a 404,
-        // depending on the data in the request
-        let (status_line, filename) = if buffer.starts_with(get) {
-            ("HTTP/1.1 200 OK\r\n\r\n", "hello.html")
-        } else if buffer.starts_with(sleep) {
-            task::sleep(Duration::from_secs(5)).await;
-            ("HTTP/1.1 200 OK\r\n\r\n", "hello.html")
-        } else {
-            ("HTTP/1.1 404 NOT FOUND\r\n\r\n", "404.html")
+fn routes() -> Router {
+    Router::new()
+        .route("GET", "/", index)
+        .route("GET", "/sleep", sleep)
+        .route("POST", "/echo", echo)
+        .route("GET", "/static/*path", StaticFiles::new("public"))
+        .fallback(not_found)
+}
+
+fn index<'a>(_: &'a mut Request<'_>) -> BoxFuture<'a, router::Result> {
+    async { Ok(Response::file(Status::OK, "hello.html").await?) }.boxed()
+}
+
+fn sleep<'a>(_: &'a mut Request<'_>) -> BoxFuture<'a, router::Result> {
+    async {
+        task::sleep(Duration::from_secs(5)).await;
+        Ok(Response::file(Status::OK, "hello.html").await?)
+    }
+    .boxed()
+}
+
+/// Sends the request body back, read as it arrives
+fn echo<'a>(request: &'a mut Request<'_>) -> BoxFuture<'a, router::Result> {
+    async move {
+        let content_type = request
+            .header("content-type")
+            .unwrap_or("application/octet-stream")
+            .to_string();
+        let body = request.body.read_to_end(MAX_ECHO).await?;
+        Ok(Response::new(Status::OK).with_body(&content_type, body))
+    }
+    .boxed()
+}
+
+fn not_found<'a>(_: &'a mut Request<'_>) -> BoxFuture<'a, router::Result> {
+    async { Ok(Response::file(Status::NOT_FOUND, "404.html").await?) }.boxed()
+}
+
+/// Answers requests on `stream` until the client closes it, asks to, or stays
+/// quiet for longer than `KEEP_ALIVE`.
+async fn handle_connection(
+    stream: impl Read + Write + Unpin + Send,
+    router: &Router,
+) -> io::Result<()> {
+    let mut reader = BufReader::new(stream);
+    loop {
+        let mut request = match future::timeout(KEEP_ALIVE, http::read_request(&mut reader)).await {
+            Err(_) | Ok(Ok(None)) => return Ok(()),
+            Ok(Ok(Some(request))) => request,
+            Ok(Err(error)) => return refuse(reader.get_mut(), error).await,
+        };
+        let head_only = request.method == "HEAD";
+        let response = match router.handle(&mut request).await {
+            Ok(response) => response,
+            Err(http::Error::Io(error))
+                if !matches!(
+                    error.kind(),
+                    io::ErrorKind::UnexpectedEof | io::ErrorKind::InvalidData
+                ) =>
+            {
+                // not the client's doing, so not the client's business
+                eprintln!("{} {} failed: {error}", request.method, request.path);
+                drop(request);
+                let response = Response::text(Status::INTERNAL_SERVER_ERROR, "internal error\n");
+                http::write_response(reader.get_mut(), response, false, head_only).await?;
+                return reader.get_mut().close().await;
+            }
+            Err(error) => {
+                drop(request);
+                return refuse(reader.get_mut(), error).await;
+            }
+        };
+        // the next request starts after this one's body, read or not
+        let keep_alive = request.keep_alive() && request.body.drain(MAX_DRAIN).await?;
+        drop(request);
+        http::write_response(reader.get_mut(), response, keep_alive, head_only).await?;
+        if !keep_alive {
+            return Ok(());
+        }
+    }
+}
+
+/// Answers a request that went wrong, if the client can still be answered, and
+/// closes the connection
+async fn refuse(stream: &mut (impl Write + Unpin), error: http::Error) -> io::Result<()> {
+    let Some(response) = error.response() else {
+        return match error {
+            http::Error::Io(error) if error.kind() == io::ErrorKind::UnexpectedEof => Ok(()),
+            http::Error::Io(error) => Err(error),
+            _ => unreachable!("every other error has a response"),
         };
-        let contents = fs::read_to_string(filename).unwrap();
-    
-        // Write response back to the stream,
-        // and flush the stream to ensure the response is sent back to the client
-        let response = format!("{status_line}{contents}");
-        stream.write_all(response.as_bytes()).await.unwrap();
-        stream.flush().await.unwrap();
+    };
+    http::write_response(stream, response, false, false).await?;
+    stream.close().await
 }
 
+#[cfg(test)]
 mod test {
     use super::*;
     use futures::io::Error;
     use futures::task::{Context, Poll};
 
     use std::cmp::min;
+    use std::fs;
     use std::pin::Pin;
 
-    struct MockTcpStream {
+    /// Plays back `read_data` at most `chunk` bytes per read, then reports end
+    /// of stream, and keeps everything written
+    pub(crate) struct MockTcpStream {
         read_data: Vec<u8>,
-        write_data: Vec<u8>,
+        read_pos: usize,
+        chunk: usize,
+        pub(crate) write_data: Vec<u8>,
+    }
+
+    impl MockTcpStream {
+        pub(crate) fn new(read_data: &[u8], chunk: usize) -> MockTcpStream {
+            MockTcpStream {
+                read_data: read_data.to_vec(),
+                read_pos: 0,
+                chunk,
+                write_data: Vec::new(),
+            }
+        }
     }
 
     impl Read for MockTcpStream {
         fn poll_read(
-            self: Pin<&mut Self>,
+            mut self: Pin<&mut Self>,
             _: &mut Context,
             buf: &mut [u8],
         ) -> Poll<Result<usize, Error>> {
-            let size: usize = min(self.read_data.len(), buf.len());
-            buf[..size].copy_from_slice(&self.read_data[..size]);
+            let remaining = &self.read_data[self.read_pos..];
+            let size: usize = min(min(remaining.len(), buf.len()), self.chunk);
+            buf[..size].copy_from_slice(&remaining[..size]);
+            self.read_pos += size;
             Poll::Ready(Ok(size))
         }
     }
@@ -82,7 +189,7 @@ mod test {
             _: &mut Context,
             buf: &[u8],
         ) -> Poll<Result<usize, Error>> {
-            self.write_data = Vec::from(buf);
+            self.write_data.extend_from_slice(buf);
 
             Poll::Ready(Ok(buf.len()))
         }
@@ -98,22 +205,156 @@ mod test {
 
     impl Unpin for MockTcpStream {}
 
+    #[derive(Debug)]
+    struct Reply {
+        status: u16,
+        headers: Vec<(String, String)>,
+        body: Vec<u8>,
+    }
+
+    impl Reply {
+        fn header(&self, name: &str) -> Option<&str> {
+            self.headers
+                .iter()
+                .find(|(key, _)| key.eq_ignore_ascii_case(name))
+                .map(|(_, value)| value.as_str())
+        }
+    }
+
+    /// Splits what the server wrote into responses, trusting its Content-Length
+    fn replies(mut written: &[u8]) -> Vec<Reply> {
+        let mut replies = Vec::new();
+        while !written.is_empty() {
+            let end = written
+                .windows(4)
+                .position(|window| window == b"\r\n\r\n")
+                .expect("a complete head");
+            let head = std::str::from_utf8(&written[..end]).unwrap();
+            let mut lines = head.split("\r\n");
+            let status = lines.next().unwrap()[9..12].parse().unwrap();
+            let headers: Vec<(String, String)> = lines
+                .map(|line| {
+                    let (name, value) = line.split_once(": ").unwrap();
+                    (name.to_string(), value.to_string())
+                })
+                .collect();
+            let len: usize = headers
+                .iter()
+                .find(|(name, _)| name == "Content-Length")
+                .map(|(_, len)| len.parse().unwrap())
+                .unwrap();
+            let body = written[end + 4..end + 4 + len].to_vec();
+            written = &written[end + 4 + len..];
+            replies.push(Reply {
+                status,
+                headers,
+                body,
+            });
+        }
+        replies
+    }
+
+    async fn serve(requests: &[u8], chunk: usize) -> Vec<Reply> {
+        let mut stream = MockTcpStream::new(requests, chunk);
+        handle_connection(&mut stream, &routes()).await.unwrap();
+        replies(&stream.write_data)
+    }
+
     #[async_std::test]
     async fn test_handle_connection() {
-        let input_bytes = b"GET / HTTP/1.1\r\n";
-        let mut contents = vec![0u8; 1024];
-        contents[..input_bytes.len()].clone_from_slice(input_bytes);
-        let mut stream = MockTcpStream {
-            read_data: contents,
-            write_data: Vec::new(),
-        };
+        let replies = serve(b"GET / HTTP/1.1\r\n\r\n", 1024).await;
+        assert_eq!(replies.len(), 1);
+        assert_eq!(replies[0].status, 200);
+        assert_eq!(
+            replies[0].header("Content-Type"),
+            Some("text/html; charset=utf-8")
+        );
+        assert_eq!(replies[0].header("Connection"), Some("keep-alive"));
+        assert_eq!(replies[0].body, fs::read("hello.html").unwrap());
+    }
 
-        handle_connection(&mut stream).await;
+    #[async_std::test]
+    async fn parses_requests_a_byte_at_a_time() {
+        let request =
+            b"POST /echo HTTP/1.1\r\nHost: localhost\r\nContent-Length: 11\r\n\r\nhello there";
+        let replies = serve(request, 1).await;
+        assert_eq!(replies[0].status, 200);
+        assert_eq!(replies[0].body, b"hello there");
+    }
 
-        let expected_contents = fs::read_to_string("hello.html").unwrap();
-        let expected_response = format!("HTTP/1.1 200 OK\r\n\r\n{}", expected_contents);
-        assert!(stream.write_data.starts_with(expected_response.as_bytes()));
+    #[async_std::test]
+    async fn keeps_connections_alive_until_asked_to_close() {
+        let requests = b"GET / HTTP/1.1\r\n\r\n\
+            POST /echo HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n3\r\nabc\r\n0\r\n\r\n\
+            GET /missing HTTP/1.1\r\nConnection: close\r\n\r\n\
+            GET / HTTP/1.1\r\n\r\n";
+        let replies = serve(requests, 7).await;
+        let statuses: Vec<u16> = replies.iter().map(|reply| reply.status).collect();
+        // nothing after the one that asked to close
+        assert_eq!(statuses, [200, 200, 404]);
+        assert_eq!(replies[1].body, b"abc");
+        assert_eq!(replies[2].body, fs::read("404.html").unwrap());
+        assert_eq!(replies[2].header("Connection"), Some("close"));
     }
 
+    #[async_std::test]
+    async fn skips_bodies_nobody_read() {
+        let requests = b"POST / HTTP/1.1\r\nContent-Length: 5\r\n\r\nhello\
+            GET / HTTP/1.0\r\n\r\n";
+        let replies = serve(requests, 1024).await;
+        let statuses: Vec<u16> = replies.iter().map(|reply| reply.status).collect();
+        assert_eq!(statuses, [405, 200]);
+        assert_eq!(replies[0].header("Allow"), Some("GET, HEAD"));
+        // HTTP/1.0 closes by default
+        assert_eq!(replies[1].header("Connection"), Some("close"));
+    }
+
+    #[async_std::test]
+    async fn answers_head_without_a_body() {
+        let mut stream = MockTcpStream::new(b"HEAD / HTTP/1.1\r\nConnection: close\r\n\r\n", 1024);
+        handle_connection(&mut stream, &routes()).await.unwrap();
+        let written = String::from_utf8(stream.write_data).unwrap();
+        let len = fs::metadata("hello.html").unwrap().len();
+        assert!(written.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(written.contains(&format!("Content-Length: {len}\r\n")));
+        assert!(written.ends_with("\r\n\r\n"));
+    }
+
+    #[async_std::test]
+    async fn serves_static_files() {
+        let requests = b"GET /static/style.css HTTP/1.1\r\n\r\n\
+            GET /static/../Cargo.toml HTTP/1.1\r\n\r\n\
+            GET /static/Cargo.toml HTTP/1.1\r\n\r\n\
+            GET /static/src/main.rs HTTP/1.1\r\n\r\n";
+        let replies = serve(requests, 1024).await;
+        let statuses: Vec<u16> = replies.iter().map(|reply| reply.status).collect();
+        // only what's in public/, never the crate's own files
+        assert_eq!(statuses, [200, 404, 404, 404]);
+        assert_eq!(replies[0].body, fs::read("public/style.css").unwrap());
+        assert_eq!(replies[0].header("Content-Type"), Some("text/css"));
+    }
 
-}
\ No newline at end of file
+    #[async_std::test]
+    async fn refuses_bad_requests_and_hangs_up() {
+        let replies = serve(
+            b"GET / HTTP/1.1\r\nbad header\r\n\r\nGET / HTTP/1.1\r\n\r\n",
+            1024,
+        )
+        .await;
+        assert_eq!(replies.len(), 1);
+        assert_eq!(replies[0].status, 400);
+        assert_eq!(replies[0].header("Connection"), Some("close"));
+
+        let body = format!(
+            "POST /echo HTTP/1.1\r\nContent-Length: {}\r\n\r\n",
+            MAX_ECHO + 1
+        );
+        let mut request = body.into_bytes();
+        request.resize(request.len() + MAX_ECHO + 1, b'x');
+        let replies = serve(&request, 64 * 1024).await;
+        assert_eq!(replies[0].status, 413);
+
+        // a client hanging up mid-request is nothing to report
+        assert!(serve(b"GET / HT", 1024).await.is_empty());
+    }
+}
@@ -0,0 +1,265 @@
+//! The route table: a method and a path pattern per handler.
+//!
+//! Patterns are split on `/`. A `:name` segment matches any one segment and a
+//! trailing `*name` matches the rest of the path; both end up in
+//! [`Request::params`]. Routes are tried in the order they were added.
+
+use std::{
+    io,
+    path::{Component, Path, PathBuf},
+};
+
+use futures::future::{BoxFuture, FutureExt};
+
+use crate::http::{Error, Request, Response, Status};
+
+pub type Result = std::result::Result<Response, Error>;
+
+/// Anything that can answer a request. Plain functions qualify:
+///
+/// ```ignore
+/// fn hello<'a>(_: &'a mut Request<'_>) -> BoxFuture<'a, router::Result> {
+///     async { Ok(Response::text(Status::OK, "hello")) }.boxed()
+/// }
+/// ```
+pub trait Handler: Send + Sync {
+    fn call<'a>(&'a self, request: &'a mut Request<'_>) -> BoxFuture<'a, Result>;
+}
+
+impl<F> Handler for F
+where
+    F: for<'a, 'c> Fn(&'a mut Request<'c>) -> BoxFuture<'a, Result> + Send + Sync,
+{
+    fn call<'a>(&'a self, request: &'a mut Request<'_>) -> BoxFuture<'a, Result> {
+        self(request)
+    }
+}
+
+struct Route {
+    method: String,
+    pattern: Vec<Segment>,
+    handler: Box<dyn Handler>,
+}
+
+#[derive(Debug, PartialEq)]
+enum Segment {
+    Literal(String),
+    Param(String),
+    Rest(String),
+}
+
+pub struct Router {
+    routes: Vec<Route>,
+    fallback: Box<dyn Handler>,
+}
+
+impl Default for Router {
+    fn default() -> Router {
+        Router::new()
+    }
+}
+
+impl Router {
+    /// No routes yet; anything asked for is a plain 404.
+    pub fn new() -> Router {
+        fn not_found<'a>(_: &'a mut Request<'_>) -> BoxFuture<'a, Result> {
+            async { Ok(Response::text(Status::NOT_FOUND, "not found\n")) }.boxed()
+        }
+        Router {
+            routes: Vec::new(),
+            fallback: Box::new(not_found),
+        }
+    }
+
+    /// # Panics
+    ///
+    /// If `pattern` doesn't start with `/`, or has a `*name` anywhere but at the end.
+    pub fn route(mut self, method: &str, pattern: &str, handler: impl Handler + 'static) -> Router {
+        let pattern = parse_pattern(pattern);
+        self.routes.push(Route {
+            method: method.to_ascii_uppercase(),
+            pattern,
+            handler: Box::new(handler),
+        });
+        self
+    }
+
+    /// Answers paths no route matches
+    pub fn fallback(mut self, handler: impl Handler + 'static) -> Router {
+        self.fallback = Box::new(handler);
+        self
+    }
+
+    /// Hands `request` to the first route that matches it. A path that only
+    /// matches routes for other methods gets a 405; HEAD is answered by GET
+    /// routes, leaving out the body is up to whoever writes the response.
+    pub async fn handle(&self, request: &mut Request<'_>) -> Result {
+        let method = match request.method.as_str() {
+            "HEAD" => "GET",
+            method => method,
+        };
+        let mut allowed = Vec::new();
+        for route in &self.routes {
+            let Some(params) = matches(&route.pattern, &request.path) else {
+                continue;
+            };
+            if route.method != method {
+                allowed.push(route.method.as_str());
+                continue;
+            }
+            request.params = params;
+            return route.handler.call(request).await;
+        }
+        if allowed.is_empty() {
+            return self.fallback.call(request).await;
+        }
+        if allowed.contains(&"GET") {
+            allowed.push("HEAD");
+        }
+        Ok(
+            Response::text(Status::METHOD_NOT_ALLOWED, "method not allowed\n")
+                .with_header("Allow", allowed.join(", ")),
+        )
+    }
+}
+
+fn parse_pattern(pattern: &str) -> Vec<Segment> {
+    let segments = pattern
+        .strip_prefix('/')
+        .unwrap_or_else(|| panic!("pattern {pattern:?} must start with /"));
+    let segments: Vec<Segment> = segments
+        .split('/')
+        .filter(|segment| !segment.is_empty())
+        .map(|segment| {
+            if let Some(name) = segment.strip_prefix(':') {
+                Segment::Param(name.to_string())
+            } else if let Some(name) = segment.strip_prefix('*') {
+                Segment::Rest(name.to_string())
+            } else {
+                Segment::Literal(segment.to_string())
+            }
+        })
+        .collect();
+    let rest = segments
+        .iter()
+        .position(|segment| matches!(segment, Segment::Rest(_)));
+    assert!(
+        rest.is_none_or(|rest| rest == segments.len() - 1),
+        "*name must come last in {pattern:?}"
+    );
+    segments
+}
+
+/// The parameters `path` fills in, if it matches
+fn matches(pattern: &[Segment], path: &str) -> Option<Vec<(String, String)>> {
+    let mut params = Vec::new();
+    let mut segments = path.split('/').filter(|segment| !segment.is_empty());
+    for expected in pattern {
+        match expected {
+            Segment::Rest(name) => {
+                let rest: Vec<&str> = segments.by_ref().collect();
+                params.push((name.clone(), rest.join("/")));
+            }
+            Segment::Param(name) => params.push((name.clone(), segments.next()?.to_string())),
+            Segment::Literal(literal) => {
+                if segments.next()? != literal {
+                    return None;
+                }
+            }
+        }
+    }
+    segments.next().is_none().then_some(params)
+}
+
+/// Serves files under `root`, taking the path from the route's `*path`.
+#[derive(Debug, Clone)]
+pub struct StaticFiles {
+    root: PathBuf,
+}
+
+impl StaticFiles {
+    pub fn new(root: impl Into<PathBuf>) -> StaticFiles {
+        StaticFiles { root: root.into() }
+    }
+
+    /// `path` under the root, unless it tries to climb out of it
+    fn resolve(&self, path: &str) -> Option<PathBuf> {
+        let path = Path::new(path);
+        path.components()
+            .all(|component| matches!(component, Component::Normal(_)))
+            .then(|| self.root.join(path))
+    }
+}
+
+impl Handler for StaticFiles {
+    fn call<'a>(&'a self, request: &'a mut Request<'_>) -> BoxFuture<'a, Result> {
+        async move {
+            let path = request.param("path").unwrap_or_default();
+            let not_found = || Response::text(Status::NOT_FOUND, "not found\n");
+            let Some(path) = self.resolve(path) else {
+                return Ok(not_found());
+            };
+            match Response::file(Status::OK, &path).await {
+                Ok(response) => Ok(response),
+                Err(error)
+                    if matches!(
+                        error.kind(),
+                        io::ErrorKind::NotFound | io::ErrorKind::PermissionDenied
+                    ) =>
+                {
+                    Ok(not_found())
+                }
+                Err(error) => Err(error.into()),
+            }
+        }
+        .boxed()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn params(pattern: &str, path: &str) -> Option<Vec<(String, String)>> {
+        matches(&parse_pattern(pattern), path)
+    }
+
+    #[test]
+    fn matches_literals_params_and_rests() {
+        assert_eq!(params("/", "/"), Some(vec![]));
+        assert_eq!(params("/sleep", "/sleep"), Some(vec![]));
+        assert_eq!(params("/sleep", "/sleep/more"), None);
+        assert_eq!(params("/sleep", "/"), None);
+        assert_eq!(
+            params("/users/:id", "/users/7"),
+            Some(vec![("id".to_string(), "7".to_string())])
+        );
+        assert_eq!(params("/users/:id", "/users"), None);
+        assert_eq!(
+            params("/static/*path", "/static/css/site.css"),
+            Some(vec![("path".to_string(), "css/site.css".to_string())])
+        );
+        assert_eq!(
+            params("/static/*path", "/static"),
+            Some(vec![("path".to_string(), String::new())])
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "*name must come last")]
+    fn rests_come_last() {
+        parse_pattern("/static/*path/more");
+    }
+
+    #[test]
+    fn static_files_stay_under_their_root() {
+        let files = StaticFiles::new("public");
+        assert_eq!(
+            files.resolve("css/site.css"),
+            Some(PathBuf::from("public/css/site.css"))
+        );
+        assert_eq!(files.resolve("../Cargo.toml"), None);
+        assert_eq!(files.resolve("css/../../secret"), None);
+        assert_eq!(files.resolve("/etc/passwd"), None);
+    }
+}
@@ -0,0 +1,504 @@
+//! A multi-threaded executor with work stealing.
+//!
+//! Each worker thread keeps its own FIFO queue of tasks. Tasks woken on a
+//! worker go to that worker's queue; tasks spawned or woken anywhere else go to
+//! a global injector queue. A worker with nothing to do takes a batch from the
+//! injector, then tries to steal half of another worker's queue, and only when
+//! all of that comes up empty does it sleep until something is scheduled.
+//!
+//! A separate driver thread owns the epoll [reactor](crate::reactor) and the
+//! [timer wheel](crate::timer): it sleeps in `epoll_wait` until a socket is
+//! ready or the next timer is due, and wakes the tasks waiting on them.
+
+use std::{
+    any::Any,
+    cell::RefCell,
+    collections::HashMap,
+    future::Future,
+    io,
+    panic::{self, AssertUnwindSafe},
+    pin::{pin, Pin},
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc, Condvar, Mutex,
+    },
+    task::{Context, Poll, Wake, Waker},
+    thread::{self, JoinHandle as ThreadHandle, Thread},
+};
+
+use crossbeam_deque::{Injector, Steal, Stealer, Worker};
+use futures::future::{BoxFuture, FutureExt};
+
+use crate::{reactor::Reactor, timer::Timers};
+
+/// The executor, its worker threads and its driver thread. Dropping it stops
+/// them all and drops every task that hasn't finished.
+#[derive(Debug)]
+pub struct Runtime {
+    handle: Handle,
+    workers: Vec<ThreadHandle<()>>,
+    driver: Option<ThreadHandle<()>>,
+}
+
+/// A cheap, cloneable reference to a [`Runtime`] for spawning onto it.
+#[derive(Debug, Clone)]
+pub struct Handle {
+    shared: Arc<Shared>,
+}
+
+/// What the threads of a runtime share.
+struct Shared {
+    injector: Injector<Arc<Task>>,
+    stealers: Vec<Stealer<Arc<Task>>>,
+    /// Every task that hasn't finished, so shutdown can drop their futures.
+    tasks: Mutex<HashMap<usize, Arc<Task>>>,
+    next_id: AtomicUsize,
+    /// Number of workers asleep on `wakeup`.
+    idle: Mutex<usize>,
+    wakeup: Condvar,
+    shutdown: AtomicBool,
+    driver: Driver,
+}
+
+/// The reactor and the timers, both run by the driver thread.
+#[derive(Debug)]
+pub(crate) struct Driver {
+    pub(crate) reactor: Reactor,
+    pub(crate) timers: Timers,
+}
+
+/// A spawned future. The `Mutex` only ever sees contention when a task is woken
+/// while it's being polled and another worker picks it up straight away.
+struct Task {
+    id: usize,
+    future: Mutex<Option<BoxFuture<'static, ()>>>,
+    /// Set while the task sits in a queue, so waking it twice queues it once.
+    scheduled: AtomicBool,
+    shared: Arc<Shared>,
+}
+
+/// A worker's own queue, with the runtime it belongs to.
+type LocalQueue = (Arc<Shared>, Worker<Arc<Task>>);
+
+thread_local! {
+    /// The runtime whose task, worker or `block_on` this thread is running.
+    static CURRENT: RefCell<Option<Handle>> = const { RefCell::new(None) };
+    /// The queue of the worker running on this thread, if it is one.
+    static LOCAL: RefCell<Option<LocalQueue>> = const { RefCell::new(None) };
+}
+
+impl Runtime {
+    /// A runtime with `workers` worker threads.
+    ///
+    /// # Panics
+    ///
+    /// If `workers` is zero.
+    pub fn new(workers: usize) -> io::Result<Runtime> {
+        assert!(workers > 0, "a runtime needs at least one worker");
+        let queues: Vec<Worker<Arc<Task>>> = (0..workers).map(|_| Worker::new_fifo()).collect();
+        let shared = Arc::new(Shared {
+            injector: Injector::new(),
+            stealers: queues.iter().map(Worker::stealer).collect(),
+            tasks: Mutex::default(),
+            next_id: AtomicUsize::new(0),
+            idle: Mutex::new(0),
+            wakeup: Condvar::new(),
+            shutdown: AtomicBool::new(false),
+            driver: Driver {
+                reactor: Reactor::new()?,
+                timers: Timers::new(),
+            },
+        });
+        let workers = queues
+            .into_iter()
+            .enumerate()
+            .map(|(index, queue)| {
+                let shared = Arc::clone(&shared);
+                thread::Builder::new()
+                    .name(format!("worker-{index}"))
+                    .spawn(move || shared.run_worker(index, queue))
+            })
+            .collect::<io::Result<_>>()?;
+        let driver = {
+            let shared = Arc::clone(&shared);
+            thread::Builder::new()
+                .name("driver".to_string())
+                .spawn(move || shared.run_driver())?
+        };
+        Ok(Runtime {
+            handle: Handle { shared },
+            workers,
+            driver: Some(driver),
+        })
+    }
+
+    pub fn handle(&self) -> &Handle {
+        &self.handle
+    }
+
+    /// Runs `future` on the worker threads.
+    pub fn spawn<F>(&self, future: F) -> JoinHandle<F::Output>
+    where
+        F: Future + Send + 'static,
+        F::Output: Send + 'static,
+    {
+        self.handle.spawn(future)
+    }
+
+    /// Runs `future` on this thread until it completes, with this runtime as
+    /// the current one so that it can [`spawn`], [`sleep`](crate::sleep) and do
+    /// I/O. Spawned tasks keep running on the workers meanwhile.
+    pub fn block_on<F: Future>(&self, future: F) -> F::Output {
+        let _enter = self.handle.enter();
+        let waker = Waker::from(Arc::new(ThreadWaker(thread::current())));
+        let mut cx = Context::from_waker(&waker);
+        let mut future = pin!(future);
+        loop {
+            if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+                return output;
+            }
+            // a wake before this returns straight away, so none is lost
+            thread::park();
+        }
+    }
+}
+
+impl Drop for Runtime {
+    fn drop(&mut self) {
+        let shared = &self.handle.shared;
+        shared.shutdown.store(true, Ordering::SeqCst);
+        drop(shared.idle.lock().unwrap());
+        shared.wakeup.notify_all();
+        shared.driver.reactor.notify();
+        for worker in self.workers.drain(..) {
+            // a worker only panics if a task's waker did, which is already reported
+            let _ = worker.join();
+        }
+        if let Some(driver) = self.driver.take() {
+            let _ = driver.join();
+        }
+        // Tasks hold the runtime, and the runtime holds their wakers: dropping
+        // the futures breaks the cycle, and wakes any `JoinHandle` awaiting them
+        let tasks: Vec<_> = shared
+            .tasks
+            .lock()
+            .unwrap()
+            .drain()
+            .map(|(_, task)| task)
+            .collect();
+        for task in tasks {
+            drop(task.future.lock().unwrap().take());
+        }
+        while !shared.injector.steal().is_empty() {}
+        for stealer in &shared.stealers {
+            while !stealer.steal().is_empty() {}
+        }
+    }
+}
+
+/// Unparks the thread in `block_on` when its future is woken.
+struct ThreadWaker(Thread);
+
+impl Wake for ThreadWaker {
+    fn wake(self: Arc<Self>) {
+        self.0.unpark()
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        self.0.unpark()
+    }
+}
+
+/// Makes a runtime current on this thread until dropped.
+struct Enter(Option<Handle>);
+
+impl Drop for Enter {
+    fn drop(&mut self) {
+        CURRENT.with(|current| *current.borrow_mut() = self.0.take());
+    }
+}
+
+impl Handle {
+    /// The runtime this thread is running in.
+    ///
+    /// # Panics
+    ///
+    /// Outside a runtime's worker threads and `block_on`.
+    pub fn current() -> Handle {
+        Handle::try_current().expect("must be called from within a runtime")
+    }
+
+    pub fn try_current() -> Option<Handle> {
+        CURRENT.with(|current| current.borrow().clone())
+    }
+
+    fn enter(&self) -> Enter {
+        Enter(CURRENT.with(|current| current.borrow_mut().replace(self.clone())))
+    }
+
+    pub(crate) fn driver(&self) -> &Driver {
+        &self.shared.driver
+    }
+
+    pub fn spawn<F>(&self, future: F) -> JoinHandle<F::Output>
+    where
+        F: Future + Send + 'static,
+        F::Output: Send + 'static,
+    {
+        let join = Arc::new(Mutex::new(JoinState {
+            output: None,
+            finished: false,
+            waker: None,
+        }));
+        let completion = Completion(Arc::clone(&join));
+        let future = async move {
+            let output = AssertUnwindSafe(future).catch_unwind().await;
+            completion.complete(output.map_err(Cancelled::Panicked));
+        };
+        let id = self.shared.next_id.fetch_add(1, Ordering::Relaxed);
+        let task = Arc::new(Task {
+            id,
+            future: Mutex::new(Some(future.boxed())),
+            scheduled: AtomicBool::new(true),
+            shared: Arc::clone(&self.shared),
+        });
+        if self.shared.shutdown.load(Ordering::SeqCst) {
+            // dropping the future tells the `JoinHandle`
+            drop(task.future.lock().unwrap().take());
+        } else {
+            self.shared
+                .tasks
+                .lock()
+                .unwrap()
+                .insert(id, Arc::clone(&task));
+            self.shared.schedule(task);
+        }
+        JoinHandle { state: join }
+    }
+}
+
+/// Runs `future` on the current runtime.
+///
+/// # Panics
+///
+/// Outside a runtime; see [`Handle::current`].
+pub fn spawn<F>(future: F) -> JoinHandle<F::Output>
+where
+    F: Future + Send + 'static,
+    F::Output: Send + 'static,
+{
+    Handle::current().spawn(future)
+}
+
+impl Shared {
+    fn schedule(self: &Arc<Self>, task: Arc<Task>) {
+        let task = LOCAL.with(|local| match &*local.borrow() {
+            Some((shared, queue)) if Arc::ptr_eq(shared, self) => {
+                queue.push(task);
+                None
+            }
+            _ => Some(task),
+        });
+        if let Some(task) = task {
+            self.injector.push(task);
+        }
+        // taking the lock orders the push before a worker's last look at the queues
+        if *self.idle.lock().unwrap() > 0 {
+            self.wakeup.notify_one();
+        }
+    }
+
+    fn run_worker(self: Arc<Self>, index: usize, queue: Worker<Arc<Task>>) {
+        let _enter = Handle {
+            shared: Arc::clone(&self),
+        }
+        .enter();
+        LOCAL.with(|local| *local.borrow_mut() = Some((Arc::clone(&self), queue)));
+        while !self.shutdown.load(Ordering::SeqCst) {
+            match self.find_task(index) {
+                Some(task) => task.run(),
+                None => self.sleep(),
+            }
+        }
+        LOCAL.with(|local| local.borrow_mut().take());
+    }
+
+    fn find_task(&self, index: usize) -> Option<Arc<Task>> {
+        LOCAL.with(|local| {
+            let local = local.borrow();
+            let (_, queue) = local.as_ref().expect("on a worker thread");
+            queue.pop().or_else(|| loop {
+                // start with the next worker along, so thieves spread out
+                let others = (1..self.stealers.len())
+                    .map(|offset| &self.stealers[(index + offset) % self.stealers.len()]);
+                let steal = self.injector.steal_batch_and_pop(queue).or_else(|| {
+                    others
+                        .map(|other| other.steal_batch_and_pop(queue))
+                        .collect()
+                });
+                match steal {
+                    Steal::Success(task) => return Some(task),
+                    Steal::Empty => return None,
+                    Steal::Retry => {}
+                }
+            })
+        })
+    }
+
+    fn has_work(&self) -> bool {
+        !self.injector.is_empty() || self.stealers.iter().any(|stealer| !stealer.is_empty())
+    }
+
+    fn sleep(&self) {
+        let mut idle = self.idle.lock().unwrap();
+        if self.has_work() || self.shutdown.load(Ordering::SeqCst) {
+            return;
+        }
+        *idle += 1;
+        idle = self.wakeup.wait(idle).unwrap();
+        *idle -= 1;
+    }
+
+    fn run_driver(self: Arc<Self>) {
+        let _enter = Handle {
+            shared: Arc::clone(&self),
+        }
+        .enter();
+        let driver = &self.driver;
+        while !self.shutdown.load(Ordering::SeqCst) {
+            let timeout = driver.timers.fire_due();
+            if let Err(error) = driver.reactor.wait(timeout) {
+                eprintln!("reactor failed: {error}");
+                return;
+            }
+        }
+    }
+}
+
+impl Task {
+    fn run(self: Arc<Self>) {
+        // cleared first: a wake during the poll has to queue it again
+        self.scheduled.store(false, Ordering::SeqCst);
+        let waker = Waker::from(Arc::clone(&self));
+        let mut cx = Context::from_waker(&waker);
+        let mut slot = self.future.lock().unwrap();
+        let Some(future) = slot.as_mut() else {
+            return;
+        };
+        // the future catches its own panics; see `Handle::spawn`
+        if future.as_mut().poll(&mut cx).is_ready() {
+            *slot = None;
+            self.shared.tasks.lock().unwrap().remove(&self.id);
+        }
+    }
+}
+
+impl Wake for Task {
+    fn wake(self: Arc<Self>) {
+        if !self.scheduled.swap(true, Ordering::SeqCst) {
+            let shared = Arc::clone(&self.shared);
+            shared.schedule(self);
+        }
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        Arc::clone(self).wake()
+    }
+}
+
+/// Awaits the output of a spawned task. Dropping it lets the task run on
+/// unobserved.
+#[derive(Debug)]
+pub struct JoinHandle<T> {
+    state: Arc<Mutex<JoinState<T>>>,
+}
+
+#[derive(Debug)]
+struct JoinState<T> {
+    output: Option<Result<T, Cancelled>>,
+    /// Stays set once `output` has been taken.
+    finished: bool,
+    waker: Option<Waker>,
+}
+
+/// Why a task finished without an output.
+enum Cancelled {
+    Panicked(Box<dyn Any + Send>),
+    /// The runtime shut down first.
+    Dropped,
+}
+
+impl std::fmt::Debug for Cancelled {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Cancelled::Panicked(_) => f.write_str("Panicked(..)"),
+            Cancelled::Dropped => f.write_str("Dropped"),
+        }
+    }
+}
+
+/// Owned by the task: reports its output, or that there won't be one.
+struct Completion<T>(Arc<Mutex<JoinState<T>>>);
+
+impl<T> Completion<T> {
+    fn complete(&self, output: Result<T, Cancelled>) {
+        let mut state = self.0.lock().unwrap();
+        if state.finished {
+            return;
+        }
+        state.output = Some(output);
+        state.finished = true;
+        if let Some(waker) = state.waker.take() {
+            waker.wake()
+        }
+    }
+}
+
+impl<T> Drop for Completion<T> {
+    fn drop(&mut self) {
+        self.complete(Err(Cancelled::Dropped))
+    }
+}
+
+impl<T> JoinHandle<T> {
+    pub fn is_finished(&self) -> bool {
+        self.state.lock().unwrap().finished
+    }
+}
+
+impl<T> Future for JoinHandle<T> {
+    type Output = T;
+
+    /// # Panics
+    ///
+    /// With the task's own panic if it panicked, or if the runtime shut down
+    /// before it finished.
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<T> {
+        let output = {
+            let mut state = self.state.lock().unwrap();
+            let output = state.output.take();
+            if output.is_none() {
+                state.waker = Some(cx.waker().clone());
+            }
+            output
+        };
+        // panicking with the lock held would poison it for the task
+        match output {
+            Some(Ok(output)) => Poll::Ready(output),
+            Some(Err(Cancelled::Panicked(payload))) => panic::resume_unwind(payload),
+            Some(Err(Cancelled::Dropped)) => {
+                panic!("the runtime shut down before the task finished")
+            }
+            None => Poll::Pending,
+        }
+    }
+}
+
+impl std::fmt::Debug for Shared {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Shared")
+            .field("workers", &self.stealers.len())
+            .field("shutdown", &self.shutdown)
+            .finish_non_exhaustive()
+    }
+}
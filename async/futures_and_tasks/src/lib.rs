@@ -0,0 +1,31 @@
+//! A small async runtime, grown out of the executor and `TimerFuture` from the
+//! async book's "Futures and Tasks" chapter.
+//!
+//! - [`Runtime`] runs tasks on several worker threads that steal work from
+//!   each other, and hands back a [`JoinHandle`] for each task's output.
+//! - [`sleep`] and [`timeout`] share one hierarchical timer wheel instead of a
+//!   thread per timer.
+//! - [`net`] does TCP through an epoll reactor, without async-std or tokio.
+//!
+//! ```no_run
+//! use futures_and_tasks::{sleep, spawn, Runtime};
+//! use std::time::Duration;
+//!
+//! let runtime = Runtime::new(4).unwrap();
+//! let answer = runtime.block_on(async {
+//!     let task = spawn(async {
+//!         sleep(Duration::from_millis(10)).await;
+//!         6 * 7
+//!     });
+//!     task.await
+//! });
+//! assert_eq!(answer, 42);
+//! ```
+
+pub mod executor;
+pub mod net;
+mod reactor;
+pub mod timer;
+
+pub use executor::{spawn, Handle, JoinHandle, Runtime};
+pub use timer::{sleep, sleep_until, timeout, Elapsed};
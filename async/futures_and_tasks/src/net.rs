@@ -0,0 +1,200 @@
+//! TCP on the runtime's reactor: non-blocking std sockets that park the task,
+//! rather than the thread, when the kernel has nothing for them.
+//!
+//! `TcpStream` implements the `futures` `AsyncRead` and `AsyncWrite` traits, so
+//! `futures::io::{AsyncReadExt, AsyncWriteExt, BufReader}` all work on it.
+
+use std::{
+    io::{self, Read, Write},
+    net::{self, Shutdown, SocketAddr, ToSocketAddrs},
+    os::fd::AsRawFd,
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
+};
+
+use futures::{
+    future::poll_fn,
+    io::{AsyncRead, AsyncWrite},
+};
+use socket2::{Domain, Protocol, Socket, Type};
+
+use crate::{
+    executor::Handle,
+    reactor::{Direction, Source},
+};
+
+/// A std socket registered with the reactor, deregistered again on drop.
+#[derive(Debug)]
+struct Registered<T: AsRawFd> {
+    io: T,
+    source: Arc<Source>,
+    handle: Handle,
+}
+
+impl<T: AsRawFd> Registered<T> {
+    fn new(io: T) -> io::Result<Registered<T>> {
+        let handle = Handle::current();
+        let source = handle.driver().reactor.register(io.as_raw_fd())?;
+        Ok(Registered { io, source, handle })
+    }
+}
+
+impl<T: AsRawFd> Drop for Registered<T> {
+    fn drop(&mut self) {
+        self.handle.driver().reactor.deregister(&self.source);
+    }
+}
+
+#[derive(Debug)]
+pub struct TcpListener {
+    inner: Registered<net::TcpListener>,
+}
+
+impl TcpListener {
+    /// Binds to the first of `addr`'s addresses that works.
+    ///
+    /// # Panics
+    ///
+    /// Outside a runtime, like everything else here.
+    pub async fn bind(addr: impl ToSocketAddrs) -> io::Result<TcpListener> {
+        // binding doesn't block, so there's nothing to wait for
+        let listener = net::TcpListener::bind(addr)?;
+        listener.set_nonblocking(true)?;
+        Ok(TcpListener {
+            inner: Registered::new(listener)?,
+        })
+    }
+
+    pub async fn accept(&self) -> io::Result<(TcpStream, SocketAddr)> {
+        let (stream, addr) = poll_fn(|cx| {
+            self.inner
+                .source
+                .poll_io(Direction::Read, cx, || self.inner.io.accept())
+        })
+        .await?;
+        stream.set_nonblocking(true)?;
+        Ok((TcpStream::from_std(stream)?, addr))
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.inner.io.local_addr()
+    }
+}
+
+#[derive(Debug)]
+pub struct TcpStream {
+    inner: Registered<net::TcpStream>,
+}
+
+impl TcpStream {
+    /// Connects to the first of `addr`'s addresses that accepts.
+    pub async fn connect(addr: impl ToSocketAddrs) -> io::Result<TcpStream> {
+        let mut last_error = None;
+        for addr in addr.to_socket_addrs()? {
+            match TcpStream::connect_addr(addr).await {
+                Ok(stream) => return Ok(stream),
+                Err(error) => last_error = Some(error),
+            }
+        }
+        Err(last_error.unwrap_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidInput, "no addresses to connect to")
+        }))
+    }
+
+    async fn connect_addr(addr: SocketAddr) -> io::Result<TcpStream> {
+        let socket = Socket::new(Domain::for_address(addr), Type::STREAM, Some(Protocol::TCP))?;
+        socket.set_nonblocking(true)?;
+        match socket.connect(&addr.into()) {
+            Ok(()) => {}
+            Err(error) if error.raw_os_error() == Some(libc::EINPROGRESS) => {}
+            Err(error) => return Err(error),
+        }
+        let stream = TcpStream::from_std(socket.into())?;
+        // writable once the handshake is over, one way or the other
+        poll_fn(|cx| stream.inner.source.poll_ready(Direction::Write, cx)).await;
+        match stream.inner.io.take_error()? {
+            Some(error) => Err(error),
+            None => Ok(stream),
+        }
+    }
+
+    fn from_std(stream: net::TcpStream) -> io::Result<TcpStream> {
+        Ok(TcpStream {
+            inner: Registered::new(stream)?,
+        })
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.inner.io.local_addr()
+    }
+
+    pub fn peer_addr(&self) -> io::Result<SocketAddr> {
+        self.inner.io.peer_addr()
+    }
+
+    pub fn shutdown(&self, how: Shutdown) -> io::Result<()> {
+        self.inner.io.shutdown(how)
+    }
+}
+
+/// Reading and writing only need a shared reference, so one task can read while
+/// another writes: `let (reader, writer) = (&stream, &stream);`
+impl AsyncRead for &TcpStream {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        let Registered { io, source, .. } = &self.inner;
+        source.poll_io(Direction::Read, cx, || (&*io).read(buf))
+    }
+}
+
+impl AsyncWrite for &TcpStream {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let Registered { io, source, .. } = &self.inner;
+        source.poll_io(Direction::Write, cx, || (&*io).write(buf))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        // nothing is buffered on this side of the kernel
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_close(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(self.shutdown(Shutdown::Write))
+    }
+}
+
+impl AsyncRead for TcpStream {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut &*self).poll_read(cx, buf)
+    }
+}
+
+impl AsyncWrite for TcpStream {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut &*self).poll_write(cx, buf)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut &*self).poll_flush(cx)
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut &*self).poll_close(cx)
+    }
+}
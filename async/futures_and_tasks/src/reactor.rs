@@ -0,0 +1,234 @@
+//! The reactor: one epoll instance watching every socket the runtime owns.
+//!
+//! Sockets are registered edge-triggered for both directions at once. An event
+//! marks the direction ready and wakes whoever was waiting on it; the waiter
+//! then retries its read or write until the kernel says `WouldBlock`, which
+//! clears the mark again. Each mark carries a tick so that an event arriving
+//! between the failed attempt and the clearing isn't lost.
+
+use std::{
+    collections::HashMap,
+    io,
+    os::fd::{AsRawFd, FromRawFd, OwnedFd, RawFd},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    task::{Context, Poll, Waker},
+    time::Duration,
+};
+
+/// The token of the eventfd that interrupts `epoll_wait`.
+const NOTIFY: u64 = 0;
+
+#[derive(Debug)]
+pub(crate) struct Reactor {
+    epoll: OwnedFd,
+    notify: OwnedFd,
+    sources: Mutex<HashMap<u64, Arc<Source>>>,
+    next_token: AtomicU64,
+}
+
+/// A registered socket's readiness, one per direction.
+#[derive(Debug)]
+pub(crate) struct Source {
+    fd: RawFd,
+    token: u64,
+    directions: [Mutex<Readiness>; 2],
+}
+
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum Direction {
+    Read = 0,
+    Write = 1,
+}
+
+#[derive(Debug, Default)]
+struct Readiness {
+    ready: bool,
+    /// Counts events, so a stale `clear_ready` can be told apart from a fresh one.
+    tick: u64,
+    waker: Option<Waker>,
+}
+
+fn check(result: libc::c_int) -> io::Result<libc::c_int> {
+    if result < 0 {
+        Err(io::Error::last_os_error())
+    } else {
+        Ok(result)
+    }
+}
+
+impl Reactor {
+    pub(crate) fn new() -> io::Result<Reactor> {
+        // Safety: plain syscalls; each fd is owned as soon as it's created
+        let epoll =
+            unsafe { OwnedFd::from_raw_fd(check(libc::epoll_create1(libc::EPOLL_CLOEXEC))?) };
+        let notify = unsafe {
+            OwnedFd::from_raw_fd(check(libc::eventfd(
+                0,
+                libc::EFD_CLOEXEC | libc::EFD_NONBLOCK,
+            ))?)
+        };
+        let reactor = Reactor {
+            epoll,
+            notify,
+            sources: Mutex::default(),
+            next_token: AtomicU64::new(NOTIFY + 1),
+        };
+        // level-triggered: it stays readable until `wait` drains it
+        reactor.ctl(
+            libc::EPOLL_CTL_ADD,
+            reactor.notify.as_raw_fd(),
+            libc::EPOLLIN as u32,
+            NOTIFY,
+        )?;
+        Ok(reactor)
+    }
+
+    fn ctl(&self, op: libc::c_int, fd: RawFd, events: u32, token: u64) -> io::Result<()> {
+        let mut event = libc::epoll_event { events, u64: token };
+        check(unsafe { libc::epoll_ctl(self.epoll.as_raw_fd(), op, fd, &mut event) })?;
+        Ok(())
+    }
+
+    /// Starts watching `fd`, which must already be non-blocking.
+    pub(crate) fn register(&self, fd: RawFd) -> io::Result<Arc<Source>> {
+        let token = self.next_token.fetch_add(1, Ordering::Relaxed);
+        let source = Arc::new(Source {
+            fd,
+            token,
+            directions: Default::default(),
+        });
+        self.sources
+            .lock()
+            .unwrap()
+            .insert(token, Arc::clone(&source));
+        let events = libc::EPOLLIN | libc::EPOLLOUT | libc::EPOLLRDHUP | libc::EPOLLET;
+        if let Err(error) = self.ctl(libc::EPOLL_CTL_ADD, fd, events as u32, token) {
+            self.sources.lock().unwrap().remove(&token);
+            return Err(error);
+        }
+        Ok(source)
+    }
+
+    /// Stops watching a source; call before closing its fd.
+    pub(crate) fn deregister(&self, source: &Source) {
+        self.sources.lock().unwrap().remove(&source.token);
+        // fails only if the fd is already closed, which removed it anyway
+        let _ = self.ctl(libc::EPOLL_CTL_DEL, source.fd, 0, source.token);
+    }
+
+    /// Interrupts a `wait` in progress, or the next one.
+    pub(crate) fn notify(&self) {
+        let one = 1u64.to_ne_bytes();
+        // a full counter is just as good at waking the driver
+        let _ = unsafe { libc::write(self.notify.as_raw_fd(), one.as_ptr().cast(), one.len()) };
+    }
+
+    /// Blocks until a socket is ready, `notify` is called or `timeout` runs
+    /// out, then wakes every task waiting on what became ready.
+    pub(crate) fn wait(&self, timeout: Option<Duration>) -> io::Result<()> {
+        let mut events = [libc::epoll_event { events: 0, u64: 0 }; 64];
+        let timeout = match timeout {
+            // round up, or a timer a fraction of a millisecond away would spin
+            Some(timeout) => timeout
+                .as_nanos()
+                .div_ceil(1_000_000)
+                .min(libc::c_int::MAX as u128) as libc::c_int,
+            None => -1,
+        };
+        let n = match check(unsafe {
+            libc::epoll_wait(
+                self.epoll.as_raw_fd(),
+                events.as_mut_ptr(),
+                events.len() as libc::c_int,
+                timeout,
+            )
+        }) {
+            Ok(n) => n as usize,
+            Err(error) if error.kind() == io::ErrorKind::Interrupted => return Ok(()),
+            Err(error) => return Err(error),
+        };
+        let sources = self.sources.lock().unwrap();
+        for event in &events[..n] {
+            let (flags, token) = (event.events, event.u64);
+            if token == NOTIFY {
+                let mut count = [0u8; 8];
+                let _ = unsafe {
+                    libc::read(
+                        self.notify.as_raw_fd(),
+                        count.as_mut_ptr().cast(),
+                        count.len(),
+                    )
+                };
+                continue;
+            }
+            let Some(source) = sources.get(&token) else {
+                continue;
+            };
+            // errors and hang-ups wake both sides so they find out for themselves
+            let hangup = (libc::EPOLLERR | libc::EPOLLHUP) as u32;
+            if flags & (libc::EPOLLIN | libc::EPOLLRDHUP) as u32 | hangup != 0 {
+                source.wake(Direction::Read);
+            }
+            if flags & libc::EPOLLOUT as u32 | hangup != 0 {
+                source.wake(Direction::Write);
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Source {
+    fn wake(&self, direction: Direction) {
+        let mut readiness = self.directions[direction as usize].lock().unwrap();
+        readiness.ready = true;
+        readiness.tick += 1;
+        if let Some(waker) = readiness.waker.take() {
+            waker.wake()
+        }
+    }
+
+    /// Ready when the last attempt in `direction` may no longer block; gives the
+    /// tick to pass to `clear_ready` if it does.
+    pub(crate) fn poll_ready(&self, direction: Direction, cx: &mut Context<'_>) -> Poll<u64> {
+        let mut readiness = self.directions[direction as usize].lock().unwrap();
+        if readiness.ready {
+            Poll::Ready(readiness.tick)
+        } else {
+            readiness.waker = Some(cx.waker().clone());
+            Poll::Pending
+        }
+    }
+
+    /// Records that an attempt made at `tick` would have blocked.
+    pub(crate) fn clear_ready(&self, direction: Direction, tick: u64) {
+        let mut readiness = self.directions[direction as usize].lock().unwrap();
+        if readiness.tick == tick {
+            readiness.ready = false;
+        }
+    }
+
+    /// Retries `op` each time `direction` becomes ready until it stops saying
+    /// `WouldBlock`.
+    pub(crate) fn poll_io<T>(
+        &self,
+        direction: Direction,
+        cx: &mut Context<'_>,
+        mut op: impl FnMut() -> io::Result<T>,
+    ) -> Poll<io::Result<T>> {
+        loop {
+            let tick = match self.poll_ready(direction, cx) {
+                Poll::Ready(tick) => tick,
+                Poll::Pending => return Poll::Pending,
+            };
+            match op() {
+                Err(error) if error.kind() == io::ErrorKind::WouldBlock => {
+                    self.clear_ready(direction, tick)
+                }
+                result => return Poll::Ready(result),
+            }
+        }
+    }
+}
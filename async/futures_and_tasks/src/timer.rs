@@ -0,0 +1,496 @@
+//! Timers: a hierarchical wheel of millisecond ticks, driven by the runtime's
+//! driver thread, and the `sleep`/`timeout` futures built on it.
+//!
+//! The wheel has `LEVELS` levels of `SLOTS` slots. A slot on level 0 is one
+//! tick, a slot on level 1 is 64 ticks, and so on, so six levels cover a little
+//! over two years. A timer goes in the lowest level whose slot can tell its
+//! deadline apart from now; when time reaches a slot on a higher level its
+//! timers cascade down to finer slots until they land in level 0 and fire.
+//! Inserting and firing are O(1), however many timers are pending.
+
+use std::{
+    fmt,
+    future::Future,
+    mem,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    task::{Context, Poll, Waker},
+    time::{Duration, Instant},
+};
+
+use crate::executor::Handle;
+
+const SLOT_BITS: u32 = 6;
+const SLOTS: usize = 1 << SLOT_BITS;
+const LEVELS: usize = 6;
+/// The furthest a timer can be from now; later deadlines are clamped to it.
+const MAX_TICKS: u64 = (1 << (SLOT_BITS * LEVELS as u32)) - 1;
+
+/// One pending timer. The wheel and the `Sleep` waiting on it share it.
+#[derive(Debug)]
+pub(crate) struct Entry {
+    deadline: u64,
+    state: Mutex<EntryState>,
+}
+
+#[derive(Debug, Default)]
+struct EntryState {
+    fired: bool,
+    waker: Option<Waker>,
+    /// The level and slot it's filed under, while the wheel holds it.
+    slot: Option<(usize, usize)>,
+}
+
+impl Entry {
+    fn new(deadline: u64) -> Arc<Entry> {
+        Arc::new(Entry {
+            deadline,
+            state: Mutex::default(),
+        })
+    }
+
+    fn fire(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.fired = true;
+        if let Some(waker) = state.waker.take() {
+            waker.wake()
+        }
+    }
+}
+
+/// The wheel itself, counting in ticks: it knows nothing about clocks, which
+/// makes it easy to test.
+#[derive(Debug)]
+pub(crate) struct Wheel {
+    /// Every timer due at or before this tick has fired.
+    elapsed: u64,
+    levels: [Level; LEVELS],
+}
+
+#[derive(Debug)]
+struct Level {
+    /// Bit `n` is set when `slots[n]` has timers in it.
+    occupied: u64,
+    slots: [Vec<Arc<Entry>>; SLOTS],
+}
+
+/// The next slot to process, and the tick at which it starts.
+#[derive(Debug, PartialEq)]
+struct Expiration {
+    level: usize,
+    slot: usize,
+    deadline: u64,
+}
+
+impl Wheel {
+    pub(crate) fn new() -> Wheel {
+        Wheel {
+            elapsed: 0,
+            levels: Default::default(),
+        }
+    }
+
+    /// Files `entry` under its deadline, or hands it back if that has passed.
+    fn insert(&mut self, entry: Arc<Entry>) -> Result<(), Arc<Entry>> {
+        if entry.deadline <= self.elapsed {
+            return Err(entry);
+        }
+        let deadline = entry.deadline.min(self.elapsed + MAX_TICKS);
+        // a clamped deadline can wrap around into the top level's past slots
+        let level = level_for(self.elapsed, deadline).min(LEVELS - 1);
+        let slot = slot_for(deadline, level);
+        entry.state.lock().unwrap().slot = Some((level, slot));
+        let level = &mut self.levels[level];
+        level.occupied |= 1 << slot;
+        level.slots[slot].push(entry);
+        Ok(())
+    }
+
+    /// Takes `entry` out of the wheel, if it's still waiting there.
+    fn remove(&mut self, entry: &Arc<Entry>) {
+        let Some((level, slot)) = entry.state.lock().unwrap().slot.take() else {
+            return;
+        };
+        let level = &mut self.levels[level];
+        let entries = &mut level.slots[slot];
+        if let Some(index) = entries.iter().position(|e| Arc::ptr_eq(e, entry)) {
+            entries.swap_remove(index);
+        }
+        if entries.is_empty() {
+            level.occupied &= !(1 << slot);
+        }
+    }
+
+    /// The tick at which the next slot is due, if anything is pending. A slot on
+    /// a higher level may only cascade its timers down, so this is a time to
+    /// look again rather than a promise that something fires.
+    pub(crate) fn next_deadline(&self) -> Option<u64> {
+        self.next_expiration().map(|expiration| expiration.deadline)
+    }
+
+    fn next_expiration(&self) -> Option<Expiration> {
+        self.levels
+            .iter()
+            .enumerate()
+            .find_map(|(level, slots)| slots.next_expiration(level, self.elapsed))
+    }
+
+    /// Moves time forward to `now`, returning the timers that fell due, in the
+    /// order of their deadlines.
+    pub(crate) fn advance(&mut self, now: u64) -> Vec<Arc<Entry>> {
+        let mut due = Vec::new();
+        while let Some(expiration) = self.next_expiration() {
+            if expiration.deadline > now {
+                break;
+            }
+            let level = &mut self.levels[expiration.level];
+            level.occupied &= !(1 << expiration.slot);
+            let entries = mem::take(&mut level.slots[expiration.slot]);
+            self.elapsed = self.elapsed.max(expiration.deadline);
+            for entry in entries {
+                // anything still in the future goes down a level
+                if let Err(entry) = self.insert(entry) {
+                    entry.state.lock().unwrap().slot = None;
+                    due.push(entry);
+                }
+            }
+        }
+        self.elapsed = self.elapsed.max(now);
+        due.sort_by_key(|entry| entry.deadline);
+        due
+    }
+}
+
+impl Default for Level {
+    fn default() -> Level {
+        Level {
+            occupied: 0,
+            slots: std::array::from_fn(|_| Vec::new()),
+        }
+    }
+}
+
+impl Level {
+    fn next_expiration(&self, level: usize, now: u64) -> Option<Expiration> {
+        if self.occupied == 0 {
+            return None;
+        }
+        let slot_ticks = 1u64 << (SLOT_BITS * level as u32);
+        let level_ticks = slot_ticks << SLOT_BITS;
+        let now_slot = slot_for(now, level);
+        let slot = (now_slot
+            + self.occupied.rotate_right(now_slot as u32).trailing_zeros() as usize)
+            % SLOTS;
+        let mut deadline = (now & !(level_ticks - 1)) + slot as u64 * slot_ticks;
+        if deadline < now {
+            // a clamped deadline that wrapped around the top level
+            deadline += level_ticks;
+        }
+        Some(Expiration {
+            level,
+            slot,
+            deadline,
+        })
+    }
+}
+
+/// The lowest level whose slots are coarse enough that `deadline` lands in a
+/// later slot than `now`.
+fn level_for(now: u64, deadline: u64) -> usize {
+    let significant = 63 - ((now ^ deadline) | (SLOTS as u64 - 1)).leading_zeros();
+    (significant / SLOT_BITS) as usize
+}
+
+fn slot_for(tick: u64, level: usize) -> usize {
+    ((tick >> (SLOT_BITS * level as u32)) as usize) & (SLOTS - 1)
+}
+
+/// The runtime's timers, shared between the driver thread and every `Sleep`.
+pub(crate) struct Timers {
+    start: Instant,
+    wheel: Mutex<Wheel>,
+    /// The tick the driver means to wake at; an earlier timer has to wake it.
+    driver_wakes_at: AtomicU64,
+}
+
+impl Timers {
+    pub(crate) fn new() -> Timers {
+        Timers {
+            start: Instant::now(),
+            wheel: Mutex::new(Wheel::new()),
+            driver_wakes_at: AtomicU64::new(u64::MAX),
+        }
+    }
+
+    /// Ticks are whole milliseconds since the runtime started, rounded up so
+    /// that no timer fires early.
+    fn tick_at(&self, instant: Instant) -> u64 {
+        let since = instant.saturating_duration_since(self.start);
+        let millis = since.as_millis() as u64;
+        if since > Duration::from_millis(millis) {
+            millis + 1
+        } else {
+            millis
+        }
+    }
+
+    fn now(&self) -> u64 {
+        self.start.elapsed().as_millis() as u64
+    }
+
+    /// Registers a timer for `deadline`. Returns whether the driver has to be
+    /// woken to notice it.
+    fn register(&self, deadline: Instant) -> (Arc<Entry>, bool) {
+        let entry = Entry::new(self.tick_at(deadline));
+        let mut wheel = self.wheel.lock().unwrap();
+        match wheel.insert(Arc::clone(&entry)) {
+            Ok(()) => {
+                let wake = entry.deadline < self.driver_wakes_at.load(Ordering::Acquire);
+                (entry, wake)
+            }
+            Err(entry) => {
+                entry.fire();
+                (entry, false)
+            }
+        }
+    }
+
+    /// Forgets a timer nobody is waiting for any more.
+    fn cancel(&self, entry: &Arc<Entry>) {
+        self.wheel.lock().unwrap().remove(entry);
+    }
+
+    /// Fires whatever is due, and says how long the driver may sleep before
+    /// something else is.
+    pub(crate) fn fire_due(&self) -> Option<Duration> {
+        let mut wheel = self.wheel.lock().unwrap();
+        let due = wheel.advance(self.now());
+        let next = wheel.next_deadline();
+        self.driver_wakes_at
+            .store(next.unwrap_or(u64::MAX), Ordering::Release);
+        drop(wheel);
+        for entry in due {
+            entry.fire();
+        }
+        next.map(|tick| Duration::from_millis(tick.saturating_sub(self.now())))
+    }
+}
+
+impl fmt::Debug for Timers {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Timers")
+            .field("start", &self.start)
+            .finish()
+    }
+}
+
+/// Waits until `duration` has passed. Must be awaited on a runtime. A duration
+/// too long for an `Instant` to hold, like `Duration::MAX`, never passes.
+pub fn sleep(duration: Duration) -> Sleep {
+    Sleep {
+        deadline: Instant::now().checked_add(duration),
+        entry: None,
+    }
+}
+
+/// Waits until `deadline`. Must be awaited on a runtime.
+pub fn sleep_until(deadline: Instant) -> Sleep {
+    Sleep {
+        deadline: Some(deadline),
+        entry: None,
+    }
+}
+
+/// Future returned by [`sleep`] and [`sleep_until`].
+///
+/// It only joins the wheel when first polled, and leaves it when dropped.
+#[derive(Debug)]
+#[must_use = "futures do nothing unless you `.await` or poll them"]
+pub struct Sleep {
+    /// `None` for a sleep that never ends.
+    deadline: Option<Instant>,
+    /// The runtime whose wheel holds the entry.
+    entry: Option<(Handle, Arc<Entry>)>,
+}
+
+impl Sleep {
+    /// When it ends - `None` if it never does.
+    pub fn deadline(&self) -> Option<Instant> {
+        self.deadline
+    }
+}
+
+impl Future for Sleep {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        let Some(deadline) = self.deadline else {
+            // nothing will ever wake it, so there's nothing to register
+            return Poll::Pending;
+        };
+        let (_, entry) = self.entry.get_or_insert_with(|| {
+            let handle = Handle::current();
+            let (entry, wake_driver) = handle.driver().timers.register(deadline);
+            if wake_driver {
+                handle.driver().reactor.notify();
+            }
+            (handle, entry)
+        });
+        let mut state = entry.state.lock().unwrap();
+        if state.fired {
+            Poll::Ready(())
+        } else {
+            // the task may have moved since the last poll, so always take the
+            // newest waker
+            state.waker = Some(cx.waker().clone());
+            Poll::Pending
+        }
+    }
+}
+
+impl Drop for Sleep {
+    fn drop(&mut self) {
+        if let Some((handle, entry)) = &self.entry {
+            handle.driver().timers.cancel(entry);
+        }
+    }
+}
+
+/// Runs `future`, giving up once `duration` has passed.
+pub fn timeout<F: Future>(duration: Duration, future: F) -> Timeout<F> {
+    Timeout {
+        future,
+        sleep: sleep(duration),
+    }
+}
+
+/// Future returned by [`timeout`].
+#[derive(Debug)]
+#[must_use = "futures do nothing unless you `.await` or poll them"]
+pub struct Timeout<F> {
+    future: F,
+    sleep: Sleep,
+}
+
+/// The error from a [`timeout`] that ran out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Elapsed;
+
+impl fmt::Display for Elapsed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("deadline has elapsed")
+    }
+}
+
+impl std::error::Error for Elapsed {}
+
+impl<F: Future> Future for Timeout<F> {
+    type Output = Result<F::Output, Elapsed>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        // Safety: neither field is moved out of; `sleep` is Unpin anyway
+        let this = unsafe { self.get_unchecked_mut() };
+        let future = unsafe { Pin::new_unchecked(&mut this.future) };
+        if let Poll::Ready(output) = future.poll(cx) {
+            return Poll::Ready(Ok(output));
+        }
+        Pin::new(&mut this.sleep).poll(cx).map(|()| Err(Elapsed))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn deadlines(entries: Vec<Arc<Entry>>) -> Vec<u64> {
+        entries.iter().map(|entry| entry.deadline).collect()
+    }
+
+    #[test]
+    fn levels_split_on_the_highest_differing_slot() {
+        assert_eq!(level_for(0, 1), 0);
+        assert_eq!(level_for(0, 63), 0);
+        assert_eq!(level_for(0, 64), 1);
+        assert_eq!(level_for(60, 70), 1);
+        assert_eq!(level_for(64, 127), 0);
+        assert_eq!(level_for(0, 64 * 64), 2);
+        assert_eq!(level_for(0, MAX_TICKS), LEVELS - 1);
+    }
+
+    #[test]
+    fn fires_timers_in_order_once_due() {
+        let mut wheel = Wheel::new();
+        for deadline in [5, 3, 64, 200, 4096 + 7, 3] {
+            wheel.insert(Entry::new(deadline)).unwrap();
+        }
+        assert_eq!(wheel.next_deadline(), Some(3));
+        assert_eq!(deadlines(wheel.advance(2)), []);
+        assert_eq!(deadlines(wheel.advance(4)), [3, 3]);
+        assert_eq!(deadlines(wheel.advance(64)), [5, 64]);
+        // 200 sits in a level 1 slot starting at 192; reaching it only cascades
+        assert_eq!(wheel.next_deadline(), Some(192));
+        assert_eq!(deadlines(wheel.advance(199)), []);
+        assert_eq!(wheel.next_deadline(), Some(200));
+        assert_eq!(deadlines(wheel.advance(10_000)), [200, 4096 + 7]);
+        assert_eq!(wheel.next_deadline(), None);
+    }
+
+    #[test]
+    fn cascades_through_every_level() {
+        let mut wheel = Wheel::new();
+        let deadline = 3 * 64u64.pow(4) + 2 * 64u64.pow(2) + 5;
+        wheel.insert(Entry::new(deadline)).unwrap();
+        let mut fired = Vec::new();
+        let mut steps = 0;
+        while let Some(next) = wheel.next_deadline() {
+            fired.extend(deadlines(wheel.advance(next)));
+            steps += 1;
+        }
+        assert_eq!(fired, [deadline]);
+        // one visit per level it passed through, not one per tick
+        assert!(steps <= LEVELS, "{steps} steps");
+    }
+
+    #[test]
+    fn hands_back_timers_already_due() {
+        let mut wheel = Wheel::new();
+        wheel.advance(100);
+        assert!(wheel.insert(Entry::new(100)).is_err());
+        assert!(wheel.insert(Entry::new(101)).is_ok());
+        assert_eq!(deadlines(wheel.advance(101)), [101]);
+    }
+
+    #[test]
+    fn removes_timers_wherever_they_have_cascaded_to() {
+        let mut wheel = Wheel::new();
+        let early = Entry::new(5);
+        let late = Entry::new(200);
+        wheel.insert(Arc::clone(&early)).unwrap();
+        wheel.insert(Arc::clone(&late)).unwrap();
+        wheel.remove(&early);
+        assert_eq!(wheel.next_deadline(), Some(192));
+        // down from level 1 to level 0
+        assert_eq!(deadlines(wheel.advance(192)), []);
+        wheel.remove(&late);
+        assert_eq!(wheel.next_deadline(), None);
+        assert_eq!(deadlines(wheel.advance(10_000)), []);
+        // removing twice, or after firing, does nothing
+        wheel.remove(&late);
+        let fired = Entry::new(10_001);
+        wheel.insert(Arc::clone(&fired)).unwrap();
+        assert_eq!(deadlines(wheel.advance(10_001)), [10_001]);
+        wheel.remove(&fired);
+    }
+
+    #[test]
+    fn clamps_deadlines_beyond_the_top_level() {
+        let mut wheel = Wheel::new();
+        wheel.advance(10);
+        wheel.insert(Entry::new(u64::MAX)).unwrap();
+        let next = wheel.next_deadline().unwrap();
+        assert!(next > 10 && next <= 10 + MAX_TICKS, "{next}");
+    }
+}
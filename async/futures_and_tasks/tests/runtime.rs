@@ -0,0 +1,212 @@
+//! The runtime end to end: tasks, timers and sockets on real threads.
+
+use std::{
+    collections::HashSet,
+    future::Future,
+    panic::{self, AssertUnwindSafe},
+    pin::Pin,
+    sync::{Arc, Mutex},
+    task::{Context, Poll},
+    thread,
+    time::{Duration, Instant},
+};
+
+use futures::{
+    future::join_all,
+    io::{AsyncReadExt, AsyncWriteExt},
+};
+use futures_and_tasks::{
+    net::{TcpListener, TcpStream},
+    sleep, spawn, timeout, Elapsed, Runtime,
+};
+
+/// Gives the worker back once, like a long computation checking in.
+struct YieldNow(bool);
+
+impl Future for YieldNow {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        if self.0 {
+            Poll::Ready(())
+        } else {
+            self.0 = true;
+            cx.waker().wake_by_ref();
+            Poll::Pending
+        }
+    }
+}
+
+fn thread_name() -> String {
+    thread::current().name().unwrap_or_default().to_string()
+}
+
+#[test]
+fn join_handles_return_outputs() {
+    let runtime = Runtime::new(4).unwrap();
+    let total = runtime.block_on(async {
+        let tasks = (0..1000u64).map(|i| {
+            spawn(async move {
+                for _ in 0..i % 5 {
+                    YieldNow(false).await;
+                }
+                i
+            })
+        });
+        join_all(tasks).await.into_iter().sum::<u64>()
+    });
+    assert_eq!(total, 999 * 1000 / 2);
+}
+
+#[test]
+fn idle_workers_steal_from_busy_ones() {
+    let runtime = Runtime::new(4).unwrap();
+    let threads = Arc::new(Mutex::new(HashSet::new()));
+    let spawner = {
+        let threads = Arc::clone(&threads);
+        // spawned from a worker, so every task starts out in that worker's queue
+        runtime.spawn(async move {
+            let tasks = (0..40).map(|_| {
+                let threads = Arc::clone(&threads);
+                spawn(async move {
+                    thread::sleep(Duration::from_millis(5));
+                    threads.lock().unwrap().insert(thread_name());
+                })
+            });
+            join_all(tasks.collect::<Vec<_>>()).await;
+        })
+    };
+    runtime.block_on(spawner);
+    let threads = threads.lock().unwrap();
+    assert!(threads.len() > 1, "all ran on {threads:?}");
+    assert!(threads.iter().all(|name| name.starts_with("worker-")));
+}
+
+#[test]
+fn join_handles_pass_on_panics() {
+    let runtime = Runtime::new(2).unwrap();
+    let task = runtime.spawn(async { panic!("oops") });
+    let payload = panic::catch_unwind(AssertUnwindSafe(|| runtime.block_on(task))).unwrap_err();
+    assert_eq!(payload.downcast_ref::<&str>(), Some(&"oops"));
+    // and the worker lives on
+    assert_eq!(runtime.block_on(runtime.spawn(async { 1 })), 1);
+}
+
+#[test]
+fn sleeps_wake_in_deadline_order() {
+    let runtime = Runtime::new(2).unwrap();
+    let woken = Arc::new(Mutex::new(Vec::new()));
+    let start = Instant::now();
+    runtime.block_on(async {
+        let tasks = [30, 10, 20, 0].map(|millis| {
+            let woken = Arc::clone(&woken);
+            spawn(async move {
+                sleep(Duration::from_millis(millis)).await;
+                assert!(start.elapsed() >= Duration::from_millis(millis));
+                woken.lock().unwrap().push(millis);
+            })
+        });
+        join_all(tasks).await;
+    });
+    assert_eq!(*woken.lock().unwrap(), [0, 10, 20, 30]);
+}
+
+#[test]
+fn thousands_of_timers_share_one_wheel() {
+    let runtime = Runtime::new(4).unwrap();
+    let start = Instant::now();
+    let fired = runtime.block_on(async {
+        let tasks = (0..10_000u64).map(|i| {
+            spawn(async move {
+                sleep(Duration::from_millis(i % 100)).await;
+                1
+            })
+        });
+        join_all(tasks).await.into_iter().sum::<u32>()
+    });
+    assert_eq!(fired, 10_000);
+    assert!(start.elapsed() < Duration::from_secs(5));
+}
+
+#[test]
+fn timeouts_give_up_or_pass_through() {
+    let runtime = Runtime::new(1).unwrap();
+    let start = Instant::now();
+    let late = runtime.block_on(timeout(
+        Duration::from_millis(20),
+        sleep(Duration::from_secs(10)),
+    ));
+    assert_eq!(late, Err(Elapsed));
+    assert!(start.elapsed() < Duration::from_secs(5));
+    let on_time = runtime.block_on(timeout(Duration::from_secs(10), async { 5 }));
+    assert_eq!(on_time, Ok(5));
+
+    // too far off for an `Instant`: never
+    let never = runtime.block_on(timeout(Duration::from_millis(20), sleep(Duration::MAX)));
+    assert_eq!(never, Err(Elapsed));
+    let no_limit = runtime.block_on(timeout(Duration::MAX, async { 5 }));
+    assert_eq!(no_limit, Ok(5));
+}
+
+#[test]
+fn dropping_the_runtime_cancels_what_is_left() {
+    let runtime = Runtime::new(2).unwrap();
+    let forever = runtime.spawn(sleep(Duration::from_secs(3600)));
+    let done = runtime.spawn(async {});
+    runtime.block_on(async {
+        while !done.is_finished() {
+            sleep(Duration::from_millis(1)).await;
+        }
+    });
+    assert!(!forever.is_finished());
+    drop(runtime);
+    assert!(forever.is_finished());
+}
+
+#[test]
+fn echoes_over_tcp() {
+    let runtime = Runtime::new(4).unwrap();
+    runtime.block_on(async {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        spawn(async move {
+            loop {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                spawn(async move {
+                    let mut buf = [0; 1024];
+                    loop {
+                        match stream.read(&mut buf).await.unwrap() {
+                            0 => break,
+                            n => stream.write_all(&buf[..n]).await.unwrap(),
+                        }
+                    }
+                });
+            }
+        });
+
+        let clients = (0..20).map(|i| {
+            spawn(async move {
+                let stream = TcpStream::connect(addr).await.unwrap();
+                // big enough to fill the socket buffers and block both sides
+                let message: Vec<u8> = (0..1 << 20).map(|b: u32| (b * i) as u8).collect();
+                let (mut reader, mut writer) = (&stream, &stream);
+                let write = async {
+                    writer.write_all(&message).await.unwrap();
+                    writer.close().await.unwrap();
+                };
+                let mut echoed = Vec::new();
+                let read = reader.read_to_end(&mut echoed);
+                let (_, read) = futures::join!(write, read);
+                read.unwrap();
+                echoed == message
+            })
+        });
+        assert!(join_all(clients).await.into_iter().all(|same| same));
+
+        let refused = {
+            let unused = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+            unused.local_addr().unwrap()
+        };
+        assert!(TcpStream::connect(refused).await.is_err());
+    });
+}
@@ -0,0 +1,125 @@
+//! Time-window batching.
+
+use std::{
+    pin::Pin,
+    task::{Context, Poll},
+    time::Duration,
+};
+
+use futures::{
+    future::{BoxFuture, FutureExt},
+    stream::{Fuse, FusedStream, Stream, StreamExt},
+};
+use pin_project_lite::pin_project;
+
+use crate::clock::Clock;
+
+pin_project! {
+    /// Created by [`StreamToolsExt::chunks_timeout`](crate::StreamToolsExt::chunks_timeout).
+    #[must_use = "streams do nothing unless polled"]
+    pub struct ChunksTimeout<S: Stream, C> {
+        #[pin]
+        stream: Fuse<S>,
+        clock: C,
+        max: usize,
+        window: Duration,
+        items: Vec<S::Item>,
+        // running from the first item of the batch
+        deadline: Option<BoxFuture<'static, ()>>,
+    }
+}
+
+impl<S: Stream, C: Clock> ChunksTimeout<S, C> {
+    pub(crate) fn new(stream: S, max: usize, window: Duration, clock: C) -> Self {
+        assert!(max > 0, "a batch holds at least one item");
+        ChunksTimeout {
+            stream: stream.fuse(),
+            clock,
+            max,
+            window,
+            items: Vec::with_capacity(max),
+            deadline: None,
+        }
+    }
+}
+
+impl<S: Stream, C: Clock> Stream for ChunksTimeout<S, C> {
+    type Item = Vec<S::Item>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let mut this = self.project();
+        loop {
+            match this.stream.as_mut().poll_next(cx) {
+                Poll::Ready(Some(item)) => {
+                    if this.items.is_empty() {
+                        *this.deadline = Some(this.clock.sleep(*this.window));
+                    }
+                    this.items.push(item);
+                    if this.items.len() == *this.max {
+                        *this.deadline = None;
+                        return Poll::Ready(Some(std::mem::take(this.items)));
+                    }
+                }
+                Poll::Ready(None) => {
+                    *this.deadline = None;
+                    if this.items.is_empty() {
+                        return Poll::Ready(None);
+                    }
+                    return Poll::Ready(Some(std::mem::take(this.items)));
+                }
+                Poll::Pending => break,
+            }
+        }
+        if let Some(deadline) = this.deadline {
+            if deadline.poll_unpin(cx).is_ready() {
+                *this.deadline = None;
+                return Poll::Ready(Some(std::mem::take(this.items)));
+            }
+        }
+        Poll::Pending
+    }
+}
+
+impl<S: Stream, C: Clock> FusedStream for ChunksTimeout<S, C> {
+    fn is_terminated(&self) -> bool {
+        self.stream.is_terminated() && self.items.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{test::Harness, StreamToolsExt};
+
+    const WINDOW: Duration = Duration::from_millis(100);
+
+    #[test]
+    fn full_batches_go_out_at_once() {
+        let mut harness = Harness::new(|input, clock| input.chunks_timeout(3, WINDOW, clock));
+        for i in 0..7 {
+            harness.send(i);
+        }
+        assert_eq!(harness.take(), [vec![0, 1, 2], vec![3, 4, 5]]);
+        harness.close();
+        assert_eq!(harness.take(), [vec![6]]);
+        assert!(harness.finished());
+    }
+
+    #[test]
+    fn partial_batches_go_out_when_the_window_closes() {
+        let mut harness = Harness::new(|input, clock| input.chunks_timeout(10, WINDOW, clock));
+        harness.send(1);
+        harness.advance(Duration::from_millis(60));
+        harness.send(2);
+        assert!(harness.take().is_empty());
+        // the window started with the first item, not the last
+        harness.advance(Duration::from_millis(40));
+        assert_eq!(harness.take(), [vec![1, 2]]);
+        // and nothing comes out of an idle stream
+        harness.advance(WINDOW * 5);
+        assert!(harness.take().is_empty());
+        harness.send(3);
+        harness.advance(WINDOW);
+        assert_eq!(harness.take(), [vec![3]]);
+    }
+}
@@ -0,0 +1,138 @@
+//! Where the adapters get the time from.
+//!
+//! Everything time-based in this crate asks a [`Clock`] instead of the system,
+//! so tests can hand it a [`MockClock`] and move time forward by hand.
+
+use std::{
+    future::Future,
+    pin::Pin,
+    sync::{Arc, Mutex},
+    task::{Context, Poll, Waker},
+    time::{Duration, Instant},
+};
+
+use futures::future::{BoxFuture, FutureExt};
+
+pub trait Clock: Clone + Send + Sync + 'static {
+    fn now(&self) -> Instant;
+
+    /// Resolves once `now()` has reached `deadline`.
+    fn sleep_until(&self, deadline: Instant) -> BoxFuture<'static, ()>;
+
+    fn sleep(&self, duration: Duration) -> BoxFuture<'static, ()> {
+        self.sleep_until(self.now() + duration)
+    }
+}
+
+/// The real time, sleeping on async-std's timers.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+
+    fn sleep_until(&self, deadline: Instant) -> BoxFuture<'static, ()> {
+        async_std::task::sleep(deadline.saturating_duration_since(Instant::now())).boxed()
+    }
+}
+
+/// A clock that stands still until [`advance`](MockClock::advance) is called.
+/// Clones share the same time.
+#[derive(Debug, Clone)]
+pub struct MockClock {
+    inner: Arc<Mutex<MockInner>>,
+}
+
+#[derive(Debug)]
+struct MockInner {
+    now: Instant,
+    sleepers: Vec<(Instant, Waker)>,
+}
+
+impl Default for MockClock {
+    fn default() -> MockClock {
+        MockClock::new()
+    }
+}
+
+impl MockClock {
+    pub fn new() -> MockClock {
+        MockClock {
+            inner: Arc::new(Mutex::new(MockInner {
+                now: Instant::now(),
+                sleepers: Vec::new(),
+            })),
+        }
+    }
+
+    /// Moves time forward and wakes every sleep that is now due.
+    pub fn advance(&self, duration: Duration) {
+        let due: Vec<Waker> = {
+            let mut inner = self.inner.lock().unwrap();
+            inner.now += duration;
+            let now = inner.now;
+            let (due, waiting) = inner
+                .sleepers
+                .drain(..)
+                .partition(|(deadline, _)| *deadline <= now);
+            inner.sleepers = waiting;
+            due.into_iter().map(|(_, waker)| waker).collect()
+        };
+        // outside the lock, a woken task may well ask the time right away
+        due.into_iter().for_each(Waker::wake);
+    }
+}
+
+impl Clock for MockClock {
+    fn now(&self) -> Instant {
+        self.inner.lock().unwrap().now
+    }
+
+    fn sleep_until(&self, deadline: Instant) -> BoxFuture<'static, ()> {
+        MockSleep {
+            clock: self.clone(),
+            deadline,
+        }
+        .boxed()
+    }
+}
+
+struct MockSleep {
+    clock: MockClock,
+    deadline: Instant,
+}
+
+impl Future for MockSleep {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        let mut inner = self.clock.inner.lock().unwrap();
+        if inner.now >= self.deadline {
+            return Poll::Ready(());
+        }
+        inner.sleepers.push((self.deadline, cx.waker().clone()));
+        Poll::Pending
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::task::noop_waker_ref;
+
+    #[test]
+    fn mock_sleeps_finish_when_time_is_advanced() {
+        let clock = MockClock::new();
+        let start = clock.now();
+        let mut cx = Context::from_waker(noop_waker_ref());
+        let mut sleep = clock.sleep(Duration::from_secs(2));
+        assert!(sleep.poll_unpin(&mut cx).is_pending());
+        clock.advance(Duration::from_secs(1));
+        assert!(sleep.poll_unpin(&mut cx).is_pending());
+        clock.advance(Duration::from_secs(1));
+        assert!(sleep.poll_unpin(&mut cx).is_ready());
+        assert_eq!(clock.now() - start, Duration::from_secs(2));
+    }
+}
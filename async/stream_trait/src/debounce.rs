@@ -0,0 +1,175 @@
+//! Thinning out bursts: [`Debounce`] waits for a burst to settle, [`Throttle`]
+//! lets its first item through and drops the rest.
+
+use std::{
+    pin::Pin,
+    task::{Context, Poll},
+    time::Duration,
+};
+
+use futures::{
+    future::{BoxFuture, FutureExt},
+    stream::{Fuse, FusedStream, Stream, StreamExt},
+};
+use pin_project_lite::pin_project;
+
+use crate::clock::Clock;
+
+pin_project! {
+    /// Created by [`StreamToolsExt::debounce`](crate::StreamToolsExt::debounce).
+    #[must_use = "streams do nothing unless polled"]
+    pub struct Debounce<S: Stream, C> {
+        #[pin]
+        stream: Fuse<S>,
+        clock: C,
+        quiet: Duration,
+        latest: Option<S::Item>,
+        // restarted by every item
+        sleep: Option<BoxFuture<'static, ()>>,
+    }
+}
+
+impl<S: Stream, C: Clock> Debounce<S, C> {
+    pub(crate) fn new(stream: S, quiet: Duration, clock: C) -> Self {
+        Debounce {
+            stream: stream.fuse(),
+            clock,
+            quiet,
+            latest: None,
+            sleep: None,
+        }
+    }
+}
+
+impl<S: Stream, C: Clock> Stream for Debounce<S, C> {
+    type Item = S::Item;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<S::Item>> {
+        let mut this = self.project();
+        loop {
+            match this.stream.as_mut().poll_next(cx) {
+                Poll::Ready(Some(item)) => {
+                    *this.latest = Some(item);
+                    *this.sleep = Some(this.clock.sleep(*this.quiet));
+                }
+                // the last burst doesn't have to wait for a quiet spell
+                Poll::Ready(None) => {
+                    *this.sleep = None;
+                    return Poll::Ready(this.latest.take());
+                }
+                Poll::Pending => break,
+            }
+        }
+        if let Some(sleep) = this.sleep {
+            if sleep.poll_unpin(cx).is_ready() {
+                *this.sleep = None;
+                return Poll::Ready(this.latest.take());
+            }
+        }
+        Poll::Pending
+    }
+}
+
+impl<S: Stream, C: Clock> FusedStream for Debounce<S, C> {
+    fn is_terminated(&self) -> bool {
+        self.stream.is_terminated() && self.latest.is_none()
+    }
+}
+
+pin_project! {
+    /// Created by [`StreamToolsExt::throttle`](crate::StreamToolsExt::throttle).
+    #[must_use = "streams do nothing unless polled"]
+    pub struct Throttle<S, C> {
+        #[pin]
+        stream: S,
+        clock: C,
+        interval: Duration,
+        // running after an item went through
+        gate: Option<BoxFuture<'static, ()>>,
+    }
+}
+
+impl<S: Stream, C: Clock> Throttle<S, C> {
+    pub(crate) fn new(stream: S, interval: Duration, clock: C) -> Self {
+        Throttle {
+            stream,
+            clock,
+            interval,
+            gate: None,
+        }
+    }
+}
+
+impl<S: Stream, C: Clock> Stream for Throttle<S, C> {
+    type Item = S::Item;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<S::Item>> {
+        let mut this = self.project();
+        loop {
+            let item = match this.stream.as_mut().poll_next(cx) {
+                Poll::Ready(Some(item)) => item,
+                other => return other,
+            };
+            if let Some(gate) = this.gate {
+                if gate.poll_unpin(cx).is_pending() {
+                    continue;
+                }
+            }
+            *this.gate = Some(this.clock.sleep(*this.interval));
+            return Poll::Ready(Some(item));
+        }
+    }
+}
+
+impl<S: FusedStream, C: Clock> FusedStream for Throttle<S, C> {
+    fn is_terminated(&self) -> bool {
+        self.stream.is_terminated()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{test::Harness, StreamToolsExt};
+
+    const MS: Duration = Duration::from_millis(1);
+
+    #[test]
+    fn debounce_waits_for_a_quiet_spell() {
+        let mut harness = Harness::new(|input, clock| input.debounce(MS * 50, clock));
+        harness.send(1);
+        harness.advance(MS * 30);
+        harness.send(2);
+        harness.advance(MS * 30);
+        harness.send(3);
+        harness.advance(MS * 49);
+        assert!(harness.take().is_empty());
+        harness.advance(MS);
+        assert_eq!(harness.take(), [3]);
+        harness.advance(MS * 500);
+        assert!(harness.take().is_empty());
+
+        harness.send(4);
+        harness.send(5);
+        harness.close();
+        assert_eq!(harness.take(), [5]);
+        assert!(harness.finished());
+    }
+
+    #[test]
+    fn throttle_drops_what_comes_too_soon() {
+        let mut harness = Harness::new(|input, clock| input.throttle(MS * 50, clock));
+        harness.send(1);
+        harness.send(2);
+        harness.advance(MS * 49);
+        harness.send(3);
+        harness.advance(MS);
+        harness.send(4);
+        harness.send(5);
+        harness.advance(MS * 100);
+        harness.send(6);
+        harness.close();
+        assert_eq!(harness.take(), [1, 4, 6]);
+        assert!(harness.finished());
+    }
+}
@@ -0,0 +1,196 @@
+//! Streams of records read off anything buffered: files, sockets, byte slices.
+
+use std::{
+    io,
+    path::Path,
+    pin::Pin,
+    task::{ready, Context, Poll},
+};
+
+use async_std::{
+    fs::File,
+    io::BufReader,
+    net::{TcpStream, ToSocketAddrs},
+};
+use futures::{
+    io::AsyncBufRead,
+    stream::{FusedStream, Stream, StreamExt},
+};
+use pin_project_lite::pin_project;
+
+/// The longest line [`file_lines`] and [`tcp_lines`] put up with.
+pub const MAX_LINE: usize = 64 * 1024;
+
+pin_project! {
+    /// Created by [`records`].
+    #[must_use = "streams do nothing unless polled"]
+    pub struct Records<R> {
+        #[pin]
+        reader: R,
+        delimiter: u8,
+        max_len: usize,
+        record: Vec<u8>,
+        done: bool,
+    }
+}
+
+/// Splits what `reader` gives on `delimiter`, which is left off. The last
+/// record needn't be terminated. One longer than `max_len` is an
+/// `InvalidData` error, after which the stream ends.
+pub fn records<R: AsyncBufRead>(reader: R, delimiter: u8, max_len: usize) -> Records<R> {
+    Records {
+        reader,
+        delimiter,
+        max_len,
+        record: Vec::new(),
+        done: false,
+    }
+}
+
+impl<R: AsyncBufRead> Stream for Records<R> {
+    type Item = io::Result<Vec<u8>>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let mut this = self.project();
+        while !*this.done {
+            let available = match ready!(this.reader.as_mut().poll_fill_buf(cx)) {
+                Ok(available) => available,
+                Err(error) => {
+                    *this.done = true;
+                    return Poll::Ready(Some(Err(error)));
+                }
+            };
+            if available.is_empty() {
+                *this.done = true;
+                if this.record.is_empty() {
+                    break;
+                }
+                return Poll::Ready(Some(Ok(std::mem::take(this.record))));
+            }
+            let end = available.iter().position(|byte| byte == this.delimiter);
+            let taken = end.unwrap_or(available.len());
+            if this.record.len() + taken > *this.max_len {
+                *this.done = true;
+                return Poll::Ready(Some(Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("record longer than {} bytes", this.max_len),
+                ))));
+            }
+            this.record.extend_from_slice(&available[..taken]);
+            match end {
+                Some(end) => {
+                    this.reader.as_mut().consume(end + 1);
+                    return Poll::Ready(Some(Ok(std::mem::take(this.record))));
+                }
+                None => this.reader.as_mut().consume(taken),
+            }
+        }
+        Poll::Ready(None)
+    }
+}
+
+impl<R: AsyncBufRead> FusedStream for Records<R> {
+    fn is_terminated(&self) -> bool {
+        self.done
+    }
+}
+
+/// The UTF-8 lines of `reader`, without their `\n` or `\r\n`.
+pub fn lines<R: AsyncBufRead>(
+    reader: R,
+    max_len: usize,
+) -> impl FusedStream<Item = io::Result<String>> {
+    records(reader, b'\n', max_len).map(|record| {
+        let mut line = String::from_utf8(record?)
+            .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))?;
+        if line.ends_with('\r') {
+            line.pop();
+        }
+        Ok(line)
+    })
+}
+
+/// The lines of the file at `path`.
+pub async fn file_lines(
+    path: impl AsRef<Path>,
+) -> io::Result<impl FusedStream<Item = io::Result<String>>> {
+    let file = File::open(path.as_ref()).await?;
+    Ok(lines(BufReader::new(file), MAX_LINE))
+}
+
+/// The lines sent by whoever is listening at `addr`, until they hang up.
+pub async fn tcp_lines(
+    addr: impl ToSocketAddrs,
+) -> io::Result<impl FusedStream<Item = io::Result<String>>> {
+    let stream = TcpStream::connect(addr).await?;
+    Ok(lines(BufReader::new(stream), MAX_LINE))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use async_std::{io::WriteExt, net::TcpListener, task};
+    use futures::{io::Cursor, TryStreamExt};
+
+    fn read_lines(input: &[u8], max_len: usize) -> Vec<io::Result<String>> {
+        task::block_on(lines(Cursor::new(input), max_len).collect())
+    }
+
+    #[test]
+    fn splits_lines_and_drops_their_endings() {
+        let lines: Vec<String> = read_lines(b"one\r\ntwo\n\nthree", 10)
+            .into_iter()
+            .collect::<io::Result<_>>()
+            .unwrap();
+        assert_eq!(lines, ["one", "two", "", "three"]);
+        assert!(read_lines(b"", 10).is_empty());
+    }
+
+    #[test]
+    fn records_split_on_any_delimiter_across_reads() {
+        // a tiny buffer makes records straddle several fills
+        let reader = futures::io::BufReader::with_capacity(2, Cursor::new(b"a,bcd,,efgh,"));
+        let records: Vec<Vec<u8>> =
+            task::block_on(records(reader, b',', 10).try_collect()).unwrap();
+        assert_eq!(records, [&b"a"[..], b"bcd", b"", b"efgh"]);
+    }
+
+    #[test]
+    fn long_lines_and_bad_utf8_are_invalid_data() {
+        let mut lines = read_lines(b"fits\nmuch too long\nnever read\n", 8).into_iter();
+        assert_eq!(lines.next().unwrap().unwrap(), "fits");
+        let error = lines.next().unwrap().unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        assert!(lines.next().is_none());
+
+        let error = read_lines(b"\xff\n", 8).remove(0).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn reads_files_and_sockets() {
+        let path = std::env::temp_dir().join(format!("stream_trait-{}.txt", std::process::id()));
+        std::fs::write(&path, "1\n2\n3\n").unwrap();
+        let from_file: Vec<String> = task::block_on(async {
+            file_lines(&path)
+                .await
+                .unwrap()
+                .try_collect()
+                .await
+                .unwrap()
+        });
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(from_file, ["1", "2", "3"]);
+
+        let from_socket: Vec<String> = task::block_on(async {
+            let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let addr = listener.local_addr().unwrap();
+            task::spawn(async move {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                stream.write_all(b"hello\r\nworld\r\n").await.unwrap();
+            });
+            tcp_lines(addr).await.unwrap().try_collect().await.unwrap()
+        });
+        assert_eq!(from_socket, ["hello", "world"]);
+    }
+}
@@ -0,0 +1,181 @@
+//! Stream adapters beyond what `StreamExt` offers, and streams of lines read
+//! off files and sockets to feed them.
+//!
+//! The adapters that care about time take a [`Clock`](clock::Clock): the
+//! [`SystemClock`](clock::SystemClock) in real code, a
+//! [`MockClock`](clock::MockClock) in tests, where time only moves when told to.
+//!
+//! ```no_run
+//! use std::time::Duration;
+//! use futures::StreamExt;
+//! use stream_trait::{clock::SystemClock, io::file_lines, StreamToolsExt};
+//!
+//! # async_std::task::block_on(async {
+//! let mut batches = file_lines("events.log")
+//!     .await?
+//!     .chunks_timeout(100, Duration::from_secs(1), SystemClock);
+//! while let Some(batch) = batches.next().await {
+//!     println!("{} lines", batch.len());
+//! }
+//! # std::io::Result::Ok(())
+//! # });
+//! ```
+
+use std::{future::Future, time::Duration};
+
+use futures::Stream;
+
+pub mod batch;
+pub mod clock;
+pub mod debounce;
+pub mod io;
+pub mod merge;
+pub mod retry;
+
+pub use batch::ChunksTimeout;
+pub use debounce::{Debounce, Throttle};
+pub use merge::{merge_by_key, MergeByKey};
+pub use retry::{BufferUnorderedRetry, Limits};
+
+use clock::Clock;
+
+pub trait StreamToolsExt: Stream + Sized {
+    /// Groups items into batches of up to `max`, sending a short batch when
+    /// `window` has passed since its first item.
+    ///
+    /// # Panics
+    ///
+    /// If `max` is zero.
+    fn chunks_timeout<C: Clock>(
+        self,
+        max: usize,
+        window: Duration,
+        clock: C,
+    ) -> ChunksTimeout<Self, C> {
+        ChunksTimeout::new(self, max, window, clock)
+    }
+
+    /// Holds each item back until `quiet` has passed without another, which
+    /// replaces it. So only the last of a burst goes out.
+    fn debounce<C: Clock>(self, quiet: Duration, clock: C) -> Debounce<Self, C> {
+        Debounce::new(self, quiet, clock)
+    }
+
+    /// Lets an item through, then drops everything else for `interval`.
+    fn throttle<C: Clock>(self, interval: Duration, clock: C) -> Throttle<Self, C> {
+        Throttle::new(self, interval, clock)
+    }
+
+    /// Like `buffer_unordered` over `self.map(f)`, within `limits`: how many
+    /// at once, how often one may start, and how often a failed item is tried
+    /// again. The item is cloned for each attempt.
+    ///
+    /// # Panics
+    ///
+    /// If `limits` allow no concurrency or no attempts.
+    fn buffer_unordered_retry<C, F, Fut, T, E>(
+        self,
+        limits: Limits,
+        clock: C,
+        f: F,
+    ) -> BufferUnorderedRetry<Self, C, F, T, E>
+    where
+        Self::Item: Clone + Send + 'static,
+        C: Clock,
+        F: FnMut(Self::Item) -> Fut,
+        Fut: Future<Output = Result<T, E>> + Send + 'static,
+        T: Send + 'static,
+        E: Send + 'static,
+    {
+        BufferUnorderedRetry::new(self, limits, clock, f)
+    }
+}
+
+impl<S: Stream> StreamToolsExt for S {}
+
+#[cfg(test)]
+pub(crate) mod test {
+    use std::{
+        cell::{Cell, RefCell},
+        rc::Rc,
+        time::Duration,
+    };
+
+    use futures::{channel::mpsc, executor::LocalPool, task::LocalSpawnExt, Stream, StreamExt};
+
+    use crate::clock::MockClock;
+
+    /// Drives a stream on one thread, feeding it from a channel and moving a
+    /// [`MockClock`] by hand. Each call runs everything until it is stuck again,
+    /// so what comes out only depends on the order of the calls.
+    pub(crate) struct Harness<T, U> {
+        pool: LocalPool,
+        clock: MockClock,
+        input: Option<mpsc::UnboundedSender<T>>,
+        output: Rc<RefCell<Vec<U>>>,
+        finished: Rc<Cell<bool>>,
+    }
+
+    impl<T: 'static, U: 'static> Harness<T, U> {
+        pub(crate) fn new<S>(
+            stream: impl FnOnce(mpsc::UnboundedReceiver<T>, MockClock) -> S,
+        ) -> Self
+        where
+            S: Stream<Item = U> + 'static,
+        {
+            let clock = MockClock::new();
+            let (input, receiver) = mpsc::unbounded();
+            let stream = stream(receiver, clock.clone());
+            let pool = LocalPool::new();
+            let output: Rc<RefCell<Vec<U>>> = Rc::default();
+            let finished = Rc::new(Cell::new(false));
+            {
+                let (output, finished) = (Rc::clone(&output), Rc::clone(&finished));
+                pool.spawner()
+                    .spawn_local(async move {
+                        futures::pin_mut!(stream);
+                        while let Some(item) = stream.next().await {
+                            output.borrow_mut().push(item);
+                        }
+                        finished.set(true);
+                    })
+                    .unwrap();
+            }
+            let mut harness = Harness {
+                pool,
+                clock,
+                input: Some(input),
+                output,
+                finished,
+            };
+            harness.pool.run_until_stalled();
+            harness
+        }
+
+        pub(crate) fn send(&mut self, item: T) {
+            self.input.as_ref().unwrap().unbounded_send(item).unwrap();
+            self.pool.run_until_stalled();
+        }
+
+        /// Ends the input.
+        pub(crate) fn close(&mut self) {
+            self.input = None;
+            self.pool.run_until_stalled();
+        }
+
+        pub(crate) fn advance(&mut self, duration: Duration) {
+            self.clock.advance(duration);
+            self.pool.run_until_stalled();
+        }
+
+        /// What came out since the last call.
+        pub(crate) fn take(&mut self) -> Vec<U> {
+            std::mem::take(&mut self.output.borrow_mut())
+        }
+
+        /// Whether the stream has ended.
+        pub(crate) fn finished(&self) -> bool {
+            self.finished.get()
+        }
+    }
+}
@@ -5,6 +5,7 @@ use futures::pin_mut;
 use futures::executor::block_on;
 use futures::channel::mpsc;
 use futures::SinkExt;
+use stream_trait::io::{lines, MAX_LINE};
 
 async fn async_main() {
     send_recv().await;
@@ -12,7 +13,15 @@ async fn async_main() {
     pin_mut!(s1);
     let x = sum_with_next(s1).await;
     println!("{x}"); 
-    //TODO: construct an IO stream of results as input
+
+    // an IO stream of results: lines off a reader, parsed as they come
+    let numbers = b"4\n5\n6\n";
+    let s2 = lines(&numbers[..], MAX_LINE).map(|line| {
+        line?.parse::<i32>().map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    });
+    pin_mut!(s2);
+    let y = sum_with_try_next(s2).await.unwrap();
+    println!("{y}");
 }
 
 fn main() {
@@ -47,7 +56,6 @@ async fn sum_with_next(mut stream: Pin<&mut dyn Stream<Item = i32>>) -> i32 {
 }
 
 
-#[allow(dead_code)]
 async fn sum_with_try_next(
     mut stream: Pin<&mut dyn Stream<Item = Result<i32, io::Error>>>,
 ) -> Result<i32, io::Error> {
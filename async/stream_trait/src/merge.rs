@@ -0,0 +1,147 @@
+//! Merging streams that are each sorted into one sorted stream.
+
+use std::{
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use futures::stream::{FusedStream, Stream, StreamExt};
+
+/// One of the inputs, with the item it has to offer.
+struct Input<S: Stream> {
+    stream: Pin<Box<S>>,
+    head: Option<S::Item>,
+    done: bool,
+}
+
+/// Created by [`merge_by_key`].
+#[must_use = "streams do nothing unless polled"]
+pub struct MergeByKey<S: Stream, F> {
+    inputs: Vec<Input<S>>,
+    key: F,
+}
+
+/// Interleaves `streams`, each already sorted by `key`, so that the result is
+/// sorted too. Equal keys come out in the order of the streams they came from.
+///
+/// An item can only go out once every unfinished stream has offered one to
+/// compare it with, so one slow input holds up the rest.
+pub fn merge_by_key<S, K, F>(streams: impl IntoIterator<Item = S>, key: F) -> MergeByKey<S, F>
+where
+    S: Stream,
+    K: Ord,
+    F: FnMut(&S::Item) -> K,
+{
+    MergeByKey {
+        inputs: streams
+            .into_iter()
+            .map(|stream| Input {
+                stream: Box::pin(stream),
+                head: None,
+                done: false,
+            })
+            .collect(),
+        key,
+    }
+}
+
+// the inputs are boxed and nothing else is ever pinned
+impl<S: Stream, F> Unpin for MergeByKey<S, F> {}
+
+impl<S, K, F> Stream for MergeByKey<S, F>
+where
+    S: Stream,
+    K: Ord,
+    F: FnMut(&S::Item) -> K,
+{
+    type Item = S::Item;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<S::Item>> {
+        let this = &mut *self;
+        let mut waiting = false;
+        for input in this.inputs.iter_mut() {
+            if input.head.is_some() || input.done {
+                continue;
+            }
+            match input.stream.poll_next_unpin(cx) {
+                Poll::Ready(Some(item)) => input.head = Some(item),
+                Poll::Ready(None) => input.done = true,
+                Poll::Pending => waiting = true,
+            }
+        }
+        if waiting {
+            return Poll::Pending;
+        }
+        let key = &mut this.key;
+        let first = this
+            .inputs
+            .iter_mut()
+            .filter(|input| input.head.is_some())
+            // `min_by_key` keeps the first of equals
+            .min_by_key(|input| key(input.head.as_ref().unwrap()));
+        Poll::Ready(first.and_then(|input| input.head.take()))
+    }
+}
+
+impl<S, K, F> FusedStream for MergeByKey<S, F>
+where
+    S: Stream,
+    K: Ord,
+    F: FnMut(&S::Item) -> K,
+{
+    fn is_terminated(&self) -> bool {
+        self.inputs
+            .iter()
+            .all(|input| input.done && input.head.is_none())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{clock::Clock, test::Harness};
+    use futures::stream::{self, BoxStream};
+    use std::time::Duration;
+
+    const MS: Duration = Duration::from_millis(1);
+
+    /// Yields each item once the clock has moved on by its delay.
+    fn timed<T: Send + 'static>(clock: impl Clock, items: Vec<(u64, T)>) -> BoxStream<'static, T> {
+        stream::iter(items)
+            .then(move |(delay, item)| {
+                let sleep = clock.sleep(MS * delay as u32);
+                async move {
+                    sleep.await;
+                    item
+                }
+            })
+            .boxed()
+    }
+
+    #[test]
+    fn merges_in_key_order() {
+        let mut harness = Harness::new(|input, clock| {
+            merge_by_key(
+                [
+                    input.boxed(),
+                    stream::iter([(2, 'b'), (5, 'b'), (6, 'b')]).boxed(),
+                    timed(clock, vec![(10, (1, 'c')), (10, (5, 'c'))]),
+                ],
+                |(key, _)| *key,
+            )
+        });
+        harness.send((3, 'a'));
+        // nothing until the slow stream has had its say
+        assert!(harness.take().is_empty());
+        harness.advance(MS * 10);
+        assert_eq!(harness.take(), [(1, 'c')]);
+        harness.advance(MS * 10);
+        assert_eq!(harness.take(), [(2, 'b'), (3, 'a')]);
+        harness.send((5, 'a'));
+        assert_eq!(harness.take(), [(5, 'a')]);
+        harness.send((7, 'a'));
+        assert_eq!(harness.take(), [(5, 'b'), (5, 'c'), (6, 'b'), (7, 'a')]);
+        harness.close();
+        assert!(harness.finished());
+    }
+}
@@ -0,0 +1,346 @@
+//! `buffer_unordered` with a speed limit, that gives failed items another go.
+
+use std::{
+    collections::VecDeque,
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+    time::{Duration, Instant},
+};
+
+use futures::{
+    future::{BoxFuture, FutureExt},
+    stream::{Fuse, FusedStream, FuturesUnordered, Stream, StreamExt},
+};
+use pin_project_lite::pin_project;
+
+use crate::clock::Clock;
+
+/// How hard [`StreamToolsExt::buffer_unordered_retry`](crate::StreamToolsExt::buffer_unordered_retry)
+/// may push.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Limits {
+    /// Items being worked on or waiting out a backoff at once.
+    pub concurrency: usize,
+    /// The least time between two attempts starting, retries included.
+    pub interval: Duration,
+    /// Attempts per item before its error is passed on.
+    pub attempts: u32,
+    /// The wait before the first retry, doubled for each one after it.
+    pub backoff: Duration,
+}
+
+impl Limits {
+    /// `concurrency` at a time as fast as they come, each tried once.
+    pub fn new(concurrency: usize) -> Limits {
+        Limits {
+            concurrency,
+            interval: Duration::ZERO,
+            attempts: 1,
+            backoff: Duration::ZERO,
+        }
+    }
+
+    pub fn interval(self, interval: Duration) -> Limits {
+        Limits { interval, ..self }
+    }
+
+    pub fn retries(self, attempts: u32, backoff: Duration) -> Limits {
+        Limits {
+            attempts,
+            backoff,
+            ..self
+        }
+    }
+
+    fn backoff(&self, failed_attempt: u32) -> Duration {
+        self.backoff
+            .saturating_mul(2u32.saturating_pow(failed_attempt - 1))
+    }
+}
+
+enum Event<I, T, E> {
+    Finished {
+        item: I,
+        attempt: u32,
+        result: Result<T, E>,
+    },
+    Rested {
+        item: I,
+        attempt: u32,
+    },
+}
+
+pin_project! {
+    /// Created by [`StreamToolsExt::buffer_unordered_retry`](crate::StreamToolsExt::buffer_unordered_retry).
+    #[must_use = "streams do nothing unless polled"]
+    pub struct BufferUnorderedRetry<S: Stream, C, F, T, E> {
+        #[pin]
+        stream: Fuse<S>,
+        clock: C,
+        f: F,
+        limits: Limits,
+        in_flight: FuturesUnordered<BoxFuture<'static, Event<S::Item, T, E>>>,
+        // rested retries, and an item held back by the interval
+        ready: VecDeque<(S::Item, u32)>,
+        next_start: Option<Instant>,
+        gate: Option<BoxFuture<'static, ()>>,
+    }
+}
+
+impl<S, C, F, Fut, T, E> BufferUnorderedRetry<S, C, F, T, E>
+where
+    S: Stream,
+    S::Item: Clone + Send + 'static,
+    C: Clock,
+    F: FnMut(S::Item) -> Fut,
+    Fut: Future<Output = Result<T, E>> + Send + 'static,
+    T: Send + 'static,
+    E: Send + 'static,
+{
+    pub(crate) fn new(stream: S, limits: Limits, clock: C, f: F) -> Self {
+        assert!(limits.concurrency > 0, "nothing would ever start");
+        assert!(limits.attempts > 0, "every item gets at least one attempt");
+        BufferUnorderedRetry {
+            stream: stream.fuse(),
+            clock,
+            f,
+            limits,
+            in_flight: FuturesUnordered::new(),
+            ready: VecDeque::new(),
+            next_start: None,
+            gate: None,
+        }
+    }
+}
+
+impl<S, C, F, Fut, T, E> Stream for BufferUnorderedRetry<S, C, F, T, E>
+where
+    S: Stream,
+    S::Item: Clone + Send + 'static,
+    C: Clock,
+    F: FnMut(S::Item) -> Fut,
+    Fut: Future<Output = Result<T, E>> + Send + 'static,
+    T: Send + 'static,
+    E: Send + 'static,
+{
+    type Item = Result<T, E>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let mut this = self.project();
+        loop {
+            // start as much as the limits allow
+            loop {
+                if let Some(gate) = this.gate {
+                    if gate.poll_unpin(cx).is_pending() {
+                        break;
+                    }
+                    *this.gate = None;
+                }
+                let (item, attempt) = match this.ready.pop_front() {
+                    Some(next) => next,
+                    None if this.in_flight.len() < this.limits.concurrency => {
+                        match this.stream.as_mut().poll_next(cx) {
+                            Poll::Ready(Some(item)) => (item, 1),
+                            _ => break,
+                        }
+                    }
+                    None => break,
+                };
+                let now = this.clock.now();
+                match *this.next_start {
+                    Some(next_start) if now < next_start => {
+                        *this.gate = Some(this.clock.sleep_until(next_start));
+                        this.ready.push_front((item, attempt));
+                        continue;
+                    }
+                    _ => *this.next_start = Some(now + this.limits.interval),
+                }
+                let work = (this.f)(item.clone());
+                this.in_flight.push(
+                    async move {
+                        Event::Finished {
+                            item,
+                            attempt,
+                            result: work.await,
+                        }
+                    }
+                    .boxed(),
+                );
+            }
+
+            match this.in_flight.poll_next_unpin(cx) {
+                Poll::Ready(Some(Event::Finished {
+                    item,
+                    attempt,
+                    result: Err(_),
+                })) if attempt < this.limits.attempts => {
+                    let rest = this.clock.sleep(this.limits.backoff(attempt));
+                    this.in_flight.push(
+                        async move {
+                            rest.await;
+                            Event::Rested {
+                                item,
+                                attempt: attempt + 1,
+                            }
+                        }
+                        .boxed(),
+                    );
+                }
+                Poll::Ready(Some(Event::Finished { result, .. })) => {
+                    return Poll::Ready(Some(result))
+                }
+                Poll::Ready(Some(Event::Rested { item, attempt })) => {
+                    this.ready.push_back((item, attempt))
+                }
+                Poll::Ready(None) if this.stream.is_terminated() && this.ready.is_empty() => {
+                    return Poll::Ready(None)
+                }
+                Poll::Ready(None) | Poll::Pending => return Poll::Pending,
+            }
+        }
+    }
+}
+
+impl<S, C, F, Fut, T, E> FusedStream for BufferUnorderedRetry<S, C, F, T, E>
+where
+    S: Stream,
+    S::Item: Clone + Send + 'static,
+    C: Clock,
+    F: FnMut(S::Item) -> Fut,
+    Fut: Future<Output = Result<T, E>> + Send + 'static,
+    T: Send + 'static,
+    E: Send + 'static,
+{
+    fn is_terminated(&self) -> bool {
+        self.stream.is_terminated() && self.in_flight.is_empty() && self.ready.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{clock::MockClock, test::Harness, StreamToolsExt};
+    use std::sync::{Arc, Mutex};
+
+    const MS: Duration = Duration::from_millis(1);
+
+    /// Records when each attempt at an item started, in milliseconds after `start`.
+    type Attempts = Arc<Mutex<Vec<(u32, u128)>>>;
+
+    fn record(attempts: &Attempts, clock: &MockClock, start: Instant, item: u32) {
+        attempts
+            .lock()
+            .unwrap()
+            .push((item, (clock.now() - start).as_millis()));
+    }
+
+    #[test]
+    fn no_more_than_the_limit_run_at_once() {
+        let mut harness = Harness::new(|input, clock| {
+            input.buffer_unordered_retry(Limits::new(2), clock.clone(), move |item: u32| {
+                let work = clock.sleep(MS * 100);
+                async move {
+                    work.await;
+                    Ok::<_, ()>(item)
+                }
+            })
+        });
+        for item in 0..5 {
+            harness.send(item);
+        }
+        assert!(harness.take().is_empty());
+        harness.advance(MS * 100);
+        assert_eq!(harness.take().len(), 2);
+        harness.advance(MS * 100);
+        assert_eq!(harness.take().len(), 2);
+        harness.close();
+        assert!(!harness.finished());
+        harness.advance(MS * 100);
+        assert_eq!(harness.take(), [Ok(4)]);
+        assert!(harness.finished());
+    }
+
+    #[test]
+    fn starts_are_spaced_out_by_the_interval() {
+        let attempts = Attempts::default();
+        let mut harness = Harness::new(|input, clock| {
+            let start = clock.now();
+            let limits = Limits::new(10).interval(MS * 10);
+            let attempts = Arc::clone(&attempts);
+            input.buffer_unordered_retry(limits, clock.clone(), move |item| {
+                record(&attempts, &clock, start, item);
+                async move { Ok::<_, ()>(item) }
+            })
+        });
+        for item in 0..3 {
+            harness.send(item);
+        }
+        assert_eq!(harness.take(), [Ok(0)]);
+        harness.advance(MS * 9);
+        assert!(harness.take().is_empty());
+        harness.advance(MS);
+        assert_eq!(harness.take(), [Ok(1)]);
+        harness.advance(MS * 25);
+        assert_eq!(harness.take(), [Ok(2)]);
+        assert_eq!(*attempts.lock().unwrap(), [(0, 0), (1, 10), (2, 35)]);
+    }
+
+    #[test]
+    fn failures_are_retried_with_a_growing_backoff() {
+        let attempts = Attempts::default();
+        let mut harness = Harness::new(|input, clock| {
+            let start = clock.now();
+            let limits = Limits::new(4).retries(3, MS * 10);
+            let attempts = Arc::clone(&attempts);
+            input.buffer_unordered_retry(limits, clock.clone(), move |item| {
+                record(&attempts, &clock, start, item);
+                let tries = attempts
+                    .lock()
+                    .unwrap()
+                    .iter()
+                    .filter(|(tried, _)| *tried == item)
+                    .count() as u32;
+                // item n succeeds on its nth attempt
+                async move {
+                    if tries >= item {
+                        Ok(item)
+                    } else {
+                        Err(format!("{item} failed"))
+                    }
+                }
+            })
+        });
+        for item in 1..=4 {
+            harness.send(item);
+        }
+        harness.close();
+        assert_eq!(harness.take(), [Ok(1)]);
+        harness.advance(MS * 10);
+        assert_eq!(harness.take(), [Ok(2)]);
+        harness.advance(MS * 19);
+        assert!(harness.take().is_empty());
+        harness.advance(MS);
+        let mut last = harness.take();
+        last.sort();
+        assert_eq!(last, [Ok(3), Err("4 failed".to_string())]);
+        assert!(harness.finished());
+
+        let mut attempts = attempts.lock().unwrap().clone();
+        attempts.sort();
+        assert_eq!(
+            attempts,
+            [
+                (1, 0),
+                (2, 0),
+                (2, 10),
+                (3, 0),
+                (3, 10),
+                (3, 30),
+                (4, 0),
+                (4, 10),
+                (4, 30),
+            ]
+        );
+    }
+}
@@ -0,0 +1,204 @@
+//! Computer controlled paddles.
+//!
+//! A [`Brain`] only sees the ball as it was `reaction_ticks` ago. Easier ones just chase
+//! that stale ball, harder ones work out where it will cross their paddle, bounces off the
+//! gutters included. Each time the ball heads their way they also pick where on the paddle
+//! to take it - and with probability `error_rate` they pick a spot off the paddle entirely.
+
+use std::collections::VecDeque;
+
+use bevy::ecs::component::Component;
+use bevy::ecs::system::Resource;
+use bevy::math::Vec2;
+
+use crate::sim::{Arena, BALL_SIZE, GUTTER_HEIGHT, PADDLE_HEIGHT, PADDLE_SPEED, PADDLE_WIDTH};
+
+#[derive(Resource, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Difficulty {
+    Easy,
+    #[default]
+    Medium,
+    Hard,
+}
+
+pub struct AiParams {
+    /// ticks between the ball moving and the AI noticing
+    pub reaction_ticks: usize,
+    /// chance of misjudging a ball coming its way
+    pub error_rate: f32,
+    /// aim for where the ball will be, rather than where it is
+    pub predictive: bool,
+}
+
+impl Difficulty {
+    pub const ALL: [Difficulty; 3] = [Difficulty::Easy, Difficulty::Medium, Difficulty::Hard];
+
+    pub fn params(self) -> AiParams {
+        match self {
+            Difficulty::Easy => AiParams {
+                reaction_ticks: 24,
+                error_rate: 0.3,
+                predictive: false,
+            },
+            Difficulty::Medium => AiParams {
+                reaction_ticks: 12,
+                error_rate: 0.15,
+                predictive: true,
+            },
+            Difficulty::Hard => AiParams {
+                reaction_ticks: 2,
+                error_rate: 0.01,
+                predictive: true,
+            },
+        }
+    }
+}
+
+#[derive(Component)]
+pub struct Brain {
+    params: AiParams,
+    rng: u64,
+    /// what the ball did over the last `reaction_ticks`, oldest first
+    seen: VecDeque<(Vec2, Vec2)>,
+    approaching: bool,
+    /// where on the paddle to take the ball, relative to its centre
+    aim: f32,
+}
+
+impl Brain {
+    pub fn new(difficulty: Difficulty, seed: u64) -> Self {
+        let params = difficulty.params();
+        Brain {
+            seen: VecDeque::with_capacity(params.reaction_ticks + 1),
+            params,
+            rng: seed,
+            approaching: false,
+            aim: 0.,
+        }
+    }
+
+    /// Paddle direction (-1, 0 or 1) for this tick, given the live ball and the paddle centre
+    pub fn decide(&mut self, ball: Vec2, velocity: Vec2, paddle: Vec2, arena: &Arena) -> f32 {
+        self.seen.push_back((ball, velocity));
+        if self.seen.len() > self.params.reaction_ticks + 1 {
+            self.seen.pop_front();
+        }
+        let (ball, velocity) = self.seen[0];
+
+        let approaching = (paddle.x - ball.x) * velocity.x > 0.;
+        if approaching && !self.approaching {
+            self.aim = self.pick_aim();
+        }
+        self.approaching = approaching;
+
+        let target = match (approaching, self.params.predictive) {
+            (true, true) => intercept_y(ball, velocity, paddle.x, arena) + self.aim,
+            (true, false) => ball.y + self.aim,
+            // wait in the middle for the return
+            (false, true) => 0.,
+            (false, false) => ball.y,
+        };
+        let distance = target - paddle.y;
+        if distance.abs() < PADDLE_SPEED {
+            0.
+        } else {
+            distance.signum()
+        }
+    }
+
+    fn pick_aim(&mut self) -> f32 {
+        let side = if self.next_f32() < 0.5 { -1. } else { 1. };
+        if self.next_f32() < self.params.error_rate {
+            // just past the paddle end
+            side * (PADDLE_HEIGHT / 2. + BALL_SIZE + self.next_f32() * PADDLE_HEIGHT / 2.)
+        } else {
+            // anywhere but the very edge
+            side * self.next_f32() * PADDLE_HEIGHT * 0.35
+        }
+    }
+
+    // SplitMix64
+    fn next_f32(&mut self) -> f32 {
+        self.rng = self.rng.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.rng;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^= z >> 31;
+        (z >> 40) as f32 / (1u64 << 24) as f32
+    }
+}
+
+/// y at which the ball will reach the face of the paddle at `paddle_x`,
+/// folding in bounces off the gutters
+pub fn intercept_y(ball: Vec2, velocity: Vec2, paddle_x: f32, arena: &Arena) -> f32 {
+    if velocity.x == 0. {
+        return ball.y;
+    }
+    let face = paddle_x - (PADDLE_WIDTH / 2. + BALL_SIZE) * paddle_x.signum();
+    let ticks = ((face - ball.x) / velocity.x).max(0.);
+    let limit = arena.height / 2. - GUTTER_HEIGHT - BALL_SIZE;
+    // unfold the bounces: the ball goes back and forth over a span of 2 * limit
+    let span = 2. * limit;
+    let unfolded = (ball.y + velocity.y * ticks + limit).rem_euclid(2. * span);
+    let folded = if unfolded > span {
+        2. * span - unfolded
+    } else {
+        unfolded
+    };
+    folded - limit
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn intercept_folds_gutter_bounces() {
+        let arena = Arena::default();
+        let limit = arena.height / 2. - GUTTER_HEIGHT - BALL_SIZE;
+        let face = arena.paddle_x(1) - PADDLE_WIDTH / 2. - BALL_SIZE;
+
+        // straight across
+        assert_eq!(
+            intercept_y(
+                Vec2::new(0., 30.),
+                Vec2::new(2., 0.),
+                arena.paddle_x(1),
+                &arena
+            ),
+            30.
+        );
+        // one bounce off the top gutter: climbs 2 * limit, so it comes back down to the start
+        let ticks = 2. * limit;
+        let velocity = Vec2::new(face / ticks, 1.);
+        let y = intercept_y(Vec2::ZERO, velocity, arena.paddle_x(1), &arena);
+        assert!(y.abs() < 0.01, "{y}");
+        // heading left, towards the left paddle
+        let y = intercept_y(Vec2::ZERO, Vec2::new(-1., -0.1), arena.paddle_x(0), &arena);
+        assert!((y + 0.1 * face).abs() < 0.01, "{y}");
+    }
+
+    #[test]
+    fn reacts_late() {
+        let arena = Arena::default();
+        let mut brain = Brain::new(Difficulty::Easy, 1);
+        let paddle = Vec2::new(arena.paddle_x(0), 0.);
+        // the ball was low for a while - it jumped up, but the brain still sees it low
+        for _ in 0..10 {
+            brain.decide(Vec2::new(0., -200.), Vec2::new(1., 0.), paddle, &arena);
+        }
+        assert_eq!(
+            brain.decide(Vec2::new(0., 200.), Vec2::new(1., 0.), paddle, &arena),
+            -1.
+        );
+    }
+
+    #[test]
+    fn misses_at_the_error_rate() {
+        let mut brain = Brain::new(Difficulty::Easy, 7);
+        let misses = (0..10_000)
+            .filter(|_| brain.pick_aim().abs() > PADDLE_HEIGHT / 2.)
+            .count();
+        assert!((2_700..3_300).contains(&misses), "{misses}");
+    }
+}
@@ -1,33 +1,172 @@
-use bevy::math::bounding::{Aabb2d, BoundingCircle, BoundingVolume, IntersectsVolume};
+mod ai;
+mod net;
+mod online;
+mod rollback;
+mod sim;
+mod states;
+
+use std::net::SocketAddr;
+
 use bevy::prelude::*;
 use bevy::sprite::MaterialMesh2dBundle;
-
+use bevy::window::WindowResolution;
+
+use ai::{Brain, Difficulty};
+use net::{NetSession, UdpTransport};
+use online::{online_tick, read_local_input, LocalInput, Online};
+use sim::{
+    bounce, move_paddle, scoring_side, serve_velocity, Arena, BALL_SIZE, GUTTER_HEIGHT,
+    PADDLE_HEIGHT, PADDLE_PADDING, PADDLE_WIDTH, TICK_RATE,
+};
+use states::{
+    check_match_end, game_over_input, menu_input, spawn_status_text, toggle_pause,
+    update_status_text, GameState, MatchRules,
+};
+
+/// `pong` plays against the AI,
+/// `pong --online <local addr> <peer addr> <0|1>` against another peer - 0 takes the left paddle
 fn main() {
-    App::new()
-        .add_plugins(DefaultPlugins)
-        .init_resource::<Score>()
-        .add_event::<Scored>()
-        .add_systems(
-            Startup,
-            (spawn_ball, spawn_paddles, spawn_gutters, spawn_scoreboard, spawn_camera),
-        )
-        .add_systems(
-            Update,
+    let arena = Arena::default();
+    let mut app = App::new();
+    app.add_plugins(DefaultPlugins.set(WindowPlugin {
+        primary_window: Some(Window {
+            // online peers must share the arena, so the window can't change it
+            resolution: WindowResolution::new(arena.width, arena.height),
+            resizable: false,
+            ..default()
+        }),
+        ..default()
+    }))
+    .insert_resource(arena)
+    .insert_resource(Time::<Fixed>::from_hz(TICK_RATE))
+    .init_resource::<Score>()
+    .init_resource::<MatchRules>()
+    .init_resource::<Difficulty>()
+    .add_event::<Scored>()
+    .add_systems(
+        Startup,
+        (
+            spawn_ball,
+            spawn_paddles,
+            spawn_gutters,
+            spawn_scoreboard,
+            spawn_status_text,
+            spawn_camera,
+        ),
+    )
+    .add_systems(
+        Update,
+        (
+            // keyboard_iter,
+            update_scoreboard,
+            update_status_text,
+            project_positions,
+        ),
+    );
+
+    match online_session(arena) {
+        Some(session) => {
+            // no menu or pause - the peer wouldn't wait
+            app.insert_state(GameState::Playing)
+                .insert_resource(Online(session))
+                .init_resource::<LocalInput>()
+                .add_systems(Update, read_local_input)
+                .add_systems(
+                    FixedUpdate,
+                    (online_tick, check_match_end.after(online_tick))
+                        .run_if(in_state(GameState::Playing)),
+                );
+        }
+        None => {
+            app.init_state::<GameState>()
+                .add_systems(
+                    Update,
+                    (
+                        menu_input.run_if(in_state(GameState::Menu)),
+                        toggle_pause,
+                        game_over_input.run_if(in_state(GameState::GameOver)),
+                        handle_player_input.run_if(in_state(GameState::Playing)),
+                    ),
+                )
+                .add_systems(OnExit(GameState::Menu), start_match)
+                .add_systems(OnExit(GameState::GameOver), reset_match);
+            add_match_systems(&mut app);
+        }
+    }
+    app.run();
+}
+
+/// Offline physics and scoring - all a match needs besides input and rendering
+fn add_match_systems(app: &mut App) {
+    app.add_systems(
+        FixedUpdate,
+        (
+            // same order as `SimState::step`, so both modes play the same
             (
-                // keyboard_iter,
+                move_ai,
+                move_paddles,
                 move_ball,
-                handle_player_input,
+                handle_collisions,
                 detect_scoring,
-                move_ai,
-                reset_ball.after(detect_scoring),
-                update_score.after(detect_scoring),
-                update_scoreboard.after(update_score),
-                move_paddles.after(handle_player_input),
-                project_positions.after(move_ball),
-                handle_collisions.after(move_ball),
-            ),
+            )
+                .chain(),
+            reset_ball.after(detect_scoring),
+            update_score.after(detect_scoring),
+            check_match_end.after(update_score),
         )
-        .run();
+            .run_if(in_state(GameState::Playing)),
+    );
+}
+
+fn start_match(mut commands: Commands, difficulty: Res<Difficulty>, ai: Query<Entity, With<Ai>>) {
+    let seed = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |d| d.as_nanos() as u64);
+    for entity in &ai {
+        commands
+            .entity(entity)
+            .insert(Brain::new(*difficulty, seed));
+    }
+}
+
+fn reset_match(
+    mut score: ResMut<Score>,
+    mut ball: Query<(&mut Position, &mut Velocity), With<Ball>>,
+    // only the ball and the paddles move
+    mut paddles: Query<(&mut Position, &mut Velocity), Without<Ball>>,
+) {
+    *score = Score::default();
+    if let Ok((mut position, mut velocity)) = ball.get_single_mut() {
+        position.0 = Vec2::ZERO;
+        velocity.0 = Vec2::new(1., 0.);
+    }
+    for (mut position, mut velocity) in &mut paddles {
+        position.0.y = 0.;
+        velocity.0 = Vec2::ZERO;
+    }
+}
+
+fn online_session(arena: Arena) -> Option<NetSession> {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let [flag, local, peer, side] = args.as_slice() else {
+        return None;
+    };
+    if flag != "--online" {
+        return None;
+    }
+    let local: SocketAddr = local.parse().expect("local address like 127.0.0.1:7000");
+    let peer: SocketAddr = peer.parse().expect("peer address like 127.0.0.1:7001");
+    let side: usize = match side.as_str() {
+        "0" => 0,
+        "1" => 1,
+        _ => panic!("side must be 0 (left) or 1 (right)"),
+    };
+    let transport = UdpTransport::bind(local, peer).expect("could not bind UDP socket");
+    info!(
+        "Playing the {} paddle from {local} against {peer}",
+        ["left", "right"][side]
+    );
+    Some(NetSession::new(side, arena, transport))
 }
 
 fn spawn_camera(mut commands: Commands) {
@@ -71,8 +210,6 @@ impl BallBundle {
     }
 }
 
-const BALL_SIZE: f32 = 5.;
-
 fn spawn_ball(
     mut commands: Commands,
     mut meshes: ResMut<Assets<Mesh>>,
@@ -102,9 +239,6 @@ fn move_ball(mut ball: Query<(&mut Position, &Velocity), With<Ball>>) {
     }
 }
 
-const PADDLE_WIDTH: f32 = 10.;
-const PADDLE_HEIGHT: f32 = 50.;
-
 #[derive(Component)]
 struct Paddle;
 
@@ -137,9 +271,8 @@ fn spawn_paddles(
 
     if let Ok(window) = window.get_single() {
         let window_width = window.resolution.width();
-        let padding = 50.;
-        let right_paddle_x = window_width / 2. - padding;
-        let left_paddle_x = -window_width / 2. + padding;
+        let right_paddle_x = window_width / 2. - PADDLE_PADDING;
+        let left_paddle_x = -window_width / 2. + PADDLE_PADDING;
 
         let mesh = Mesh::from(Rectangle::new(PADDLE_WIDTH, PADDLE_HEIGHT));
 
@@ -172,71 +305,17 @@ fn project_positions(mut ball: Query<(&mut Transform, &Position)>) {
     }
 }
 
-#[derive(Debug, PartialEq, Eq, Copy, Clone)]
-enum Collision {
-    Left,
-    Right,
-    Top,
-    Bottom,
-}
-
-// Returns `Some` if `ball` collides with `wall`
-// The returned `Collision` is the side of `wall`
-// that the `ball` hit.
-fn collide_with_side(ball: BoundingCircle, wall: Aabb2d) -> Option<Collision> {
-    if !ball.intersects(&wall) {
-        return None;
-    }
-
-    let closest_point = wall.closest_point(ball.center());
-    let offset = ball.center() - closest_point;
-
-    let side = if offset.x.abs() > offset.y.abs() {
-        if offset.x < 0. {
-            Collision::Left
-        } else {
-            Collision::Right
-        }
-    } else if offset.y > 0. {
-        Collision::Top
-    } else {
-        Collision::Bottom
-    };
-
-    Some(side)
-}
-
 fn handle_collisions(
-    mut ball: Query<(&mut Velocity, &Position, &Shape), With<Ball>>,
+    mut ball: Query<(&mut Velocity, &Position), With<Ball>>,
     other_things: Query<(&Position, &Shape), Without<Ball>>,
 ) {
-    if let Ok((mut ball_velocity, ball_position, ball_shape)) = ball.get_single_mut() {
+    if let Ok((mut ball_velocity, ball_position)) = ball.get_single_mut() {
         for (position, shape) in &other_things {
-            if let Some(collision) = collide_with_side(
-                BoundingCircle::new(ball_position.0, ball_shape.0.x),
-                Aabb2d::new(position.0, shape.0 / 2.),
-            ) {
-                match collision {
-                    Collision::Left => {
-                        ball_velocity.0.x *= -1.;
-                    }
-                    Collision::Right => {
-                        ball_velocity.0.x *= -1.;
-                    }
-                    Collision::Top => {
-                        ball_velocity.0.y *= -1.;
-                    }
-                    Collision::Bottom => {
-                        ball_velocity.0.y *= -1.;
-                    }
-                }
-            }
+            ball_velocity.0 = bounce(ball_position.0, ball_velocity.0, position.0, shape.0);
         }
     }
 }
 
-const GUTTER_HEIGHT: f32 = 20.;
-
 #[derive(Component)]
 struct Gutter;
 
@@ -315,8 +394,6 @@ fn keyboard_iter(keys: Res<ButtonInput<KeyCode>>) {
     }
 }
 
-const PADDLE_SPEED: f32 = 5.;
-
 fn handle_player_input(
     keys: Res<ButtonInput<KeyCode>>,
     mut paddle: Query<&mut Velocity, With<Player>>,
@@ -332,20 +409,9 @@ fn handle_player_input(
     };
 }
 
-fn move_paddles(
-    mut paddle: Query<(&mut Position, &Velocity), With<Paddle>>,
-    window: Query<&Window>,
-) {
-    if let Ok(window) = window.get_single() {
-        let window_height = window.resolution.height();
-        let max_y = window_height / 2. - GUTTER_HEIGHT - PADDLE_HEIGHT / 2.;
-
-        for (mut position, velocity) in &mut paddle {
-            let new_position = position.0 + velocity.0 * PADDLE_SPEED;
-            if new_position.y.abs() < max_y {
-                position.0 = new_position;
-            }
-        }
+fn move_paddles(mut paddle: Query<(&mut Position, &Velocity), With<Paddle>>, arena: Res<Arena>) {
+    for (mut position, velocity) in &mut paddle {
+        position.0.y = move_paddle(position.0.y, velocity.0.y, &arena);
     }
 }
 
@@ -371,19 +437,19 @@ struct Score {
 
 fn detect_scoring(
     mut ball: Query<&mut Position, With<Ball>>,
-    window: Query<&Window>,
+    arena: Res<Arena>,
     mut events: EventWriter<Scored>,
 ) {
-    if let Ok(window) = window.get_single() {
-        let window_width = window.resolution.width();
-
-        if let Ok(ball) = ball.get_single_mut() {
-            // Here we write the events using our EventWriter
-            if ball.0.x > window_width / 2. {
+    if let Ok(ball) = ball.get_single_mut() {
+        // Here we write the events using our EventWriter
+        match scoring_side(ball.0, &arena) {
+            Some(0) => {
                 events.send(Scored(Scorer::Ai));
-            } else if ball.0.x < -window_width / 2. {
+            }
+            Some(_) => {
                 events.send(Scored(Scorer::Player));
             }
+            None => {}
         }
     }
 }
@@ -394,16 +460,11 @@ fn reset_ball(
 ) {
     for event in events.read() {
         if let Ok((mut position, mut velocity)) = ball.get_single_mut() {
-            match event.0 {
-                Scorer::Ai => {
-                    position.0 = Vec2::new(0., 0.);
-                    velocity.0 = Vec2::new(-1., 1.);
-                }
-                Scorer::Player => {
-                    position.0 = Vec2::new(0., 0.);
-                    velocity.0 = Vec2::new(1., 1.);
-                }
-            }
+            position.0 = Vec2::new(0., 0.);
+            velocity.0 = match event.0 {
+                Scorer::Ai => serve_velocity(0),
+                Scorer::Player => serve_velocity(1),
+            };
         }
     }
 }
@@ -414,12 +475,10 @@ fn update_score(mut score: ResMut<Score>, mut events: EventReader<Scored>) {
             Scorer::Ai => score.ai += 1,
             Scorer::Player => score.player += 1,
         }
+        println!("Score: {} - {}", score.player, score.ai);
     }
-
-    println!("Score: {} - {}", score.player, score.ai);
 }
 
-
 fn update_scoreboard(
     mut player_score: Query<&mut Text, With<PlayerScore>>,
     mut ai_score: Query<&mut Text, (With<AiScore>, Without<PlayerScore>)>,
@@ -482,15 +541,107 @@ fn spawn_scoreboard(mut commands: Commands) {
     ));
 }
 
+// any paddle with a `Brain` - normally just the `Ai` one
 fn move_ai(
-    mut ai: Query<(&mut Velocity, &Position), With<Ai>>,
-    ball: Query<&Position, With<Ball>>,
+    mut ai: Query<(&mut Velocity, &Position, &mut Brain), Without<Ball>>,
+    ball: Query<(&Position, &Velocity), With<Ball>>,
+    arena: Res<Arena>,
 ) {
-    if let Ok((mut velocity, position)) = ai.get_single_mut() {
-        if let Ok(ball_position) = ball.get_single() {
-            let a_to_b = ball_position.0 - position.0;
-            println!("a_to_b: {0}", a_to_b);
-            velocity.0.y = a_to_b.y.signum();
+    if let Ok((ball_position, ball_velocity)) = ball.get_single() {
+        for (mut velocity, position, mut brain) in &mut ai {
+            velocity.0.y = brain.decide(ball_position.0, ball_velocity.0, position.0, &arena);
         }
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use bevy::time::TimeUpdateStrategy;
+
+    use super::*;
+
+    /// Plays a whole match between two AIs in a windowless app, returns (left, right) points
+    fn play_match(left: Difficulty, right: Difficulty, seed: u64) -> (u32, u32) {
+        let arena = Arena::default();
+        let mut app = App::new();
+        app.add_plugins(MinimalPlugins)
+            // exactly one fixed tick per update
+            .insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_secs_f64(
+                1. / TICK_RATE,
+            )))
+            .insert_resource(Time::<Fixed>::from_hz(TICK_RATE))
+            .insert_resource(arena)
+            .init_resource::<Score>()
+            .insert_resource(MatchRules { first_to: 3 })
+            .add_event::<Scored>()
+            .insert_state(GameState::Playing);
+        add_match_systems(&mut app);
+        app.world.spawn(BallBundle::new(1., 0.));
+        app.world
+            .spawn(GutterBundle::new(0., arena.gutter_y(), arena.width));
+        app.world
+            .spawn(GutterBundle::new(0., -arena.gutter_y(), arena.width));
+        app.world.spawn((
+            Ai,
+            PaddleBundle::new(arena.paddle_x(0), 0.),
+            Brain::new(left, seed),
+        ));
+        app.world.spawn((
+            Player,
+            PaddleBundle::new(arena.paddle_x(1), 0.),
+            Brain::new(right, !seed),
+        ));
+
+        let mut updates = 0;
+        while *app.world.resource::<State<GameState>>() == GameState::Playing {
+            app.update();
+            updates += 1;
+            assert!(updates < 200_000, "match never ended");
+        }
+        let score = app.world.resource::<Score>();
+        (score.ai, score.player)
+    }
+
+    #[test]
+    fn harder_ai_wins_more() {
+        // enough matches that a lucky streak can't reorder the difficulties
+        const MATCHES: u64 = 10;
+        let mut wins = [0; 3];
+        let mut played = [0; 3];
+        for (a, b) in [(0, 1), (1, 2), (0, 2)] {
+            // both sides of the table
+            for seed in 0..MATCHES * 2 {
+                let (left, right) = if seed % 2 == 0 { (a, b) } else { (b, a) };
+                let (left_points, right_points) =
+                    play_match(Difficulty::ALL[left], Difficulty::ALL[right], seed);
+                let winner = if left_points > right_points {
+                    left
+                } else {
+                    right
+                };
+                wins[winner] += 1;
+                played[left] += 1;
+                played[right] += 1;
+            }
+        }
+        let rates: Vec<f32> = wins
+            .iter()
+            .zip(played)
+            .map(|(w, p)| *w as f32 / p as f32)
+            .collect();
+        assert!(
+            rates[0] < rates[1] && rates[1] < rates[2],
+            "win rates easy/medium/hard: {rates:?}"
+        );
+        assert!(rates[2] >= 0.75, "win rates easy/medium/hard: {rates:?}");
+    }
+
+    #[test]
+    fn match_ends_at_first_to() {
+        let (left, right) = play_match(Difficulty::Easy, Difficulty::Hard, 42);
+        assert_eq!(left.max(right), 3);
+        assert!(right > left);
+    }
+}
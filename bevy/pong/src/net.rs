@@ -0,0 +1,438 @@
+//! Input exchange between two peers.
+//!
+//! Every tick each peer sends all of its local inputs the other side hasn't acknowledged
+//! yet, so a lost or late datagram is covered by the next one - no retransmit timers needed.
+//!
+//! Packet layout (little endian):
+//! `ack: u32` (highest remote frame we have everything up to, `u32::MAX` for none),
+//! `first_frame: u32`, `count: u8`, then `count` input bytes for consecutive frames.
+
+use std::collections::VecDeque;
+use std::io;
+use std::net::{SocketAddr, UdpSocket};
+
+use bevy::log::warn;
+
+use crate::rollback::RollbackSession;
+use crate::sim::{Arena, PaddleInput, SimState};
+
+/// cap on inputs per packet - far more than `MAX_PREDICTION` frames
+const MAX_INPUTS_PER_PACKET: usize = 64;
+/// A packet starts at most just past the frames we've acknowledged, so an input further
+/// ahead of the confirmed frame than one packet's worth is forged or corrupt
+const MAX_FRAMES_AHEAD: u32 = MAX_INPUTS_PER_PACKET as u32;
+const HEADER_LEN: usize = 9;
+
+/// Moves datagrams between the two peers
+pub trait Transport: Send + Sync + 'static {
+    fn send(&mut self, packet: &[u8]);
+    /// next received datagram, without blocking
+    fn recv(&mut self) -> Option<Vec<u8>>;
+}
+
+pub struct UdpTransport {
+    socket: UdpSocket,
+    buf: [u8; 512],
+}
+
+impl UdpTransport {
+    pub fn bind(local: SocketAddr, peer: SocketAddr) -> io::Result<Self> {
+        let socket = UdpSocket::bind(local)?;
+        socket.connect(peer)?;
+        socket.set_nonblocking(true)?;
+        Ok(UdpTransport {
+            socket,
+            buf: [0; 512],
+        })
+    }
+}
+
+impl Transport for UdpTransport {
+    fn send(&mut self, packet: &[u8]) {
+        // unreliable by design - the next packet repeats anything lost
+        if let Err(e) = self.socket.send(packet) {
+            if !peer_not_ready(&e) {
+                warn!("send failed: {e}");
+            }
+        }
+    }
+
+    fn recv(&mut self) -> Option<Vec<u8>> {
+        match self.socket.recv(&mut self.buf) {
+            Ok(len) => Some(self.buf[..len].to_vec()),
+            Err(e) => {
+                if !peer_not_ready(&e) {
+                    warn!("recv failed: {e}");
+                }
+                None
+            }
+        }
+    }
+}
+
+/// Errors that only mean there's nothing to do this tick: the socket would block, or
+/// the peer isn't up yet and our last packet bounced - the next tick tries again
+fn peer_not_ready(e: &io::Error) -> bool {
+    matches!(
+        e.kind(),
+        io::ErrorKind::WouldBlock | io::ErrorKind::ConnectionRefused
+    )
+}
+
+#[derive(Debug, PartialEq)]
+pub struct InputPacket {
+    pub ack: Option<u32>,
+    pub first_frame: u32,
+    pub inputs: Vec<PaddleInput>,
+}
+
+impl InputPacket {
+    pub fn encode(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(HEADER_LEN + self.inputs.len());
+        bytes.extend_from_slice(&self.ack.unwrap_or(u32::MAX).to_le_bytes());
+        bytes.extend_from_slice(&self.first_frame.to_le_bytes());
+        bytes.push(self.inputs.len() as u8);
+        bytes.extend(self.inputs.iter().map(|input| input.0));
+        bytes
+    }
+
+    pub fn decode(bytes: &[u8]) -> Option<InputPacket> {
+        let header = bytes.get(..HEADER_LEN)?;
+        let ack = u32::from_le_bytes(header[0..4].try_into().ok()?);
+        let first_frame = u32::from_le_bytes(header[4..8].try_into().ok()?);
+        let count = header[8] as usize;
+        let inputs = bytes.get(HEADER_LEN..HEADER_LEN + count)?;
+        Some(InputPacket {
+            ack: (ack != u32::MAX).then_some(ack),
+            first_frame,
+            inputs: inputs.iter().map(|b| PaddleInput(*b)).collect(),
+        })
+    }
+}
+
+/// A [`RollbackSession`] wired to a [`Transport`]
+pub struct NetSession {
+    session: RollbackSession,
+    transport: Box<dyn Transport>,
+    /// local inputs the peer hasn't acknowledged, oldest first
+    unacked: VecDeque<(u32, PaddleInput)>,
+    /// stop simulating at this frame - lets tests line both peers up on the same frame
+    frame_limit: Option<u32>,
+}
+
+impl NetSession {
+    pub fn new(local: usize, arena: Arena, transport: impl Transport) -> Self {
+        NetSession {
+            session: RollbackSession::new(local, arena),
+            transport: Box::new(transport),
+            unacked: VecDeque::new(),
+            frame_limit: None,
+        }
+    }
+
+    #[cfg(test)]
+    pub fn with_frame_limit(mut self, frame: u32) -> Self {
+        self.frame_limit = Some(frame);
+        self
+    }
+
+    pub fn session(&self) -> &RollbackSession {
+        &self.session
+    }
+
+    pub fn state(&self) -> &SimState {
+        self.session.state()
+    }
+
+    /// One fixed tick: exchange inputs, then simulate a frame unless we have to wait
+    /// for the peer. Returns whether a frame was simulated.
+    pub fn tick(&mut self, local_input: PaddleInput) -> bool {
+        self.receive();
+
+        let runnable = self.session.can_advance()
+            && self
+                .frame_limit
+                .is_none_or(|limit| self.session.frame() < limit);
+        if runnable {
+            let frame = self.session.add_local_input(local_input);
+            self.unacked.push_back((frame, local_input));
+        }
+        self.send();
+
+        if runnable {
+            self.session.advance()
+        } else {
+            // still apply late corrections while waiting
+            self.session.settle();
+            false
+        }
+    }
+
+    fn receive(&mut self) {
+        while let Some(bytes) = self.transport.recv() {
+            let Some(packet) = InputPacket::decode(&bytes) else {
+                continue;
+            };
+            if let Some(ack) = packet.ack {
+                while self.unacked.front().is_some_and(|(frame, _)| *frame <= ack) {
+                    self.unacked.pop_front();
+                }
+            }
+            // the datagram can claim any frame - keep only those in reach
+            let confirmed = self.session.remote_confirmed().map_or(0, |f| f + 1);
+            let horizon = confirmed.saturating_add(MAX_FRAMES_AHEAD);
+            for (i, input) in packet.inputs.into_iter().enumerate() {
+                match packet.first_frame.checked_add(i as u32) {
+                    Some(frame) if frame < horizon => self.session.add_remote_input(frame, input),
+                    _ => break,
+                }
+            }
+        }
+    }
+
+    fn send(&mut self) {
+        let Some(&(first_frame, _)) = self.unacked.front() else {
+            // nothing new - still acknowledge what we have
+            let packet = InputPacket {
+                ack: self.session.remote_confirmed(),
+                first_frame: 0,
+                inputs: vec![],
+            };
+            self.transport.send(&packet.encode());
+            return;
+        };
+        let packet = InputPacket {
+            ack: self.session.remote_confirmed(),
+            first_frame,
+            inputs: self
+                .unacked
+                .iter()
+                .take(MAX_INPUTS_PER_PACKET)
+                .map(|(_, input)| *input)
+                .collect(),
+        };
+        self.transport.send(&packet.encode());
+    }
+}
+
+/// In-memory link with a fixed latency in ticks - lets tests run two peers in one process
+#[cfg(test)]
+pub mod testing {
+    use std::collections::VecDeque;
+    use std::sync::atomic::{AtomicU64, Ordering};
+    use std::sync::{Arc, Mutex};
+
+    use super::Transport;
+
+    type Queue = Arc<Mutex<VecDeque<(u64, Vec<u8>)>>>;
+
+    /// shared tick counter driving delivery of every link end
+    #[derive(Clone, Default)]
+    pub struct Clock(Arc<AtomicU64>);
+
+    impl Clock {
+        pub fn tick(&self) {
+            self.0.fetch_add(1, Ordering::SeqCst);
+        }
+
+        fn now(&self) -> u64 {
+            self.0.load(Ordering::SeqCst)
+        }
+    }
+
+    pub struct LatencyLink {
+        clock: Clock,
+        latency: u64,
+        outbox: Queue,
+        inbox: Queue,
+    }
+
+    /// both ends of a link delivering each packet `latency` ticks after it was sent
+    pub fn link(clock: &Clock, latency: u64) -> (LatencyLink, LatencyLink) {
+        uneven_link(clock, latency, latency)
+    }
+
+    /// like [`link`], with a different latency each way
+    pub fn uneven_link(clock: &Clock, a_to_b: u64, b_to_a: u64) -> (LatencyLink, LatencyLink) {
+        let (a, b): (Queue, Queue) = Default::default();
+        let end = |latency, outbox: &Queue, inbox: &Queue| LatencyLink {
+            clock: clock.clone(),
+            latency,
+            outbox: outbox.clone(),
+            inbox: inbox.clone(),
+        };
+        (end(a_to_b, &a, &b), end(b_to_a, &b, &a))
+    }
+
+    impl Transport for LatencyLink {
+        fn send(&mut self, packet: &[u8]) {
+            let due = self.clock.now() + self.latency;
+            self.outbox
+                .lock()
+                .unwrap()
+                .push_back((due, packet.to_vec()));
+        }
+
+        fn recv(&mut self) -> Option<Vec<u8>> {
+            let mut inbox = self.inbox.lock().unwrap();
+            match inbox.front() {
+                Some((due, _)) if *due <= self.clock.now() => inbox.pop_front().map(|(_, p)| p),
+                _ => None,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::testing::{link, uneven_link, Clock};
+    use super::*;
+
+    /// a few seconds of button mashing, different for each side
+    pub fn scripted_input(player: usize, frame: u32) -> PaddleInput {
+        let phase = (frame / (37 + 23 * player as u32)) % 3;
+        PaddleInput::new(phase == 0, phase == 2)
+    }
+
+    #[test]
+    fn packet_round_trip() {
+        let packet = InputPacket {
+            ack: Some(41),
+            first_frame: 40,
+            inputs: vec![PaddleInput(1), PaddleInput(0), PaddleInput(2)],
+        };
+        assert_eq!(InputPacket::decode(&packet.encode()), Some(packet));
+
+        let empty = InputPacket {
+            ack: None,
+            first_frame: 0,
+            inputs: vec![],
+        };
+        assert_eq!(InputPacket::decode(&empty.encode()), Some(empty));
+        assert_eq!(InputPacket::decode(&[1, 2, 3]), None);
+    }
+
+    #[test]
+    fn peers_agree_despite_latency() {
+        const FRAMES: u32 = 2400;
+        let clock = Clock::default();
+        let (a, b) = link(&clock, 5);
+        let mut peers = [
+            NetSession::new(0, Arena::default(), a).with_frame_limit(FRAMES),
+            NetSession::new(1, Arena::default(), b).with_frame_limit(FRAMES),
+        ];
+
+        // extra ticks let the last inputs arrive once both peers hit the limit
+        for _ in 0..FRAMES * 2 {
+            for (player, peer) in peers.iter_mut().enumerate() {
+                let frame = peer.session().frame();
+                peer.tick(scripted_input(player, frame));
+            }
+            clock.tick();
+        }
+
+        let [a, b] = &peers;
+        assert_eq!(a.state().frame, FRAMES);
+        assert_eq!(b.state().frame, FRAMES);
+        assert_eq!(a.state().score, b.state().score);
+        assert_eq!(a.state().checksum(), b.state().checksum());
+        // 5 ticks of latency is more than the input delay, so predictions must have failed
+        assert!(a.session().rollbacks() > 0);
+        assert!(
+            a.state().score.iter().sum::<u32>() > 0,
+            "nobody scored: {:?}",
+            a.state()
+        );
+    }
+
+    #[test]
+    fn peers_agree_when_one_is_ahead() {
+        const FRAMES: u32 = 1200;
+        // no latency at all, or much less one way: a peer can hear about frames it
+        // hasn't simulated yet
+        for (a_to_b, b_to_a) in [(0, 0), (0, 9), (1, 6)] {
+            let clock = Clock::default();
+            let (a, b) = uneven_link(&clock, a_to_b, b_to_a);
+            let mut peers = [
+                NetSession::new(0, Arena::default(), a).with_frame_limit(FRAMES),
+                NetSession::new(1, Arena::default(), b).with_frame_limit(FRAMES),
+            ];
+            for _ in 0..FRAMES * 2 {
+                for (player, peer) in peers.iter_mut().enumerate() {
+                    let frame = peer.session().frame();
+                    peer.tick(scripted_input(player, frame));
+                }
+                clock.tick();
+            }
+
+            let [a, b] = &peers;
+            let latency = (a_to_b, b_to_a);
+            assert_eq!(a.state().frame, FRAMES, "stalled with latency {latency:?}");
+            assert_eq!(b.state().frame, FRAMES, "stalled with latency {latency:?}");
+            assert_eq!(a.state(), b.state(), "desync with latency {latency:?}");
+        }
+    }
+
+    #[test]
+    fn matches_lockstep_simulation() {
+        const FRAMES: u32 = 1200;
+        let clock = Clock::default();
+        let (a, b) = link(&clock, 7);
+        let mut peers = [
+            NetSession::new(0, Arena::default(), a).with_frame_limit(FRAMES),
+            NetSession::new(1, Arena::default(), b).with_frame_limit(FRAMES),
+        ];
+        for _ in 0..FRAMES * 2 {
+            for (player, peer) in peers.iter_mut().enumerate() {
+                let frame = peer.session().frame();
+                peer.tick(scripted_input(player, frame));
+            }
+            clock.tick();
+        }
+
+        // the same inputs without any network, delayed the same way
+        let mut expected = SimState::default();
+        let arena = Arena::default();
+        let delayed = |player, frame: u32| match frame.checked_sub(crate::rollback::INPUT_DELAY) {
+            Some(f) => scripted_input(player, f),
+            None => PaddleInput::default(),
+        };
+        for frame in 0..FRAMES {
+            expected.step([delayed(0, frame), delayed(1, frame)], &arena);
+        }
+        assert_eq!(peers[0].state(), &expected);
+        assert_eq!(peers[1].state(), &expected);
+    }
+
+    #[test]
+    fn ignores_inputs_too_far_ahead() {
+        let clock = Clock::default();
+        let (a, mut b) = link(&clock, 0);
+        let mut peer = NetSession::new(0, Arena::default(), a);
+        let confirmed = peer.session().remote_confirmed();
+        for first_frame in [u32::MAX - 1, MAX_FRAMES_AHEAD + 10] {
+            let packet = InputPacket {
+                ack: None,
+                first_frame,
+                inputs: vec![PaddleInput(1); 3],
+            };
+            b.send(&packet.encode());
+        }
+        peer.tick(PaddleInput::default());
+        assert_eq!(peer.session().remote_confirmed(), confirmed);
+    }
+
+    #[test]
+    fn waits_for_silent_peer() {
+        let clock = Clock::default();
+        let (a, _b) = link(&clock, 1);
+        let mut peer = NetSession::new(0, Arena::default(), a);
+        let simulated = (0..100)
+            .filter(|_| peer.tick(PaddleInput::default()))
+            .count();
+        assert_eq!(
+            simulated as u32,
+            crate::rollback::INPUT_DELAY + crate::rollback::MAX_PREDICTION
+        );
+    }
+}
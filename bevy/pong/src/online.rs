@@ -0,0 +1,156 @@
+//! Two player mode over UDP - the ECS side of [`NetSession`].
+//!
+//! The session owns the game state; these systems only feed it the keyboard and copy
+//! its state onto the ball, the paddles and the [`Score`]. The [`Player`] paddle is
+//! whichever side this peer plays, the [`Ai`](crate::Ai) one the other peer's.
+
+use bevy::prelude::*;
+
+use crate::net::NetSession;
+use crate::sim::{Arena, PaddleInput};
+use crate::{Ball, Paddle, Player, Position, Score};
+
+#[derive(Resource)]
+pub struct Online(pub NetSession);
+
+/// Buttons held this frame, sampled by every fixed tick
+#[derive(Resource, Default)]
+pub struct LocalInput(pub PaddleInput);
+
+type PaddleFilter = (With<Paddle>, Without<Ball>);
+
+pub fn read_local_input(keys: Res<ButtonInput<KeyCode>>, mut input: ResMut<LocalInput>) {
+    input.0 = PaddleInput::new(
+        keys.pressed(KeyCode::ArrowUp),
+        keys.pressed(KeyCode::ArrowDown),
+    );
+}
+
+pub fn online_tick(
+    mut online: ResMut<Online>,
+    input: Res<LocalInput>,
+    arena: Res<Arena>,
+    mut score: ResMut<Score>,
+    mut ball: Query<&mut Position, With<Ball>>,
+    mut paddles: Query<(&mut Position, Has<Player>), PaddleFilter>,
+) {
+    online.0.tick(input.0);
+    let state = online.0.state();
+    let local = online.0.session().local();
+
+    if let Ok(mut position) = ball.get_single_mut() {
+        position.0 = state.ball;
+    }
+    for (mut position, is_player) in &mut paddles {
+        let side = if is_player { local } else { 1 - local };
+        position.0 = Vec2::new(arena.paddle_x(side), state.paddles[side]);
+    }
+
+    // only count points that can't be rolled back - a match must not end on a guess
+    let confirmed = online.0.session().confirmed_state();
+    let (ai, player) = (confirmed.score[1 - local], confirmed.score[local]);
+    if score.ai != ai || score.player != player {
+        score.ai = ai;
+        score.player = player;
+        // compare the checksums logged by both peers to spot a desync
+        info!(
+            "Score: {} - {} (frame {}, checksum {:016x}, rollbacks {})",
+            score.player,
+            score.ai,
+            confirmed.frame,
+            confirmed.checksum(),
+            online.0.session().rollbacks()
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::net::testing::{link, uneven_link, Clock, LatencyLink};
+    use crate::Ai;
+
+    const FRAMES: u32 = 3000;
+
+    fn peer(session: NetSession) -> App {
+        let mut app = App::new();
+        app.add_plugins(MinimalPlugins)
+            .init_resource::<Score>()
+            .init_resource::<LocalInput>()
+            .insert_resource(Arena::default())
+            .insert_resource(Online(session))
+            .add_systems(FixedUpdate, online_tick);
+        app.world.spawn((Ball, Position(Vec2::ZERO)));
+        app.world
+            .spawn((Ai, Paddle, Position(Vec2::new(-590., 0.))));
+        app.world
+            .spawn((Player, Paddle, Position(Vec2::new(590., 0.))));
+        app
+    }
+
+    /// two headless peers wiggling their paddles for `FRAMES` frames
+    fn play(clock: &Clock, (a, b): (LatencyLink, LatencyLink)) -> [App; 2] {
+        let mut peers = [
+            peer(NetSession::new(0, Arena::default(), a).with_frame_limit(FRAMES)),
+            peer(NetSession::new(1, Arena::default(), b).with_frame_limit(FRAMES)),
+        ];
+
+        for _ in 0..FRAMES * 2 {
+            for (side, app) in peers.iter_mut().enumerate() {
+                let frame = app.world.resource::<Online>().0.session().frame();
+                // each side wiggles its paddle at its own pace
+                let phase = (frame / (41 + 19 * side as u32)) % 3;
+                app.world.resource_mut::<LocalInput>().0 = PaddleInput::new(phase == 0, phase == 2);
+                app.world.run_schedule(FixedUpdate);
+            }
+            clock.tick();
+        }
+        peers
+    }
+
+    #[test]
+    fn headless_peers_agree_on_score() {
+        let clock = Clock::default();
+        let mut peers = play(&clock, link(&clock, 6));
+
+        let scores: Vec<(u32, u32)> = peers
+            .iter()
+            .map(|app| {
+                let score = app.world.resource::<Score>();
+                (score.player, score.ai)
+            })
+            .collect();
+        // each peer's own points are the other's opponent's
+        assert_eq!(scores[0], (scores[1].1, scores[1].0));
+        assert!(scores[0].0 + scores[0].1 > 0, "nobody scored");
+
+        // and each sees its own paddle on the side it plays
+        for (side, app) in peers.iter_mut().enumerate() {
+            let mut query = app.world.query_filtered::<&Position, With<Player>>();
+            let x = query.single(&app.world).0.x;
+            assert_eq!(x, Arena::default().paddle_x(side));
+        }
+
+        let mut balls: Vec<Vec2> = vec![];
+        for app in &mut peers {
+            let mut query = app.world.query_filtered::<&Position, With<Ball>>();
+            balls.push(query.single(&app.world).0);
+        }
+        assert_eq!(balls[0], balls[1]);
+    }
+
+    #[test]
+    fn headless_peers_keep_going_when_one_hears_the_other_first() {
+        let clock = Clock::default();
+        // the right peer learns the left one's inputs before it simulates those frames
+        let peers = play(&clock, uneven_link(&clock, 0, 8));
+        for app in &peers {
+            assert_eq!(app.world.resource::<Online>().0.session().frame(), FRAMES);
+        }
+        let [a, b] = &peers;
+        assert_eq!(
+            a.world.resource::<Online>().0.state(),
+            b.world.resource::<Online>().0.state()
+        );
+    }
+}
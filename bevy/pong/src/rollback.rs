@@ -0,0 +1,207 @@
+//! GGPO-style rollback for two players.
+//!
+//! The local input is applied right away (after a small fixed delay), the remote one is
+//! predicted by repeating the last input we got from the peer. When the real remote input
+//! for a frame arrives and differs from the prediction, the state saved before that frame
+//! is restored and every frame since is re-simulated - so the local paddle never waits on
+//! the network, and both peers end up with identical states once all inputs are in.
+
+use std::collections::{BTreeMap, VecDeque};
+
+use crate::sim::{Arena, PaddleInput, SimState};
+
+/// frames of delay added to local inputs - hides small latencies without any rollback
+pub const INPUT_DELAY: u32 = 2;
+/// how far we simulate ahead of the last confirmed remote input before waiting for the peer
+pub const MAX_PREDICTION: u32 = 8;
+
+pub struct RollbackSession {
+    local: usize,
+    arena: Arena,
+    state: SimState,
+    /// state at the start of each frame that may still need re-simulating
+    snapshots: VecDeque<SimState>,
+    /// inputs known for sure, per player, by frame
+    inputs: [BTreeMap<u32, PaddleInput>; 2],
+    /// remote inputs we guessed, by frame
+    predictions: BTreeMap<u32, PaddleInput>,
+    /// every remote input up to and including this frame is known (`None`: not even frame 0)
+    remote_confirmed: Option<u32>,
+    /// earliest frame simulated with a wrong prediction
+    rollback_to: Option<u32>,
+    rollbacks: u32,
+}
+
+impl RollbackSession {
+    /// `local` is the side (0 left, 1 right) this peer controls
+    pub fn new(local: usize, arena: Arena) -> Self {
+        let mut inputs = [BTreeMap::new(), BTreeMap::new()];
+        // nobody can have pressed anything during the initial delay
+        for player_inputs in &mut inputs {
+            for frame in 0..INPUT_DELAY {
+                player_inputs.insert(frame, PaddleInput::default());
+            }
+        }
+        RollbackSession {
+            local,
+            arena,
+            state: SimState::default(),
+            snapshots: VecDeque::new(),
+            inputs,
+            predictions: BTreeMap::new(),
+            remote_confirmed: INPUT_DELAY.checked_sub(1),
+            rollback_to: None,
+            rollbacks: 0,
+        }
+    }
+
+    /// the side this peer controls
+    pub fn local(&self) -> usize {
+        self.local
+    }
+
+    fn remote(&self) -> usize {
+        1 - self.local
+    }
+
+    /// the next frame to be simulated
+    pub fn frame(&self) -> u32 {
+        self.state.frame
+    }
+
+    /// current state - may include predicted remote inputs
+    pub fn state(&self) -> &SimState {
+        &self.state
+    }
+
+    /// Latest state built from real inputs only - unlike [`state`](Self::state),
+    /// a rollback can't change it
+    pub fn confirmed_state(&self) -> &SimState {
+        // snapshots at or before the confirmed frame are pruned, so the oldest one left
+        // is the state right after it
+        self.snapshots.front().unwrap_or(&self.state)
+    }
+
+    pub fn remote_confirmed(&self) -> Option<u32> {
+        self.remote_confirmed
+    }
+
+    /// number of times a misprediction forced a re-simulation
+    pub fn rollbacks(&self) -> u32 {
+        self.rollbacks
+    }
+
+    /// Would [`advance`](Self::advance) simulate a frame, or wait for the peer?
+    pub fn can_advance(&self) -> bool {
+        let confirmed = self.remote_confirmed.map_or(0, |f| f + 1);
+        self.frame() < confirmed + MAX_PREDICTION
+    }
+
+    /// Schedules the local input for `INPUT_DELAY` frames from now - returns that frame,
+    /// which is what has to be sent to the peer
+    pub fn add_local_input(&mut self, input: PaddleInput) -> u32 {
+        let frame = self.frame() + INPUT_DELAY;
+        self.inputs[self.local].insert(frame, input);
+        frame
+    }
+
+    pub fn add_remote_input(&mut self, frame: u32, input: PaddleInput) {
+        let remote = self.remote();
+        if self.inputs[remote].contains_key(&frame) {
+            return;
+        }
+        self.inputs[remote].insert(frame, input);
+
+        if let Some(predicted) = self.predictions.remove(&frame) {
+            if predicted != input {
+                self.rollback_to = Some(self.rollback_to.map_or(frame, |f| f.min(frame)));
+            }
+        }
+
+        let mut next = self.remote_confirmed.map_or(0, |f| f + 1);
+        while self.inputs[remote].contains_key(&next) {
+            self.remote_confirmed = Some(next);
+            next += 1;
+        }
+    }
+
+    /// Re-simulates from the earliest mispredicted frame, if any
+    pub fn settle(&mut self) {
+        if let Some(from) = self.rollback_to.take() {
+            self.rollback(from);
+        }
+        self.prune();
+    }
+
+    fn rollback(&mut self, from: u32) {
+        let target = self.frame();
+        let first = self.snapshots.front().map_or(target, |s| s.frame);
+        let Some(idx) = from
+            .checked_sub(first)
+            .filter(|&i| (i as usize) < self.snapshots.len())
+        else {
+            return;
+        };
+        self.rollbacks += 1;
+        self.state = self.snapshots[idx as usize];
+        self.snapshots.truncate(idx as usize);
+        while self.frame() < target {
+            self.simulate();
+        }
+    }
+
+    /// Settles any misprediction, then simulates one frame - `false` if it had to wait
+    pub fn advance(&mut self) -> bool {
+        self.settle();
+        if !self.can_advance() || !self.inputs[self.local].contains_key(&self.frame()) {
+            return false;
+        }
+        self.simulate();
+        self.prune();
+        true
+    }
+
+    fn simulate(&mut self) {
+        let frame = self.frame();
+        let inputs = [self.input_for(0, frame), self.input_for(1, frame)];
+        self.snapshots.push_back(self.state);
+        self.state.step(inputs, &self.arena);
+    }
+
+    fn input_for(&mut self, player: usize, frame: u32) -> PaddleInput {
+        if let Some(input) = self.inputs[player].get(&frame) {
+            return *input;
+        }
+        // players tend to keep holding what they held last
+        let predicted = self.inputs[player]
+            .range(..frame)
+            .next_back()
+            .map(|(_, input)| *input)
+            .unwrap_or_default();
+        self.predictions.insert(frame, predicted);
+        predicted
+    }
+
+    // snapshots and inputs before the confirmed frame can never be rolled back to
+    fn prune(&mut self) {
+        let Some(confirmed) = self.remote_confirmed else {
+            return;
+        };
+        while self.snapshots.front().is_some_and(|s| s.frame <= confirmed) {
+            self.snapshots.pop_front();
+        }
+        // the peer can be ahead of us: inputs for frames not simulated yet are still
+        // needed, confirmed or not
+        let keep_from = confirmed.min(self.frame());
+        for player_inputs in &mut self.inputs {
+            // keep the last confirmed input around for predictions
+            while player_inputs.len() > 1
+                && player_inputs
+                    .first_key_value()
+                    .is_some_and(|(&f, _)| f < keep_from)
+            {
+                player_inputs.pop_first();
+            }
+        }
+    }
+}
@@ -0,0 +1,267 @@
+//! Deterministic pong physics, stepped at a fixed rate.
+//!
+//! Everything the rollback netcode needs to save, restore and re-simulate lives in
+//! [`SimState`] - plain `Copy` data, no ECS. Only `+ - *` and comparisons on `f32`
+//! are used, so the same inputs give bit-identical states on every peer.
+
+use bevy::ecs::system::Resource;
+use bevy::math::bounding::{Aabb2d, BoundingCircle, BoundingVolume, IntersectsVolume};
+use bevy::math::Vec2;
+
+/// physics steps per second
+pub const TICK_RATE: f64 = 60.;
+
+pub const BALL_SIZE: f32 = 5.;
+pub const PADDLE_WIDTH: f32 = 10.;
+pub const PADDLE_HEIGHT: f32 = 50.;
+pub const PADDLE_SPEED: f32 = 5.;
+pub const PADDLE_PADDING: f32 = 50.;
+pub const GUTTER_HEIGHT: f32 = 20.;
+
+/// The playing field - online peers must agree on it, so it doesn't follow the window size
+#[derive(Resource, Clone, Copy, Debug, PartialEq)]
+pub struct Arena {
+    pub width: f32,
+    pub height: f32,
+}
+
+impl Default for Arena {
+    fn default() -> Self {
+        Arena {
+            width: 1280.,
+            height: 720.,
+        }
+    }
+}
+
+impl Arena {
+    /// x of the left (index 0) and right (index 1) paddles
+    pub fn paddle_x(&self, side: usize) -> f32 {
+        let x = self.width / 2. - PADDLE_PADDING;
+        if side == 0 {
+            -x
+        } else {
+            x
+        }
+    }
+
+    /// how far a paddle centre may travel from the middle
+    pub fn paddle_max_y(&self) -> f32 {
+        self.height / 2. - GUTTER_HEIGHT - PADDLE_HEIGHT / 2.
+    }
+
+    pub fn gutter_y(&self) -> f32 {
+        self.height / 2. - GUTTER_HEIGHT / 2.
+    }
+}
+
+/// Buttons held by one player during one tick
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct PaddleInput(pub u8);
+
+impl PaddleInput {
+    pub const UP: u8 = 1;
+    pub const DOWN: u8 = 2;
+
+    pub fn new(up: bool, down: bool) -> Self {
+        PaddleInput(if up { Self::UP } else { 0 } | if down { Self::DOWN } else { 0 })
+    }
+
+    /// up wins if both are held, matching `handle_player_input`
+    pub fn direction(self) -> f32 {
+        if self.0 & Self::UP != 0 {
+            1.
+        } else if self.0 & Self::DOWN != 0 {
+            -1.
+        } else {
+            0.
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum Collision {
+    Left,
+    Right,
+    Top,
+    Bottom,
+}
+
+// Returns `Some` if `ball` collides with `wall`
+// The returned `Collision` is the side of `wall`
+// that the `ball` hit.
+pub fn collide_with_side(ball: BoundingCircle, wall: Aabb2d) -> Option<Collision> {
+    if !ball.intersects(&wall) {
+        return None;
+    }
+
+    let closest_point = wall.closest_point(ball.center());
+    let offset = ball.center() - closest_point;
+
+    let side = if offset.x.abs() > offset.y.abs() {
+        if offset.x < 0. {
+            Collision::Left
+        } else {
+            Collision::Right
+        }
+    } else if offset.y > 0. {
+        Collision::Top
+    } else {
+        Collision::Bottom
+    };
+
+    Some(side)
+}
+
+/// New ball velocity after hitting the box at `position` with size `shape`. Only a
+/// velocity heading into the side that was hit is reflected - a ball still overlapping
+/// the box on the next tick is already on its way out, and flipping it again would
+/// trap it inside.
+pub fn bounce(ball: Vec2, velocity: Vec2, position: Vec2, shape: Vec2) -> Vec2 {
+    let Vec2 { x, y } = velocity;
+    match collide_with_side(
+        BoundingCircle::new(ball, BALL_SIZE),
+        Aabb2d::new(position, shape / 2.),
+    ) {
+        Some(Collision::Left) if x > 0. => Vec2::new(-x, y),
+        Some(Collision::Right) if x < 0. => Vec2::new(-x, y),
+        Some(Collision::Top) if y < 0. => Vec2::new(x, -y),
+        Some(Collision::Bottom) if y > 0. => Vec2::new(x, -y),
+        _ => velocity,
+    }
+}
+
+/// Paddle y after one tick of moving in `direction` - stays put rather than entering a gutter
+pub fn move_paddle(y: f32, direction: f32, arena: &Arena) -> f32 {
+    let new_y = y + direction * PADDLE_SPEED;
+    if new_y.abs() < arena.paddle_max_y() {
+        new_y
+    } else {
+        y
+    }
+}
+
+/// Which side scored, if the ball left the arena
+pub fn scoring_side(ball: Vec2, arena: &Arena) -> Option<usize> {
+    if ball.x > arena.width / 2. {
+        Some(0)
+    } else if ball.x < -arena.width / 2. {
+        Some(1)
+    } else {
+        None
+    }
+}
+
+/// the ball is served towards the side that just scored
+pub fn serve_velocity(scorer: usize) -> Vec2 {
+    if scorer == 0 {
+        Vec2::new(-1., 1.)
+    } else {
+        Vec2::new(1., 1.)
+    }
+}
+
+/// The whole game at one tick
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SimState {
+    pub frame: u32,
+    pub ball: Vec2,
+    pub ball_velocity: Vec2,
+    /// y of the left (index 0) and right (index 1) paddles
+    pub paddles: [f32; 2],
+    /// points of the left (index 0) and right (index 1) side
+    pub score: [u32; 2],
+}
+
+impl Default for SimState {
+    fn default() -> Self {
+        SimState {
+            frame: 0,
+            ball: Vec2::ZERO,
+            ball_velocity: Vec2::new(1., 0.),
+            paddles: [0., 0.],
+            score: [0, 0],
+        }
+    }
+}
+
+impl SimState {
+    /// Advances one tick - same order as the local systems:
+    /// paddles, ball, collisions, scoring
+    pub fn step(&mut self, inputs: [PaddleInput; 2], arena: &Arena) {
+        for (y, input) in self.paddles.iter_mut().zip(inputs) {
+            *y = move_paddle(*y, input.direction(), arena);
+        }
+
+        self.ball += self.ball_velocity;
+
+        let paddle_shape = Vec2::new(PADDLE_WIDTH, PADDLE_HEIGHT);
+        for (side, y) in self.paddles.iter().enumerate() {
+            let position = Vec2::new(arena.paddle_x(side), *y);
+            self.ball_velocity = bounce(self.ball, self.ball_velocity, position, paddle_shape);
+        }
+        let gutter_shape = Vec2::new(arena.width, GUTTER_HEIGHT);
+        for y in [arena.gutter_y(), -arena.gutter_y()] {
+            let position = Vec2::new(0., y);
+            self.ball_velocity = bounce(self.ball, self.ball_velocity, position, gutter_shape);
+        }
+
+        if let Some(scorer) = scoring_side(self.ball, arena) {
+            self.score[scorer] += 1;
+            self.ball = Vec2::ZERO;
+            self.ball_velocity = serve_velocity(scorer);
+        }
+
+        self.frame += 1;
+    }
+
+    /// bit-exact fingerprint, handy for spotting desyncs between peers
+    pub fn checksum(&self) -> u64 {
+        let words = [
+            self.frame,
+            self.ball.x.to_bits(),
+            self.ball.y.to_bits(),
+            self.ball_velocity.x.to_bits(),
+            self.ball_velocity.y.to_bits(),
+            self.paddles[0].to_bits(),
+            self.paddles[1].to_bits(),
+            self.score[0],
+            self.score[1],
+        ];
+        // FNV-1a
+        words
+            .iter()
+            .flat_map(|w| w.to_le_bytes())
+            .fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+                (hash ^ byte as u64).wrapping_mul(0x0100_0000_01b3)
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bounce_lets_an_overlapping_ball_out() {
+        let paddle = Vec2::new(100., 0.);
+        let shape = Vec2::new(PADDLE_WIDTH, PADDLE_HEIGHT);
+        // touching the left face
+        let ball = Vec2::new(100. - PADDLE_WIDTH / 2. - BALL_SIZE + 1., 0.);
+        let out = bounce(ball, Vec2::new(2., 1.), paddle, shape);
+        assert_eq!(out, Vec2::new(-2., 1.));
+        // a tick later it still overlaps, and keeps going the way it was sent
+        assert_eq!(bounce(ball + out, out, paddle, shape), out);
+
+        // dropping onto the top edge, then leaving it
+        let ball = Vec2::new(100., PADDLE_HEIGHT / 2. + BALL_SIZE - 1.);
+        assert_eq!(
+            bounce(ball, Vec2::new(1., -1.), paddle, shape),
+            Vec2::new(1., 1.)
+        );
+        assert_eq!(
+            bounce(ball, Vec2::new(1., 1.), paddle, shape),
+            Vec2::new(1., 1.)
+        );
+    }
+}
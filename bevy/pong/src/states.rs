@@ -0,0 +1,159 @@
+//! Menu, pause and match end.
+//!
+//! Gameplay systems only run in [`GameState::Playing`]; the first side to reach
+//! [`MatchRules::first_to`] ends the match.
+
+use bevy::prelude::*;
+
+use crate::ai::Difficulty;
+use crate::online::Online;
+use crate::Score;
+
+#[derive(States, Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum GameState {
+    #[default]
+    Menu,
+    Playing,
+    Paused,
+    GameOver,
+}
+
+#[derive(Resource)]
+pub struct MatchRules {
+    pub first_to: u32,
+}
+
+impl Default for MatchRules {
+    fn default() -> Self {
+        MatchRules { first_to: 5 }
+    }
+}
+
+#[derive(Component)]
+pub struct StatusText;
+
+pub fn spawn_status_text(mut commands: Commands) {
+    commands
+        .spawn(NodeBundle {
+            style: Style {
+                width: Val::Percent(100.),
+                height: Val::Percent(100.),
+                align_items: AlignItems::Center,
+                justify_content: JustifyContent::Center,
+                ..default()
+            },
+            ..default()
+        })
+        .with_children(|parent| {
+            parent.spawn((
+                TextBundle::from_section(
+                    "",
+                    TextStyle {
+                        font_size: 40.0,
+                        color: Color::WHITE,
+                        ..default()
+                    },
+                )
+                .with_text_justify(JustifyText::Center),
+                StatusText,
+            ));
+        });
+}
+
+pub fn menu_input(
+    keys: Res<ButtonInput<KeyCode>>,
+    mut difficulty: ResMut<Difficulty>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    for (key, choice) in [KeyCode::Digit1, KeyCode::Digit2, KeyCode::Digit3]
+        .into_iter()
+        .zip(Difficulty::ALL)
+    {
+        if keys.just_pressed(key) {
+            *difficulty = choice;
+        }
+    }
+    if keys.just_pressed(KeyCode::Enter) {
+        next_state.set(GameState::Playing);
+    }
+}
+
+pub fn toggle_pause(
+    keys: Res<ButtonInput<KeyCode>>,
+    state: Res<State<GameState>>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    if keys.just_pressed(KeyCode::KeyP) || keys.just_pressed(KeyCode::Escape) {
+        match state.get() {
+            GameState::Playing => next_state.set(GameState::Paused),
+            GameState::Paused => next_state.set(GameState::Playing),
+            _ => {}
+        }
+    }
+}
+
+pub fn game_over_input(
+    keys: Res<ButtonInput<KeyCode>>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    if keys.just_pressed(KeyCode::Enter) {
+        next_state.set(GameState::Menu);
+    }
+}
+
+pub fn check_match_end(
+    score: Res<Score>,
+    rules: Res<MatchRules>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    if score.player >= rules.first_to || score.ai >= rules.first_to {
+        next_state.set(GameState::GameOver);
+    }
+}
+
+pub fn update_status_text(
+    state: Res<State<GameState>>,
+    difficulty: Res<Difficulty>,
+    score: Res<Score>,
+    rules: Res<MatchRules>,
+    online: Option<Res<Online>>,
+    mut text: Query<&mut Text, With<StatusText>>,
+) {
+    let status = match state.get() {
+        GameState::Menu => {
+            let choices: Vec<String> = Difficulty::ALL
+                .iter()
+                .enumerate()
+                .map(|(i, choice)| {
+                    let marker = if choice == difficulty.as_ref() {
+                        ">"
+                    } else {
+                        " "
+                    };
+                    format!("{marker}{} {choice:?}", i + 1)
+                })
+                .collect();
+            format!(
+                "PONG - first to {}\n{}\nEnter to play, P to pause",
+                rules.first_to,
+                choices.join("   ")
+            )
+        }
+        GameState::Playing => String::new(),
+        GameState::Paused => "Paused - P to resume".to_string(),
+        GameState::GameOver => {
+            let (winner, hint) = match (online.is_some(), score.player > score.ai) {
+                (false, true) => ("You win", "\nEnter for the menu"),
+                (false, false) => ("AI wins", "\nEnter for the menu"),
+                (true, true) => ("Right paddle wins", ""),
+                (true, false) => ("Left paddle wins", ""),
+            };
+            format!("{winner} {} - {}{hint}", score.player, score.ai)
+        }
+    };
+    if let Ok(mut text) = text.get_single_mut() {
+        if text.sections[0].value != status {
+            text.sections[0].value = status;
+        }
+    }
+}
@@ -0,0 +1,132 @@
+//! `cargo run --bin bz -- [-d] [-k] [-c] [-1..-9] [--work-factor N] [--small] [FILE...]`
+//!
+//! Compresses each FILE to FILE.bz2, or with `-d` decompresses FILE.bz2 back
+//! to FILE, removing the original unless `-k` is given. With `-c`, or no
+//! files at all, writes to stdout instead (reading stdin if there are no files).
+
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+use bindgen_experiments::{BzDecoder, BzEncoder, Params};
+
+#[derive(Default)]
+struct Options {
+    decompress: bool,
+    keep: bool,
+    stdout: bool,
+    small: bool,
+    params: Params,
+}
+
+fn main() -> ExitCode {
+    let mut options = Options::default();
+    let mut files = Vec::new();
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-d" | "--decompress" => options.decompress = true,
+            "-z" | "--compress" => options.decompress = false,
+            "-k" | "--keep" => options.keep = true,
+            "-c" | "--stdout" => options.stdout = true,
+            "-s" | "--small" => options.small = true,
+            "--work-factor" => match args.next().and_then(|n| n.parse().ok()) {
+                Some(n) => options.params = options.params.work_factor(n),
+                None => return usage("--work-factor needs a number"),
+            },
+            "-h" | "--help" => return usage(""),
+            _ if arg.len() == 2 && matches!(arg.as_bytes(), [b'-', b'1'..=b'9']) => {
+                let block_size = (arg.as_bytes()[1] - b'0') as u32;
+                options.params = options.params.block_size(block_size);
+            }
+            _ if arg.starts_with('-') => return usage(&format!("unexpected argument {arg:?}")),
+            _ => files.push(PathBuf::from(arg)),
+        }
+    }
+
+    if files.is_empty() {
+        let result = run(&options, io::stdin().lock(), io::stdout().lock());
+        return report("(stdin)".as_ref(), result);
+    }
+    let mut status = ExitCode::SUCCESS;
+    for file in &files {
+        if report(file, convert(&options, file)) != ExitCode::SUCCESS {
+            status = ExitCode::FAILURE;
+        }
+    }
+    status
+}
+
+fn usage(problem: &str) -> ExitCode {
+    if !problem.is_empty() {
+        eprintln!("{problem}");
+    }
+    eprintln!("usage: bz [-d] [-k] [-c] [-1..-9] [--work-factor N] [--small] [FILE...]");
+    if problem.is_empty() {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::from(2)
+    }
+}
+
+fn report(file: &Path, result: io::Result<()>) -> ExitCode {
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("bz: {}: {e}", file.display());
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// Converts one file, to stdout or next to it.
+fn convert(options: &Options, file: &Path) -> io::Result<()> {
+    let input = File::open(file)?;
+    if options.stdout {
+        return run(options, input, io::stdout().lock());
+    }
+
+    let output = output_path(options.decompress, file)?;
+    let result = File::create_new(&output).and_then(|out| run(options, input, BufWriter::new(out)));
+    match result {
+        Ok(()) if options.keep => Ok(()),
+        Ok(()) => fs::remove_file(file),
+        Err(e) => {
+            // don't leave half a file behind, unless it was already there
+            if e.kind() != io::ErrorKind::AlreadyExists {
+                let _ = fs::remove_file(&output);
+            }
+            Err(e)
+        }
+    }
+}
+
+fn output_path(decompress: bool, file: &Path) -> io::Result<PathBuf> {
+    if !decompress {
+        let mut name = file.as_os_str().to_owned();
+        name.push(".bz2");
+        return Ok(name.into());
+    }
+    match file.extension() {
+        Some(ext) if ext == "bz2" => Ok(file.with_extension("")),
+        _ => Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "doesn't end in .bz2, so there's no name to decompress it to",
+        )),
+    }
+}
+
+fn run(options: &Options, mut input: impl Read, mut output: impl Write) -> io::Result<()> {
+    if options.decompress {
+        io::copy(
+            &mut BzDecoder::with_small(input, options.small),
+            &mut output,
+        )?;
+        output.flush()
+    } else {
+        let mut encoder = BzEncoder::with_params(output, options.params)?;
+        io::copy(&mut input, &mut encoder)?;
+        encoder.finish()?.flush()
+    }
+}
@@ -0,0 +1,262 @@
+//! Safe handles on the generated `bz_stream` bindings, and the error every
+//! `BZ_*` return code maps to.
+
+use std::fmt;
+use std::io;
+use std::mem;
+use std::os::raw::{c_int, c_uint};
+
+use crate::*;
+
+/// A `BZ_*` code saying something went wrong.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    /// `BZ_SEQUENCE_ERROR`: the stream was driven in the wrong order.
+    Sequence,
+    /// `BZ_PARAM_ERROR`: a parameter out of range, such as a block size of 10.
+    Param,
+    /// `BZ_MEM_ERROR`: bzip2 couldn't allocate.
+    Mem,
+    /// `BZ_DATA_ERROR`: the compressed data is corrupt.
+    Data,
+    /// `BZ_DATA_ERROR_MAGIC`: the input doesn't start like a bzip2 stream.
+    DataMagic,
+    /// `BZ_IO_ERROR`: from the `BZFILE` API, which we don't use.
+    Io,
+    /// `BZ_UNEXPECTED_EOF`: the compressed data ends before the stream does.
+    UnexpectedEof,
+    /// `BZ_OUTBUFF_FULL`: from the buffer-to-buffer API, which we don't use.
+    OutbuffFull,
+    /// `BZ_CONFIG_ERROR`: libbz2 was built for a different platform.
+    Config,
+    /// A code `bzlib.h` doesn't define.
+    Unknown(c_int),
+}
+
+impl Error {
+    fn from_code(code: c_int) -> Error {
+        match code {
+            c if c == BZ_SEQUENCE_ERROR as c_int => Error::Sequence,
+            c if c == BZ_PARAM_ERROR as c_int => Error::Param,
+            c if c == BZ_MEM_ERROR as c_int => Error::Mem,
+            c if c == BZ_DATA_ERROR as c_int => Error::Data,
+            c if c == BZ_DATA_ERROR_MAGIC as c_int => Error::DataMagic,
+            c if c == BZ_IO_ERROR as c_int => Error::Io,
+            c if c == BZ_UNEXPECTED_EOF as c_int => Error::UnexpectedEof,
+            c if c == BZ_OUTBUFF_FULL as c_int => Error::OutbuffFull,
+            c if c == BZ_CONFIG_ERROR as c_int => Error::Config,
+            c => Error::Unknown(c),
+        }
+    }
+
+    /// The `BZ_*` code this came from.
+    pub fn code(self) -> c_int {
+        match self {
+            Error::Sequence => BZ_SEQUENCE_ERROR as c_int,
+            Error::Param => BZ_PARAM_ERROR as c_int,
+            Error::Mem => BZ_MEM_ERROR as c_int,
+            Error::Data => BZ_DATA_ERROR as c_int,
+            Error::DataMagic => BZ_DATA_ERROR_MAGIC as c_int,
+            Error::Io => BZ_IO_ERROR as c_int,
+            Error::UnexpectedEof => BZ_UNEXPECTED_EOF as c_int,
+            Error::OutbuffFull => BZ_OUTBUFF_FULL as c_int,
+            Error::Config => BZ_CONFIG_ERROR as c_int,
+            Error::Unknown(code) => code,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let message = match self {
+            Error::Sequence => "bzip2 calls made out of sequence",
+            Error::Param => "bzip2 parameter out of range",
+            Error::Mem => "bzip2 ran out of memory",
+            Error::Data => "corrupt bzip2 data",
+            Error::DataMagic => "not bzip2 data",
+            Error::Io => "bzip2 I/O error",
+            Error::UnexpectedEof => "bzip2 data ends unexpectedly",
+            Error::OutbuffFull => "bzip2 output buffer full",
+            Error::Config => "libbz2 is misconfigured for this platform",
+            Error::Unknown(code) => return write!(f, "unknown bzip2 return code {code}"),
+        };
+        f.write_str(message)
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<Error> for io::Error {
+    fn from(err: Error) -> io::Error {
+        let kind = match err {
+            Error::Data | Error::DataMagic => io::ErrorKind::InvalidData,
+            Error::UnexpectedEof => io::ErrorKind::UnexpectedEof,
+            Error::Param => io::ErrorKind::InvalidInput,
+            Error::Mem => io::ErrorKind::OutOfMemory,
+            _ => io::ErrorKind::Other,
+        };
+        io::Error::new(kind, err)
+    }
+}
+
+/// The codes that aren't errors.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Status {
+    Ok,
+    RunOk,
+    FlushOk,
+    FinishOk,
+    StreamEnd,
+}
+
+fn check(code: c_int) -> Result<Status, Error> {
+    match code {
+        c if c == BZ_OK as c_int => Ok(Status::Ok),
+        c if c == BZ_RUN_OK as c_int => Ok(Status::RunOk),
+        c if c == BZ_FLUSH_OK as c_int => Ok(Status::FlushOk),
+        c if c == BZ_FINISH_OK as c_int => Ok(Status::FinishOk),
+        c if c == BZ_STREAM_END as c_int => Ok(Status::StreamEnd),
+        c => Err(Error::from_code(c)),
+    }
+}
+
+/// How to compress: the block size trades memory for ratio, and the work
+/// factor says how hard to try before falling back to the slower sort that
+/// copes with repetitive input.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Params {
+    block_size: u32,
+    work_factor: u32,
+}
+
+impl Params {
+    /// Blocks of `block_size` x 100 KB, from 1 to 9.
+    pub fn block_size(mut self, block_size: u32) -> Self {
+        self.block_size = block_size;
+        self
+    }
+
+    /// From 1 to 250, or 0 for the library's default of 30.
+    pub fn work_factor(mut self, work_factor: u32) -> Self {
+        self.work_factor = work_factor;
+        self
+    }
+}
+
+impl Default for Params {
+    /// What the `bzip2` command does: 900 KB blocks and the default work factor.
+    fn default() -> Self {
+        Params {
+            block_size: 9,
+            work_factor: 0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum Action {
+    Run,
+    Flush,
+    Finish,
+}
+
+/// How much of the input a call took and how much output it gave.
+pub(crate) struct Progress {
+    pub(crate) consumed: usize,
+    pub(crate) produced: usize,
+    pub(crate) status: Status,
+}
+
+/// A `bz_stream` set up for compressing. Boxed because libbz2 keeps a
+/// pointer back to it and refuses to work with one that has moved.
+pub(crate) struct Compress(Box<bz_stream>);
+
+impl Compress {
+    pub(crate) fn new(params: Params) -> Result<Self, Error> {
+        let mut stream = Box::new(unsafe { mem::zeroed::<bz_stream>() });
+        let (block_size, work_factor) = (params.block_size as c_int, params.work_factor as c_int);
+        // verbosity 0: nothing on stderr
+        check(unsafe { BZ2_bzCompressInit(&mut *stream, block_size, 0, work_factor) })?;
+        Ok(Compress(stream))
+    }
+
+    pub(crate) fn compress(
+        &mut self,
+        input: &[u8],
+        output: &mut [u8],
+        action: Action,
+    ) -> Result<Progress, Error> {
+        let action = match action {
+            Action::Run => BZ_RUN,
+            Action::Flush => BZ_FLUSH,
+            Action::Finish => BZ_FINISH,
+        };
+        run(&mut self.0, input, output, |stream| unsafe {
+            BZ2_bzCompress(stream, action as c_int)
+        })
+    }
+}
+
+impl Drop for Compress {
+    fn drop(&mut self) {
+        unsafe { BZ2_bzCompressEnd(&mut *self.0) };
+    }
+}
+
+/// A `bz_stream` set up for decompressing one member of a bzip2 file.
+pub(crate) struct Decompress(Box<bz_stream>);
+
+impl Decompress {
+    /// `small` uses an algorithm that needs about half the memory and runs
+    /// at about half the speed.
+    pub(crate) fn new(small: bool) -> Result<Self, Error> {
+        let mut stream = Box::new(unsafe { mem::zeroed::<bz_stream>() });
+        check(unsafe { BZ2_bzDecompressInit(&mut *stream, 0, small as c_int) })?;
+        Ok(Decompress(stream))
+    }
+
+    pub(crate) fn decompress(
+        &mut self,
+        input: &[u8],
+        output: &mut [u8],
+    ) -> Result<Progress, Error> {
+        run(&mut self.0, input, output, |stream| unsafe {
+            BZ2_bzDecompress(stream)
+        })
+    }
+}
+
+impl Drop for Decompress {
+    fn drop(&mut self) {
+        unsafe { BZ2_bzDecompressEnd(&mut *self.0) };
+    }
+}
+
+/// Points the stream at `input` and `output` for one call, so it never
+/// holds on to either past it.
+fn run(
+    stream: &mut bz_stream,
+    input: &[u8],
+    output: &mut [u8],
+    call: impl FnOnce(*mut bz_stream) -> c_int,
+) -> Result<Progress, Error> {
+    // the lengths are `c_uint`s; anything past that waits for the next call
+    let avail_in = input.len().min(c_uint::MAX as usize);
+    let avail_out = output.len().min(c_uint::MAX as usize);
+    stream.next_in = input.as_ptr() as *mut _;
+    stream.avail_in = avail_in as c_uint;
+    stream.next_out = output.as_mut_ptr() as *mut _;
+    stream.avail_out = avail_out as c_uint;
+
+    let result = call(stream);
+
+    let consumed = avail_in - stream.avail_in as usize;
+    let produced = avail_out - stream.avail_out as usize;
+    stream.next_in = std::ptr::null_mut();
+    stream.next_out = std::ptr::null_mut();
+    Ok(Progress {
+        consumed,
+        produced,
+        status: check(result)?,
+    })
+}
@@ -5,9 +5,18 @@
 include!(concat!(env!("OUT_DIR"), "/", "shared_lib_bindings.rs"));
 include!(concat!(env!("OUT_DIR"), "/", "static_lib_bindings.rs"));
 
+mod bz;
+pub mod read;
+pub mod write;
+
+pub use bz::{Error, Params};
+pub use read::BzDecoder;
+pub use write::BzEncoder;
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::io::{self, Read, Write};
     use std::mem;
 
     #[test]
@@ -99,6 +108,114 @@ mod tests {
         }
     }
 
+    fn compress(input: &[u8], params: Params) -> Vec<u8> {
+        let mut encoder = BzEncoder::with_params(Vec::new(), params).unwrap();
+        encoder.write_all(input).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    fn decompress(compressed: &[u8]) -> io::Result<Vec<u8>> {
+        let mut out = Vec::new();
+        BzDecoder::new(compressed).read_to_end(&mut out)?;
+        Ok(out)
+    }
+
+    fn bz_error(err: io::Error) -> Error {
+        *err.into_inner().unwrap().downcast::<Error>().unwrap()
+    }
+
+    #[test]
+    fn encoder_and_decoder_round_trip() {
+        let input = include_str!("../futurama_quotes.txt").as_bytes().repeat(50);
+        for params in [
+            Params::default(),
+            Params::default().block_size(1),
+            Params::default().block_size(1).work_factor(250),
+        ] {
+            let compressed = compress(&input, params);
+            assert!(compressed.len() < input.len() / 10);
+            assert_eq!(decompress(&compressed).unwrap(), input);
+        }
+
+        // a byte at a time both ways
+        let mut encoder = BzEncoder::new(Vec::new());
+        for byte in &input[..1000] {
+            encoder.write_all(&[*byte]).unwrap();
+        }
+        let compressed = encoder.finish().unwrap();
+        let mut decoder = BzDecoder::new(&compressed[..]);
+        let mut out = Vec::new();
+        let mut byte = [0];
+        while decoder.read(&mut byte).unwrap() == 1 {
+            out.push(byte[0]);
+        }
+        assert_eq!(out, &input[..1000]);
+    }
+
+    #[test]
+    fn flush_writes_out_the_block_so_far() {
+        let mut encoder = BzEncoder::new(Vec::new());
+        encoder.write_all(b"so far").unwrap();
+        assert!(encoder.get_ref().is_empty());
+        encoder.flush().unwrap();
+        // the stream header, then the block
+        assert!(encoder.get_ref().len() > 10);
+
+        encoder.write_all(b", and then some").unwrap();
+        let compressed = encoder.finish().unwrap();
+        assert_eq!(decompress(&compressed).unwrap(), b"so far, and then some");
+    }
+
+    #[test]
+    fn reads_every_member() {
+        let mut compressed = compress(b"first ", Params::default());
+        compressed.extend(compress(b"", Params::default()));
+        compressed.extend(compress(b"second", Params::default().block_size(1)));
+
+        let mut decoder = BzDecoder::new(&compressed[..]);
+        let mut out = String::new();
+        decoder.read_to_string(&mut out).unwrap();
+        assert_eq!(out, "first second");
+        assert_eq!(decoder.members(), 3);
+    }
+
+    #[test]
+    fn maps_return_codes_to_errors() {
+        let err = BzEncoder::with_params(Vec::new(), Params::default().block_size(10));
+        assert_eq!(err.unwrap_err(), Error::Param);
+        let err = BzEncoder::with_params(Vec::new(), Params::default().work_factor(251));
+        assert_eq!(err.unwrap_err(), Error::Param);
+
+        let err = decompress(b"definitely not bzip2").unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(bz_error(err), Error::DataMagic);
+
+        let mut compressed = compress(b"hello hello hello", Params::default());
+        let end = compressed.len();
+        assert_eq!(
+            bz_error(decompress(&compressed[..end - 5]).unwrap_err()),
+            Error::UnexpectedEof
+        );
+        assert_eq!(bz_error(decompress(b"").unwrap_err()), Error::UnexpectedEof);
+
+        compressed[end / 2] ^= 0xff;
+        assert_eq!(bz_error(decompress(&compressed).unwrap_err()), Error::Data);
+
+        // trailing garbage is treated as a member that isn't one
+        let mut trailing = compress(b"hello", Params::default());
+        trailing.extend_from_slice(b"garbage");
+        assert_eq!(
+            bz_error(decompress(&trailing).unwrap_err()),
+            Error::DataMagic
+        );
+
+        assert_eq!(Error::Config.code(), BZ_CONFIG_ERROR);
+        assert_eq!(
+            Error::Unknown(-42).to_string(),
+            "unknown bzip2 return code -42"
+        );
+    }
+
     #[test]
     fn static_lib_test() {
         let i: i32;
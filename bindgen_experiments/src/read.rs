@@ -0,0 +1,90 @@
+//! Decompressing what's read from another reader.
+
+use std::fmt;
+use std::io::{self, BufRead, BufReader, Read};
+
+use crate::bz::{Decompress, Error, Status};
+
+/// Reads the decompressed data out of a bzip2 stream on `R`.
+///
+/// Like the `bzip2` command it reads every member of a multi-member stream
+/// (what concatenating `.bz2` files or `pbzip2` gives you) as one. Anything
+/// after the last member that isn't another one is an error.
+pub struct BzDecoder<R: Read> {
+    inner: BufReader<R>,
+    /// `None` between members.
+    stream: Option<Decompress>,
+    small: bool,
+    members: usize,
+}
+
+impl<R: Read> BzDecoder<R> {
+    pub fn new(inner: R) -> Self {
+        Self::with_small(inner, false)
+    }
+
+    /// With `small`, uses about half the memory at about half the speed.
+    pub fn with_small(inner: R, small: bool) -> Self {
+        BzDecoder {
+            inner: BufReader::new(inner),
+            stream: None,
+            small,
+            members: 0,
+        }
+    }
+
+    /// How many members have been read to the end so far.
+    pub fn members(&self) -> usize {
+        self.members
+    }
+
+    pub fn get_ref(&self) -> &R {
+        self.inner.get_ref()
+    }
+
+    /// Any input that was read ahead is lost with the decoder.
+    pub fn into_inner(self) -> R {
+        self.inner.into_inner()
+    }
+}
+
+impl<R: Read> Read for BzDecoder<R> {
+    fn read(&mut self, out: &mut [u8]) -> io::Result<usize> {
+        if out.is_empty() {
+            return Ok(0);
+        }
+        loop {
+            let input = self.inner.fill_buf()?;
+            if self.stream.is_none() {
+                // a clean end, after at least one member
+                if input.is_empty() && self.members > 0 {
+                    return Ok(0);
+                }
+                self.stream = Some(Decompress::new(self.small)?);
+            }
+            let stream = self.stream.as_mut().unwrap();
+            if input.is_empty() {
+                return Err(Error::UnexpectedEof.into());
+            }
+
+            let progress = stream.decompress(input, out)?;
+            self.inner.consume(progress.consumed);
+            if progress.status == Status::StreamEnd {
+                self.stream = None;
+                self.members += 1;
+            }
+            if progress.produced > 0 {
+                return Ok(progress.produced);
+            }
+        }
+    }
+}
+
+impl<R: Read + fmt::Debug> fmt::Debug for BzDecoder<R> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BzDecoder")
+            .field("inner", self.inner.get_ref())
+            .field("members", &self.members)
+            .finish()
+    }
+}
@@ -0,0 +1,115 @@
+//! Compressing whatever is written through to another writer.
+
+use std::fmt;
+use std::io::{self, Write};
+
+use crate::bz::{Action, Compress, Error, Params, Status};
+
+const BUF_SIZE: usize = 32 * 1024;
+
+/// Compresses everything written to it into a bzip2 stream on `W`.
+///
+/// Call [`finish`](BzEncoder::finish) when done: it writes the end of the
+/// stream and hands `W` back. Dropping it finishes too, but loses any error.
+pub struct BzEncoder<W: Write> {
+    /// `None` only once `finish` has taken it.
+    inner: Option<W>,
+    stream: Compress,
+    buf: Vec<u8>,
+    finished: bool,
+}
+
+impl<W: Write> BzEncoder<W> {
+    /// Compresses with [`Params::default`].
+    pub fn new(inner: W) -> Self {
+        Self::with_params(inner, Params::default()).expect("default params are valid")
+    }
+
+    /// Fails with [`Error::Param`] if `params` are out of range.
+    pub fn with_params(inner: W, params: Params) -> Result<Self, Error> {
+        Ok(BzEncoder {
+            inner: Some(inner),
+            stream: Compress::new(params)?,
+            buf: vec![0; BUF_SIZE],
+            finished: false,
+        })
+    }
+
+    pub fn get_ref(&self) -> &W {
+        self.inner.as_ref().unwrap()
+    }
+
+    /// Writing to it directly will most likely corrupt the stream.
+    pub fn get_mut(&mut self) -> &mut W {
+        self.inner.as_mut().unwrap()
+    }
+
+    /// Writes out the end of the stream and hands back the writer.
+    pub fn finish(mut self) -> io::Result<W> {
+        self.try_finish()?;
+        Ok(self.inner.take().unwrap())
+    }
+
+    fn try_finish(&mut self) -> io::Result<()> {
+        if !self.finished {
+            self.drive(Action::Finish, Status::StreamEnd)?;
+            self.finished = true;
+        }
+        self.get_mut().flush()
+    }
+
+    /// Calls bzip2 with no more input until it says `done`, writing out what
+    /// it gives.
+    fn drive(&mut self, action: Action, done: Status) -> io::Result<()> {
+        loop {
+            let progress = self.stream.compress(&[], &mut self.buf, action)?;
+            let inner = self.inner.as_mut().unwrap();
+            inner.write_all(&self.buf[..progress.produced])?;
+            if progress.status == done {
+                return Ok(());
+            }
+        }
+    }
+}
+
+impl<W: Write> Write for BzEncoder<W> {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        if data.is_empty() {
+            return Ok(0);
+        }
+        loop {
+            let progress = self.stream.compress(data, &mut self.buf, Action::Run)?;
+            let inner = self.inner.as_mut().unwrap();
+            inner.write_all(&self.buf[..progress.produced])?;
+            // it only holds back input when the output buffer filled up
+            if progress.consumed > 0 {
+                return Ok(progress.consumed);
+            }
+        }
+    }
+
+    /// Ends the current block early and writes it out, all but the last few
+    /// bits, which bzip2 holds on to until the next block or the end of the
+    /// stream. Costs some compression.
+    fn flush(&mut self) -> io::Result<()> {
+        self.drive(Action::Flush, Status::RunOk)?;
+        self.get_mut().flush()
+    }
+}
+
+impl<W: Write> Drop for BzEncoder<W> {
+    fn drop(&mut self) {
+        if self.inner.is_some() {
+            let _ = self.try_finish();
+        }
+    }
+}
+
+impl<W: Write + fmt::Debug> fmt::Debug for BzEncoder<W> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BzEncoder")
+            .field("inner", &self.inner)
+            .field("finished", &self.finished)
+            .finish()
+    }
+}
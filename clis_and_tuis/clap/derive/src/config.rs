@@ -0,0 +1,49 @@
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+/// Settings that can come from a `--config` TOML file or the command line
+#[derive(Debug, Default, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// name to operate on
+    pub name: Option<String>,
+    /// debug level
+    pub debug: Option<u8>,
+    /// settings for the `test` subcommand
+    pub test: TestConfig,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TestConfig {
+    /// lists test values
+    pub list: Option<bool>,
+}
+
+impl Config {
+    /// Reads and parses a TOML config file
+    pub fn load(path: &Path) -> Result<Self> {
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("could not read config file `{}`", path.display()))?;
+        toml::from_str(&content)
+            .with_context(|| format!("could not parse config file `{}`", path.display()))
+    }
+
+    /// Layers `overrides` on top of `self` - any value set in `overrides` wins
+    pub fn merge(self, overrides: Config) -> Config {
+        Config {
+            name: overrides.name.or(self.name),
+            debug: overrides.debug.or(self.debug),
+            test: TestConfig {
+                list: overrides.test.list.or(self.test.list),
+            },
+        }
+    }
+
+    /// Renders the config back into TOML, e.g. to seed a new config file
+    pub fn to_toml(&self) -> Result<String> {
+        toml::to_string(self).context("could not serialize config")
+    }
+}
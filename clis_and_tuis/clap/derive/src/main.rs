@@ -1,6 +1,12 @@
+use std::io;
 use std::path::PathBuf;
 
-use clap::{Parser, Subcommand};
+use anyhow::{Context, Result};
+use clap::{CommandFactory, Parser, Subcommand, ValueEnum};
+use clap_complete::Shell;
+
+mod config;
+use config::{Config, TestConfig};
 
 #[derive(Parser)]
 #[command(version, about, long_about = None)]
@@ -9,11 +15,11 @@ struct Cli {
     name: Option<String>,
 
     /// Sets a custom config file
-    #[arg(short, long, value_name = "FILE")]
+    #[arg(short, long, value_name = "FILE", global = true)]
     config: Option<PathBuf>,
 
     /// Turn debugging information on
-    #[arg(short, long, action = clap::ArgAction::Count)]
+    #[arg(short, long, action = clap::ArgAction::Count, global = true)]
     debug: u8,
 
     #[command(subcommand)]
@@ -28,13 +34,116 @@ enum Commands {
         #[arg(short, long)]
         list: bool,
     },
+    /// inspects the configuration
+    Config {
+        #[command(subcommand)]
+        command: ConfigCommands,
+    },
+    /// generates shell completions
+    Completions {
+        /// shell to generate completions for
+        #[arg(value_enum)]
+        shell: CompletionShell,
+    },
+    /// generates man pages
+    Man {
+        /// writes a page per (sub)command into this directory instead of stdout
+        #[arg(short, long, value_name = "DIR")]
+        out_dir: Option<PathBuf>,
+    },
+}
+
+#[derive(Subcommand)]
+enum ConfigCommands {
+    /// prints the config file merged with command line args, as TOML
+    Show,
+    /// prints the path of the config file in use
+    Path,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum CompletionShell {
+    Bash,
+    Zsh,
+    Fish,
+}
+
+impl From<CompletionShell> for Shell {
+    fn from(shell: CompletionShell) -> Self {
+        match shell {
+            CompletionShell::Bash => Shell::Bash,
+            CompletionShell::Zsh => Shell::Zsh,
+            CompletionShell::Fish => Shell::Fish,
+        }
+    }
+}
+
+impl Cli {
+    /// The command line args that also have a config file equivalent.
+    /// Flags that were not given stay `None` so they don't mask the config file.
+    fn overrides(&self) -> Config {
+        let list = match &self.command {
+            Some(Commands::Test { list: true }) => Some(true),
+            _ => None,
+        };
+        Config {
+            name: self.name.clone(),
+            debug: (self.debug > 0).then_some(self.debug),
+            test: TestConfig { list },
+        }
+    }
+
+    /// Loads the config file (if any) and layers the command line args on top
+    fn settings(&self) -> Result<Config> {
+        let file = match self.config.as_deref() {
+            Some(path) => Config::load(path)?,
+            None => Config::default(),
+        };
+        Ok(file.merge(self.overrides()))
+    }
 }
 
-fn main() {
+fn main() -> Result<()> {
     let cli = Cli::parse();
 
+    // generators write to stdout, so keep them free of any other output
+    match &cli.command {
+        Some(Commands::Completions { shell }) => {
+            let mut cmd = Cli::command();
+            let bin_name = cmd.get_name().to_string();
+            clap_complete::generate(Shell::from(*shell), &mut cmd, bin_name, &mut io::stdout());
+            return Ok(());
+        }
+        Some(Commands::Man { out_dir }) => {
+            let cmd = Cli::command();
+            match out_dir {
+                Some(dir) => {
+                    std::fs::create_dir_all(dir)
+                        .with_context(|| format!("could not create `{}`", dir.display()))?;
+                    clap_mangen::generate_to(cmd, dir)?;
+                }
+                None => clap_mangen::Man::new(cmd).render(&mut io::stdout())?,
+            }
+            return Ok(());
+        }
+        _ => {}
+    }
+
+    let settings = cli.settings()?;
+
+    if let Some(Commands::Config { command }) = &cli.command {
+        match command {
+            ConfigCommands::Show => print!("{}", settings.to_toml()?),
+            ConfigCommands::Path => match cli.config.as_deref() {
+                Some(path) => println!("{}", path.display()),
+                None => println!("no config file in use"),
+            },
+        }
+        return Ok(());
+    }
+
     // You can check the value provided by positional arguments, or option arguments
-    if let Some(name) = cli.name.as_deref() {
+    if let Some(name) = settings.name.as_deref() {
         println!("Value for name: {name}");
     }
 
@@ -44,7 +153,7 @@ fn main() {
 
     // You can see how many times a particular flag or argument occurred
     // Note, only flags can have multiple occurrences
-    match cli.debug {
+    match settings.debug.unwrap_or(0) {
         0 => println!("Debug mode is off"),
         1 => println!("Debug mode is kind of on"),
         2 => println!("Debug mode is on"),
@@ -53,16 +162,14 @@ fn main() {
 
     // You can check for the existence of subcommands, and if found use their
     // matches just as you would the top level cmd
-    match &cli.command {
-        Some(Commands::Test { list }) => {
-            if *list {
-                println!("Printing testing lists...");
-            } else {
-                println!("Not printing testing lists...");
-            }
+    if let Some(Commands::Test { .. }) = &cli.command {
+        if settings.test.list.unwrap_or(false) {
+            println!("Printing testing lists...");
+        } else {
+            println!("Not printing testing lists...");
         }
-        None => {}
     }
 
     // Continued program logic goes here...
-}
\ No newline at end of file
+    Ok(())
+}
@@ -0,0 +1,136 @@
+use assert_cmd::prelude::*; // Add methods on commands
+use assert_fs::prelude::*;
+use predicates::prelude::*; // Used for writing assertions
+use std::process::Command; // Run programs
+
+type TestResult = Result<(), Box<dyn std::error::Error>>;
+
+#[test]
+fn test_list_matches_golden() -> TestResult {
+    let mut cmd = Command::cargo_bin("derive")?;
+    cmd.arg("test").arg("--list");
+    cmd.assert()
+        .success()
+        .stdout(include_str!("golden/test_list.txt"));
+
+    Ok(())
+}
+
+#[test]
+fn global_options_after_subcommand() -> TestResult {
+    let mut cmd = Command::cargo_bin("derive")?;
+    cmd.arg("bob").arg("test").arg("-dd");
+    cmd.assert()
+        .success()
+        .stdout(include_str!("golden/test_global_debug.txt"));
+
+    Ok(())
+}
+
+#[test]
+fn config_file_merged_with_args() -> TestResult {
+    let file = assert_fs::NamedTempFile::new("derive.toml")?;
+    file.write_str("name = \"alice\"\ndebug = 2\n\n[test]\nlist = true\n")?;
+
+    // the positional name overrides the config file, the rest comes from the file
+    let mut cmd = Command::cargo_bin("derive")?;
+    cmd.arg("bob").arg("config").arg("show").arg("--config").arg(file.path());
+    cmd.assert()
+        .success()
+        .stdout(include_str!("golden/config_show.toml"));
+
+    Ok(())
+}
+
+#[test]
+fn config_file_drives_subcommand() -> TestResult {
+    let file = assert_fs::NamedTempFile::new("derive.toml")?;
+    file.write_str("[test]\nlist = true\n")?;
+
+    let mut cmd = Command::cargo_bin("derive")?;
+    cmd.arg("--config").arg(file.path()).arg("test");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("Printing testing lists..."));
+
+    Ok(())
+}
+
+#[test]
+fn config_file_doesnt_exist() -> TestResult {
+    let mut cmd = Command::cargo_bin("derive")?;
+    cmd.arg("--config").arg("test/file/doesnt/exist.toml");
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("could not read config file"));
+
+    Ok(())
+}
+
+#[test]
+fn config_file_invalid() -> TestResult {
+    let file = assert_fs::NamedTempFile::new("derive.toml")?;
+    file.write_str("colour = \"blue\"\n")?;
+
+    let mut cmd = Command::cargo_bin("derive")?;
+    cmd.arg("--config").arg(file.path());
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("could not parse config file"));
+
+    Ok(())
+}
+
+#[test]
+fn completions_per_shell() -> TestResult {
+    let expected = [
+        ("bash", "_derive()"),
+        ("zsh", "#compdef derive"),
+        ("fish", "complete -c derive"),
+    ];
+    for (shell, marker) in expected {
+        let mut cmd = Command::cargo_bin("derive")?;
+        cmd.arg("completions").arg(shell);
+        cmd.assert()
+            .success()
+            .stdout(predicate::str::contains(marker).and(predicate::str::contains("completions")));
+    }
+
+    Ok(())
+}
+
+#[test]
+fn completions_unknown_shell() -> TestResult {
+    let mut cmd = Command::cargo_bin("derive")?;
+    cmd.arg("completions").arg("tcsh");
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("invalid value 'tcsh'"));
+
+    Ok(())
+}
+
+#[test]
+fn man_page_to_stdout() -> TestResult {
+    let mut cmd = Command::cargo_bin("derive")?;
+    cmd.arg("man");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains(".TH derive").and(predicate::str::contains("completions")));
+
+    Ok(())
+}
+
+#[test]
+fn man_pages_to_dir() -> TestResult {
+    let dir = assert_fs::TempDir::new()?;
+
+    let mut cmd = Command::cargo_bin("derive")?;
+    cmd.arg("man").arg("--out-dir").arg(dir.path());
+    cmd.assert().success();
+
+    dir.child("derive.1").assert(predicate::path::exists());
+    dir.child("derive-config-show.1").assert(predicate::path::exists());
+
+    Ok(())
+}
@@ -0,0 +1,102 @@
+//! `cargo run --bin repl -- [--catalog FILE] [--mode pretty|csv|json]`
+//!
+//! Reads SQL from stdin - interactively or piped in - and prints the results.
+//! Tables created with `CREATE EXTERNAL TABLE` are saved to the catalog file
+//! (`datafusion_catalog.sql` in the current directory by default) and come back next time.
+//! Piped input stops at the first failing statement, with an exit status saying what kind
+//! of failure it was - see `QueryError::exit_code`.
+
+use std::io::{self, BufRead, IsTerminal, Write};
+use std::process::ExitCode;
+use std::time::Instant;
+
+use data_fusion::repl::{Catalog, OutputMode, Repl, Reply};
+
+const DEFAULT_CATALOG: &str = "datafusion_catalog.sql";
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    let mut catalog = DEFAULT_CATALOG.to_string();
+    let mut mode = OutputMode::default();
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match (arg.as_str(), args.next()) {
+            ("--catalog", Some(path)) => catalog = path,
+            ("--mode", Some(name)) => match name.parse() {
+                Ok(m) => mode = m,
+                Err(e) => return usage(&e.to_string()),
+            },
+            _ => return usage(&format!("unexpected argument {arg:?}")),
+        }
+    }
+
+    let catalog = match Catalog::open(&catalog) {
+        Ok(catalog) => catalog,
+        Err(e) => {
+            eprintln!("can't read the catalog: {e}");
+            return ExitCode::FAILURE;
+        }
+    };
+    let mut repl = Repl::new(catalog);
+    repl.set_mode(mode);
+    for (table, e) in repl.restore().await {
+        eprintln!("skipping table {table}: {e}");
+    }
+
+    let interactive = io::stdin().is_terminal();
+    if interactive {
+        println!("DataFusion SQL shell - \\help for help, \\quit to leave");
+    }
+    let mut statement = String::new();
+    let mut lines = io::stdin().lock().lines();
+    loop {
+        if interactive {
+            print!("{}", if statement.is_empty() { "> " } else { ".. " });
+            io::stdout().flush().ok();
+        }
+        let Some(Ok(line)) = lines.next() else {
+            break;
+        };
+        // commands are a line of their own, SQL runs up to the closing `;`
+        let command = statement.is_empty() && line.trim_start().starts_with('\\');
+        if !command {
+            statement.push_str(&line);
+            statement.push('\n');
+            if !line.trim_end().ends_with(';') {
+                continue;
+            }
+        }
+        let input = if command {
+            line
+        } else {
+            std::mem::take(&mut statement)
+        };
+
+        let start = Instant::now();
+        match repl.execute(&input).await {
+            Ok(Reply::Quit) => break,
+            Ok(Reply::Print(text)) => {
+                if !text.is_empty() {
+                    println!("{text}");
+                }
+                if interactive && !command {
+                    println!("({:.3}s)", start.elapsed().as_secs_f64());
+                }
+            }
+            Err(e) => {
+                let error = repl.explain_error(e);
+                eprintln!("{error}");
+                // a script stops at its first failure
+                if !interactive {
+                    return ExitCode::from(error.exit_code());
+                }
+            }
+        }
+    }
+    ExitCode::SUCCESS
+}
+
+fn usage(problem: &str) -> ExitCode {
+    eprintln!("{problem}\nusage: repl [--catalog FILE] [--mode pretty|csv|json]");
+    ExitCode::from(2)
+}
@@ -0,0 +1,442 @@
+//! SQL over the IoT device registry from `ch5_trees`.
+//!
+//! [`DeviceTable`] keeps every device twice: in the B-tree by `numerical_id` and in the
+//! trie by `path`. Filters on either column are pushed down into a lookup on the matching
+//! structure instead of a walk over every device - see [`Access`].
+
+use std::any::Any;
+use std::ops::RangeInclusive;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use ch5_trees::btree::DeviceDatabase;
+use ch5_trees::trie::BestDeviceRegistry;
+use ch5_trees::IoTDevice;
+use datafusion::arrow::array::{RecordBatch, StringArray, UInt64Array};
+use datafusion::arrow::datatypes::{DataType, Field, Schema, SchemaRef};
+use datafusion::catalog::Session;
+use datafusion::common::{plan_err, Result, ScalarValue};
+use datafusion::datasource::{TableProvider, TableType};
+use datafusion::logical_expr::expr::{Like, ScalarFunction};
+use datafusion::logical_expr::utils::split_conjunction;
+use datafusion::logical_expr::{Between, BinaryExpr, Expr, Operator, TableProviderFilterPushDown};
+use datafusion::physical_plan::memory::MemoryExec;
+use datafusion::physical_plan::ExecutionPlan;
+
+// nodes in the B-tree hold up to this many devices
+const BTREE_ORDER: usize = 8;
+
+/// How a scan finds its devices
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Access {
+    /// every device, in id order
+    FullScan,
+    /// a B-tree range lookup
+    Ids(RangeInclusive<u64>),
+    /// a trie walk below this prefix
+    PathPrefix(String),
+    /// the id filters contradict each other - no lookup at all
+    Nothing,
+}
+
+/// The `numerical_id`, `address` and `path` of every registered device.
+/// Ids and paths are unique - the B-tree and trie are keyed on them.
+pub struct DeviceTable {
+    by_id: DeviceDatabase,
+    by_path: BestDeviceRegistry,
+    schema: SchemaRef,
+    pushdown: bool,
+}
+
+impl DeviceTable {
+    pub fn new() -> Self {
+        DeviceTable {
+            by_id: DeviceDatabase::new_empty(BTREE_ORDER),
+            by_path: BestDeviceRegistry::new_empty(),
+            schema: Arc::new(Schema::new(vec![
+                Field::new("numerical_id", DataType::UInt64, false),
+                Field::new("address", DataType::Utf8, false),
+                Field::new("path", DataType::Utf8, false),
+            ])),
+            pushdown: true,
+        }
+    }
+
+    /// Always scans every device and leaves the filtering to DataFusion -
+    /// the baseline to check pushdown against
+    pub fn without_pushdown(mut self) -> Self {
+        self.pushdown = false;
+        self
+    }
+
+    pub fn add(&mut self, device: IoTDevice) -> Result<()> {
+        if self.by_id.find(device.numerical_id).is_some() {
+            return plan_err!("device {} is already registered", device.numerical_id);
+        }
+        if device.path.is_empty() {
+            return plan_err!("device {} has no path", device.numerical_id);
+        }
+        let mut taken = false;
+        self.by_path
+            .walk_prefix(&device.path, |d| taken |= d.path == device.path);
+        if taken {
+            return plan_err!("path {} is already registered", device.path);
+        }
+        self.by_path.add(device.clone());
+        self.by_id.add(device);
+        Ok(())
+    }
+
+    pub fn len(&self) -> u64 {
+        self.by_id.length
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The lookup a scan with these (conjunctive) filters uses. An id range wins over a
+    /// path prefix - it's usually the tighter of the two.
+    pub fn access(&self, filters: &[Expr]) -> Access {
+        if !self.pushdown {
+            return Access::FullScan;
+        }
+        let filters: Vec<&Expr> = filters.iter().flat_map(split_conjunction).collect();
+
+        let mut ids: Option<(i128, i128)> = None;
+        for filter in &filters {
+            if let Some((low, high)) = id_bounds(filter) {
+                let (l, h) = ids.unwrap_or((0, u64::MAX as i128));
+                ids = Some((l.max(low), h.min(high)));
+            }
+        }
+        if let Some((low, high)) = ids {
+            let clamp = |v: i128| v.clamp(0, u64::MAX as i128) as u64;
+            return if low > high || high < 0 {
+                Access::Nothing
+            } else {
+                Access::Ids(clamp(low)..=clamp(high))
+            };
+        }
+
+        // the longest prefix is the most selective
+        filters
+            .iter()
+            .filter_map(|f| path_prefix(f))
+            .max_by_key(|prefix| prefix.len())
+            .map_or(Access::FullScan, Access::PathPrefix)
+    }
+
+    fn devices(&self, access: &Access) -> Vec<IoTDevice> {
+        let mut devices = vec![];
+        match access {
+            Access::FullScan => self.by_id.range(0..=u64::MAX, |d| devices.push(d.clone())),
+            Access::Ids(ids) => self.by_id.range(ids.clone(), |d| devices.push(d.clone())),
+            Access::PathPrefix(prefix) => {
+                self.by_path
+                    .walk_prefix(prefix, |d| devices.push(d.clone()));
+                devices.sort_by_key(|d| d.numerical_id);
+            }
+            Access::Nothing => {}
+        }
+        devices
+    }
+
+    fn batch(&self, devices: &[IoTDevice]) -> Result<RecordBatch> {
+        Ok(RecordBatch::try_new(
+            Arc::clone(&self.schema),
+            vec![
+                Arc::new(UInt64Array::from_iter_values(
+                    devices.iter().map(|d| d.numerical_id),
+                )),
+                Arc::new(StringArray::from_iter_values(
+                    devices.iter().map(|d| &d.address),
+                )),
+                Arc::new(StringArray::from_iter_values(
+                    devices.iter().map(|d| &d.path),
+                )),
+            ],
+        )?)
+    }
+}
+
+impl Default for DeviceTable {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait]
+impl TableProvider for DeviceTable {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn schema(&self) -> SchemaRef {
+        Arc::clone(&self.schema)
+    }
+
+    fn table_type(&self) -> TableType {
+        TableType::Base
+    }
+
+    async fn scan(
+        &self,
+        _state: &dyn Session,
+        projection: Option<&Vec<usize>>,
+        filters: &[Expr],
+        _limit: Option<usize>,
+    ) -> Result<Arc<dyn ExecutionPlan>> {
+        let batch = self.batch(&self.devices(&self.access(filters)))?;
+        Ok(Arc::new(MemoryExec::try_new(
+            &[vec![batch]],
+            self.schema(),
+            projection.cloned(),
+        )?))
+    }
+
+    // Inexact: lookups narrow the scan down, DataFusion still applies the filters as
+    // written - that covers whichever of several filters the lookup didn't use
+    fn supports_filters_pushdown(
+        &self,
+        filters: &[&Expr],
+    ) -> Result<Vec<TableProviderFilterPushDown>> {
+        Ok(filters
+            .iter()
+            .map(|f| {
+                let usable = split_conjunction(f)
+                    .into_iter()
+                    .any(|f| id_bounds(f).is_some() || path_prefix(f).is_some());
+                if self.pushdown && usable {
+                    TableProviderFilterPushDown::Inexact
+                } else {
+                    TableProviderFilterPushDown::Unsupported
+                }
+            })
+            .collect())
+    }
+}
+
+/// Inclusive bounds on `numerical_id` from `numerical_id <op> literal`, either way
+/// round, or `numerical_id BETWEEN low AND high`. Wider than u64, so `id > u64::MAX`
+/// and `id < 0` can be empty ranges.
+fn id_bounds(filter: &Expr) -> Option<(i128, i128)> {
+    const ALL: (i128, i128) = (i128::MIN, i128::MAX);
+    match filter {
+        Expr::BinaryExpr(BinaryExpr { left, op, right }) => {
+            let (op, value) = match (
+                is_column(left, "numerical_id"),
+                is_column(right, "numerical_id"),
+            ) {
+                (true, false) => (*op, integer(right)?),
+                (false, true) => (op.swap()?, integer(left)?),
+                _ => return None,
+            };
+            match op {
+                Operator::Eq => Some((value, value)),
+                Operator::Lt => Some((ALL.0, value - 1)),
+                Operator::LtEq => Some((ALL.0, value)),
+                Operator::Gt => Some((value + 1, ALL.1)),
+                Operator::GtEq => Some((value, ALL.1)),
+                _ => None,
+            }
+        }
+        Expr::Between(Between {
+            expr,
+            negated: false,
+            low,
+            high,
+        }) if is_column(expr, "numerical_id") => Some((integer(low)?, integer(high)?)),
+        _ => None,
+    }
+}
+
+/// The prefix from `path LIKE 'prefix%'`, `starts_with(path, 'prefix')` or `path = 'exact'`
+fn path_prefix(filter: &Expr) -> Option<String> {
+    match filter {
+        Expr::Like(Like {
+            negated: false,
+            expr,
+            pattern,
+            escape_char: None,
+            case_insensitive: false,
+        }) if is_column(expr, "path") => {
+            let prefix = string(pattern)?.strip_suffix('%')?;
+            // any other wildcard and it's not a plain prefix any more
+            (!prefix.contains(['%', '_'])).then(|| prefix.to_string())
+        }
+        Expr::ScalarFunction(ScalarFunction { func, args })
+            if func.name() == "starts_with" && args.len() == 2 && is_column(&args[0], "path") =>
+        {
+            string(&args[1]).map(str::to_string)
+        }
+        Expr::BinaryExpr(BinaryExpr {
+            left,
+            op: Operator::Eq,
+            right,
+        }) => match (is_column(left, "path"), is_column(right, "path")) {
+            (true, false) => string(right).map(str::to_string),
+            (false, true) => string(left).map(str::to_string),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+fn is_column(expr: &Expr, name: &str) -> bool {
+    matches!(expr, Expr::Column(column) if column.name == name)
+}
+
+fn integer(expr: &Expr) -> Option<i128> {
+    match expr {
+        Expr::Literal(ScalarValue::Int8(Some(v))) => Some(*v as i128),
+        Expr::Literal(ScalarValue::Int16(Some(v))) => Some(*v as i128),
+        Expr::Literal(ScalarValue::Int32(Some(v))) => Some(*v as i128),
+        Expr::Literal(ScalarValue::Int64(Some(v))) => Some(*v as i128),
+        Expr::Literal(ScalarValue::UInt8(Some(v))) => Some(*v as i128),
+        Expr::Literal(ScalarValue::UInt16(Some(v))) => Some(*v as i128),
+        Expr::Literal(ScalarValue::UInt32(Some(v))) => Some(*v as i128),
+        Expr::Literal(ScalarValue::UInt64(Some(v))) => Some(*v as i128),
+        _ => None,
+    }
+}
+
+fn string(expr: &Expr) -> Option<&str> {
+    match expr {
+        Expr::Literal(ScalarValue::Utf8(Some(s)) | ScalarValue::LargeUtf8(Some(s))) => Some(s),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use datafusion::arrow::util::pretty::pretty_format_batches;
+    use datafusion::prelude::{col, lit, SessionContext};
+
+    use super::*;
+
+    const DEVICES: u64 = 500;
+
+    fn table() -> DeviceTable {
+        let mut table = DeviceTable::new();
+        // out of order, so the B-tree has to do some work
+        for i in (0..DEVICES).map(|i| (i * 7919) % DEVICES) {
+            let path = format!("factory{}/machine{}/{i}", i % 7, i % 3);
+            table
+                .add(IoTDevice::new(i, format!("My address is {i}"), path))
+                .unwrap();
+        }
+        table
+    }
+
+    // `sql` against the table with pushdown, and against one without
+    async fn both_ways(sql: &str) -> (String, String) {
+        let mut results = vec![];
+        for table in [table(), table().without_pushdown()] {
+            let ctx = SessionContext::new();
+            ctx.register_table("devices", Arc::new(table)).unwrap();
+            let batches = ctx.sql(sql).await.unwrap().collect().await.unwrap();
+            results.push(pretty_format_batches(&batches).unwrap().to_string());
+        }
+        let without = results.pop().unwrap();
+        (results.pop().unwrap(), without)
+    }
+
+    #[tokio::test]
+    async fn pushdown_gives_the_same_results() {
+        for (filter, rows) in [
+            ("numerical_id BETWEEN 100 AND 120", 21),
+            ("numerical_id > 490", 9),
+            ("numerical_id >= 17 AND numerical_id < 20", 3),
+            ("numerical_id = 42", 1),
+            (
+                "300 < numerical_id AND numerical_id <= 305 AND path LIKE 'factory1%'",
+                1,
+            ),
+            ("numerical_id > 10000", 0),
+            ("numerical_id < 0", 0),
+            ("path LIKE 'factory3/machine2/%'", 23),
+            ("path LIKE 'factory3/machine_/1%'", 17),
+            ("starts_with(path, 'factory6/machine0/4')", 6),
+            ("path = 'factory0/machine0/0'", 1),
+            ("path LIKE 'factory2%' OR numerical_id = 1", 73),
+            ("address LIKE '%9'", 50),
+        ] {
+            let sql = format!("SELECT * FROM devices WHERE {filter} ORDER BY numerical_id");
+            let (with, without) = both_ways(&sql).await;
+            assert_eq!(with, without, "{filter}");
+            // a header, the rows and three borders - or just two borders if there are no rows
+            let lines = if rows == 0 { 2 } else { rows + 4 };
+            assert_eq!(with.lines().count(), lines, "{filter}\n{with}");
+        }
+    }
+
+    #[test]
+    fn picks_a_lookup_for_each_filter() {
+        let table = table();
+        let id = || col("numerical_id");
+        let path = || col("path");
+        assert_eq!(
+            table.access(&[id().gt(lit(10u64)), id().lt_eq(lit(20u64))]),
+            Access::Ids(11..=20)
+        );
+        assert_eq!(
+            table.access(&[lit(10u64).lt(id()).and(path().like(lit("f%")))]),
+            Access::Ids(11..=u64::MAX)
+        );
+        assert_eq!(
+            table.access(&[id().between(lit(5), lit(3))]),
+            Access::Nothing
+        );
+        assert_eq!(
+            table.access(&[
+                path().like(lit("factory1%")),
+                path().like(lit("factory1/m%"))
+            ]),
+            Access::PathPrefix("factory1/m".into())
+        );
+        assert_eq!(table.access(&[path().like(lit("a_b%"))]), Access::FullScan);
+        assert_eq!(
+            table.access(&[id().gt(lit(3)).or(path().eq(lit("x")))]),
+            Access::FullScan
+        );
+        assert_eq!(
+            table.without_pushdown().access(&[id().eq(lit(1))]),
+            Access::FullScan
+        );
+    }
+
+    #[tokio::test]
+    async fn scans_use_the_lookup() {
+        let ctx = SessionContext::new();
+        ctx.register_table("devices", Arc::new(table())).unwrap();
+        // the filters handed to the table show up in the plan - BETWEEN arrives as two bounds
+        let plan = ctx
+            .sql("EXPLAIN SELECT address FROM devices WHERE numerical_id BETWEEN 3 AND 4")
+            .await
+            .unwrap()
+            .collect()
+            .await
+            .unwrap();
+        let plan = pretty_format_batches(&plan).unwrap().to_string();
+        assert!(
+            plan.contains("partial_filters=[devices.numerical_id >= UInt64(3)"),
+            "{plan}"
+        );
+    }
+
+    #[test]
+    fn rejects_duplicates() {
+        let mut table = table();
+        assert!(table.add(IoTDevice::new(1, "a", "new/path")).is_err());
+        assert!(table
+            .add(IoTDevice::new(DEVICES, "a", "factory1/machine1/1"))
+            .is_err());
+        assert!(table.add(IoTDevice::new(DEVICES, "a", "")).is_err());
+        // a prefix of an existing path is a path of its own
+        table
+            .add(IoTDevice::new(DEVICES, "a", "factory1/machine1"))
+            .unwrap();
+        assert_eq!(table.len(), DEVICES + 1);
+    }
+}
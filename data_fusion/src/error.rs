@@ -0,0 +1,366 @@
+//! Query failures sorted into what the caller can do about them.
+//!
+//! [`DataFusionError`] says where an error came from - the parser, the planner, Arrow,
+//! the object store. [`QueryError`] says whose problem it is: the user's (a typo in the
+//! SQL, an unknown column), the machine's (out of memory, a missing file) or ours.
+//! Each failure also gets an [`ErrorCode`] that scripts can match on, and a suggestion
+//! when we can guess what was meant.
+
+use std::fmt;
+
+use datafusion::arrow::error::ArrowError;
+use datafusion::common::{DataFusionError, SchemaError};
+
+/// Identifies a kind of failure. The codes are stable - new kinds get new codes, and a
+/// code is never reused for something else.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ErrorCode {
+    UnknownColumn,
+    UnknownTable,
+    AmbiguousColumn,
+    SqlSyntax,
+    InvalidQuery,
+    Unsupported,
+    ExecutionFailed,
+    InvalidConfig,
+    ResourcesExhausted,
+    Io,
+    Internal,
+}
+
+impl ErrorCode {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ErrorCode::UnknownColumn => "DF-U001",
+            ErrorCode::UnknownTable => "DF-U002",
+            ErrorCode::AmbiguousColumn => "DF-U003",
+            ErrorCode::SqlSyntax => "DF-U004",
+            ErrorCode::InvalidQuery => "DF-U005",
+            ErrorCode::Unsupported => "DF-U006",
+            ErrorCode::ExecutionFailed => "DF-U007",
+            ErrorCode::InvalidConfig => "DF-U008",
+            ErrorCode::ResourcesExhausted => "DF-R001",
+            ErrorCode::Io => "DF-I001",
+            ErrorCode::Internal => "DF-X001",
+        }
+    }
+}
+
+impl fmt::Display for ErrorCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// A failed query, by who can fix it
+#[derive(Debug)]
+pub enum QueryError {
+    /// the query itself is wrong - fix it and try again
+    User {
+        code: ErrorCode,
+        message: String,
+        suggestion: Option<String>,
+    },
+    /// the query is fine but needs more memory, disk or time than it was allowed
+    ResourcesExhausted { message: String },
+    /// reading or writing data failed
+    Io { message: String },
+    /// a bug - in DataFusion or in this crate
+    Internal { message: String },
+}
+
+impl QueryError {
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            QueryError::User { code, .. } => *code,
+            QueryError::ResourcesExhausted { .. } => ErrorCode::ResourcesExhausted,
+            QueryError::Io { .. } => ErrorCode::Io,
+            QueryError::Internal { .. } => ErrorCode::Internal,
+        }
+    }
+
+    pub fn message(&self) -> &str {
+        match self {
+            QueryError::User { message, .. }
+            | QueryError::ResourcesExhausted { message }
+            | QueryError::Io { message }
+            | QueryError::Internal { message } => message,
+        }
+    }
+
+    pub fn suggestion(&self) -> Option<&str> {
+        match self {
+            QueryError::User { suggestion, .. } => suggestion.as_deref(),
+            _ => None,
+        }
+    }
+
+    /// Process exit status for this class of error, following BSD `sysexits.h`
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            QueryError::User { .. } => 65,               // EX_DATAERR
+            QueryError::ResourcesExhausted { .. } => 75, // EX_TEMPFAIL
+            QueryError::Io { .. } => 74,                 // EX_IOERR
+            QueryError::Internal { .. } => 70,           // EX_SOFTWARE
+        }
+    }
+
+    /// Suggests one of `tables` when the query names a table that doesn't exist.
+    /// Column suggestions come for free - the error lists the valid ones - table names don't.
+    pub fn with_known_tables<I, S>(mut self, tables: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        if let QueryError::User {
+            code: ErrorCode::UnknownTable,
+            message,
+            suggestion,
+        } = &mut self
+        {
+            if let Some(missing) = quoted(message) {
+                // the planner reports the fully qualified name
+                let missing = missing.rsplit('.').next().unwrap_or(missing);
+                *suggestion =
+                    closest(missing, tables).map(|t| format!("did you mean table `{t}`?"));
+            }
+        }
+        self
+    }
+}
+
+impl From<DataFusionError> for QueryError {
+    fn from(error: DataFusionError) -> Self {
+        let message = error.strip_backtrace();
+        let user = |code| QueryError::User {
+            code,
+            message: message.clone(),
+            suggestion: None,
+        };
+
+        match error.find_root() {
+            DataFusionError::SchemaError(
+                SchemaError::FieldNotFound {
+                    field,
+                    valid_fields,
+                },
+                _,
+            ) => {
+                let names = valid_fields.iter().map(|f| f.name.as_str());
+                QueryError::User {
+                    code: ErrorCode::UnknownColumn,
+                    suggestion: closest(&field.name, names)
+                        .map(|name| format!("did you mean column `{name}`?")),
+                    message,
+                }
+            }
+            DataFusionError::SchemaError(..) => user(ErrorCode::AmbiguousColumn),
+            DataFusionError::Plan(plan)
+                if plan.starts_with("table '") && plan.ends_with("' not found") =>
+            {
+                user(ErrorCode::UnknownTable)
+            }
+            DataFusionError::Plan(_) => user(ErrorCode::InvalidQuery),
+            DataFusionError::SQL(..) => user(ErrorCode::SqlSyntax),
+            DataFusionError::NotImplemented(_) => user(ErrorCode::Unsupported),
+            DataFusionError::Configuration(_) => user(ErrorCode::InvalidConfig),
+            DataFusionError::Execution(_) => user(ErrorCode::ExecutionFailed),
+            DataFusionError::ArrowError(arrow, _) => match arrow {
+                ArrowError::IoError(..) => QueryError::Io { message },
+                ArrowError::MemoryError(_) => QueryError::ResourcesExhausted { message },
+                ArrowError::DivideByZero
+                | ArrowError::ArithmeticOverflow(_)
+                | ArrowError::CastError(_)
+                | ArrowError::ParseError(_)
+                | ArrowError::CsvError(_)
+                | ArrowError::JsonError(_)
+                | ArrowError::InvalidArgumentError(_) => user(ErrorCode::ExecutionFailed),
+                _ => QueryError::Internal { message },
+            },
+            DataFusionError::ResourcesExhausted(_) => QueryError::ResourcesExhausted { message },
+            DataFusionError::IoError(_)
+            | DataFusionError::ObjectStore(_)
+            | DataFusionError::ParquetError(_) => QueryError::Io { message },
+            _ => QueryError::Internal { message },
+        }
+    }
+}
+
+impl fmt::Display for QueryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "error[{}]: {}", self.code(), self.message())?;
+        if let Some(suggestion) = self.suggestion() {
+            write!(f, "\n  help: {suggestion}")?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for QueryError {}
+
+// the first '...' quoted part of `message`
+fn quoted(message: &str) -> Option<&str> {
+    let start = message.find('\'')? + 1;
+    let len = message[start..].find('\'')?;
+    Some(&message[start..start + len])
+}
+
+/// The candidate closest to `name`, if any is close enough to be a plausible typo -
+/// about one edit for every three characters
+fn closest<I, S>(name: &str, candidates: I) -> Option<String>
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<str>,
+{
+    let name = name.to_lowercase();
+    let allowed = (name.chars().count() / 3).max(1);
+    candidates
+        .into_iter()
+        .map(|c| c.as_ref().to_string())
+        .filter(|c| c.to_lowercase() != name)
+        .map(|c| (edit_distance(&name, &c.to_lowercase()), c))
+        .filter(|(distance, _)| *distance <= allowed)
+        .min()
+        .map(|(_, c)| c)
+}
+
+// Levenshtein distance, one row at a time
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let substitute = diagonal + usize::from(ca != *cb);
+            diagonal = row[j + 1];
+            row[j + 1] = substitute.min(row[j] + 1).min(diagonal + 1);
+        }
+    }
+    row[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use datafusion::arrow::array::{Int32Array, RecordBatch, StringArray};
+    use datafusion::arrow::datatypes::{DataType, Field, Schema};
+    use datafusion::execution::memory_pool::{GreedyMemoryPool, MemoryConsumer, MemoryPool};
+    use datafusion::prelude::SessionContext;
+
+    use super::*;
+
+    fn context() -> SessionContext {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("letter", DataType::Utf8, false),
+            Field::new("number", DataType::Int32, false),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema,
+            vec![
+                Arc::new(StringArray::from(vec!["a", "b"])),
+                Arc::new(Int32Array::from(vec![1, 0])),
+            ],
+        )
+        .unwrap();
+        let ctx = SessionContext::new();
+        ctx.register_batch("letters", batch).unwrap();
+        ctx
+    }
+
+    async fn fail(ctx: &SessionContext, sql: &str) -> QueryError {
+        let result = match ctx.sql(sql).await {
+            Ok(df) => df.collect().await.map(|_| ()),
+            Err(e) => Err(e),
+        };
+        QueryError::from(result.expect_err(sql))
+    }
+
+    #[test]
+    fn edit_distances() {
+        assert_eq!(edit_distance("letter", "letter"), 0);
+        assert_eq!(edit_distance("lettr", "letter"), 1);
+        assert_eq!(edit_distance("nubmer", "number"), 2);
+        assert_eq!(edit_distance("", "abc"), 3);
+        assert_eq!(
+            closest("lettr", ["number", "letter"]),
+            Some("letter".into())
+        );
+        assert_eq!(closest("blah", ["number", "letter"]), None);
+    }
+
+    #[tokio::test]
+    async fn suggests_similar_columns() {
+        let ctx = context();
+        let error = fail(&ctx, "SELECT lettr FROM letters").await;
+        assert_eq!(error.code(), ErrorCode::UnknownColumn);
+        assert_eq!(error.suggestion(), Some("did you mean column `letter`?"));
+        assert_eq!(error.exit_code(), 65);
+        assert!(error
+            .to_string()
+            .starts_with("error[DF-U001]: Schema error: No field named lettr"));
+        assert!(error
+            .to_string()
+            .ends_with("\n  help: did you mean column `letter`?"));
+
+        // nothing close enough
+        let error = fail(&ctx, "SELECT blah FROM letters").await;
+        assert_eq!(error.code(), ErrorCode::UnknownColumn);
+        assert_eq!(error.suggestion(), None);
+    }
+
+    #[tokio::test]
+    async fn suggests_similar_tables() {
+        let ctx = context();
+        let error = fail(&ctx, "SELECT * FROM leters").await;
+        assert_eq!(error.code(), ErrorCode::UnknownTable);
+        let error = error.with_known_tables(["numbers", "letters"]);
+        assert_eq!(error.suggestion(), Some("did you mean table `letters`?"));
+    }
+
+    #[tokio::test]
+    async fn classifies_user_errors() {
+        let ctx = context();
+        for (sql, code) in [
+            ("SELEC 1", ErrorCode::SqlSyntax),
+            ("SELECT letter + 1 FROM letters", ErrorCode::InvalidQuery),
+            ("SELECT 1 / number FROM letters", ErrorCode::ExecutionFailed),
+            (
+                "SELECT CAST(letter AS INT) FROM letters",
+                ErrorCode::ExecutionFailed,
+            ),
+            ("SET datafusion.nope = 1", ErrorCode::InvalidConfig),
+        ] {
+            let error = fail(&ctx, sql).await;
+            assert_eq!(error.code(), code, "{sql}: {error}");
+            assert_eq!(error.exit_code(), 65);
+        }
+    }
+
+    #[test]
+    fn classifies_other_errors() {
+        let io = QueryError::from(DataFusionError::IoError(std::io::Error::other(
+            "disk on fire",
+        )));
+        assert_eq!((io.code().as_str(), io.exit_code()), ("DF-I001", 74));
+
+        // found underneath the wrapping, as returned from a failed allocation
+        let pool = GreedyMemoryPool::new(10);
+        let mut reservation =
+            MemoryConsumer::new("test").register(&(Arc::new(pool) as Arc<dyn MemoryPool>));
+        let exhausted = reservation.try_grow(100).unwrap_err().context("sorting");
+        let exhausted = QueryError::from(DataFusionError::ArrowError(
+            ArrowError::ExternalError(Box::new(exhausted)),
+            None,
+        ));
+        assert_eq!(exhausted.code(), ErrorCode::ResourcesExhausted);
+        assert_eq!(exhausted.exit_code(), 75);
+
+        let internal = QueryError::from(DataFusionError::Internal("oops".into()));
+        assert_eq!(
+            (internal.code().as_str(), internal.exit_code()),
+            ("DF-X001", 70)
+        );
+    }
+}
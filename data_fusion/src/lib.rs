@@ -0,0 +1,4 @@
+pub mod devices;
+pub mod error;
+pub mod repl;
+pub mod spark;
@@ -1,9 +1,12 @@
+use data_fusion::error::QueryError;
+use data_fusion::spark::{greatest, register_spark_functions};
 use datafusion::arrow::array::{Int32Array, StringArray};
 use datafusion::arrow::datatypes::{DataType, Field, Schema};
 use datafusion::arrow::record_batch::RecordBatch;
 use datafusion::common::DataFusionError;
-use datafusion::functions_aggregate::expr_fn::{max, min};
+use datafusion::functions_aggregate::expr_fn::min;
 use datafusion::prelude::*;
+use std::process::ExitCode;
 use std::sync::Arc;
 
 async fn sql_over_csv() -> datafusion::error::Result<()> {
@@ -88,67 +91,82 @@ fn build_record_batch() -> datafusion::error::Result<RecordBatch> {
     Ok(batch)
 }
 
+fn build_spark_batch() -> datafusion::error::Result<RecordBatch> {
+    let schema = Arc::new(Schema::new(vec![
+        Field::new("a", DataType::Int32, true),
+        Field::new("b", DataType::Int32, true),
+        Field::new("c", DataType::Int32, true),
+    ]));
+
+    // define data - nulls are skipped by greatest, unless the whole row is null
+    let batch = RecordBatch::try_new(
+        schema,
+        vec![
+            Arc::new(Int32Array::from(vec![Some(1), None, None])),
+            Arc::new(Int32Array::from(vec![Some(4), Some(2), None])),
+            Arc::new(Int32Array::from(vec![Some(3), Some(5), None])),
+        ],
+    )?;
+    Ok(batch)
+}
+
 // df = spark.createDataFrame([(1, 4, 3)], ['a', 'b', 'c'])
 // df.select(greatest(df.a, df.b, df.c).alias("greatest")).collect()
 
-async fn df_greatest(batch: &RecordBatch, col_name: &str) -> datafusion::error::Result<DataFrame> {
+async fn df_greatest(
+    batch: &RecordBatch,
+    col_names: &[&str],
+) -> datafusion::error::Result<DataFrame> {
     // declare a new context. In spark API, this corresponds to a new spark SQLsession
     let ctx = SessionContext::new();
+    // so SQL can call greatest(...) too
+    register_spark_functions(&ctx);
 
     // declare a table in memory. In spark API, this corresponds to createDataFrame(...).
     ctx.register_batch("greatest", batch.clone())?;
     let df = ctx.table("greatest").await?;
 
-    // use max aggregate function on passed in col name
-    let df = df.aggregate(vec![], vec![max(col(col_name))])?;
+    // row-wise maximum across the passed in col names
+    let cols = col_names.iter().map(|name| col(*name)).collect();
+    let df = df.select(vec![greatest(cols).alias("greatest")])?;
 
     // Turbofish for propogating async results
     Ok::<DataFrame, DataFusionError>(df)
 }
 
-#[tokio::main]
-async fn main() -> datafusion::error::Result<()> {
+async fn run() -> datafusion::error::Result<()> {
     sql_over_csv().await?;
     df_over_csv().await?;
     df_in_memory().await?;
 
-    let batch = build_record_batch().unwrap();
-    let df1 = df_greatest(&batch, "number").await?;
+    let df1 = df_greatest(&build_spark_batch()?, &["a", "b", "c"]).await?;
     df1.show().await?;
 
-    let df2 = df_greatest(&batch, "letter").await?;
+    let batch = build_record_batch().unwrap();
+    let df2 = df_greatest(&batch, &["letter", "letter"]).await?;
     df2.show().await?;
 
-    match df_greatest(&batch, "blah").await {
+    // a typo - the error points at the column that was probably meant
+    match df_greatest(&batch, &["number", "lettr"]).await {
         Ok(_df) => println!("ok!"),
-        Err(e) => {
-            // TODO: custom logic for different error types
-            match e {
-                DataFusionError::ArrowError(_, _) => {}
-                DataFusionError::IoError(_) => {}
-                DataFusionError::SQL(_, _) => {}
-                DataFusionError::NotImplemented(_) => {}
-                DataFusionError::Internal(_) => {}
-                DataFusionError::Plan(_) => {}
-                DataFusionError::Configuration(_) => {}
-                DataFusionError::SchemaError(_, _) => {}
-                DataFusionError::Execution(_) => {}
-                DataFusionError::ExecutionJoin(_) => {}
-                DataFusionError::ResourcesExhausted(_) => {}
-                DataFusionError::External(_) => {}
-                DataFusionError::Context(_, _) => {}
-                DataFusionError::Substrait(_) => {}
-                // note: 2 new enum vals not in rustdoc:
-                DataFusionError::ParquetError(_) => {}
-                DataFusionError::ObjectStore(_) => {}
-            };
-            println!("{}", e.message())
-        }
+        Err(e) => println!("{}", QueryError::from(e)),
     };
     println!("no panic");
     Ok(())
 }
 
+#[tokio::main]
+async fn main() -> ExitCode {
+    match run().await {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            let error = QueryError::from(e);
+            eprintln!("{error}");
+            ExitCode::from(error.exit_code())
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
 
@@ -158,49 +176,49 @@ mod tests {
     #[tokio::test]
     async fn test_df_greatest() {
         // arrange:
+        let spark_batch = build_spark_batch().unwrap();
         let batch = build_record_batch().unwrap();
 
         //act:
-        let df1 = df_greatest(&batch, "number").await.unwrap();
-        let df2 = df_greatest(&batch, "letter").await.unwrap();
+        let df1 = df_greatest(&spark_batch, &["a", "b", "c"]).await.unwrap();
+        let df2 = df_greatest(&batch, &["letter", "letter"]).await.unwrap();
 
         // assert:
         let count = df1.clone().count().await.unwrap();
-        assert!(count == 1); // row count AFAIK
+        assert!(count == 3); // one row per input row
 
         let batches1 = df1.collect().await.unwrap();
         assert_batches_eq!(
-            vec![
-                "+----------------------+",
-                "| max(greatest.number) |",
-                "+----------------------+",
-                "| 4                    |",
-                "+----------------------+",
+            [
+                "+----------+",
+                "| greatest |",
+                "+----------+",
+                "| 4        |",
+                "| 5        |",
+                "|          |",
+                "+----------+",
             ],
             &batches1
         );
 
         let batches2 = df2.collect().await.unwrap();
         assert_batches_eq!(
-            vec![
-                "+----------------------+",
-                "| max(greatest.letter) |",
-                "+----------------------+",
-                "| c                    |",
-                "+----------------------+",
+            [
+                "+----------+",
+                "| greatest |",
+                "+----------+",
+                "| a        |",
+                "| b        |",
+                "| c        |",
+                "+----------+",
             ],
             &batches2
         );
 
-        let is_schema_error = match df_greatest(&batch, "blah").await {
+        let is_schema_error = match df_greatest(&batch, &["number", "blah"]).await {
             Ok(_) => false,
-            Err(e) => {
-                match e {
-                    DataFusionError::SchemaError(_, _) => true,
-                    _ => false,
-                }
-            }
+            Err(e) => matches!(e, DataFusionError::SchemaError(_, _)),
         };
-        assert!(is_schema_error == true);
+        assert!(is_schema_error);
     }
 }
@@ -0,0 +1,164 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::PathBuf;
+
+use datafusion::common::{plan_err, Result, TableReference};
+use datafusion::sql::parser::{DFParser, Statement};
+
+/// The `CREATE EXTERNAL TABLE` statements that built the session's tables, keyed by table
+/// name. With a path they're kept in a SQL file - each statement as it was typed, ended
+/// by a `;` - so the next session can replay them.
+#[derive(Debug, Default)]
+pub struct Catalog {
+    path: Option<PathBuf>,
+    tables: BTreeMap<String, String>,
+}
+
+impl Catalog {
+    /// A catalog that forgets everything when the session ends
+    pub fn in_memory() -> Self {
+        Catalog::default()
+    }
+
+    /// Reads the catalog at `path` - a missing file is an empty catalog
+    pub fn open(path: impl Into<PathBuf>) -> Result<Self> {
+        let path = path.into();
+        let text = match fs::read_to_string(&path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == ErrorKind::NotFound => String::new(),
+            Err(e) => return Err(e.into()),
+        };
+
+        let mut tables = BTreeMap::new();
+        for (number, sql) in statements(&text) {
+            match DFParser::parse_sql(sql)?.pop_front() {
+                Some(Statement::CreateExternalTable(create)) => {
+                    tables.insert(table_key(&create.name), sql.to_string());
+                }
+                _ => {
+                    return plan_err!(
+                        "{}:{}: expected a CREATE EXTERNAL TABLE statement",
+                        path.display(),
+                        number
+                    )
+                }
+            }
+        }
+        Ok(Catalog {
+            path: Some(path),
+            tables,
+        })
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.tables.contains_key(&table_key(name))
+    }
+
+    /// Table names and the statements that create them, by name
+    pub fn tables(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.tables
+            .iter()
+            .map(|(name, sql)| (name.as_str(), sql.as_str()))
+    }
+
+    /// Remembers `sql` as it is - a newline may be inside a quoted path or option
+    pub fn insert(&mut self, name: &str, sql: &str) -> Result<()> {
+        let sql = sql.trim().trim_end_matches(';').trim_end();
+        self.tables.insert(table_key(name), sql.to_string());
+        self.save()
+    }
+
+    pub fn remove(&mut self, name: &str) -> Result<()> {
+        if self.tables.remove(&table_key(name)).is_some() {
+            self.save()?;
+        }
+        Ok(())
+    }
+
+    fn save(&self) -> Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        let mut text = String::from("-- tables for the data_fusion repl, replayed at startup\n");
+        for sql in self.tables.values() {
+            text.push_str(sql);
+            // a `;` after a trailing `-- comment` would be part of the comment
+            if sql.lines().last().is_some_and(|line| line.contains("--")) {
+                text.push('\n');
+            }
+            text.push_str(";\n");
+        }
+        fs::write(path, text)?;
+        Ok(())
+    }
+}
+
+/// The statements in a catalog file, with the line each starts on. Only a `;` outside
+/// quotes and comments ends one, and the comments between statements are dropped.
+fn statements(text: &str) -> Vec<(usize, &str)> {
+    enum State {
+        Code,
+        Quoted(char),
+        LineComment,
+        BlockComment,
+    }
+
+    let mut statements = vec![];
+    let mut state = State::Code;
+    // where the statement being read starts, and on which line
+    let mut start = None;
+    let mut line = 1;
+    let mut chars = text.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        let next = chars.peek().map(|&(_, next)| next);
+        match state {
+            State::Code => match c {
+                ';' => {
+                    if let Some((from, line)) = start.take() {
+                        statements.push((line, text[from..i].trim_end()));
+                    }
+                }
+                '-' if next == Some('-') => state = State::LineComment,
+                '/' if next == Some('*') => {
+                    chars.next();
+                    state = State::BlockComment;
+                }
+                c if c.is_whitespace() => {}
+                c => {
+                    start.get_or_insert((i, line));
+                    if c == '\'' || c == '"' {
+                        state = State::Quoted(c);
+                    }
+                }
+            },
+            // a doubled quote closes the literal and opens it straight away again
+            State::Quoted(quote) if c == quote => state = State::Code,
+            State::LineComment if c == '\n' => state = State::Code,
+            State::BlockComment if c == '*' && next == Some('/') => {
+                chars.next();
+                state = State::Code;
+            }
+            _ => {}
+        }
+        if c == '\n' {
+            line += 1;
+        }
+    }
+    // the last one may have lost its `;` to a hand edit
+    if let Some((from, line)) = start {
+        statements.push((line, text[from..].trim_end()));
+    }
+    statements
+}
+
+// unquoted identifiers are case insensitive, and the session's tables all live in
+// `datafusion.public` - so `Trips`, `public.TRIPS` and `datafusion.public.trips` are the
+// same table. Those are keyed by the bare name, anything elsewhere by the full one.
+fn table_key(name: &str) -> String {
+    let table = TableReference::parse_str(name).resolve("datafusion", "public");
+    match (&*table.catalog, &*table.schema) {
+        ("datafusion", "public") => table.table.to_string(),
+        _ => table.to_string(),
+    }
+}
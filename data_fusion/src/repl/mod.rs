@@ -0,0 +1,434 @@
+//! An interactive SQL session.
+//!
+//! Tables come from `CREATE EXTERNAL TABLE name STORED AS CSV|PARQUET|JSON|ARROW LOCATION
+//! 'path'`, where `path` is a file or a directory of files, and are remembered in a
+//! [`Catalog`]. Everything else DataFusion's SQL supports works too - `EXPLAIN ANALYZE`,
+//! `COPY ... TO`, `SHOW TABLES`. Lines starting with `\` are commands for the shell itself.
+
+mod catalog;
+mod output;
+
+use datafusion::common::{plan_err, DataFusionError, Result};
+use datafusion::prelude::{SessionConfig, SessionContext};
+use datafusion::sql::parser::{DFParser, Statement};
+use datafusion::sql::sqlparser::ast::{ObjectType, Statement as SqlStatement};
+
+pub use catalog::Catalog;
+pub use output::OutputMode;
+
+use crate::error::QueryError;
+use crate::spark::register_spark_functions;
+
+pub const HELP: &str = "\
+SQL statements end with `;` and may span lines.
+
+  CREATE EXTERNAL TABLE t STORED AS CSV|PARQUET|JSON|ARROW LOCATION 'file or dir/';
+  DROP TABLE t;
+  EXPLAIN ANALYZE SELECT ...;
+  COPY (SELECT ...) TO 'out.parquet';
+
+  \\mode [pretty|csv|json]   show or set the output format
+  \\tables                   list the saved tables
+  \\help                     this message
+  \\quit                     leave";
+
+/// What the shell should do after a line of input
+#[derive(Debug, PartialEq, Eq)]
+pub enum Reply {
+    /// print this - may be empty
+    Print(String),
+    Quit,
+}
+
+pub struct Repl {
+    ctx: SessionContext,
+    catalog: Catalog,
+    mode: OutputMode,
+}
+
+impl Repl {
+    pub fn new(catalog: Catalog) -> Self {
+        let config = SessionConfig::new().with_information_schema(true);
+        let ctx = SessionContext::new_with_config(config);
+        register_spark_functions(&ctx);
+        Repl {
+            ctx,
+            catalog,
+            mode: OutputMode::default(),
+        }
+    }
+
+    pub fn mode(&self) -> OutputMode {
+        self.mode
+    }
+
+    pub fn set_mode(&mut self, mode: OutputMode) {
+        self.mode = mode;
+    }
+
+    /// Replays the catalog. Tables that can't be created - their files moved, say - are
+    /// returned along with why, and stay in the catalog.
+    pub async fn restore(&mut self) -> Vec<(String, DataFusionError)> {
+        let mut failed = vec![];
+        for (name, sql) in self.catalog.tables() {
+            if let Err(e) = self.ctx.sql(sql).await {
+                failed.push((name.to_string(), e));
+            }
+        }
+        failed
+    }
+
+    /// Classifies a failure from [`execute`](Self::execute), suggesting tables by name
+    pub fn explain_error(&self, error: DataFusionError) -> QueryError {
+        let tables = self
+            .ctx
+            .catalog("datafusion")
+            .and_then(|catalog| catalog.schema("public"))
+            .map(|schema| schema.table_names())
+            .unwrap_or_default();
+        QueryError::from(error).with_known_tables(tables)
+    }
+
+    /// Runs one SQL statement or `\` command
+    pub async fn execute(&mut self, input: &str) -> Result<Reply> {
+        let input = input.trim();
+        match input.strip_prefix('\\') {
+            Some(command) => self.command(command),
+            None => self.sql(input).await.map(Reply::Print),
+        }
+    }
+
+    fn command(&mut self, command: &str) -> Result<Reply> {
+        let mut words = command.split_whitespace();
+        let text = match (words.next(), words.next()) {
+            (Some("q" | "quit"), None) => return Ok(Reply::Quit),
+            (Some("?" | "help"), None) => HELP.to_string(),
+            (Some("mode"), None) => self.mode.to_string(),
+            (Some("mode"), Some(mode)) => {
+                self.mode = mode.parse()?;
+                String::new()
+            }
+            (Some("tables"), None) => self
+                .catalog
+                .tables()
+                .map(|(_, sql)| format!("{sql};"))
+                .collect::<Vec<_>>()
+                .join("\n"),
+            _ => return plan_err!("unknown command \\{command}, try \\help"),
+        };
+        Ok(Reply::Print(text))
+    }
+
+    async fn sql(&mut self, sql: &str) -> Result<String> {
+        let mut statements = DFParser::parse_sql(sql)?;
+        let statement = match (statements.pop_front(), statements.is_empty()) {
+            (Some(statement), true) => statement,
+            (None, _) => return Ok(String::new()),
+            (Some(_), false) => return plan_err!("one statement at a time, please"),
+        };
+        // only remember tables that DataFusion accepted
+        let batches = self.ctx.sql(sql).await?.collect().await?;
+
+        match statement {
+            Statement::CreateExternalTable(create)
+                if !(create.if_not_exists && self.catalog.contains(&create.name)) =>
+            {
+                self.catalog.insert(&create.name, sql)?;
+            }
+            Statement::Statement(statement) => {
+                if let SqlStatement::Drop {
+                    object_type: ObjectType::Table,
+                    names,
+                    ..
+                } = *statement
+                {
+                    for name in names {
+                        self.catalog.remove(&name.to_string())?;
+                    }
+                }
+            }
+            _ => {}
+        }
+
+        // DDL and the like come back without columns
+        if batches.iter().all(|b| b.num_columns() == 0) {
+            return Ok(String::new());
+        }
+        self.mode.format(&batches)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use tempfile::TempDir;
+
+    use super::*;
+
+    async fn run(repl: &mut Repl, input: &str) -> String {
+        match repl.execute(input).await.unwrap() {
+            Reply::Print(text) => text,
+            Reply::Quit => panic!("unexpected quit"),
+        }
+    }
+
+    // a small csv file to build everything else from
+    async fn seed(repl: &mut Repl, dir: &Path) {
+        let csv = dir.join("letters.csv");
+        run(
+            repl,
+            &format!(
+                "COPY (VALUES ('a', 1), ('b', 10), ('c', 100)) TO '{}' STORED AS CSV",
+                csv.display()
+            ),
+        )
+        .await;
+        run(
+            repl,
+            &format!(
+                "CREATE EXTERNAL TABLE letters STORED AS CSV LOCATION '{}' \
+                 OPTIONS ('format.has_header' 'true')",
+                csv.display()
+            ),
+        )
+        .await;
+    }
+
+    #[tokio::test]
+    async fn reads_every_format_from_files_and_directories() {
+        let dir = TempDir::new().unwrap();
+        let mut repl = Repl::new(Catalog::in_memory());
+        repl.set_mode(OutputMode::Csv);
+        seed(&mut repl, dir.path()).await;
+
+        for format in ["PARQUET", "JSON", "ARROW", "CSV"] {
+            let file = dir.path().join(format!("file.{format}"));
+            let folder = dir.path().join(format!("dir_{format}"));
+            let copy = |location: String| {
+                format!(
+                    "COPY (SELECT column1 AS letter, column2 AS number FROM letters) \
+                     TO '{location}' STORED AS {format}"
+                )
+            };
+            run(&mut repl, &copy(file.display().to_string())).await;
+            // a trailing slash writes a directory of files
+            run(&mut repl, &copy(format!("{}/", folder.display()))).await;
+
+            for (table, location) in [("from_file", file), ("from_dir", folder)] {
+                let options = if format == "CSV" {
+                    " OPTIONS ('format.has_header' 'true')"
+                } else {
+                    ""
+                };
+                run(
+                    &mut repl,
+                    &format!(
+                        "CREATE EXTERNAL TABLE {table} STORED AS {format} LOCATION '{}'{options}",
+                        location.display()
+                    ),
+                )
+                .await;
+                assert_eq!(
+                    run(
+                        &mut repl,
+                        &format!("SELECT letter, number FROM {table} ORDER BY number")
+                    )
+                    .await,
+                    "letter,number\na,1\nb,10\nc,100\n",
+                    "{table} stored as {format}"
+                );
+                run(&mut repl, &format!("DROP TABLE {table}")).await;
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn catalog_survives_restarts() {
+        let dir = TempDir::new().unwrap();
+        let catalog = dir.path().join("catalog.sql");
+
+        let mut repl = Repl::new(Catalog::open(&catalog).unwrap());
+        seed(&mut repl, dir.path()).await;
+        run(
+            &mut repl,
+            &format!(
+                "CREATE EXTERNAL TABLE again\nSTORED AS CSV\nLOCATION '{}'",
+                dir.path().join("letters.csv").display()
+            ),
+        )
+        .await;
+        run(&mut repl, "DROP TABLE again").await;
+
+        let mut repl = Repl::new(Catalog::open(&catalog).unwrap());
+        assert!(repl.restore().await.is_empty());
+        assert_eq!(
+            run(&mut repl, "SELECT sum(column2) AS total FROM letters").await,
+            [
+                "+-------+",
+                "| total |",
+                "+-------+",
+                "| 111   |",
+                "+-------+",
+            ]
+            .join("\n")
+        );
+        assert_eq!(run(&mut repl, "\\tables").await.lines().count(), 1);
+
+        run(&mut repl, "DROP TABLE LETTERS").await;
+        let repl = Repl::new(Catalog::open(&catalog).unwrap());
+        assert_eq!(repl.catalog.tables().count(), 0);
+    }
+
+    #[tokio::test]
+    async fn qualified_names_are_the_same_table() {
+        let dir = TempDir::new().unwrap();
+        let catalog = dir.path().join("catalog.sql");
+        let mut repl = Repl::new(Catalog::open(&catalog).unwrap());
+        seed(&mut repl, dir.path()).await;
+        let csv = dir.path().join("letters.csv");
+        run(
+            &mut repl,
+            &format!(
+                "CREATE EXTERNAL TABLE again STORED AS CSV LOCATION '{}'",
+                csv.display()
+            ),
+        )
+        .await;
+        assert!(repl.catalog.contains("public.again") && repl.catalog.contains("Letters"));
+
+        run(&mut repl, "DROP TABLE public.LETTERS").await;
+        run(&mut repl, "DROP TABLE datafusion.public.again").await;
+        let repl = Repl::new(Catalog::open(&catalog).unwrap());
+        assert_eq!(repl.catalog.tables().count(), 0);
+    }
+
+    #[tokio::test]
+    async fn catalog_keeps_statements_as_typed() {
+        let dir = TempDir::new().unwrap();
+        let catalog = dir.path().join("catalog.sql");
+        // a `;` and a newline inside options, neither of which may end up anywhere but
+        // inside their literals
+        let data = dir.path().join("semicolons");
+        std::fs::create_dir(&data).unwrap();
+        std::fs::write(data.join("prices.csv"), "item;price\nbread;3\ncake;12\n").unwrap();
+
+        let mut repl = Repl::new(Catalog::open(&catalog).unwrap());
+        run(
+            &mut repl,
+            &format!(
+                "CREATE EXTERNAL TABLE prices -- from the bakery\n\
+                 STORED AS CSV\n\
+                 LOCATION '{}/'\n\
+                 OPTIONS ('format.has_header' 'true', 'format.delimiter' ';',\n\
+                 'format.null_value' 'none\nat all') -- semicolons",
+                data.display()
+            ),
+        )
+        .await;
+        let saved = std::fs::read_to_string(&catalog).unwrap();
+        assert!(
+            saved.contains("'none\nat all') -- semicolons\n;\n"),
+            "{saved}"
+        );
+
+        let mut repl = Repl::new(Catalog::open(&catalog).unwrap());
+        assert!(repl.restore().await.is_empty());
+        assert_eq!(
+            run(&mut repl, "SELECT sum(price) AS total FROM prices").await,
+            [
+                "+-------+",
+                "| total |",
+                "+-------+",
+                "| 15    |",
+                "+-------+",
+            ]
+            .join("\n")
+        );
+    }
+
+    #[test]
+    fn catalog_files_may_be_edited_by_hand() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("catalog.sql");
+        let text = "-- written by hand\n\
+                    /* two tables; */ CREATE EXTERNAL TABLE a STORED AS CSV LOCATION 'a;b.csv';\n\
+                    \n\
+                    CREATE EXTERNAL TABLE \"B\"\n  STORED AS CSV LOCATION 'it''s.csv'";
+        std::fs::write(&path, text).unwrap();
+        let catalog = Catalog::open(&path).unwrap();
+        assert_eq!(
+            catalog.tables().collect::<Vec<_>>(),
+            [
+                (
+                    "B",
+                    "CREATE EXTERNAL TABLE \"B\"\n  STORED AS CSV LOCATION 'it''s.csv'"
+                ),
+                (
+                    "a",
+                    "CREATE EXTERNAL TABLE a STORED AS CSV LOCATION 'a;b.csv'"
+                ),
+            ]
+        );
+
+        std::fs::write(
+            &path,
+            "CREATE EXTERNAL TABLE a STORED AS CSV LOCATION 'a.csv';\nSELECT 1;",
+        )
+        .unwrap();
+        let error = Catalog::open(&path).unwrap_err().to_string();
+        assert!(
+            error.contains("catalog.sql:2: expected a CREATE EXTERNAL TABLE"),
+            "{error}"
+        );
+    }
+
+    #[tokio::test]
+    async fn reports_tables_that_no_longer_load() {
+        let dir = TempDir::new().unwrap();
+        let catalog = dir.path().join("catalog.sql");
+        let mut repl = Repl::new(Catalog::open(&catalog).unwrap());
+        seed(&mut repl, dir.path()).await;
+        std::fs::remove_file(dir.path().join("letters.csv")).unwrap();
+
+        let mut repl = Repl::new(Catalog::open(&catalog).unwrap());
+        let failed = repl.restore().await;
+        assert_eq!(failed.len(), 1);
+        assert_eq!(failed[0].0, "letters");
+        assert!(repl.catalog.contains("letters"));
+    }
+
+    #[tokio::test]
+    async fn explain_analyze_shows_metrics() {
+        let dir = TempDir::new().unwrap();
+        let mut repl = Repl::new(Catalog::in_memory());
+        seed(&mut repl, dir.path()).await;
+        let plan = run(
+            &mut repl,
+            "EXPLAIN ANALYZE SELECT column1 FROM letters WHERE column2 > 5",
+        )
+        .await;
+        assert!(plan.contains("Plan with Metrics"), "{plan}");
+        assert!(plan.contains("output_rows=2"), "{plan}");
+    }
+
+    #[tokio::test]
+    async fn commands() {
+        let mut repl = Repl::new(Catalog::in_memory());
+        assert_eq!(run(&mut repl, "\\mode").await, "pretty");
+        run(&mut repl, "\\mode json").await;
+        assert_eq!(repl.mode(), OutputMode::Json);
+        assert_eq!(run(&mut repl, "SELECT 1 AS one").await, r#"[{"one":1}]"#);
+        assert_eq!(repl.execute("\\q").await.unwrap(), Reply::Quit);
+        assert!(repl.execute("\\mode xml").await.is_err());
+        assert!(repl.execute("\\nope").await.is_err());
+        assert!(repl.execute("SELECT 1; SELECT 2").await.is_err());
+
+        run(&mut repl, "CREATE TABLE numbers AS VALUES (1)").await;
+        let error = repl.execute("SELECT * FROM number").await.unwrap_err();
+        assert_eq!(
+            repl.explain_error(error).suggestion(),
+            Some("did you mean table `numbers`?")
+        );
+    }
+}
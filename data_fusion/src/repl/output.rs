@@ -0,0 +1,134 @@
+use std::fmt;
+use std::str::FromStr;
+
+use datafusion::arrow::array::RecordBatch;
+use datafusion::arrow::csv::WriterBuilder;
+use datafusion::arrow::json::ArrayWriter;
+use datafusion::arrow::util::pretty::pretty_format_batches;
+use datafusion::common::{plan_err, DataFusionError, Result};
+
+/// How query results are printed
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum OutputMode {
+    /// ASCII table
+    #[default]
+    Pretty,
+    /// CSV with a header row
+    Csv,
+    /// a JSON array with one object per row
+    Json,
+}
+
+impl OutputMode {
+    pub fn format(self, batches: &[RecordBatch]) -> Result<String> {
+        match self {
+            OutputMode::Pretty => Ok(pretty_format_batches(batches)?.to_string()),
+            OutputMode::Csv => {
+                let mut writer = WriterBuilder::new().with_header(true).build(Vec::new());
+                for batch in batches {
+                    writer.write(batch)?;
+                }
+                utf8(writer.into_inner())
+            }
+            OutputMode::Json => {
+                let mut writer = ArrayWriter::new(Vec::new());
+                for batch in batches {
+                    writer.write(batch)?;
+                }
+                writer.finish()?;
+                let json = writer.into_inner();
+                // no rows written means no output at all - still print a valid document
+                if json.is_empty() {
+                    Ok("[]".to_string())
+                } else {
+                    utf8(json)
+                }
+            }
+        }
+    }
+}
+
+fn utf8(bytes: Vec<u8>) -> Result<String> {
+    String::from_utf8(bytes).map_err(|e| DataFusionError::External(Box::new(e)))
+}
+
+impl FromStr for OutputMode {
+    type Err = DataFusionError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "pretty" | "table" => Ok(OutputMode::Pretty),
+            "csv" => Ok(OutputMode::Csv),
+            "json" => Ok(OutputMode::Json),
+            _ => plan_err!("unknown output mode {s:?}, expected pretty, csv or json"),
+        }
+    }
+}
+
+impl fmt::Display for OutputMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            OutputMode::Pretty => "pretty",
+            OutputMode::Csv => "csv",
+            OutputMode::Json => "json",
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use datafusion::arrow::array::{Int32Array, StringArray};
+    use datafusion::arrow::datatypes::{DataType, Field, Schema};
+
+    use super::*;
+
+    fn batch() -> RecordBatch {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("letter", DataType::Utf8, false),
+            Field::new("number", DataType::Int32, true),
+        ]));
+        RecordBatch::try_new(
+            schema,
+            vec![
+                Arc::new(StringArray::from(vec!["a", "b"])),
+                Arc::new(Int32Array::from(vec![Some(1), None])),
+            ],
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn formats_each_mode() {
+        let batches = [batch(), batch()];
+        assert_eq!(
+            OutputMode::Csv.format(&batches).unwrap(),
+            "letter,number\na,1\nb,\na,1\nb,\n"
+        );
+        assert_eq!(
+            OutputMode::Json.format(&batches[..1]).unwrap(),
+            r#"[{"letter":"a","number":1},{"letter":"b"}]"#
+        );
+        assert_eq!(
+            OutputMode::Pretty.format(&batches[..1]).unwrap(),
+            [
+                "+--------+--------+",
+                "| letter | number |",
+                "+--------+--------+",
+                "| a      | 1      |",
+                "| b      |        |",
+                "+--------+--------+",
+            ]
+            .join("\n")
+        );
+        assert_eq!(OutputMode::Json.format(&[]).unwrap(), "[]");
+    }
+
+    #[test]
+    fn parses_mode_names() {
+        assert_eq!("CSV".parse::<OutputMode>().unwrap(), OutputMode::Csv);
+        assert_eq!("table".parse::<OutputMode>().unwrap(), OutputMode::Pretty);
+        assert!("xml".parse::<OutputMode>().is_err());
+    }
+}
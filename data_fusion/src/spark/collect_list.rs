@@ -0,0 +1,95 @@
+use std::any::Any;
+
+use datafusion::arrow::array::ArrayRef;
+use datafusion::arrow::datatypes::{DataType, Field};
+use datafusion::common::{Result, ScalarValue};
+use datafusion::logical_expr::function::{AccumulatorArgs, StateFieldsArgs};
+use datafusion::logical_expr::utils::format_state_name;
+use datafusion::logical_expr::{Accumulator, AggregateUDFImpl, Signature, Volatility};
+
+use super::values::CollectedValues;
+
+/// `collect_list(col)`: the group's non-null values as a list, `[]` if there are none.
+/// Unlike DataFusion's `array_agg`, nulls are dropped - matching Spark.
+#[derive(Debug)]
+pub struct CollectList {
+    signature: Signature,
+}
+
+impl CollectList {
+    pub fn new() -> Self {
+        CollectList {
+            signature: Signature::any(1, Volatility::Immutable),
+        }
+    }
+}
+
+impl Default for CollectList {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl AggregateUDFImpl for CollectList {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn name(&self) -> &str {
+        "collect_list"
+    }
+
+    fn signature(&self) -> &Signature {
+        &self.signature
+    }
+
+    fn return_type(&self, arg_types: &[DataType]) -> Result<DataType> {
+        Ok(DataType::new_list(arg_types[0].clone(), true))
+    }
+
+    fn is_nullable(&self) -> bool {
+        false
+    }
+
+    fn accumulator(&self, acc_args: AccumulatorArgs) -> Result<Box<dyn Accumulator>> {
+        let DataType::List(item) = acc_args.return_type else {
+            unreachable!("return_type is always a list");
+        };
+        Ok(Box::new(CollectListAccumulator(CollectedValues::new(
+            item.data_type().clone(),
+        ))))
+    }
+
+    fn state_fields(&self, args: StateFieldsArgs) -> Result<Vec<Field>> {
+        Ok(vec![Field::new(
+            format_state_name(args.name, "collect_list"),
+            args.return_type.clone(),
+            true,
+        )])
+    }
+}
+
+#[derive(Debug)]
+struct CollectListAccumulator(CollectedValues);
+
+impl Accumulator for CollectListAccumulator {
+    fn update_batch(&mut self, values: &[ArrayRef]) -> Result<()> {
+        self.0.update(&values[0])
+    }
+
+    fn merge_batch(&mut self, states: &[ArrayRef]) -> Result<()> {
+        self.0.merge(&states[0])
+    }
+
+    fn state(&mut self) -> Result<Vec<ScalarValue>> {
+        Ok(vec![self.0.state()?])
+    }
+
+    fn evaluate(&mut self) -> Result<ScalarValue> {
+        self.0.state()
+    }
+
+    fn size(&self) -> usize {
+        self.0.size()
+    }
+}
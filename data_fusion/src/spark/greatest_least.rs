@@ -0,0 +1,114 @@
+use std::any::Any;
+use std::sync::Arc;
+
+use datafusion::arrow::array::Array;
+use datafusion::arrow::compute::kernels::cmp::{gt, lt};
+use datafusion::arrow::compute::kernels::zip::zip;
+use datafusion::arrow::compute::{is_null, or, prep_null_mask_filter};
+use datafusion::arrow::datatypes::DataType;
+use datafusion::common::{plan_err, Result, ScalarValue};
+use datafusion::logical_expr::type_coercion::binary::comparison_coercion;
+use datafusion::logical_expr::{ColumnarValue, ScalarUDFImpl, Signature, Volatility};
+
+/// `greatest(a, b, ...)` / `least(a, b, ...)`: per row, the largest / smallest of the
+/// arguments. Nulls are skipped - the result is null only when every argument is.
+/// Arguments are coerced to a common type first, so `greatest(int_col, 2.5)` is a double.
+#[derive(Debug)]
+pub struct GreatestLeast {
+    name: &'static str,
+    greatest: bool,
+    signature: Signature,
+}
+
+impl GreatestLeast {
+    pub fn greatest() -> Self {
+        Self::new("greatest", true)
+    }
+
+    pub fn least() -> Self {
+        Self::new("least", false)
+    }
+
+    fn new(name: &'static str, greatest: bool) -> Self {
+        GreatestLeast {
+            name,
+            greatest,
+            signature: Signature::user_defined(Volatility::Immutable),
+        }
+    }
+
+    fn common_type(&self, arg_types: &[DataType]) -> Result<DataType> {
+        if arg_types.len() < 2 {
+            return plan_err!(
+                "{} needs at least two arguments, got {}",
+                self.name,
+                arg_types.len()
+            );
+        }
+        arg_types[1..]
+            .iter()
+            .try_fold(
+                arg_types[0].clone(),
+                |common, next| match comparison_coercion(&common, next) {
+                    Some(coerced) => Ok(coerced),
+                    None => plan_err!("{} can't compare {common} with {next}", self.name),
+                },
+            )
+    }
+}
+
+impl ScalarUDFImpl for GreatestLeast {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn name(&self) -> &str {
+        self.name
+    }
+
+    fn signature(&self) -> &Signature {
+        &self.signature
+    }
+
+    fn return_type(&self, arg_types: &[DataType]) -> Result<DataType> {
+        self.common_type(arg_types)
+    }
+
+    fn coerce_types(&self, arg_types: &[DataType]) -> Result<Vec<DataType>> {
+        let common = self.common_type(arg_types)?;
+        Ok(vec![common; arg_types.len()])
+    }
+
+    fn invoke(&self, args: &[ColumnarValue]) -> Result<ColumnarValue> {
+        let all_scalars = args
+            .iter()
+            .all(|arg| matches!(arg, ColumnarValue::Scalar(_)));
+        let arrays = ColumnarValue::values_to_arrays(args)?;
+
+        let mut result = Arc::clone(&arrays[0]);
+        for next in &arrays[1..] {
+            // null wherever either side is null - those count as "not better"
+            let better = if self.greatest {
+                gt(&result, next)?
+            } else {
+                lt(&result, next)?
+            };
+            let better = if better.null_count() > 0 {
+                prep_null_mask_filter(&better)
+            } else {
+                better
+            };
+            // keep what we have when it wins (ties too), or when there's nothing to replace it
+            let keep = or(&better, &is_null(next.as_ref())?)?;
+            result = zip(&keep, &result, next)?;
+        }
+
+        if all_scalars {
+            Ok(ColumnarValue::Scalar(ScalarValue::try_from_array(
+                &result, 0,
+            )?))
+        } else {
+            Ok(ColumnarValue::Array(result))
+        }
+    }
+}
@@ -0,0 +1,264 @@
+//! Spark SQL functions DataFusion doesn't ship (or ships with different semantics).
+//!
+//! Use them from the DataFrame API through the `expr_fn`-style helpers below, or call
+//! [`register_spark_functions`] to make them available to SQL as well.
+
+mod collect_list;
+mod greatest_least;
+mod percentile_approx;
+mod values;
+
+use datafusion::logical_expr::{AggregateUDF, Expr, ScalarUDF};
+use datafusion::prelude::SessionContext;
+
+pub use collect_list::CollectList;
+pub use greatest_least::GreatestLeast;
+pub use percentile_approx::PercentileApprox;
+
+pub fn greatest_udf() -> ScalarUDF {
+    ScalarUDF::from(GreatestLeast::greatest())
+}
+
+pub fn least_udf() -> ScalarUDF {
+    ScalarUDF::from(GreatestLeast::least())
+}
+
+pub fn collect_list_udaf() -> AggregateUDF {
+    AggregateUDF::from(CollectList::new())
+}
+
+pub fn percentile_approx_udaf() -> AggregateUDF {
+    AggregateUDF::from(PercentileApprox::new())
+}
+
+/// Row-wise maximum of `args`, skipping nulls - like Spark `greatest(a, b, c)`
+pub fn greatest(args: Vec<Expr>) -> Expr {
+    greatest_udf().call(args)
+}
+
+/// Row-wise minimum of `args`, skipping nulls - like Spark `least(a, b, c)`
+pub fn least(args: Vec<Expr>) -> Expr {
+    least_udf().call(args)
+}
+
+/// Non-null values of `expr` as a list - like Spark `collect_list(col)`
+pub fn collect_list(expr: Expr) -> Expr {
+    collect_list_udaf().call(vec![expr])
+}
+
+/// Value at `percentage` (a `0.0..=1.0` literal) of the sorted non-null values of `expr`
+/// - like Spark `percentile_approx(col, percentage)`
+pub fn percentile_approx(expr: Expr, percentage: Expr) -> Expr {
+    percentile_approx_udaf().call(vec![expr, percentage])
+}
+
+/// Makes `greatest`, `least`, `collect_list` and `percentile_approx` callable from SQL
+pub fn register_spark_functions(ctx: &SessionContext) {
+    ctx.register_udf(greatest_udf());
+    ctx.register_udf(least_udf());
+    ctx.register_udaf(collect_list_udaf());
+    ctx.register_udaf(percentile_approx_udaf());
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use datafusion::arrow::array::{Float64Array, Int32Array, Int64Array, StringArray};
+    use datafusion::arrow::datatypes::{DataType, Field, Schema};
+    use datafusion::arrow::record_batch::RecordBatch;
+    use datafusion::assert_batches_eq;
+    use datafusion::error::Result;
+    use datafusion::prelude::*;
+
+    use super::*;
+
+    // mixed types and nulls: a Int32, b Int64, c Float64, name Utf8
+    fn context() -> Result<SessionContext> {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("a", DataType::Int32, true),
+            Field::new("b", DataType::Int64, true),
+            Field::new("c", DataType::Float64, true),
+            Field::new("name", DataType::Utf8, true),
+            Field::new("grp", DataType::Utf8, false),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema,
+            vec![
+                Arc::new(Int32Array::from(vec![
+                    Some(1),
+                    Some(5),
+                    None,
+                    None,
+                    Some(7),
+                ])),
+                Arc::new(Int64Array::from(vec![
+                    Some(4),
+                    None,
+                    Some(2),
+                    None,
+                    Some(7),
+                ])),
+                Arc::new(Float64Array::from(vec![
+                    Some(3.5),
+                    Some(2.0),
+                    Some(-1.0),
+                    None,
+                    Some(0.5),
+                ])),
+                Arc::new(StringArray::from(vec![
+                    Some("b"),
+                    None,
+                    Some("a"),
+                    Some("c"),
+                    Some("d"),
+                ])),
+                Arc::new(StringArray::from(vec!["x", "x", "y", "y", "x"])),
+            ],
+        )?;
+        let ctx = SessionContext::new();
+        register_spark_functions(&ctx);
+        ctx.register_batch("t", batch)?;
+        Ok(ctx)
+    }
+
+    async fn sql(query: &str) -> Result<Vec<RecordBatch>> {
+        context()?.sql(query).await?.collect().await
+    }
+
+    #[tokio::test]
+    async fn greatest_and_least_are_row_wise() -> Result<()> {
+        let batches = sql("SELECT greatest(a, b, c) AS g, least(a, b, c) AS l FROM t").await?;
+        assert_batches_eq!(
+            [
+                "+-----+------+",
+                "| g   | l    |",
+                "+-----+------+",
+                "| 4.0 | 1.0  |",
+                "| 5.0 | 2.0  |",
+                "| 2.0 | -1.0 |",
+                "|     |      |",
+                "| 7.0 | 0.5  |",
+                "+-----+------+",
+            ],
+            &batches
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn greatest_keeps_common_integer_type() -> Result<()> {
+        let df = context()?.table("t").await?;
+        let df = df.select(vec![greatest(vec![col("a"), col("b")]).alias("g")])?;
+        assert_eq!(df.schema().field(0).data_type(), &DataType::Int64);
+        assert_batches_eq!(
+            [
+                "+---+", //
+                "| g |", "+---+", "| 4 |", "| 5 |", "| 2 |", "|   |", "| 7 |", "+---+",
+            ],
+            &df.collect().await?
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn greatest_of_strings_and_literals() -> Result<()> {
+        let batches =
+            sql("SELECT least(name, 'b') AS l, greatest(3, 8, 5) AS g FROM t WHERE grp = 'y'")
+                .await?;
+        assert_batches_eq!(
+            [
+                "+---+---+", //
+                "| l | g |",
+                "+---+---+",
+                "| a | 8 |",
+                "| b | 8 |",
+                "+---+---+",
+            ],
+            &batches
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn greatest_rejects_incomparable_types() -> Result<()> {
+        let err = context()?
+            .sql("SELECT greatest(a, to_timestamp(0)) FROM t")
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("greatest"), "{err}");
+        let err = context()?.sql("SELECT least(a) FROM t").await.unwrap_err();
+        assert!(err.to_string().contains("at least two"), "{err}");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn collect_list_skips_nulls() -> Result<()> {
+        let batches = sql(
+            "SELECT grp, collect_list(a) AS a, collect_list(name) AS names FROM t GROUP BY grp ORDER BY grp",
+        )
+        .await?;
+        assert_batches_eq!(
+            [
+                "+-----+-----------+--------+",
+                "| grp | a         | names  |",
+                "+-----+-----------+--------+",
+                "| x   | [1, 5, 7] | [b, d] |",
+                "| y   | []        | [a, c] |",
+                "+-----+-----------+--------+",
+            ],
+            &batches
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn percentile_approx_estimates_from_a_digest() -> Result<()> {
+        // c sorted is [-1.0, 0.5, 2.0, 3.5]: the digest puts the first quartile halfway
+        // between the first two
+        let batches = sql(
+            "SELECT percentile_approx(a, 0.5) AS median_a, percentile_approx(c, 0.25, 100) AS q1_c, \
+             percentile_approx(b, 1.0) AS max_b FROM t",
+        )
+        .await?;
+        assert_batches_eq!(
+            [
+                "+----------+-------+-------+",
+                "| median_a | q1_c  | max_b |",
+                "+----------+-------+-------+",
+                "| 5        | -0.25 | 7     |",
+                "+----------+-------+-------+",
+            ],
+            &batches
+        );
+
+        let df = context()?.table("t").await?;
+        let df = df.aggregate(
+            vec![col("grp")],
+            vec![percentile_approx(col("b"), lit(0.0)).alias("min_b")],
+        )?;
+        let df = df.sort(vec![col("grp").sort(true, false)])?;
+        assert_batches_eq!(
+            [
+                "+-----+-------+",
+                "| grp | min_b |",
+                "+-----+-------+",
+                "| x   | 4     |",
+                "| y   | 2     |",
+                "+-----+-------+",
+            ],
+            &df.collect().await?
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn percentile_approx_validates_percentage() -> Result<()> {
+        let df = context()?
+            .sql("SELECT percentile_approx(a, 1.5) FROM t")
+            .await?;
+        let err = df.collect().await.unwrap_err();
+        assert!(err.to_string().contains("between 0.0 and 1.0"), "{err}");
+        Ok(())
+    }
+}
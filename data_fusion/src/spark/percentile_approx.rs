@@ -0,0 +1,175 @@
+use std::any::Any;
+use std::sync::Arc;
+
+use datafusion::arrow::array::{ArrayRef, RecordBatch};
+use datafusion::arrow::compute::cast;
+use datafusion::arrow::datatypes::{DataType, Field, Schema};
+use datafusion::common::{plan_err, Result, ScalarValue};
+use datafusion::functions_aggregate::approx_percentile_cont::{
+    ApproxPercentileAccumulator, ApproxPercentileCont,
+};
+use datafusion::logical_expr::function::{AccumulatorArgs, StateFieldsArgs};
+use datafusion::logical_expr::{
+    Accumulator, AggregateUDFImpl, ColumnarValue, Signature, Volatility,
+};
+use datafusion::physical_plan::PhysicalExpr;
+
+/// Spark's default `accuracy`, and the most centroids a digest is allowed to keep
+const MAX_ACCURACY: i64 = 10_000;
+
+/// `percentile_approx(col, percentage [, accuracy])`: roughly the value in the group that
+/// `percentage` of the values are less than or equal to, in the input type.
+///
+/// Each group is summarised by a t-digest of at most `accuracy` centroids - the same one
+/// DataFusion's `approx_percentile_cont` uses - so memory stays bounded however many rows
+/// there are. A group with no more values than that is answered exactly; past it, the
+/// relative error is about `1 / accuracy`, like Spark. Accuracies above Spark's default
+/// of 10000 are treated as 10000.
+#[derive(Debug)]
+pub struct PercentileApprox {
+    signature: Signature,
+}
+
+impl PercentileApprox {
+    pub fn new() -> Self {
+        PercentileApprox {
+            signature: Signature::user_defined(Volatility::Immutable),
+        }
+    }
+}
+
+impl Default for PercentileApprox {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl AggregateUDFImpl for PercentileApprox {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn name(&self) -> &str {
+        "percentile_approx"
+    }
+
+    fn signature(&self) -> &Signature {
+        &self.signature
+    }
+
+    fn coerce_types(&self, arg_types: &[DataType]) -> Result<Vec<DataType>> {
+        let [value, _percentage, rest @ ..] = arg_types else {
+            return plan_err!(
+                "percentile_approx(col, percentage [, accuracy]) needs 2 or 3 arguments"
+            );
+        };
+        if rest.len() > 1 {
+            return plan_err!(
+                "percentile_approx(col, percentage [, accuracy]) needs 2 or 3 arguments"
+            );
+        }
+        if !(value.is_numeric() || value.is_temporal()) {
+            return plan_err!("percentile_approx needs a numeric or temporal column, got {value}");
+        }
+        let mut coerced = vec![value.clone(), DataType::Float64];
+        coerced.extend(rest.iter().map(|_| DataType::Int64));
+        Ok(coerced)
+    }
+
+    fn return_type(&self, arg_types: &[DataType]) -> Result<DataType> {
+        Ok(arg_types[0].clone())
+    }
+
+    fn accumulator(&self, acc_args: AccumulatorArgs) -> Result<Box<dyn Accumulator>> {
+        let percentage = match literal(&acc_args.exprs[1])? {
+            ScalarValue::Float64(Some(p)) if (0.0..=1.0).contains(&p) => p,
+            other => {
+                return plan_err!(
+                "percentile_approx percentage must be a literal between 0.0 and 1.0, got {other}"
+            )
+            }
+        };
+        let accuracy = match acc_args.exprs.get(2) {
+            None => MAX_ACCURACY,
+            Some(accuracy) => match literal(accuracy)? {
+                ScalarValue::Int64(Some(a)) if a > 0 => a.min(MAX_ACCURACY),
+                other => {
+                    return plan_err!(
+                        "percentile_approx accuracy must be a positive integer literal, got {other}"
+                    )
+                }
+            },
+        };
+        Ok(Box::new(PercentileAccumulator {
+            digest: ApproxPercentileAccumulator::new_with_max_size(
+                percentage,
+                DataType::Float64,
+                accuracy as usize,
+            ),
+            return_type: acc_args.return_type.clone(),
+        }))
+    }
+
+    // the digest's own: its size, count, sum, min, max and centroids
+    fn state_fields(&self, args: StateFieldsArgs) -> Result<Vec<Field>> {
+        ApproxPercentileCont::new().state_fields(args)
+    }
+}
+
+// constant arguments arrive as physical expressions - evaluating them needs no input
+fn literal(expr: &Arc<dyn PhysicalExpr>) -> Result<ScalarValue> {
+    match expr.evaluate(&RecordBatch::new_empty(Arc::new(Schema::empty())))? {
+        ColumnarValue::Scalar(value) => Ok(value),
+        ColumnarValue::Array(_) => {
+            plan_err!("percentile_approx arguments after the column must be literals")
+        }
+    }
+}
+
+/// Digests every type as `f64` - dates and times by their underlying integer - and turns
+/// the answer back into the input type
+#[derive(Debug)]
+struct PercentileAccumulator {
+    digest: ApproxPercentileAccumulator,
+    return_type: DataType,
+}
+
+impl Accumulator for PercentileAccumulator {
+    fn update_batch(&mut self, values: &[ArrayRef]) -> Result<()> {
+        let mut values = Arc::clone(&values[0]);
+        if values.data_type().is_temporal() {
+            values = cast(&values, &DataType::Int64)?;
+        }
+        self.digest
+            .update_batch(&[cast(&values, &DataType::Float64)?])
+    }
+
+    fn merge_batch(&mut self, states: &[ArrayRef]) -> Result<()> {
+        self.digest.merge_batch(states)
+    }
+
+    fn state(&mut self) -> Result<Vec<ScalarValue>> {
+        self.digest.state()
+    }
+
+    fn evaluate(&mut self) -> Result<ScalarValue> {
+        let ScalarValue::Float64(Some(value)) = self.digest.evaluate()? else {
+            return ScalarValue::try_from(&self.return_type);
+        };
+        if self.return_type.is_floating()
+            || matches!(
+                self.return_type,
+                DataType::Decimal128(..) | DataType::Decimal256(..)
+            )
+        {
+            return ScalarValue::Float64(Some(value)).cast_to(&self.return_type);
+        }
+        // between two integers the digest interpolates - round to the nearer
+        let value = ScalarValue::Int64(Some(value.round() as i64));
+        value.cast_to(&self.return_type)
+    }
+
+    fn size(&self) -> usize {
+        std::mem::size_of_val(self) + self.digest.size() - std::mem::size_of_val(&self.digest)
+    }
+}
@@ -0,0 +1,68 @@
+use std::sync::Arc;
+
+use datafusion::arrow::array::{new_empty_array, Array, ArrayRef, ListArray};
+use datafusion::arrow::compute::{concat, filter, is_not_null};
+use datafusion::arrow::datatypes::DataType;
+use datafusion::common::cast::as_list_array;
+use datafusion::common::utils::array_into_list_array_nullable;
+use datafusion::common::{Result, ScalarValue};
+
+/// The non-null input values of one group, in arrival order.
+#[derive(Debug)]
+pub(super) struct CollectedValues {
+    data_type: DataType,
+    arrays: Vec<ArrayRef>,
+}
+
+impl CollectedValues {
+    pub fn new(data_type: DataType) -> Self {
+        CollectedValues {
+            data_type,
+            arrays: vec![],
+        }
+    }
+
+    pub fn update(&mut self, values: &ArrayRef) -> Result<()> {
+        let values = if values.null_count() > 0 {
+            filter(values, &is_not_null(values)?)?
+        } else {
+            Arc::clone(values)
+        };
+        if !values.is_empty() {
+            self.arrays.push(values);
+        }
+        Ok(())
+    }
+
+    /// Takes in partial results - one list per row, as produced by [`state`](Self::state)
+    pub fn merge(&mut self, state: &ArrayRef) -> Result<()> {
+        let lists = as_list_array(state)?;
+        for list in lists.iter().flatten() {
+            self.update(&list)?;
+        }
+        Ok(())
+    }
+
+    pub fn values(&self) -> Result<ArrayRef> {
+        if self.arrays.is_empty() {
+            return Ok(new_empty_array(&self.data_type));
+        }
+        let arrays: Vec<&dyn Array> = self.arrays.iter().map(|a| a.as_ref()).collect();
+        Ok(concat(&arrays)?)
+    }
+
+    /// Everything collected as a single list value
+    pub fn state(&self) -> Result<ScalarValue> {
+        let list: ListArray = array_into_list_array_nullable(self.values()?);
+        Ok(ScalarValue::List(Arc::new(list)))
+    }
+
+    pub fn size(&self) -> usize {
+        std::mem::size_of_val(self)
+            + self
+                .arrays
+                .iter()
+                .map(|a| a.get_array_memory_size())
+                .sum::<usize>()
+    }
+}
@@ -10,6 +10,7 @@ struct Node {
 }
 
 impl Node {
+    #[allow(clippy::redundant_field_names)]
     pub fn new(dev: IoTDevice) -> Tree {
         Some(Box::new(Node {
             dev: dev,
@@ -32,6 +33,7 @@ impl DeviceRegistry {
         }
     }
 
+    #[allow(clippy::mem_replace_option_with_none)]
     pub fn add(&mut self, device: IoTDevice) {
         self.length += 1;
         let root = mem::replace(&mut self.root, None);
@@ -71,10 +73,12 @@ impl DeviceRegistry {
         }
     }
     
+    #[allow(clippy::unused_unit)]
     pub fn walk(&self, callback: impl Fn(&IoTDevice) -> ()) {
         self.walk_in_order(&self.root, &callback);
     }
 
+    #[allow(clippy::unused_unit)]
     fn walk_in_order(&self, node: &Tree, callback: &impl Fn(&IoTDevice) -> ()) {
         if let Some(n) = node {
             self.walk_in_order(&n.left, callback);
@@ -1,7 +1,9 @@
 use crate::IoTDevice;
 use std::cmp;
+#[allow(unused_imports)]
 use std::collections::HashMap;
 use std::mem;
+use std::ops::RangeInclusive;
 
 type Tree = Box<Node>;
 type KeyType = u64;
@@ -37,6 +39,7 @@ impl Node {
         Node::new(NodeType::Regular)
     }
 
+    #[allow(clippy::redundant_field_names)]
     fn new(node_type: NodeType) -> Tree {
         Box::new(Node {
             left_child: None,
@@ -90,6 +93,7 @@ impl Node {
         true
     }
 
+    #[allow(clippy::mem_replace_option_with_none)]
     pub fn remove_key(&mut self, id: KeyType) -> Option<(KeyType, Data)> {
         match self.find_closest_index(id) {
             Direction::Left => {
@@ -118,6 +122,7 @@ impl Node {
         index
     }
 
+    #[allow(clippy::manual_flatten)]
     pub fn get_device(&self, key: KeyType) -> Option<&IoTDevice> {
         let mut result = None;
         for d in self.devices.iter() {
@@ -146,6 +151,7 @@ pub struct DeviceDatabase {
 }
 
 impl DeviceDatabase {
+    #[allow(clippy::redundant_field_names)]
     pub fn new_empty(order: usize) -> DeviceDatabase {
         DeviceDatabase {
             root: None,
@@ -154,6 +160,7 @@ impl DeviceDatabase {
         }
     }
 
+    #[allow(clippy::mem_replace_option_with_none)]
     pub fn add(&mut self, device: IoTDevice) {
         let node = if self.root.is_some() {
             mem::replace(&mut self.root, None).unwrap()
@@ -219,6 +226,7 @@ impl DeviceDatabase {
         }
     }
 
+    #[allow(clippy::legacy_numeric_constants, clippy::manual_flatten)]
     fn validate(&self, node: &Tree, level: usize) -> (bool, usize, usize) {
         //node.print(format!("Level: {}", level));
         match node.node_type {
@@ -266,12 +274,56 @@ impl DeviceDatabase {
         }
     }
 
+    #[allow(clippy::unused_unit)]
     pub fn walk(&self, callback: impl Fn(&IoTDevice) -> ()) {
         if let Some(ref root) = self.root {
             self.walk_in_order(root, &callback);
         }
     }
 
+    /// Calls back with every device whose id is in `ids`, in order, skipping the subtrees
+    /// that can't hold any of them.
+    pub fn range(&self, ids: RangeInclusive<KeyType>, mut callback: impl FnMut(&IoTDevice)) {
+        if let Some(ref root) = self.root {
+            self.range_r(root, &ids, &mut callback);
+        }
+    }
+
+    fn range_r(
+        &self,
+        node: &Tree,
+        ids: &RangeInclusive<KeyType>,
+        callback: &mut impl FnMut(&IoTDevice),
+    ) {
+        let id = |i: usize| node.devices.get(i)?.as_ref().map(|d| d.numerical_id);
+
+        // the left child holds everything below the first key
+        if let Some(ref left) = node.left_child {
+            if id(0).is_none_or(|first| *ids.start() < first) {
+                self.range_r(left, ids, callback);
+            }
+        }
+
+        for i in 0..node.devices.len() {
+            if let Some(ref k) = node.devices[i] {
+                if k.numerical_id > *ids.end() {
+                    return;
+                }
+                if ids.contains(&k.numerical_id) {
+                    callback(k);
+                }
+            }
+
+            // children[i] holds what's between this key and the next
+            if let Some(ref c) = node.children[i] {
+                if id(i + 1).is_none_or(|next| *ids.start() <= next) {
+                    self.range_r(c, ids, callback);
+                }
+            }
+        }
+    }
+
+    #[allow(clippy::needless_borrow, clippy::unused_unit)]
     fn walk_in_order(&self, node: &Tree, callback: &impl Fn(&IoTDevice) -> ()) {
         if let Some(ref left) = node.left_child {
             self.walk_in_order(left, callback);
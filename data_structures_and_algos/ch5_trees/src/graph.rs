@@ -1,5 +1,8 @@
+#[allow(unused_imports)]
 use std::cmp::{Ord, Ordering, min};
+#[allow(unused_imports)]
 use std::collections::{BTreeSet, BinaryHeap, HashMap, HashSet};
+#[allow(unused_imports)]
 use std::iter::FromIterator;
 
 type KeyType = u64;
@@ -37,6 +40,7 @@ struct Edge {
     node: usize,
 }
 
+#[allow(clippy::clone_on_copy, clippy::needless_borrow, clippy::needless_return, clippy::ptr_arg)]
 fn min_index(weights: &Vec<TentativeWeight>, nodes: &Vec<usize>) -> usize {
     let mut min_weight = (weights[0].clone(), 0);
     for node in nodes.iter() {
@@ -79,6 +83,7 @@ impl InternetOfThings {
         self.adjacency_list = vec![vec![]; self.nodes.len()]
     }
 
+    #[allow(clippy::manual_map)]
     pub fn set_edges(&mut self, from: KeyType, edges: Vec<(u32, KeyType)>) {
         let edges: Vec<Edge> = edges.into_iter().filter_map(|e| {
             if let Some(to) = self.get_node_index(e.1) {
@@ -95,6 +100,7 @@ impl InternetOfThings {
         }
     }
 
+    #[allow(clippy::clone_on_copy, clippy::unnecessary_unwrap, clippy::useless_conversion)]
     pub fn shortest_path(&self, from: KeyType, to: KeyType) -> Option<(u32, Vec<KeyType>)> {
         let mut src = None;
         let mut dest = None;
@@ -169,6 +175,7 @@ impl InternetOfThings {
         }
     }
 
+    #[allow(clippy::clone_on_copy)]
     pub fn connected(&self, from: KeyType, degree: usize) -> Option<HashSet<KeyType>> {
         self.nodes.iter().position(|n| n == &from).map(|i| {
             self.connected_r(i, degree).into_iter().map(|n| self.nodes[n].clone()).collect()
@@ -2,6 +2,7 @@ use crate::MessageNotification;
 use std::boxed::Box;
 use std::mem;
 
+#[allow(clippy::vec_box)]
 pub struct MessageChecker {
     pub length: usize,
     heap: Vec<Box<MessageNotification>>,
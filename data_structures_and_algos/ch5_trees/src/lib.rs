@@ -1,8 +1,14 @@
+// only the tests below use these - .cargo/config allows that for the chapter workspace,
+// but not when data_fusion builds this crate from the root one
+#[allow(dead_code)]
 mod binary_search_tree;
+#[allow(dead_code)]
 mod red_black_tree;
+#[allow(dead_code)]
 mod heap;
-mod trie;
-mod btree;
+pub mod trie;
+pub mod btree;
+#[allow(dead_code)]
 mod graph;
 
 #[derive(Clone, Debug)]
@@ -34,6 +40,7 @@ pub struct MessageNotification {
     pub device: IoTDevice,
 }
 impl MessageNotification {
+    #[allow(clippy::redundant_field_names)]
     pub fn new(device: IoTDevice, no_messages: u64) -> MessageNotification {
         MessageNotification {
             no_messages: no_messages,
@@ -76,6 +83,7 @@ mod tests {
     mod binary_search_tree_tests {
         use super::*;
         #[test]
+        #[allow(clippy::redundant_locals, clippy::unnecessary_sort_by)]
         fn binary_search_tree_walk_in_order() {
             let len = 10;
 
@@ -139,6 +147,7 @@ mod tests {
         }
 
         #[test]
+        #[allow(clippy::redundant_locals, clippy::unnecessary_sort_by)]
         fn red_black_tree_walk_in_order() {
             let len = 10;
 
@@ -248,6 +257,7 @@ mod tests {
         }
 
         #[test]
+        #[allow(clippy::unnecessary_sort_by)]
         fn trie_walk_in_order() {
             let mut trie = trie::BestDeviceRegistry::new_empty();
             let len = 10;
@@ -292,6 +302,27 @@ mod tests {
             assert_eq!(trie.length, len);
             assert_eq!(trie.find("100"), None);
         }
+
+        #[test]
+        fn trie_walk_prefix() {
+            let mut trie = trie::BestDeviceRegistry::new_empty();
+            trie.add(new_device_with_id_path(1, "factory1/machineA/1"));
+            trie.add(new_device_with_id_path(2, "factory1/machineB/2"));
+            trie.add(new_device_with_id_path(3, "factory2/machineA/3"));
+            trie.add(new_device_with_id_path(4, "factory10/machineA/4"));
+
+            let ids = |prefix: &str| {
+                let mut ids = vec![];
+                trie.walk_prefix(prefix, |d| ids.push(d.numerical_id));
+                ids.sort();
+                ids
+            };
+            assert_eq!(ids("factory1/"), vec![1, 2]);
+            assert_eq!(ids("factory1"), vec![1, 2, 4]);
+            assert_eq!(ids("factory2/machineA/3"), vec![3]);
+            assert_eq!(ids(""), vec![1, 2, 3, 4]);
+            assert_eq!(ids("factory3"), Vec::<u64>::new());
+        }
     }
     
     mod btree_tests {
@@ -313,6 +344,7 @@ mod tests {
         }
 
         #[test]
+        #[allow(clippy::redundant_locals, clippy::unnecessary_sort_by)]
         fn btree_walk_in_order() {
             let len = 7;
 
@@ -358,11 +390,38 @@ mod tests {
             assert_eq!(tree.find(6), Some(new_device_with_id(6)));
             assert_eq!(tree.find(7), Some(new_device_with_id(7)));
         }
+
+        #[test]
+        #[allow(clippy::reversed_empty_ranges)]
+        fn btree_range() {
+            let len = 50;
+
+            let mut tree = btree::DeviceDatabase::new_empty(3);
+            let mut items: Vec<IoTDevice> = (0..len).map(|i| new_device_with_id(i * 2)).collect();
+            items.shuffle(&mut thread_rng());
+            for item in items.iter() {
+                tree.add(item.clone());
+            }
+            assert!(tree.is_a_valid_btree());
+
+            let ids = |range| {
+                let mut ids = vec![];
+                tree.range(range, |d| ids.push(d.numerical_id));
+                ids
+            };
+            assert_eq!(ids(10..=16), vec![10, 12, 14, 16]);
+            assert_eq!(ids(11..=17), vec![12, 14, 16]);
+            assert_eq!(ids(95..=1000), vec![96, 98]);
+            assert_eq!(ids(0..=u64::MAX), (0..len).map(|i| i * 2).collect::<Vec<_>>());
+            assert_eq!(ids(13..=13), Vec::<u64>::new());
+            assert_eq!(ids(20..=10), Vec::<u64>::new());
+        }
     }
 
     mod graph_tests {
         use super::*;
 
+        #[allow(clippy::clone_on_copy, clippy::ptr_arg)]
         fn build_graph(g: graph::InternetOfThings, items: &Vec<IoTDevice>) -> graph::InternetOfThings {
             let mut g = g;
     
@@ -1,4 +1,5 @@
 use crate::IoTDevice;
+#[allow(unused_imports)]
 use std::cell::{Ref, RefCell};
 use std::cmp;
 use std::mem;
@@ -40,6 +41,7 @@ impl PartialEq for Node {
 }
 
 impl Node {
+    #[allow(clippy::redundant_field_names)]
     pub fn new(dev: IoTDevice) -> Tree {
         Some(Rc::new(RefCell::new(Node {
             color: Color::Red,
@@ -64,6 +66,7 @@ impl BetterDeviceRegistry {
         }
     }
 
+    #[allow(clippy::mem_replace_option_with_none)]
     pub fn add(&mut self, device: IoTDevice) {
         self.length += 1;
         let root = mem::replace(&mut self.root, None);
@@ -149,6 +152,7 @@ impl BetterDeviceRegistry {
         n.borrow().parent.as_ref().unwrap().borrow().color.clone()
     }
 
+    #[allow(clippy::manual_inspect, clippy::unnecessary_unwrap)]
     fn fix_tree(&mut self, inserted: BareTree) -> Tree {
         let mut not_root = inserted.borrow().parent.is_some();
 
@@ -268,6 +272,7 @@ impl BetterDeviceRegistry {
         })
     }
 
+    #[allow(clippy::unnecessary_unwrap)]
     fn rotate(&self, node: BareTree, direction: Rotation) {
         match direction {
             Rotation::Right => {
@@ -363,6 +368,7 @@ impl BetterDeviceRegistry {
         )
     }
 
+    #[allow(clippy::needless_borrow)]
     fn find_r(&self, node: &Tree, dev: &IoTDevice) -> Option<IoTDevice> {
         match node {
             Some(n) => {
@@ -380,10 +386,12 @@ impl BetterDeviceRegistry {
         }
     }
 
+    #[allow(clippy::unused_unit)]
     pub fn walk(&self, callback: impl Fn(&IoTDevice) -> ()) {
         self.walk_in_order(&self.root, &callback);
     }
 
+    #[allow(clippy::unused_unit)]
     fn walk_in_order(&self, node: &Tree, callback: &impl Fn(&IoTDevice) -> ()) {
         if let Some(n) = node {
             let n = n.borrow();
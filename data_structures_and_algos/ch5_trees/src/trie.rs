@@ -1,7 +1,9 @@
 use crate::IoTDevice;
 use std::boxed::Box;
 use std::collections::HashMap;
+#[allow(unused_imports)]
 use std::mem;
+#[allow(unused_imports)]
 use std::str::Chars;
 
 type Link = Box<Node>;
@@ -13,6 +15,7 @@ struct Node {
 }
 
 impl Node {
+    #[allow(clippy::redundant_field_names)]
     pub fn new(key: char, device: Option<IoTDevice>) -> Link {
         Box::new(Node {
             key: key,
@@ -56,6 +59,7 @@ impl BestDeviceRegistry {
         }
     }
 
+    #[allow(clippy::needless_borrow, clippy::option_map_or_none)]
     pub fn find(&self, path: &str) -> Option<IoTDevice> {
         let mut path = path.chars();
 
@@ -74,12 +78,45 @@ impl BestDeviceRegistry {
         }
     }
 
+    #[allow(clippy::needless_borrow, clippy::unused_unit)]
     pub fn walk(&self, callback: impl Fn(&IoTDevice) -> ()) {
         for r in  self.root.values() {
             self.walk_r(&r, &callback);
         }
     }
 
+    /// Calls back with every device whose path starts with `prefix`
+    pub fn walk_prefix(&self, prefix: &str, mut callback: impl FnMut(&IoTDevice)) {
+        let mut prefix = prefix.chars();
+        let Some(start) = prefix.next() else {
+            for r in self.root.values() {
+                self.walk_prefix_r(r, &mut callback);
+            }
+            return;
+        };
+        let mut node = match self.root.get(&start) {
+            Some(n) => n,
+            None => return,
+        };
+        for c in prefix {
+            match node.next.get(&c) {
+                Some(n) => node = n,
+                None => return,
+            }
+        }
+        self.walk_prefix_r(node, &mut callback);
+    }
+
+    fn walk_prefix_r(&self, node: &Link, callback: &mut impl FnMut(&IoTDevice)) {
+        for n in node.next.values() {
+            self.walk_prefix_r(n, callback);
+        }
+        if let Some(ref dev) = node.value {
+            callback(dev);
+        }
+    }
+
+    #[allow(clippy::needless_borrow, clippy::unused_unit)]
     fn walk_r(&self, node: &Link, callback: &impl Fn(&IoTDevice) -> ()) {
         for n in  node.next.values() {
             self.walk_r(&n, callback);
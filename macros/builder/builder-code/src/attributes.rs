@@ -0,0 +1,103 @@
+use syn::meta::ParseNestedMeta;
+use syn::{Attribute, Expr, Ident, LitStr, Result};
+
+/// What a field's `#[builder(...)]` attributes ask for.
+#[derive(Default)]
+pub struct FieldOptions {
+    pub default: Option<DefaultValue>,
+    pub rename: Option<Ident>,
+    pub each: Option<Ident>,
+}
+
+pub enum DefaultValue {
+    /// `#[builder(default)]`
+    Trait,
+    /// `#[builder(default = expr)]`
+    Expr(Expr),
+}
+
+impl FieldOptions {
+    pub fn parse(attrs: &[Attribute]) -> Result<Self> {
+        let mut options = FieldOptions::default();
+        for attr in attrs.iter().filter(|a| a.path().is_ident("builder")) {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("default") {
+                    let value = if meta.input.peek(syn::Token![=]) {
+                        DefaultValue::Expr(meta.value()?.parse()?)
+                    } else {
+                        DefaultValue::Trait
+                    };
+                    set_once(&meta, &mut options.default, value)
+                } else if meta.path.is_ident("rename") {
+                    let name = ident_value(&meta)?;
+                    set_once(&meta, &mut options.rename, name)
+                } else if meta.path.is_ident("each") {
+                    let name = ident_value(&meta)?;
+                    set_once(&meta, &mut options.each, name)
+                } else {
+                    Err(meta.error(format!(
+                        "unknown builder attribute `{}`, expected `default`, `rename` or `each`",
+                        path_name(&meta)
+                    )))
+                }
+            })?;
+        }
+        Ok(options)
+    }
+}
+
+/// What the struct's own `#[builder(...)]` attributes ask for.
+#[derive(Default)]
+pub struct StructOptions {
+    /// Track the required fields in the builder's type, so that `build`
+    /// only compiles once they're all set.
+    pub typestate: bool,
+}
+
+impl StructOptions {
+    pub fn parse(attrs: &[Attribute]) -> Result<Self> {
+        let mut options = StructOptions::default();
+        for attr in attrs.iter().filter(|a| a.path().is_ident("builder")) {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("typestate") {
+                    if options.typestate {
+                        return Err(meta.error("`typestate` given more than once"));
+                    }
+                    options.typestate = true;
+                    Ok(())
+                } else {
+                    Err(meta.error(format!(
+                        "unknown builder attribute `{}`, expected `typestate`",
+                        path_name(&meta)
+                    )))
+                }
+            })?;
+        }
+        Ok(options)
+    }
+}
+
+fn set_once<T>(meta: &ParseNestedMeta, slot: &mut Option<T>, value: T) -> Result<()> {
+    if slot.is_some() {
+        return Err(meta.error(format!("`{}` given more than once", path_name(meta))));
+    }
+    *slot = Some(value);
+    Ok(())
+}
+
+/// `= "name"`, which has to be usable as a method name.
+fn ident_value(meta: &ParseNestedMeta) -> Result<Ident> {
+    let lit: LitStr = meta.value()?.parse()?;
+    lit.parse().map_err(|_| {
+        syn::Error::new(
+            lit.span(),
+            format!("`{}` isn't a valid method name", lit.value()),
+        )
+    })
+}
+
+fn path_name(meta: &ParseNestedMeta) -> String {
+    meta.path
+        .get_ident()
+        .map_or_else(|| "?".to_string(), Ident::to_string)
+}
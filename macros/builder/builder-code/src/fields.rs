@@ -1,89 +1,188 @@
-use quote::quote;
+use quote::{quote, quote_spanned};
 use syn::__private::TokenStream2;
-use syn::punctuated::Punctuated;
-use syn::token::Comma;
-use syn::{Field, Ident, Type};
-
-#[allow(dead_code)]
-fn matches_type(ty: &Type, type_name: &str) -> bool {
-    if let Type::Path(ref p) = ty {
-        let first_match = p.path.segments[0].ident.to_string();
-        return first_match == *type_name;
-    }
-    false
-}
+use syn::spanned::Spanned;
+use syn::{Field, GenericArgument, Ident, PathArguments, Type};
+
+use crate::attributes::{DefaultValue, FieldOptions};
 
-fn get_name_and_type<'a>(f: &'a Field) -> (&'a Option<Ident>, &'a Type) {
+fn get_name_and_type(f: &Field) -> (&Option<Ident>, &Type) {
     let field_name = &f.ident;
     let field_type = &f.ty;
     (field_name, field_type)
 }
 
-pub fn builder_field_definitions(
-    fields: &Punctuated<Field, Comma>,
-) -> impl Iterator<Item = TokenStream2> + '_ {
+/// `Some(T)` if `ty` is `wrapper<T>`, going by the last path segment so that
+/// `std::option::Option<T>` counts too.
+fn inner_type<'a>(ty: &'a Type, wrapper: &str) -> Option<&'a Type> {
+    let Type::Path(p) = ty else {
+        return None;
+    };
+    let segment = p.path.segments.last()?;
+    if segment.ident != wrapper {
+        return None;
+    }
+    let PathArguments::AngleBracketed(args) = &segment.arguments else {
+        return None;
+    };
+    match args.args.iter().collect::<Vec<_>>()[..] {
+        [GenericArgument::Type(inner)] => Some(inner),
+        _ => None,
+    }
+}
+
+/// A field of the original struct, and how the builder treats it.
+pub struct BuilderField<'a> {
+    name: &'a Ident,
+    ty: &'a Type,
+    /// The setter's name: the field's, unless renamed.
+    setter: Ident,
+    /// For an `Option<T>` field, the `T` its setter takes.
+    optional: Option<&'a Type>,
+    default: Option<DefaultValue>,
+    /// For a `Vec<T>` field, the method adding one `T`.
+    each: Option<(Ident, &'a Type)>,
+}
+
+impl<'a> BuilderField<'a> {
+    pub fn parse(f: &'a Field) -> syn::Result<Self> {
+        let (name, ty) = get_name_and_type(f);
+        let name = name.as_ref().expect("named fields have names");
+        let options = FieldOptions::parse(&f.attrs)?;
+
+        let each = match options.each {
+            None => None,
+            Some(item) => {
+                let Some(item_ty) = inner_type(ty, "Vec") else {
+                    return Err(syn::Error::new(ty.span(), "`each` needs a `Vec<_>` field"));
+                };
+                if options.default.is_some() {
+                    return Err(syn::Error::new(
+                        name.span(),
+                        "`each` fields start out empty, so they can't have a `default` too",
+                    ));
+                }
+                Some((item, item_ty))
+            }
+        };
+        Ok(BuilderField {
+            name,
+            ty,
+            setter: options.rename.unwrap_or_else(|| name.clone()),
+            optional: inner_type(ty, "Option"),
+            default: options.default,
+            each,
+        })
+    }
+
+    pub fn name(&self) -> &'a Ident {
+        self.name
+    }
+
+    pub fn setter(&self) -> &Ident {
+        &self.setter
+    }
+
+    /// Has to be set before `build`: no default, not an `Option`, not added
+    /// to one item at a time.
+    pub fn is_required(&self) -> bool {
+        self.default.is_none() && self.optional.is_none() && self.each.is_none()
+    }
+
+    /// What the setter takes.
+    pub fn input_type(&self) -> &Type {
+        self.optional.unwrap_or(self.ty)
+    }
+}
+
+pub fn builder_field_definitions<'a>(
+    fields: &'a [BuilderField],
+) -> impl Iterator<Item = TokenStream2> + 'a {
     fields.iter().map(|f| {
-        let (name, f_type) = get_name_and_type(f);
-        quote! { pub #name: Option<#f_type> }
+        let (name, f_type) = (f.name, f.ty);
+        if f.each.is_some() {
+            return quote! { pub #name: #f_type };
+        }
+        let input_type = f.input_type();
+        quote! { pub #name: Option<#input_type> }
     })
 }
 
-// pub fn original_struct_setters(
-//     fields: &Punctuated<Field, Comma>,
-// ) -> impl Iterator<Item = TokenStream2> + '_ {
-//     fields.iter().map(|f| {
-//         let (field_name, field_type) = get_name_and_type(f);
-//         let field_name_as_string = field_name.as_ref().unwrap().to_string();
-//         let error = quote!(expect(&format!("Field {} not set", #field_name_as_string)));
-//         let handle_type = if matches_type(field_type, "String") {
-//           quote! {
-//             as_ref()
-//             .#error
-//             .to_string()
-//           }
-//         } else {
-//           quote! {
-//             #error
-//           }
-//         };
-//         quote! {
-//           #field_name: self.#field_name.#handle_type
-//         }
-//     })
-// }
-
-pub fn original_struct_setters(
-    fields: &Punctuated<Field, Comma>,
-) -> impl Iterator<Item = TokenStream2> + '_ {
+pub fn original_struct_setters<'a>(
+    fields: &'a [BuilderField],
+) -> impl Iterator<Item = TokenStream2> + 'a {
     fields.iter().map(|f| {
-        let field_name = &f.ident;
-        let field_name_as_string = field_name.as_ref().unwrap().to_string();
+        let field_name = f.name;
+        let field_name_as_string = field_name.to_string();
+        let value = match (&f.default, f.optional, &f.each) {
+            (_, _, Some(_)) => quote! { self.#field_name },
+            (None, Some(_), _) | (Some(DefaultValue::Trait), Some(_), _) => {
+                quote! { self.#field_name }
+            }
+            (Some(DefaultValue::Expr(expr)), Some(_), _) => {
+                quote! { self.#field_name.or_else(|| #expr) }
+            }
+            (Some(DefaultValue::Trait), None, _) => {
+                // so a type that isn't `Default` is blamed on the field
+                quote_spanned! {f.ty.span()=> self.#field_name.unwrap_or_default() }
+            }
+            (Some(DefaultValue::Expr(expr)), None, _) => {
+                quote! { self.#field_name.unwrap_or_else(|| #expr) }
+            }
+            (None, None, _) => quote! {
+                self.#field_name
+                .expect(concat!("field not set: ", #field_name_as_string), )
+            },
+        };
         quote! {
-          #field_name: self.#field_name
-          .expect(concat!("field not set: ", #field_name_as_string), )
+          #field_name: #value
         }
     })
 }
 
-pub fn builder_methods(
-    fields: &Punctuated<Field, Comma>,
-) -> impl Iterator<Item = TokenStream2> + '_ {
-    fields.iter().map(|f| {
-        let (field_name, field_type) = get_name_and_type(f);
-        quote! {
-            pub fn #field_name(mut self, input: #field_type) -> Self {
+pub fn builder_methods<'a>(fields: &'a [BuilderField]) -> impl Iterator<Item = TokenStream2> + 'a {
+    fields.iter().map(builder_method)
+}
+
+/// The setter for one field, which leaves the builder's type as it is.
+pub fn builder_method(f: &BuilderField) -> TokenStream2 {
+    let (field_name, setter) = (f.name, &f.setter);
+    let Some((item, item_type)) = &f.each else {
+        let input_type = f.input_type();
+        return quote! {
+            pub fn #setter(mut self, input: #input_type) -> Self {
                 self.#field_name = Some(input);
                 self
             }
+        };
+    };
+    let add_one = quote! {
+        pub fn #item(mut self, #item: #item_type) -> Self {
+            self.#field_name.push(#item);
+            self
         }
-    })
+    };
+    // if they're called the same, adding one at a time wins
+    if item == setter {
+        return add_one;
+    }
+    let field_type = f.ty;
+    quote! {
+        pub fn #setter(mut self, input: #field_type) -> Self {
+            self.#field_name = input;
+            self
+        }
+        #add_one
+    }
 }
 
-pub fn builder_init_values(
-    fields: &Punctuated<Field, Comma>,
-) -> impl Iterator<Item = TokenStream2> + '_ {
+pub fn builder_init_values<'a>(
+    fields: &'a [BuilderField],
+) -> impl Iterator<Item = TokenStream2> + 'a {
     fields.iter().map(|f| {
-        let field_name = &f.ident;
+        let field_name = f.name;
+        if f.each.is_some() {
+            return quote! { #field_name: ::std::vec::Vec::new() };
+        }
         quote! { #field_name: None }
     })
 }
@@ -91,6 +190,7 @@ pub fn builder_init_values(
 #[cfg(test)]
 mod tests {
     use proc_macro2::Span;
+    use syn::punctuated::Punctuated;
     use syn::{FieldMutability, Path, PathSegment, TypePath, Visibility};
 
     use super::*;
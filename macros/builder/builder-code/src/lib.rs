@@ -1,49 +1,89 @@
 use proc_macro2::TokenStream;
 use quote::{format_ident, quote};
-use syn::Data::Struct;
-use syn::DeriveInput;
+use syn::Data::{Enum, Struct, Union};
 use syn::Fields::Named;
-use syn::{DataStruct, FieldsNamed, Ident};
+use syn::{DataStruct, DeriveInput, FieldsNamed, Ident};
 
+mod attributes;
 mod fields;
+mod typestate;
+use attributes::StructOptions;
 use fields::{
     builder_field_definitions, builder_init_values, builder_methods, original_struct_setters,
+    BuilderField,
 };
 
 pub fn create_builder(item: TokenStream) -> TokenStream {
-    let ast: DeriveInput = syn::parse2(item).unwrap();
-    let name: Ident = ast.ident;
+    expand(item).unwrap_or_else(syn::Error::into_compile_error)
+}
+
+fn expand(item: TokenStream) -> syn::Result<TokenStream> {
+    let ast: DeriveInput = syn::parse2(item)?;
+    let name: &Ident = &ast.ident;
     let builder = format_ident!("{}Builder", name);
-    let fields = match ast.data {
+    let fields = match &ast.data {
         Struct(DataStruct {
-            fields: Named(FieldsNamed { ref named, .. }),
+            fields: Named(FieldsNamed { named, .. }),
             ..
         }) => named,
-        _ => unimplemented!("only implemented for structs"),
+        Struct(_) => return Err(syn::Error::new(name.span(), "Builder needs named fields")),
+        Enum(e) => return Err(syn::Error::new(e.enum_token.span, ONLY_STRUCTS)),
+        Union(u) => return Err(syn::Error::new(u.union_token.span, ONLY_STRUCTS)),
     };
-    let builder_fields = builder_field_definitions(fields);
-    let builder_inits = builder_init_values(fields);
-    let builder_methods = builder_methods(fields);
-    let set_fields = original_struct_setters(fields);
-    quote! {
-      struct #builder {
+    let options = StructOptions::parse(&ast.attrs)?;
+    let fields = parse_fields(fields)?;
+    if options.typestate {
+        return Ok(typestate::typestate_builder(&ast, &fields));
+    }
+
+    let vis = &ast.vis;
+    let (impl_generics, ty_generics, where_clause) = ast.generics.split_for_impl();
+    let builder_fields = builder_field_definitions(&fields);
+    let builder_inits = builder_init_values(&fields);
+    let builder_methods = builder_methods(&fields);
+    let set_fields = original_struct_setters(&fields);
+    Ok(quote! {
+      #vis struct #builder #impl_generics #where_clause {
         #(#builder_fields,)*
       }
-      impl #builder {
+      impl #impl_generics #builder #ty_generics #where_clause {
         #(#builder_methods)*
-        pub fn build(self) -> #name {
+        pub fn build(self) -> #name #ty_generics {
           #name {
             #(#set_fields,)*
           }
         }
       }
-      impl #name {
-        pub fn builder() -> #builder {
+      impl #impl_generics #name #ty_generics #where_clause {
+        pub fn builder() -> #builder #ty_generics {
           #builder {
             #(#builder_inits,)*
           }
         }
       }
+    })
+}
+
+const ONLY_STRUCTS: &str = "Builder can only be derived for structs";
+
+/// Every field, or every mistake in their attributes at once.
+fn parse_fields<'a>(
+    fields: impl IntoIterator<Item = &'a syn::Field>,
+) -> syn::Result<Vec<BuilderField<'a>>> {
+    let mut parsed = Vec::new();
+    let mut errors: Option<syn::Error> = None;
+    for field in fields {
+        match BuilderField::parse(field) {
+            Ok(field) => parsed.push(field),
+            Err(e) => match &mut errors {
+                Some(errors) => errors.combine(e),
+                None => errors = Some(e),
+            },
+        }
+    }
+    match errors {
+        Some(errors) => Err(errors),
+        None => Ok(parsed),
     }
 }
 
@@ -81,6 +121,67 @@ mod tests {
         assert_eq!(actual.to_string(), expected.to_string());
     }
 
+    #[test]
+    fn builder_should_carry_the_structs_generics() {
+        let input = quote! {
+          pub struct Fetter<'a, T: Clone> where T: Default { name: &'a T }
+        };
+        let actual = create_builder(input).to_string();
+        let expected = [
+            quote!(pub struct FetterBuilder<'a, T: Clone> where T: Default),
+            quote!(impl<'a, T: Clone> FetterBuilder<'a, T> where T: Default),
+            quote!(pub fn build(self) -> Fetter<'a, T>),
+            quote!(pub fn builder() -> FetterBuilder<'a, T>),
+        ];
+        for expected in expected {
+            assert!(
+                actual.contains(&expected.to_string()),
+                "{expected} in {actual}"
+            );
+        }
+    }
+
+    #[test]
+    fn mistakes_should_become_compile_errors() {
+        let input = quote! {
+          struct Gleipnir {
+            #[builder(each = "root")]
+            roots_of: String,
+            #[builder(defualt)]
+            breath_of_a_fish: u8,
+          }
+        };
+        let actual = create_builder(input).to_string();
+        assert_eq!(actual.matches("compile_error").count(), 2, "{actual}");
+        assert!(!actual.contains("GleipnirBuilder"));
+    }
+
+    #[test]
+    fn typestate_should_only_build_once_required_fields_are_set() {
+        let input = quote! {
+          #[builder(typestate)]
+          pub struct Gleipnir {
+            roots_of: String,
+            #[builder(default)]
+            breath_of_a_fish: u8,
+          }
+        };
+        let actual = create_builder(input).to_string();
+        let expected = [
+            quote!(pub struct GleipnirBuilder<__RootsOf = gleipnir_builder_state::Unset>),
+            quote!(-> GleipnirBuilder<gleipnir_builder_state::Set>),
+            quote!(where __RootsOf: gleipnir_builder_state::RootsOfIsSet),
+            quote!(pub fn builder() -> GleipnirBuilder<gleipnir_builder_state::Unset>),
+        ];
+        for expected in expected {
+            assert!(
+                actual.contains(&expected.to_string()),
+                "{expected} in {actual}"
+            );
+        }
+        assert!(!actual.contains("BreathOfAFish"));
+    }
+
     #[ignore]
     #[test]
     fn assert_with_parsing() {
@@ -0,0 +1,226 @@
+use std::collections::HashSet;
+
+use proc_macro2::TokenStream;
+use quote::{format_ident, quote};
+use syn::{DeriveInput, GenericParam, Ident};
+
+use crate::fields::{
+    builder_field_definitions, builder_init_values, builder_method, original_struct_setters,
+    BuilderField,
+};
+
+/// `max_retries` -> `MaxRetries`
+fn upper_camel(name: &str) -> String {
+    name.trim_start_matches("r#")
+        .split('_')
+        .filter(|part| !part.is_empty())
+        .map(|part| {
+            let mut chars = part.chars();
+            let first = chars.next().unwrap().to_ascii_uppercase();
+            std::iter::once(first).chain(chars).collect::<String>()
+        })
+        .collect()
+}
+
+/// `GleipnirBuilder` -> `gleipnir_builder`
+fn snake(name: &str) -> String {
+    let mut snake = String::new();
+    for (i, c) in name.chars().enumerate() {
+        if c.is_ascii_uppercase() && i > 0 {
+            snake.push('_');
+        }
+        snake.push(c.to_ascii_lowercase());
+    }
+    snake
+}
+
+/// `base`, or `base2`, `base3`... - the first that isn't `taken`, which it
+/// then is.
+fn unique(base: String, taken: &mut HashSet<String>) -> String {
+    let name = if taken.contains(&base) {
+        (2..)
+            .map(|n| format!("{base}{n}"))
+            .find(|name| !taken.contains(name))
+            .unwrap()
+    } else {
+        base
+    };
+    taken.insert(name.clone());
+    name
+}
+
+/// A required field, and the type parameter saying whether it's been set.
+struct Required<'f, 'a> {
+    field: &'f BuilderField<'a>,
+    param: Ident,
+    /// Implemented by `Set` only, and named in the error when it isn't.
+    is_set: Ident,
+}
+
+/// The `#[builder(typestate)]` builder: one type parameter per required field
+/// that starts out `Unset` and becomes `Set` through its setter, with `build`
+/// asking for all of them to be `Set`.
+pub fn typestate_builder(ast: &DeriveInput, fields: &[BuilderField]) -> TokenStream {
+    let name = &ast.ident;
+    let vis = &ast.vis;
+    let builder = format_ident!("{}Builder", name);
+    let state = format_ident!("{}_state", snake(&builder.to_string()));
+    // `a_b` and `a__b` both camel-case to `AB`, and the struct may have
+    // generics or fields of its own going by the names we'd pick
+    let mut taken: HashSet<String> = ast
+        .generics
+        .params
+        .iter()
+        .filter_map(|p| match p {
+            GenericParam::Type(t) => Some(format!("{}", t.ident)),
+            GenericParam::Const(c) => Some(format!("{}", c.ident)),
+            GenericParam::Lifetime(_) => None,
+        })
+        .filter_map(|ident| ident.strip_prefix("__").map(str::to_string))
+        .collect();
+    let required: Vec<Required> = fields
+        .iter()
+        .filter(|f| f.is_required())
+        .map(|field| {
+            let camel = unique(upper_camel(&field.name().to_string()), &mut taken);
+            Required {
+                field,
+                param: format_ident!("__{}", camel),
+                is_set: format_ident!("{}IsSet", camel),
+            }
+        })
+        .collect();
+
+    // the struct's own generics, then the states
+    let params: Vec<&GenericParam> = ast.generics.params.iter().collect();
+    let args: Vec<TokenStream> = params
+        .iter()
+        .map(|p| match p {
+            GenericParam::Lifetime(l) => {
+                let lifetime = &l.lifetime;
+                quote!(#lifetime)
+            }
+            GenericParam::Type(t) => {
+                let ident = &t.ident;
+                quote!(#ident)
+            }
+            GenericParam::Const(c) => {
+                let ident = &c.ident;
+                quote!(#ident)
+            }
+        })
+        .collect();
+    let (impl_generics, ty_generics, where_clause) = ast.generics.split_for_impl();
+    let mut all = ast.generics.clone();
+    for r in &required {
+        let param = &r.param;
+        all.params.push(syn::parse_quote!(#param));
+    }
+    let (all_impl_generics, all_ty_generics, _) = all.split_for_impl();
+    let state_params: Vec<&Ident> = required.iter().map(|r| &r.param).collect();
+    let mut field_names = fields.iter().map(|f| f.name().to_string()).collect();
+    let phantom = format_ident!("{}", unique("__state".to_string(), &mut field_names));
+
+    let state_traits = required.iter().map(|r| {
+        let (field, setter, is_set) = (r.field.name(), r.field.setter(), &r.is_set);
+        let message = format!("`{builder}::build` needs `{field}` to be set first");
+        let label = format!("`{field}` is not set");
+        let note = format!("call `.{setter}(...)` before `.build()`");
+        quote! {
+            #[diagnostic::on_unimplemented(message = #message, label = #label, note = #note)]
+            pub trait #is_set {}
+            impl #is_set for Set {}
+        }
+    });
+
+    let required_setters = required.iter().enumerate().map(|(i, r)| {
+        let (field_name, setter, input_type) =
+            (r.field.name(), r.field.setter(), r.field.input_type());
+        let states = required.iter().enumerate().map(|(j, other)| {
+            if i == j {
+                quote!(#state::Set)
+            } else {
+                let param = &other.param;
+                quote!(#param)
+            }
+        });
+        let others = fields
+            .iter()
+            .map(|f| f.name())
+            .filter(|other| *other != field_name);
+        quote! {
+            pub fn #setter(self, input: #input_type) -> #builder<#(#args,)* #(#states),*> {
+                #builder {
+                    #field_name: Some(input),
+                    #(#others: self.#others,)*
+                    #phantom: ::core::marker::PhantomData,
+                }
+            }
+        }
+    });
+    let other_setters = fields
+        .iter()
+        .filter(|f| !f.is_required())
+        .map(builder_method);
+    let build_bounds = (!required.is_empty()).then(|| {
+        let bounds = required.iter().map(|r| {
+            let (param, is_set) = (&r.param, &r.is_set);
+            quote!(#param: #state::#is_set)
+        });
+        quote!(where #(#bounds),*)
+    });
+
+    let unset = required.iter().map(|_| quote!(#state::Unset));
+
+    let builder_fields = builder_field_definitions(fields);
+    let builder_inits = builder_init_values(fields);
+    let set_fields = original_struct_setters(fields);
+    quote! {
+      #[doc(hidden)]
+      #vis mod #state {
+        pub struct Set;
+        pub struct Unset;
+        #(#state_traits)*
+      }
+      #vis struct #builder <#(#params,)* #(#state_params = #state::Unset),*> #where_clause {
+        #(#builder_fields,)*
+        #phantom: ::core::marker::PhantomData<(#(#state_params,)*)>,
+      }
+      impl #all_impl_generics #builder #all_ty_generics #where_clause {
+        #(#required_setters)*
+        #(#other_setters)*
+        pub fn build(self) -> #name #ty_generics #build_bounds {
+          #name {
+            #(#set_fields,)*
+          }
+        }
+      }
+      impl #impl_generics #name #ty_generics #where_clause {
+        pub fn builder() -> #builder<#(#args,)* #(#unset),*> {
+          #builder {
+            #(#builder_inits,)*
+            #phantom: ::core::marker::PhantomData,
+          }
+        }
+      }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_should_convert_between_cases() {
+        assert_eq!(upper_camel("max_retries"), "MaxRetries");
+        assert_eq!(upper_camel("r#type"), "Type");
+        assert_eq!(snake("GleipnirBuilder"), "gleipnir_builder");
+    }
+
+    #[test]
+    fn names_should_not_be_reused() {
+        let mut taken = HashSet::from(["AB2".to_string()]);
+        assert_eq!(unique("AB".to_string(), &mut taken), "AB");
+        assert_eq!(unique("AB".to_string(), &mut taken), "AB3");
+    }
+}
@@ -1,6 +1,32 @@
 use builder_macro::Builder;
 
-fn main() {}
+#[derive(Builder)]
+struct Request<'a> {
+    url: &'a str,
+    #[builder(default = "GET")]
+    method: &'a str,
+    #[builder(each = "header")]
+    headers: Vec<(&'a str, String)>,
+    body: Option<String>,
+    #[builder(default, rename = "retries")]
+    max_retries: u8,
+}
+
+fn main() {
+    let request = Request::builder()
+        .url("https://example.com")
+        .header(("Accept", "text/html".to_string()))
+        .retries(3)
+        .build();
+    println!(
+        "{} {} (up to {} retries)",
+        request.method, request.url, request.max_retries
+    );
+    for (name, value) in &request.headers {
+        println!("{name}: {value}");
+    }
+    println!("\n{}", request.body.unwrap_or_default());
+}
 
 #[cfg(test)]
 mod tests {
@@ -64,6 +90,185 @@ mod tests {
         assert_eq!(gleipnir.other_necessities.len(), 3)
     }
 
+    #[test]
+    fn should_fill_in_defaults() {
+        #[derive(Builder)]
+        struct Gleipnir {
+            #[builder(default)]
+            roots_of: String,
+            #[builder(default = 7 * 6)]
+            breath_of_a_fish: u8,
+        }
+        let gleipnir = Gleipnir::builder().build();
+        assert_eq!(gleipnir.roots_of, "");
+        assert_eq!(gleipnir.breath_of_a_fish, 42);
+
+        let gleipnir = Gleipnir::builder().breath_of_a_fish(1).build();
+        assert_eq!(gleipnir.breath_of_a_fish, 1);
+    }
+
+    #[test]
+    fn should_leave_option_fields_optional() {
+        #[derive(Builder)]
+        struct Gleipnir {
+            roots_of: Option<String>,
+            #[builder(default = Some(1))]
+            breath_of_a_fish: std::option::Option<u8>,
+        }
+        let gleipnir = Gleipnir::builder().build();
+        assert_eq!(gleipnir.roots_of, None);
+        assert_eq!(gleipnir.breath_of_a_fish, Some(1));
+
+        let gleipnir = Gleipnir::builder()
+            .roots_of("mountains".to_string())
+            .breath_of_a_fish(2)
+            .build();
+        assert_eq!(gleipnir.roots_of.as_deref(), Some("mountains"));
+        assert_eq!(gleipnir.breath_of_a_fish, Some(2));
+    }
+
+    #[test]
+    fn should_rename_setters() {
+        #[derive(Builder)]
+        struct Gleipnir {
+            #[builder(rename = "roots")]
+            roots_of: String,
+            #[builder(rename = "r#type")]
+            kind: u8,
+        }
+        let gleipnir = Gleipnir::builder()
+            .roots("mountains".to_string())
+            .r#type(1)
+            .build();
+        assert_eq!(gleipnir.roots_of, "mountains");
+        assert_eq!(gleipnir.kind, 1);
+    }
+
+    #[test]
+    fn should_add_items_one_at_a_time() {
+        #[derive(Builder)]
+        struct Gleipnir {
+            #[builder(each = "necessity")]
+            other_necessities: Vec<String>,
+            #[builder(each = "sounds")]
+            sounds: Vec<&'static str>,
+        }
+        let gleipnir = Gleipnir::builder()
+            .necessity("beard of a woman".to_string())
+            .necessity("spittle of a bird".to_string())
+            .sounds("cat's footsteps")
+            .build();
+        assert_eq!(gleipnir.other_necessities.len(), 2);
+        assert_eq!(gleipnir.sounds, ["cat's footsteps"]);
+
+        let gleipnir = Gleipnir::builder()
+            .other_necessities(vec!["sinews of a bear".to_string()])
+            .necessity("breath of a fish".to_string())
+            .build();
+        assert_eq!(
+            gleipnir.other_necessities,
+            ["sinews of a bear", "breath of a fish"]
+        );
+        assert!(gleipnir.sounds.is_empty());
+    }
+
+    #[test]
+    fn should_generate_builder_for_generic_struct_with_lifetimes() {
+        #[derive(Builder)]
+        struct Fetter<'a, T: Clone, const N: usize>
+        where
+            T: Default,
+        {
+            name: &'a str,
+            #[builder(default = [(); N].map(|_| T::default()))]
+            strands: [T; N],
+            #[builder(each = "link")]
+            links: Vec<T>,
+        }
+        let name = String::from("Gleipnir");
+        let fetter: Fetter<'_, u8, 6> = Fetter::builder().name(&name).link(1).link(2).build();
+        assert_eq!(fetter.name, "Gleipnir");
+        assert_eq!(fetter.strands, [0; 6]);
+        assert_eq!(fetter.links, [1, 2]);
+    }
+
+    #[test]
+    fn should_track_required_fields_in_the_type() {
+        #[derive(Builder)]
+        #[builder(typestate)]
+        struct Gleipnir {
+            roots_of: String,
+            #[builder(rename = "fish")]
+            breath_of_a_fish: u8,
+            #[builder(default)]
+            beard_of_a_woman: bool,
+            spittle_of_a_bird: Option<String>,
+        }
+        // in any order, and the optional ones whenever
+        let gleipnir = Gleipnir::builder()
+            .fish(1)
+            .spittle_of_a_bird("none".to_string())
+            .roots_of("mountains".to_string())
+            .fish(2)
+            .build();
+        assert_eq!(gleipnir.roots_of, "mountains");
+        assert_eq!(gleipnir.breath_of_a_fish, 2);
+        assert!(!gleipnir.beard_of_a_woman);
+        assert_eq!(gleipnir.spittle_of_a_bird.as_deref(), Some("none"));
+    }
+
+    #[test]
+    fn should_track_required_fields_of_generic_structs() {
+        #[derive(Builder)]
+        #[builder(typestate)]
+        pub struct Fetter<'a, T: Clone = u8, const N: usize = 3> {
+            name: &'a str,
+            strands: [T; N],
+            #[builder(each = "link")]
+            links: Vec<T>,
+        }
+        let fetter: Fetter<'_, u8> = Fetter::builder()
+            .link(1)
+            .strands([1, 2, 3])
+            .name("Gleipnir")
+            .build();
+        assert_eq!(fetter.name, "Gleipnir");
+        assert_eq!(fetter.strands.len(), 3);
+        assert_eq!(fetter.links, [1]);
+
+        #[derive(Builder)]
+        #[builder(typestate)]
+        struct NothingRequired {
+            roots_of: Option<String>,
+        }
+        assert!(NothingRequired::builder().build().roots_of.is_none());
+    }
+
+    #[test]
+    #[allow(non_snake_case)]
+    fn should_keep_typestate_names_apart_from_the_structs() {
+        #[derive(Builder)]
+        #[builder(typestate)]
+        struct Gleipnir<__RootsOf> {
+            roots_of: __RootsOf,
+            sinews_of_a_bear: u8,
+            sinews__of_a_bear: u8,
+            __state: bool,
+        }
+        let gleipnir = Gleipnir::builder()
+            .sinews__of_a_bear(2)
+            .roots_of("mountains")
+            .__state(true)
+            .sinews_of_a_bear(1)
+            .build();
+        assert_eq!(gleipnir.roots_of, "mountains");
+        assert_eq!(
+            (gleipnir.sinews_of_a_bear, gleipnir.sinews__of_a_bear),
+            (1, 2)
+        );
+        assert!(gleipnir.__state);
+    }
+
     #[test]
     #[should_panic]
     fn should_panic_when_field_is_missing() {
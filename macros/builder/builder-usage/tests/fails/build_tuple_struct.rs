@@ -0,0 +1,5 @@
+use builder_macro::Builder;
+
+#[derive(Builder)]
+pub struct Fetter(String, u8);
+fn main() {}
@@ -0,0 +1,10 @@
+use builder_macro::Builder;
+
+struct Sinew;
+
+#[derive(Builder)]
+pub struct Gleipnir {
+    #[builder(default)]
+    sinews_of_a_bear: Sinew,
+}
+fn main() {}
@@ -0,0 +1,9 @@
+use builder_macro::Builder;
+
+#[derive(Builder)]
+pub struct Gleipnir {
+    #[builder(default)]
+    #[builder(default = 3)]
+    breath_of_a_fish: u8,
+}
+fn main() {}
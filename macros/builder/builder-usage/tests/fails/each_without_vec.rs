@@ -0,0 +1,8 @@
+use builder_macro::Builder;
+
+#[derive(Builder)]
+pub struct Gleipnir {
+    #[builder(each = "root")]
+    roots_of: String,
+}
+fn main() {}
@@ -0,0 +1,8 @@
+use builder_macro::Builder;
+
+#[derive(Builder)]
+pub struct Gleipnir {
+    #[builder(rename = "roots of")]
+    roots_of: String,
+}
+fn main() {}
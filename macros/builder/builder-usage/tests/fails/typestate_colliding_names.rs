@@ -0,0 +1,18 @@
+use builder_macro::Builder;
+
+#[derive(Builder)]
+#[builder(typestate)]
+pub struct Gleipnir<__RootsOf> {
+    roots_of: __RootsOf,
+    sinews_of_a_bear: u8,
+    sinews__of_a_bear: u8,
+    __state: bool,
+}
+
+fn main() {
+    Gleipnir::builder()
+        .roots_of("mountains")
+        .sinews_of_a_bear(1)
+        .__state(true)
+        .build();
+}
@@ -0,0 +1,15 @@
+use builder_macro::Builder;
+
+#[derive(Builder)]
+#[builder(typestate)]
+pub struct Gleipnir {
+    roots_of: String,
+    #[builder(rename = "fish")]
+    breath_of_a_fish: u8,
+    #[builder(default)]
+    beard_of_a_woman: bool,
+}
+
+fn main() {
+    Gleipnir::builder().roots_of("mountains".to_string()).build();
+}
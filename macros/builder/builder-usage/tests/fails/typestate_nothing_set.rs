@@ -0,0 +1,12 @@
+use builder_macro::Builder;
+
+#[derive(Builder)]
+#[builder(typestate)]
+pub struct Gleipnir<'a, T> {
+    roots_of: &'a str,
+    breath_of_a_fish: T,
+}
+
+fn main() {
+    let _: Gleipnir<'_, u8> = Gleipnir::builder().build();
+}
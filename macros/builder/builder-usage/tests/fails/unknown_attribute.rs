@@ -0,0 +1,10 @@
+use builder_macro::Builder;
+
+#[derive(Builder)]
+pub struct Gleipnir {
+    #[builder(defualt)]
+    roots_of: String,
+    #[builder(each = "necessity", optional)]
+    other_necessities: Vec<String>,
+}
+fn main() {}
@@ -0,0 +1,8 @@
+use builder_macro::Builder;
+
+#[derive(Builder)]
+#[builder(typestates)]
+pub struct Gleipnir {
+    roots_of: String,
+}
+fn main() {}
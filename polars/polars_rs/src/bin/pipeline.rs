@@ -0,0 +1,50 @@
+//! `cargo run --bin pipeline -- <pipeline.yaml|pipeline.toml> [--no-streaming]`
+//!
+//! Runs a pipeline file - see `polars_rs::pipeline` for the format and
+//! `../pipelines/` for examples.
+
+use std::process::ExitCode;
+
+use polars_rs::pipeline::Pipeline;
+
+fn main() -> ExitCode {
+    let mut path = None;
+    let mut streaming = true;
+    for arg in std::env::args().skip(1) {
+        match arg.as_str() {
+            "--no-streaming" => streaming = false,
+            _ if path.is_none() => path = Some(arg),
+            _ => return usage(),
+        }
+    }
+    let Some(path) = path else {
+        return usage();
+    };
+
+    let result = Pipeline::from_path(&path).and_then(|mut pipeline| {
+        pipeline.streaming &= streaming;
+        pipeline.run()
+    });
+    match result {
+        Ok(written) => {
+            for sink in written {
+                let how = if sink.streamed {
+                    "streamed"
+                } else {
+                    "collected"
+                };
+                println!("{how} {}", sink.path.display());
+            }
+            ExitCode::SUCCESS
+        }
+        Err(e) => {
+            eprintln!("{path}: {e}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn usage() -> ExitCode {
+    eprintln!("usage: pipeline <pipeline.yaml|pipeline.toml> [--no-streaming]");
+    ExitCode::from(2)
+}
@@ -0,0 +1,91 @@
+//! `cargo run --bin profile -- <data> [--format markdown|html|json] [--against <baseline>]`
+//!
+//! Profiles a CSV, Parquet or IPC file. With `--against`, compares it to a baseline -
+//! another data file, or a profile saved with `--format json` - prints what drifted and
+//! exits 1 if anything did.
+
+use std::fs;
+use std::process::ExitCode;
+
+use polars_rs::profile::{Profile, Thresholds};
+
+enum Output {
+    Markdown,
+    Html,
+    Json,
+}
+
+fn main() -> ExitCode {
+    let mut args = std::env::args().skip(1);
+    let mut path = None;
+    let mut output = Output::Markdown;
+    let mut against = None;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--format" => {
+                output = match args.next().as_deref() {
+                    Some("markdown" | "md") => Output::Markdown,
+                    Some("html") => Output::Html,
+                    Some("json") => Output::Json,
+                    _ => return usage(),
+                }
+            }
+            "--against" => match args.next() {
+                Some(baseline) => against = Some(baseline),
+                None => return usage(),
+            },
+            _ if path.is_none() && !arg.starts_with("--") => path = Some(arg),
+            _ => return usage(),
+        }
+    }
+    let Some(path) = path else {
+        return usage();
+    };
+
+    let profile = match Profile::read(&path) {
+        Ok(profile) => profile,
+        Err(e) => {
+            eprintln!("{path}: {e}");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let Some(baseline) = against else {
+        match output {
+            Output::Markdown => print!("{}", profile.to_markdown()),
+            Output::Html => print!("{}", profile.to_html()),
+            Output::Json => println!("{}", profile.to_json()),
+        }
+        return ExitCode::SUCCESS;
+    };
+
+    let before = if baseline.ends_with(".json") {
+        fs::read_to_string(&baseline)
+            .map_err(|e| e.to_string())
+            .and_then(|json| Profile::from_json(&json).map_err(|e| e.to_string()))
+    } else {
+        Profile::read(&baseline).map_err(|e| e.to_string())
+    };
+    let before = match before {
+        Ok(before) => before,
+        Err(e) => {
+            eprintln!("{baseline}: {e}");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let drift = before.drift(&profile, &Thresholds::default());
+    if drift.is_empty() {
+        println!("no drift from {baseline}");
+        return ExitCode::SUCCESS;
+    }
+    for drift in drift {
+        println!("{drift}");
+    }
+    ExitCode::FAILURE
+}
+
+fn usage() -> ExitCode {
+    eprintln!("usage: profile <data> [--format markdown|html|json] [--against <baseline>]");
+    ExitCode::from(2)
+}
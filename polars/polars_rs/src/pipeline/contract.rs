@@ -0,0 +1,89 @@
+use std::collections::BTreeMap;
+
+use polars::prelude::*;
+use serde::Deserialize;
+
+/// What a stage promises about its output: these columns, with these types.
+/// Checked against the lazy schema, so no data is read to check it.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Contract {
+    pub columns: BTreeMap<String, DType>,
+    /// no columns other than `columns`
+    #[serde(default)]
+    pub exact: bool,
+}
+
+impl Contract {
+    /// Everything wrong with `schema`, empty if it keeps the contract
+    pub fn violations(&self, schema: &Schema) -> Vec<String> {
+        let mut problems = vec![];
+        for (name, expected) in &self.columns {
+            match schema.get(name) {
+                None => problems.push(format!("missing column `{name}`")),
+                Some(actual) if !expected.matches(actual) => problems.push(format!(
+                    "column `{name}` is {actual}, expected {expected:?}"
+                )),
+                Some(_) => {}
+            }
+        }
+        if self.exact {
+            for name in schema.iter_names() {
+                if !self.columns.contains_key(name.as_str()) {
+                    problems.push(format!("unexpected column `{name}`"));
+                }
+            }
+        }
+        problems
+    }
+}
+
+/// Column types as written in a pipeline file
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DType {
+    #[serde(alias = "string", alias = "utf8")]
+    Str,
+    #[serde(alias = "boolean")]
+    Bool,
+    I8,
+    I16,
+    I32,
+    I64,
+    U8,
+    U16,
+    U32,
+    U64,
+    F32,
+    F64,
+    Date,
+    /// any time unit or zone
+    Datetime,
+    /// any of the integer types
+    Int,
+    /// any of the integer or float types
+    Number,
+}
+
+impl DType {
+    fn matches(self, dtype: &DataType) -> bool {
+        match self {
+            DType::Str => dtype == &DataType::String,
+            DType::Bool => dtype == &DataType::Boolean,
+            DType::I8 => dtype == &DataType::Int8,
+            DType::I16 => dtype == &DataType::Int16,
+            DType::I32 => dtype == &DataType::Int32,
+            DType::I64 => dtype == &DataType::Int64,
+            DType::U8 => dtype == &DataType::UInt8,
+            DType::U16 => dtype == &DataType::UInt16,
+            DType::U32 => dtype == &DataType::UInt32,
+            DType::U64 => dtype == &DataType::UInt64,
+            DType::F32 => dtype == &DataType::Float32,
+            DType::F64 => dtype == &DataType::Float64,
+            DType::Date => dtype == &DataType::Date,
+            DType::Datetime => matches!(dtype, DataType::Datetime(_, _)),
+            DType::Int => dtype.is_integer(),
+            DType::Number => dtype.is_numeric(),
+        }
+    }
+}
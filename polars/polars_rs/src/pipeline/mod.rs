@@ -0,0 +1,623 @@
+//! Declarative ETL pipelines over `LazyFrame`s.
+//!
+//! A pipeline file - YAML or TOML - names its sources, a list of transforms and where
+//! to write the results:
+//!
+//! ```yaml
+//! sources:
+//!   people: { path: ../data/output.csv }
+//! transforms:
+//!   - name: heavy
+//!     op: filter
+//!     predicate: weight > 60
+//! sinks:
+//!   - path: out/heavy.parquet
+//! ```
+//!
+//! A transform reads the stage before it unless it names an `input`; a sink writes the
+//! last transform unless it names one. Any stage can carry a [`Contract`] its output has
+//! to keep. Nothing is read until a sink runs, and sinks stream - write batch by batch
+//! without holding the whole result in memory - when polars can run the plan that way.
+//!
+//! The exception is a pivot: its columns depend on the data, so it collects its input
+//! while the pipeline is planned and hands the stages after it a frame in memory. A sink
+//! on a stage before the pivot reads that stage's sources again.
+
+mod contract;
+mod transform;
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt;
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+
+use polars::prelude::*;
+use serde::Deserialize;
+
+pub use contract::{Contract, DType};
+pub use transform::{How, Op, PivotAggregate};
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Pipeline {
+    pub sources: BTreeMap<String, Source>,
+    #[serde(default)]
+    pub transforms: Vec<Transform>,
+    pub sinks: Vec<Sink>,
+    /// write batch by batch where the plan allows it
+    #[serde(default = "yes")]
+    pub streaming: bool,
+    /// relative paths are relative to this - the pipeline file's directory
+    #[serde(skip)]
+    pub base_dir: PathBuf,
+}
+
+fn yes() -> bool {
+    true
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Source {
+    /// a file, or a glob like `data/*.parquet`
+    pub path: PathBuf,
+    /// taken from the extension if not given
+    pub format: Option<Format>,
+    pub contract: Option<Contract>,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct Transform {
+    pub name: String,
+    pub input: Option<String>,
+    #[serde(flatten)]
+    pub op: Op,
+    pub contract: Option<Contract>,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Sink {
+    pub input: Option<String>,
+    pub path: PathBuf,
+    pub format: Option<Format>,
+    pub contract: Option<Contract>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Format {
+    Csv,
+    Parquet,
+    #[serde(alias = "arrow")]
+    Ipc,
+}
+
+impl Format {
+    /// Going by the file's extension
+    pub fn from_extension(path: &Path) -> Option<Format> {
+        match path.extension().and_then(|e| e.to_str()) {
+            Some("csv") => Some(Format::Csv),
+            Some("parquet" | "pq") => Some(Format::Parquet),
+            Some("ipc" | "arrow" | "feather") => Some(Format::Ipc),
+            _ => None,
+        }
+    }
+
+    fn of(path: &Path, given: Option<Format>) -> Result<Format, PipelineError> {
+        given
+            .or_else(|| Format::from_extension(path))
+            .ok_or_else(|| {
+                PipelineError::Config(format!(
+                    "can't tell the format of {} - give it a `format`",
+                    path.display()
+                ))
+            })
+    }
+}
+
+#[derive(Debug)]
+pub enum PipelineError {
+    /// the file isn't valid YAML/TOML, or doesn't describe a pipeline
+    Parse(String),
+    /// the pipeline doesn't hang together - unknown stage names and the like
+    Config(String),
+    /// a stage's output broke its contract
+    Contract {
+        stage: String,
+        problems: Vec<String>,
+    },
+    Polars {
+        stage: String,
+        error: PolarsError,
+    },
+    Io(std::io::Error),
+}
+
+impl fmt::Display for PipelineError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PipelineError::Parse(e) => write!(f, "invalid pipeline: {e}"),
+            PipelineError::Config(e) => write!(f, "{e}"),
+            PipelineError::Contract { stage, problems } => {
+                write!(f, "`{stage}` broke its contract: {}", problems.join("; "))
+            }
+            PipelineError::Polars { stage, error } => write!(f, "`{stage}` failed: {error}"),
+            PipelineError::Io(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+impl std::error::Error for PipelineError {}
+
+impl From<std::io::Error> for PipelineError {
+    fn from(error: std::io::Error) -> Self {
+        PipelineError::Io(error)
+    }
+}
+
+/// What happened to one sink
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Written {
+    pub path: PathBuf,
+    /// false if the plan had to be collected into memory first
+    pub streamed: bool,
+}
+
+impl Pipeline {
+    /// Reads a `.yaml`/`.yml` or `.toml` pipeline file
+    pub fn from_path(path: impl AsRef<Path>) -> Result<Pipeline, PipelineError> {
+        let path = path.as_ref();
+        let text = fs::read_to_string(path)?;
+        let mut pipeline = match path.extension().and_then(|e| e.to_str()) {
+            Some("yaml" | "yml") => Pipeline::from_yaml(&text)?,
+            Some("toml") => Pipeline::from_toml(&text)?,
+            _ => {
+                return Err(PipelineError::Config(format!(
+                    "{} should be a .yaml or .toml file",
+                    path.display()
+                )))
+            }
+        };
+        pipeline.base_dir = path.parent().unwrap_or(Path::new("")).to_path_buf();
+        Ok(pipeline)
+    }
+
+    pub fn from_yaml(text: &str) -> Result<Pipeline, PipelineError> {
+        serde_yaml::from_str(text).map_err(|e| PipelineError::Parse(e.to_string()))
+    }
+
+    pub fn from_toml(text: &str) -> Result<Pipeline, PipelineError> {
+        toml::from_str(text).map_err(|e| PipelineError::Parse(e.to_string()))
+    }
+
+    /// Builds every stage's plan, checking contracts as it goes, then runs the sinks
+    pub fn run(&self) -> Result<Vec<Written>, PipelineError> {
+        let mut frames: HashMap<String, LazyFrame> = HashMap::new();
+        // stages whose data was collected into memory somewhere along the way
+        let mut in_memory: HashSet<String> = HashSet::new();
+        // what an unnamed input refers to - the one source, until there's a transform
+        let mut previous = if self.sources.len() == 1 {
+            self.sources.keys().next().cloned()
+        } else {
+            None
+        };
+
+        for (name, source) in &self.sources {
+            let frame = self.scan(source).map_err(|error| polars(name, error))?;
+            frames.insert(name.clone(), check(name, frame, source.contract.as_ref())?);
+        }
+
+        for transform in &self.transforms {
+            let name = &transform.name;
+            if frames.contains_key(name) {
+                return Err(PipelineError::Config(format!(
+                    "there are two stages called `{name}`"
+                )));
+            }
+            let input = transform
+                .input
+                .as_ref()
+                .or(previous.as_ref())
+                .ok_or_else(|| {
+                    PipelineError::Config(format!(
+                        "`{name}` needs an `input` - there's no single stage before it"
+                    ))
+                })?;
+            for stage in std::iter::once(input.as_str()).chain(transform.op.reads()) {
+                if !frames.contains_key(stage) {
+                    return Err(PipelineError::Config(format!(
+                        "`{name}` reads `{stage}`, which isn't defined before it"
+                    )));
+                }
+            }
+            if transform.op.collects()
+                || std::iter::once(input.as_str())
+                    .chain(transform.op.reads())
+                    .any(|stage| in_memory.contains(stage))
+            {
+                in_memory.insert(name.clone());
+            }
+            let frame = transform
+                .op
+                .apply(frames[input].clone(), &frames)
+                .map_err(|error| polars(name, error))?;
+            frames.insert(
+                name.clone(),
+                check(name, frame, transform.contract.as_ref())?,
+            );
+            previous = Some(name.clone());
+        }
+
+        let mut written = vec![];
+        for sink in &self.sinks {
+            let path = self.base_dir.join(&sink.path);
+            let stage = format!("sink {}", sink.path.display());
+            let input = sink.input.as_ref().or(previous.as_ref()).ok_or_else(|| {
+                PipelineError::Config(format!(
+                    "{stage} needs an `input` - there's no single stage before it"
+                ))
+            })?;
+            let frame = frames.get(input).cloned().ok_or_else(|| {
+                PipelineError::Config(format!("{stage} reads `{input}`, which isn't defined"))
+            })?;
+            let frame = check(&stage, frame, sink.contract.as_ref())?;
+            let format = Format::of(&sink.path, sink.format)?;
+            if let Some(dir) = path.parent() {
+                fs::create_dir_all(dir)?;
+            }
+            let stream = self.streaming && !in_memory.contains(input);
+            let streamed = self
+                .write(frame, &path, format, stream)
+                .map_err(|error| polars(&stage, error))?;
+            written.push(Written { path, streamed });
+        }
+        Ok(written)
+    }
+
+    fn scan(&self, source: &Source) -> PolarsResult<LazyFrame> {
+        let path = self.base_dir.join(&source.path);
+        let format = Format::of(&source.path, source.format)
+            .map_err(|e| polars_err!(ComputeError: "{e}"))?;
+        match format {
+            Format::Csv => LazyCsvReader::new(path)
+                .with_has_header(true)
+                .with_try_parse_dates(true)
+                .finish(),
+            Format::Parquet => LazyFrame::scan_parquet(path, ScanArgsParquet::default()),
+            Format::Ipc => LazyFrame::scan_ipc(path, ScanArgsIpc::default()),
+        }
+    }
+
+    /// Whether it streamed - only a plan polars can run end to end in its streaming
+    /// engine does; anything else (a window, a sort keeping ties in order) is collected
+    fn write(
+        &self,
+        frame: LazyFrame,
+        path: &Path,
+        format: Format,
+        stream: bool,
+    ) -> PolarsResult<bool> {
+        if stream && streams(&frame)? {
+            match format {
+                Format::Csv => frame.sink_csv(path, CsvWriterOptions::default())?,
+                Format::Parquet => frame.sink_parquet(path, ParquetWriteOptions::default())?,
+                Format::Ipc => frame.sink_ipc(path, IpcWriterOptions::default())?,
+            }
+            return Ok(true);
+        }
+
+        let mut df = frame.with_streaming(self.streaming).collect()?;
+        let file = File::create(path)?;
+        match format {
+            Format::Csv => CsvWriter::new(file).include_header(true).finish(&mut df)?,
+            Format::Parquet => {
+                ParquetWriter::new(file).finish(&mut df)?;
+            }
+            Format::Ipc => IpcWriter::new(file).finish(&mut df)?,
+        }
+        Ok(false)
+    }
+}
+
+/// The optimized plan says `STREAMING:` up front when every node of it streams -
+/// otherwise only the parts below some in-memory node do
+fn streams(frame: &LazyFrame) -> PolarsResult<bool> {
+    let plan = frame.clone().with_streaming(true).explain(true)?;
+    Ok(plan.starts_with("STREAMING:"))
+}
+
+fn polars(stage: &str, error: PolarsError) -> PipelineError {
+    PipelineError::Polars {
+        stage: stage.to_string(),
+        error,
+    }
+}
+
+fn check(
+    stage: &str,
+    mut frame: LazyFrame,
+    contract: Option<&Contract>,
+) -> Result<LazyFrame, PipelineError> {
+    if let Some(contract) = contract {
+        let schema = frame
+            .collect_schema()
+            .map_err(|error| polars(stage, error))?;
+        let problems = contract.violations(&schema);
+        if !problems.is_empty() {
+            return Err(PipelineError::Contract {
+                stage: stage.to_string(),
+                problems,
+            });
+        }
+    }
+    Ok(frame)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PEOPLE: &str = "\
+name,birthdate,weight,height,city
+Alice Archer,1997-01-10,57.9,1.56,Leeds
+Ben Brown,1985-02-15,72.5,1.77,York
+Chloe Cooper,1983-03-22,53.6,1.65,Leeds
+Daniel Donovan,1981-04-30,83.1,1.75,York
+Erin Evans,1992-05-02,61.0,1.70,Hull
+";
+
+    const CITIES: &str = "\
+city,region
+Leeds,West
+York,North
+";
+
+    const YAML: &str = r#"
+sources:
+  people:
+    path: people.csv
+    contract:
+      columns: { name: str, birthdate: date, weight: f64, height: f64, city: str }
+      exact: true
+  cities:
+    path: cities.csv
+transforms:
+  - name: bmi
+    input: people
+    op: with_columns
+    columns:
+      decade: (EXTRACT(YEAR FROM birthdate) / 10) * 10
+      bmi: weight / (height * height)
+  - name: located
+    op: join
+    right: cities
+    on: [city]
+    how: left
+  - name: compared
+    op: window
+    partition_by: [city]
+    columns:
+      city_bmi: AVG(bmi)
+  - name: by_decade
+    input: located
+    op: group_by
+    by: [decade]
+    aggs:
+      people: COUNT(name)
+      heaviest: MAX(weight)
+    contract:
+      columns: { decade: int, people: int, heaviest: f64 }
+  - name: decade_report
+    op: sort
+    by: [decade]
+  - name: wide
+    input: located
+    op: pivot
+    on: city
+    index: [decade]
+    values: [weight]
+    agg: sum
+sinks:
+  - input: compared
+    path: out/compared.parquet
+  - input: decade_report
+    path: out/decades.csv
+  - input: wide
+    path: out/wide.arrow
+"#;
+
+    fn fixture() -> tempfile::TempDir {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("people.csv"), PEOPLE).unwrap();
+        fs::write(dir.path().join("cities.csv"), CITIES).unwrap();
+        dir
+    }
+
+    fn pipeline(dir: &Path, yaml: &str) -> Pipeline {
+        let file = dir.join("pipeline.yaml");
+        fs::write(&file, yaml).unwrap();
+        Pipeline::from_path(file).unwrap()
+    }
+
+    #[test]
+    fn runs_every_kind_of_stage() {
+        let dir = fixture();
+        let written = pipeline(dir.path(), YAML).run().unwrap();
+        assert_eq!(written.len(), 3);
+
+        let compared =
+            LazyFrame::scan_parquet(dir.path().join("out/compared.parquet"), Default::default())
+                .unwrap()
+                .sort(["name"], Default::default())
+                .collect()
+                .unwrap();
+        assert_eq!(compared.height(), 5);
+        // new columns in the order they were written, not sorted by name
+        assert_eq!(
+            compared.get_column_names()[5..],
+            ["decade", "bmi", "region", "city_bmi"]
+        );
+        let region = compared.column("region").unwrap().str().unwrap();
+        assert_eq!(region.get(0), Some("West"));
+        // Erin's city isn't in `cities` - kept by the left join
+        assert_eq!(region.get(4), None);
+        // Alice and Chloe are both in Leeds
+        let city_bmi = compared.column("city_bmi").unwrap().f64().unwrap();
+        let bmi = compared.column("bmi").unwrap().f64().unwrap();
+        let leeds = (bmi.get(0).unwrap() + bmi.get(2).unwrap()) / 2.;
+        assert!((city_bmi.get(0).unwrap() - leeds).abs() < 1e-9);
+        assert_eq!(city_bmi.get(0), city_bmi.get(2));
+
+        let decades = fs::read_to_string(dir.path().join("out/decades.csv")).unwrap();
+        assert_eq!(
+            decades,
+            "decade,heaviest,people\n1980,83.1,3\n1990,61.0,2\n"
+        );
+
+        let wide = IpcReader::new(File::open(dir.path().join("out/wide.arrow")).unwrap())
+            .finish()
+            .unwrap()
+            .sort(["decade"], Default::default())
+            .unwrap();
+        assert_eq!(wide.get_column_names(), ["decade", "Hull", "Leeds", "York"]);
+        let york = wide.column("York").unwrap().f64().unwrap();
+        assert_eq!(york.get(0), Some(72.5 + 83.1));
+    }
+
+    #[test]
+    fn toml_says_the_same() {
+        let toml = r#"
+            [sources.people]
+            path = "people.csv"
+
+            [[transforms]]
+            name = "heavy"
+            op = "filter"
+            predicate = "weight > 60"
+
+            [[transforms]]
+            name = "tall"
+            input = "people"
+            op = "sort"
+            by = ["height"]
+            descending = true
+
+            [[sinks]]
+            path = "out/heavy.csv"
+        "#;
+        let yaml = r#"
+            sources:
+              people: { path: people.csv }
+            transforms:
+              - { name: heavy, op: filter, predicate: weight > 60 }
+              - { name: tall, input: people, op: sort, by: [height], descending: true }
+            sinks:
+              - path: out/heavy.csv
+        "#;
+        assert_eq!(
+            Pipeline::from_toml(toml).unwrap(),
+            Pipeline::from_yaml(yaml).unwrap()
+        );
+    }
+
+    #[test]
+    fn streams_what_it_can() {
+        let dir = fixture();
+        let yaml = r#"
+sources:
+  people: { path: people.csv }
+transforms:
+  - { name: heavy, op: filter, predicate: weight > 60 }
+  - { name: by_city, op: pivot, on: city, index: [name], values: [weight] }
+  - { name: ranked, op: sort, by: [name] }
+  - { name: averaged, input: people, op: window, partition_by: [city], columns: { city_weight: AVG(weight) } }
+  - { name: heavy_sorted, input: heavy, op: sort, by: [weight] }
+  - { name: by_weight, input: heavy, op: group_by, by: [city], aggs: { weight: SUM(weight) } }
+sinks:
+  - { input: heavy, path: out/heavy.csv }
+  - { input: by_city, path: out/by_city.csv }
+  - { input: ranked, path: out/ranked.csv }
+  - { input: averaged, path: out/averaged.parquet }
+  - { input: heavy_sorted, path: out/heavy_sorted.ipc }
+  - { input: by_weight, path: out/by_weight.ipc }
+"#;
+        let mut pipeline = pipeline(dir.path(), yaml);
+        let streamed: Vec<bool> = pipeline.run().unwrap().iter().map(|w| w.streamed).collect();
+        // a pivot upstream has already collected; a window, or a sort keeping ties in
+        // order, is more than the streaming engine can do
+        assert_eq!(streamed, [true, false, false, false, false, true]);
+        let heavy = fs::read_to_string(dir.path().join("out/heavy.csv")).unwrap();
+        assert_eq!(heavy.lines().count(), 4);
+
+        pipeline.streaming = false;
+        let streamed: Vec<bool> = pipeline.run().unwrap().iter().map(|w| w.streamed).collect();
+        assert_eq!(streamed, [false; 6]);
+        assert_eq!(
+            fs::read_to_string(dir.path().join("out/heavy.csv")).unwrap(),
+            heavy
+        );
+    }
+
+    #[test]
+    fn contracts_are_checked_at_every_stage() {
+        let dir = fixture();
+        let yaml = r#"
+sources:
+  people:
+    path: people.csv
+    contract: { columns: { name: str, weight: f64 } }
+transforms:
+  - name: slim
+    op: select
+    columns: [name, weight * 1000 AS grams]
+    contract:
+      columns: { name: str, weight: f64, grams: i64 }
+      exact: true
+sinks:
+  - path: out/slim.csv
+"#;
+        match pipeline(dir.path(), yaml).run() {
+            Err(PipelineError::Contract { stage, problems }) => {
+                assert_eq!(stage, "slim");
+                assert_eq!(
+                    problems,
+                    [
+                        "column `grams` is f64, expected I64",
+                        "missing column `weight`"
+                    ]
+                );
+            }
+            other => panic!("{other:?}"),
+        }
+        assert!(!dir.path().join("out/slim.csv").exists());
+    }
+
+    #[test]
+    fn reports_broken_pipelines() {
+        let dir = fixture();
+        let run = |yaml: &str| pipeline(dir.path(), yaml).run().unwrap_err().to_string();
+        assert_eq!(
+            run("sources: { people: { path: people.csv } }\n\
+                 transforms: [{ name: j, op: join, right: nope, on: [city] }]\n\
+                 sinks: [{ path: out.csv }]"),
+            "`j` reads `nope`, which isn't defined before it"
+        );
+        assert_eq!(
+            run(
+                "sources: { a: { path: people.csv }, b: { path: cities.csv } }\n\
+                 transforms: [{ name: f, op: filter, predicate: 'true' }]\n\
+                 sinks: [{ path: out.csv }]"
+            ),
+            "`f` needs an `input` - there's no single stage before it"
+        );
+        assert!(run("sources: { people: { path: people.csv } }\n\
+                     transforms: [{ name: f, op: filter, predicate: 'nope >' }]\n\
+                     sinks: [{ path: out.csv }]")
+        .starts_with("`f` failed: "));
+        assert!(Pipeline::from_yaml("sources: {}\nsinks: []\nextra: 1")
+            .unwrap_err()
+            .to_string()
+            .starts_with("invalid pipeline: unknown field `extra`"));
+    }
+}
@@ -0,0 +1,189 @@
+use std::collections::{BTreeMap, HashMap};
+
+use indexmap::IndexMap;
+use polars::lazy::frame::pivot::pivot;
+use polars::prelude::*;
+use polars::sql::sql_expr;
+use serde::Deserialize;
+
+/// One step from an input frame to an output frame. Expressions are SQL snippets -
+/// `weight / (height * height)`, `AVG(bmi)` - parsed by polars' SQL frontend.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case", deny_unknown_fields)]
+pub enum Op {
+    /// keep the rows where `predicate` holds
+    Filter { predicate: String },
+    /// keep just these expressions - `name`, `weight * 2 AS double_weight`
+    Select { columns: Vec<String> },
+    /// add (or replace) a column per entry, in the order they're written
+    WithColumns { columns: IndexMap<String, String> },
+    /// one row per distinct `by`, with a column per aggregation
+    GroupBy {
+        by: Vec<String>,
+        aggs: BTreeMap<String, String>,
+    },
+    /// join with another stage's output
+    Join {
+        right: String,
+        on: Vec<String>,
+        /// the right hand side's names for `on`, if they differ
+        #[serde(default)]
+        right_on: Option<Vec<String>>,
+        #[serde(default)]
+        how: How,
+    },
+    /// a column per distinct value of `on`, aggregating `values` within each `index` row.
+    /// Its columns aren't known until the data has been seen, so unlike the others it runs
+    /// its input there and then, while the pipeline is being planned, and keeps the result
+    /// in memory - nothing after a pivot streams.
+    Pivot {
+        on: String,
+        index: Vec<String>,
+        values: Vec<String>,
+        #[serde(default)]
+        agg: PivotAggregate,
+    },
+    /// columns computed over partitions of the rows, keeping every row -
+    /// `AVG(bmi)` over `partition_by: [decade]`
+    Window {
+        columns: BTreeMap<String, String>,
+        partition_by: Vec<String>,
+    },
+    Sort {
+        by: Vec<String>,
+        #[serde(default)]
+        descending: bool,
+    },
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum How {
+    #[default]
+    Inner,
+    Left,
+    Full,
+    Semi,
+    Anti,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PivotAggregate {
+    #[default]
+    First,
+    Last,
+    Sum,
+    Mean,
+    Median,
+    Min,
+    Max,
+    Count,
+}
+
+impl Op {
+    /// The other stages this one reads, besides its input
+    pub fn reads(&self) -> Option<&str> {
+        match self {
+            Op::Join { right, .. } => Some(right),
+            _ => None,
+        }
+    }
+
+    /// Whether applying it runs the input's plan there and then - a pivot has to see
+    /// every value of `on` before it knows its columns
+    pub fn collects(&self) -> bool {
+        matches!(self, Op::Pivot { .. })
+    }
+
+    pub fn apply(
+        &self,
+        input: LazyFrame,
+        frames: &HashMap<String, LazyFrame>,
+    ) -> PolarsResult<LazyFrame> {
+        Ok(match self {
+            Op::Filter { predicate } => input.filter(sql_expr(predicate)?),
+            Op::Select { columns } => input.select(exprs(columns)?),
+            Op::WithColumns { columns } => input.with_columns(aliased(columns)?),
+            Op::GroupBy { by, aggs } => input.group_by(exprs(by)?).agg(aliased(aggs)?),
+            Op::Join {
+                right,
+                on,
+                right_on,
+                how,
+            } => {
+                let other = frames[right].clone();
+                let left_on: Vec<Expr> = on.iter().map(col).collect();
+                let right_on: Vec<Expr> = right_on.as_ref().unwrap_or(on).iter().map(col).collect();
+                let how = match how {
+                    How::Inner => JoinType::Inner,
+                    How::Left => JoinType::Left,
+                    How::Full => JoinType::Full,
+                    How::Semi => JoinType::Semi,
+                    How::Anti => JoinType::Anti,
+                };
+                input.join(other, left_on, right_on, JoinArgs::new(how))
+            }
+            Op::Pivot {
+                on,
+                index,
+                values,
+                agg,
+            } => {
+                // any column stands in for "the values in this cell"
+                let cell = col("");
+                let agg = match agg {
+                    PivotAggregate::First => cell.first(),
+                    PivotAggregate::Last => cell.last(),
+                    PivotAggregate::Sum => cell.sum(),
+                    PivotAggregate::Mean => cell.mean(),
+                    PivotAggregate::Median => cell.median(),
+                    PivotAggregate::Min => cell.min(),
+                    PivotAggregate::Max => cell.max(),
+                    PivotAggregate::Count => cell.count(),
+                };
+                let df = input.collect()?;
+                pivot(
+                    &df,
+                    [on.as_str()],
+                    Some(index),
+                    Some(values),
+                    true,
+                    Some(agg),
+                    None,
+                )?
+                .lazy()
+            }
+            Op::Window {
+                columns,
+                partition_by,
+            } => {
+                let partition_by: Vec<Expr> = partition_by.iter().map(col).collect();
+                let columns = columns
+                    .iter()
+                    .map(|(name, sql)| Ok(sql_expr(sql)?.over(&partition_by).alias(name)))
+                    .collect::<PolarsResult<Vec<_>>>()?;
+                input.with_columns(columns)
+            }
+            Op::Sort { by, descending } => input.sort(
+                by,
+                SortMultipleOptions::default()
+                    .with_order_descending(*descending)
+                    .with_maintain_order(true),
+            ),
+        })
+    }
+}
+
+fn exprs(sql: &[String]) -> PolarsResult<Vec<Expr>> {
+    sql.iter().map(sql_expr).collect()
+}
+
+fn aliased<'a>(
+    columns: impl IntoIterator<Item = (&'a String, &'a String)>,
+) -> PolarsResult<Vec<Expr>> {
+    columns
+        .into_iter()
+        .map(|(name, sql)| Ok(sql_expr(sql)?.alias(name)))
+        .collect()
+}
@@ -0,0 +1,253 @@
+use std::fmt;
+
+use super::{Bin, ColumnProfile, Profile, Semantic};
+
+/// How big a change has to be to count as drift
+#[derive(Debug, Clone, PartialEq)]
+pub struct Thresholds {
+    /// change in the fraction of rows that are null
+    pub null_rate: f64,
+    /// change in the mean, in the earlier profile's standard deviations
+    pub mean_shift: f64,
+    /// change in distinct values per non-null value
+    pub distinct_ratio: f64,
+    /// population stability index between the two histograms - 0.1 is a small
+    /// shift, 0.25 a large one
+    pub psi: f64,
+}
+
+impl Default for Thresholds {
+    fn default() -> Self {
+        Thresholds {
+            null_rate: 0.05,
+            mean_shift: 0.5,
+            distinct_ratio: 0.2,
+            psi: 0.2,
+        }
+    }
+}
+
+/// Something about a column that changed between two profiles
+#[derive(Debug, Clone, PartialEq)]
+pub struct Drift {
+    pub column: String,
+    pub change: Change,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Change {
+    Added,
+    Removed,
+    DType {
+        before: String,
+        after: String,
+    },
+    Semantic {
+        before: Semantic,
+        after: Semantic,
+    },
+    /// fractions of the rows
+    NullRate {
+        before: f64,
+        after: f64,
+    },
+    /// for categorical columns, any change in the number of categories; otherwise a
+    /// change in distinct values per value beyond the threshold
+    Distinct {
+        before: usize,
+        after: usize,
+    },
+    Mean {
+        before: f64,
+        after: f64,
+        /// in the earlier profile's standard deviations
+        shift: f64,
+    },
+    Distribution {
+        psi: f64,
+    },
+}
+
+impl fmt::Display for Drift {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "`{}`: ", self.column)?;
+        match &self.change {
+            Change::Added => write!(f, "new column"),
+            Change::Removed => write!(f, "column removed"),
+            Change::DType { before, after } => write!(f, "type {before} -> {after}"),
+            Change::Semantic { before, after } => write!(f, "looks like {before} -> {after}"),
+            Change::NullRate { before, after } => write!(
+                f,
+                "null rate {:.1}% -> {:.1}%",
+                before * 100.0,
+                after * 100.0
+            ),
+            Change::Distinct { before, after } => {
+                write!(f, "distinct values {before} -> {after}")
+            }
+            Change::Mean {
+                before,
+                after,
+                shift,
+            } => write!(f, "mean {before:.4} -> {after:.4} ({shift:.1} std)"),
+            Change::Distribution { psi } => write!(f, "distribution shifted (PSI {psi:.2})"),
+        }
+    }
+}
+
+impl Profile {
+    /// What changed from this profile to `after`: added and removed columns first, then
+    /// the changes to each column in `after`'s order
+    pub fn drift(&self, after: &Profile, thresholds: &Thresholds) -> Vec<Drift> {
+        let mut drift = vec![];
+        let mut push = |column: &str, change: Change| {
+            drift.push(Drift {
+                column: column.to_string(),
+                change,
+            })
+        };
+        for column in &self.columns {
+            if after.column(&column.name).is_none() {
+                push(&column.name, Change::Removed);
+            }
+        }
+        for column in &after.columns {
+            if self.column(&column.name).is_none() {
+                push(&column.name, Change::Added);
+            }
+        }
+        for now in &after.columns {
+            if let Some(was) = self.column(&now.name) {
+                for change in changes(was, self.rows, now, after.rows, thresholds) {
+                    push(&now.name, change);
+                }
+            }
+        }
+        drift
+    }
+}
+
+fn changes(
+    was: &ColumnProfile,
+    rows_before: usize,
+    now: &ColumnProfile,
+    rows_after: usize,
+    thresholds: &Thresholds,
+) -> Vec<Change> {
+    let mut changes = vec![];
+    if was.dtype != now.dtype {
+        changes.push(Change::DType {
+            before: was.dtype.clone(),
+            after: now.dtype.clone(),
+        });
+    }
+    if was.semantic != now.semantic {
+        changes.push(Change::Semantic {
+            before: was.semantic,
+            after: now.semantic,
+        });
+    }
+
+    let (before, after) = (was.null_rate(rows_before), now.null_rate(rows_after));
+    if (after - before).abs() > thresholds.null_rate {
+        changes.push(Change::NullRate { before, after });
+    }
+
+    let per_value = |column: &ColumnProfile, rows: usize| {
+        let values = rows - column.nulls;
+        if values == 0 {
+            0.0
+        } else {
+            column.distinct as f64 / values as f64
+        }
+    };
+    let categorical =
+        was.semantic == Semantic::Categorical && now.semantic == Semantic::Categorical;
+    if (categorical && was.distinct != now.distinct)
+        || (!categorical
+            && (per_value(now, rows_after) - per_value(was, rows_before)).abs()
+                > thresholds.distinct_ratio)
+    {
+        changes.push(Change::Distinct {
+            before: was.distinct,
+            after: now.distinct,
+        });
+    }
+
+    if let (Some(before), Some(after)) = (&was.numeric, &now.numeric) {
+        let shift = match before.std {
+            Some(std) if std > 0.0 => (after.mean - before.mean).abs() / std,
+            _ if after.mean == before.mean => 0.0,
+            _ => f64::INFINITY,
+        };
+        if shift > thresholds.mean_shift {
+            changes.push(Change::Mean {
+                before: before.mean,
+                after: after.mean,
+                shift,
+            });
+        }
+        let psi = psi(&before.histogram, &after.histogram);
+        if psi > thresholds.psi {
+            changes.push(Change::Distribution { psi });
+        }
+    }
+    changes
+}
+
+/// Population stability index of `after` against `before`'s bins. `after`'s counts are
+/// spread evenly across each of its own bins to share them out between `before`'s;
+/// anything beyond `before`'s range lands in its first or last bin.
+fn psi(before: &[Bin], after: &[Bin]) -> f64 {
+    // an empty bin would make the index infinite
+    const FLOOR: f64 = 1e-4;
+    let (Some(first), Some(last)) = (before.first(), before.last()) else {
+        return 0.0;
+    };
+    let total = |bins: &[Bin]| bins.iter().map(|bin| bin.count).sum::<usize>() as f64;
+    let (before_total, after_total) = (total(before), total(after));
+    if before_total == 0.0 || after_total == 0.0 {
+        return 0.0;
+    }
+
+    let mut shares = vec![0.0; before.len()];
+    for bin in after {
+        let count = bin.count as f64;
+        if bin.end <= first.start {
+            shares[0] += count;
+        } else if bin.start >= last.end {
+            shares[before.len() - 1] += count;
+        } else if bin.end == bin.start {
+            let i = before
+                .iter()
+                .position(|b| bin.start < b.end)
+                .unwrap_or(before.len() - 1);
+            shares[i] += count;
+        } else {
+            let width = bin.end - bin.start;
+            for (i, b) in before.iter().enumerate() {
+                let mut start = bin.start.max(b.start);
+                let mut end = bin.end.min(b.end);
+                if i == 0 {
+                    start = bin.start;
+                }
+                if i == before.len() - 1 {
+                    end = bin.end;
+                }
+                if end > start {
+                    shares[i] += count * (end - start) / width;
+                }
+            }
+        }
+    }
+
+    before
+        .iter()
+        .zip(shares)
+        .map(|(b, share)| {
+            let expected = (b.count as f64 / before_total).max(FLOOR);
+            let actual = (share / after_total).max(FLOOR);
+            (actual - expected) * (actual / expected).ln()
+        })
+        .sum()
+}
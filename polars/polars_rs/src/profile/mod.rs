@@ -0,0 +1,599 @@
+//! Data-quality profiles.
+//!
+//! A [`Profile`] says, column by column, what's in a frame: nulls, distinct values,
+//! min/max, mean and quantiles, a histogram, the most common values and what the
+//! column looks like it holds - dates, emails, ids ([`Semantic`]). Profiles render as
+//! Markdown, HTML or JSON, and [`Profile::drift`] compares one against an earlier one
+//! to flag what has changed.
+//!
+//! ```no_run
+//! use polars_rs::profile::{Profile, Thresholds};
+//!
+//! let before = Profile::read("../data/output.csv").unwrap();
+//! let after = Profile::read("../data/latest.parquet").unwrap();
+//! println!("{}", after.to_markdown());
+//! for drift in before.drift(&after, &Thresholds::default()) {
+//!     println!("{drift}");
+//! }
+//! ```
+
+mod drift;
+mod render;
+mod semantic;
+
+use std::fs::File;
+use std::path::Path;
+
+use polars::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::pipeline::Format;
+
+pub use drift::{Change, Drift, Thresholds};
+pub use semantic::Semantic;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Profile {
+    /// the file the frame was read from, if any
+    pub source: Option<String>,
+    pub rows: usize,
+    pub columns: Vec<ColumnProfile>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ColumnProfile {
+    pub name: String,
+    /// the polars type, as polars writes it - `str`, `f64`, `date`
+    pub dtype: String,
+    pub semantic: Semantic,
+    pub nulls: usize,
+    /// distinct values, not counting null
+    pub distinct: usize,
+    /// for the types with an order - numbers, strings, dates, booleans
+    pub min: Option<String>,
+    pub max: Option<String>,
+    /// for numeric columns with at least one value
+    pub numeric: Option<NumericStats>,
+    /// the most common values, most common first
+    pub top: Vec<ValueCount>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NumericStats {
+    pub mean: f64,
+    /// sample standard deviation, if there are two or more values
+    pub std: Option<f64>,
+    pub quantiles: Vec<Quantile>,
+    /// equal width bins from min to max
+    pub histogram: Vec<Bin>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Quantile {
+    pub q: f64,
+    pub value: f64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Bin {
+    pub start: f64,
+    pub end: f64,
+    pub count: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ValueCount {
+    pub value: String,
+    pub count: usize,
+}
+
+/// How much detail to gather
+#[derive(Debug, Clone, PartialEq)]
+pub struct Options {
+    /// how many of the most common values to keep
+    pub top_k: usize,
+    pub bins: usize,
+    pub quantiles: Vec<f64>,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            top_k: 5,
+            bins: 10,
+            quantiles: vec![0.05, 0.25, 0.5, 0.75, 0.95],
+        }
+    }
+}
+
+impl ColumnProfile {
+    /// Nulls as a fraction of the rows
+    pub fn null_rate(&self, rows: usize) -> f64 {
+        if rows == 0 {
+            0.0
+        } else {
+            self.nulls as f64 / rows as f64
+        }
+    }
+
+    pub fn quantile(&self, q: f64) -> Option<f64> {
+        let numeric = self.numeric.as_ref()?;
+        numeric
+            .quantiles
+            .iter()
+            .find(|quantile| quantile.q == q)
+            .map(|quantile| quantile.value)
+    }
+}
+
+impl Profile {
+    pub fn of(df: &DataFrame) -> PolarsResult<Profile> {
+        Profile::with_options(df, &Options::default())
+    }
+
+    pub fn with_options(df: &DataFrame, options: &Options) -> PolarsResult<Profile> {
+        let columns = df
+            .get_columns()
+            .iter()
+            .map(|series| profile_column(series, options))
+            .collect::<PolarsResult<_>>()?;
+        Ok(Profile {
+            source: None,
+            rows: df.height(),
+            columns,
+        })
+    }
+
+    /// Reads a CSV, Parquet or IPC file - see [`read`] - and profiles it
+    pub fn read(path: impl AsRef<Path>) -> PolarsResult<Profile> {
+        let path = path.as_ref();
+        let mut profile = Profile::of(&read(path)?)?;
+        profile.source = Some(path.display().to_string());
+        Ok(profile)
+    }
+
+    pub fn column(&self, name: &str) -> Option<&ColumnProfile> {
+        self.columns.iter().find(|column| column.name == name)
+    }
+
+    /// A profile saved with [`Profile::to_json`]
+    pub fn from_json(json: &str) -> serde_json::Result<Profile> {
+        serde_json::from_str(json)
+    }
+}
+
+/// Reads a whole CSV, Parquet or IPC file, going by its extension. CSVs are read the
+/// way the quickstart reads them - a header, every row used to infer the types - and
+/// ISO dates become `date` columns.
+pub fn read(path: impl AsRef<Path>) -> PolarsResult<DataFrame> {
+    let path = path.as_ref();
+    match Format::from_extension(path) {
+        Some(Format::Csv) => CsvReadOptions::default()
+            .with_infer_schema_length(None)
+            .with_has_header(true)
+            .with_parse_options(CsvParseOptions::default().with_try_parse_dates(true))
+            .try_into_reader_with_file_path(Some(path.into()))?
+            .finish(),
+        Some(Format::Parquet) => ParquetReader::new(File::open(path)?).finish(),
+        Some(Format::Ipc) => IpcReader::new(File::open(path)?).finish(),
+        None => polars_bail!(ComputeError: "can't tell the format of {}", path.display()),
+    }
+}
+
+fn profile_column(series: &Series, options: &Options) -> PolarsResult<ColumnProfile> {
+    let dtype = series.dtype();
+    let nulls = series.null_count();
+    let distinct = series.n_unique()? - usize::from(nulls > 0);
+    let ordered = dtype.is_numeric()
+        || dtype.is_temporal()
+        || matches!(dtype, DataType::String | DataType::Boolean);
+
+    let (min, max) = if ordered && nulls < series.len() {
+        let extremes = series
+            .clone()
+            .into_frame()
+            .lazy()
+            .select([
+                col(series.name().clone())
+                    .min()
+                    .cast(DataType::String)
+                    .alias("min"),
+                col(series.name().clone())
+                    .max()
+                    .cast(DataType::String)
+                    .alias("max"),
+            ])
+            .collect()?;
+        let first = |i: usize| -> PolarsResult<Option<String>> {
+            Ok(extremes.get_columns()[i].str()?.get(0).map(str::to_string))
+        };
+        (first(0)?, first(1)?)
+    } else {
+        (None, None)
+    };
+
+    let numeric = if dtype.is_numeric() {
+        numeric_stats(series, options)?
+    } else {
+        None
+    };
+
+    Ok(ColumnProfile {
+        name: series.name().to_string(),
+        dtype: dtype.to_string(),
+        semantic: Semantic::infer(series, distinct)?,
+        nulls,
+        distinct,
+        min,
+        max,
+        numeric,
+        top: top_values(series, options.top_k)?,
+    })
+}
+
+fn numeric_stats(series: &Series, options: &Options) -> PolarsResult<Option<NumericStats>> {
+    let values = series.cast(&DataType::Float64)?;
+    let values: Vec<f64> = values
+        .f64()?
+        .into_iter()
+        .flatten()
+        .filter(|v| v.is_finite())
+        .collect();
+    if values.is_empty() {
+        return Ok(None);
+    }
+
+    let name = series.name().clone();
+    let value = col(name.clone()).cast(DataType::Float64);
+    let mut stats = vec![
+        value.clone().mean().alias("mean"),
+        value.clone().std(1).alias("std"),
+    ];
+    stats.extend(options.quantiles.iter().enumerate().map(|(i, &q)| {
+        value
+            .clone()
+            .quantile(lit(q), QuantileInterpolOptions::Linear)
+            .alias(format!("q{i}"))
+    }));
+    let stats = Series::new(name, values.as_slice())
+        .into_frame()
+        .lazy()
+        .select(stats)
+        .collect()?;
+    let stat = |i: usize| -> PolarsResult<Option<f64>> {
+        Ok(stats.get_columns()[i]
+            .cast(&DataType::Float64)?
+            .f64()?
+            .get(0))
+    };
+
+    let quantiles = options
+        .quantiles
+        .iter()
+        .enumerate()
+        .filter_map(|(i, &q)| match stat(i + 2) {
+            Ok(value) => value.map(|value| Ok(Quantile { q, value })),
+            Err(e) => Some(Err(e)),
+        })
+        .collect::<PolarsResult<_>>()?;
+
+    Ok(Some(NumericStats {
+        mean: stat(0)?.unwrap_or(f64::NAN),
+        std: stat(1)?.filter(|std| std.is_finite()),
+        quantiles,
+        histogram: histogram(&values, options.bins),
+    }))
+}
+
+fn histogram(values: &[f64], bins: usize) -> Vec<Bin> {
+    let min = values.iter().copied().fold(f64::INFINITY, f64::min);
+    let max = values.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+    let bins = if min == max { 1 } else { bins.max(1) };
+    let width = (max - min) / bins as f64;
+
+    let mut histogram: Vec<Bin> = (0..bins)
+        .map(|i| Bin {
+            start: min + width * i as f64,
+            end: if i + 1 == bins {
+                max
+            } else {
+                min + width * (i + 1) as f64
+            },
+            count: 0,
+        })
+        .collect();
+    for &value in values {
+        let i = if width == 0.0 {
+            0
+        } else {
+            (((value - min) / width) as usize).min(bins - 1)
+        };
+        histogram[i].count += 1;
+    }
+    histogram
+}
+
+fn top_values(series: &Series, k: usize) -> PolarsResult<Vec<ValueCount>> {
+    if k == 0 || series.null_count() == series.len() {
+        return Ok(vec![]);
+    }
+    let counts = series
+        .clone()
+        .into_frame()
+        .lazy()
+        .select([col(series.name().clone()).alias("value")])
+        .filter(col("value").is_not_null())
+        .group_by([col("value")])
+        .agg([len().alias("count")])
+        .sort(
+            ["count", "value"],
+            SortMultipleOptions::default()
+                .with_order_descending_multi([true, false])
+                .with_maintain_order(true),
+        )
+        .limit(k as IdxSize)
+        .select([
+            col("value").cast(DataType::String),
+            col("count").cast(DataType::UInt64),
+        ])
+        .collect()?;
+
+    let values = counts.get_columns()[0].str()?;
+    let count = counts.get_columns()[1].u64()?;
+    Ok(values
+        .into_iter()
+        .zip(count.into_no_null_iter())
+        .map(|(value, count)| ValueCount {
+            value: value.unwrap_or_default().to_string(),
+            count: count as usize,
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use chrono::NaiveDate;
+
+    fn customers() -> DataFrame {
+        df!(
+            "customer_id" => [101i64, 102, 103, 104, 105, 106, 107, 108],
+            "email" => [
+                "ann@example.com", "bob@example.com", "cy@example.org", "di@example.com",
+                "ed@example.net", "flo@example.com", "gus@example.com", "hal@example.com",
+            ],
+            "signed_up" => [
+                "03/01/2024", "17/01/2024", "02/02/2024", "14/02/2024",
+                "01/03/2024", "20/03/2024", "05/04/2024", "30/04/2024",
+            ],
+            "born" => [
+                NaiveDate::from_ymd_opt(1990, 1, 10),
+                NaiveDate::from_ymd_opt(1985, 2, 15),
+                None,
+                NaiveDate::from_ymd_opt(1981, 4, 30),
+                NaiveDate::from_ymd_opt(1999, 9, 9),
+                NaiveDate::from_ymd_opt(1979, 12, 1),
+                NaiveDate::from_ymd_opt(2001, 6, 21),
+                NaiveDate::from_ymd_opt(1993, 3, 3),
+            ],
+            "plan" => ["free", "pro", "free", "free", "team", "pro", "free", "free"],
+            "spend" => [Some(0.0), Some(40.0), None, Some(10.0), Some(20.0), Some(30.0), None, Some(50.0)],
+            "note" => ["likes | pipes", "<b>bold</b>", "", "called twice", "new", "referred by ann", "late payer", "vip"],
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn profiles_every_column() {
+        let profile = Profile::of(&customers()).unwrap();
+        assert_eq!(profile.rows, 8);
+        let semantics: Vec<_> = profile
+            .columns
+            .iter()
+            .map(|c| (c.name.as_str(), c.semantic))
+            .collect();
+        assert_eq!(
+            semantics,
+            [
+                ("customer_id", Semantic::Id),
+                ("email", Semantic::Email),
+                ("signed_up", Semantic::Date),
+                ("born", Semantic::Date),
+                ("plan", Semantic::Categorical),
+                ("spend", Semantic::Numeric),
+                ("note", Semantic::Text),
+            ]
+        );
+
+        let spend = profile.column("spend").unwrap();
+        assert_eq!((spend.nulls, spend.distinct), (2, 6));
+        assert_eq!(spend.null_rate(profile.rows), 0.25);
+        assert_eq!(spend.min.as_deref(), Some("0.0"));
+        assert_eq!(spend.max.as_deref(), Some("50.0"));
+        let stats = spend.numeric.as_ref().unwrap();
+        assert_eq!(stats.mean, 25.0);
+        assert_eq!(spend.quantile(0.5), Some(25.0));
+        assert_eq!(spend.quantile(0.25), Some(12.5));
+        assert_eq!(stats.histogram.len(), 10);
+        assert_eq!(stats.histogram.iter().map(|b| b.count).sum::<usize>(), 6);
+        assert_eq!(
+            (stats.histogram[0].start, stats.histogram[9].end),
+            (0.0, 50.0)
+        );
+        assert_eq!(stats.histogram[9].count, 1);
+
+        let plan = profile.column("plan").unwrap();
+        assert_eq!(plan.distinct, 3);
+        let top: Vec<_> = plan
+            .top
+            .iter()
+            .map(|v| (v.value.as_str(), v.count))
+            .collect();
+        assert_eq!(top, [("free", 5), ("pro", 2), ("team", 1)]);
+
+        let born = profile.column("born").unwrap();
+        assert_eq!(born.dtype, "date");
+        assert_eq!(born.min.as_deref(), Some("1979-12-01"));
+        assert_eq!(born.max.as_deref(), Some("2001-06-21"));
+        assert!(born.numeric.is_none());
+    }
+
+    #[test]
+    fn reads_csv_and_parquet_alike() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut df = customers();
+        let csv = dir.path().join("customers.csv");
+        CsvWriter::new(File::create(&csv).unwrap())
+            .finish(&mut df)
+            .unwrap();
+        let parquet = dir.path().join("customers.parquet");
+        ParquetWriter::new(File::create(&parquet).unwrap())
+            .finish(&mut df)
+            .unwrap();
+
+        let from_csv = Profile::read(&csv).unwrap();
+        let from_parquet = Profile::read(&parquet).unwrap();
+        assert_eq!(from_csv.source, Some(csv.display().to_string()));
+        for (csv, parquet) in from_csv.columns.iter().zip(&from_parquet.columns) {
+            if csv.name == "signed_up" {
+                // the csv reader parses the dates, parquet keeps the strings it was given;
+                // either way they're dates
+                assert_eq!(
+                    (csv.dtype.as_str(), parquet.dtype.as_str()),
+                    ("date", "str")
+                );
+                assert_eq!(
+                    (csv.semantic, parquet.semantic),
+                    (Semantic::Date, Semantic::Date)
+                );
+            } else {
+                assert_eq!(csv, parquet);
+            }
+        }
+        assert!(Profile::read(dir.path().join("customers.txt")).is_err());
+    }
+
+    #[test]
+    fn renders_markdown_html_and_json() {
+        let profile = Profile::of(&customers()).unwrap();
+
+        let markdown = profile.to_markdown();
+        assert!(markdown.contains("8 rows, 7 columns"));
+        assert!(markdown.contains("| `plan` | str | categorical | 0 | 3 | free | team |  |"));
+        assert!(markdown.contains("likes \\| pipes"));
+        assert!(markdown.contains("## `spend`"));
+
+        let html = profile.to_html();
+        assert!(html.starts_with("<!DOCTYPE html>"));
+        assert!(html.contains("&lt;b&gt;bold&lt;/b&gt;"));
+        assert!(!html.contains("<b>bold"));
+
+        let json = profile.to_json();
+        assert_eq!(Profile::from_json(&json).unwrap(), profile);
+    }
+
+    #[test]
+    fn flags_drift() {
+        let before = Profile::of(&customers()).unwrap();
+        assert_eq!(before.drift(&before, &Thresholds::default()), []);
+
+        let mut df = customers();
+        df.with_column(Series::new(
+            "spend".into(),
+            [
+                None,
+                None,
+                None,
+                Some(400.0),
+                Some(420.0),
+                None,
+                None,
+                Some(410.0),
+            ],
+        ))
+        .unwrap();
+        df.with_column(Series::new("plan".into(), [1i64, 2, 1, 1, 3, 2, 1, 1]))
+            .unwrap();
+        let df = df.drop("note").unwrap();
+        let df = df
+            .lazy()
+            .with_column(lit(true).alias("churned"))
+            .collect()
+            .unwrap();
+        let after = Profile::of(&df).unwrap();
+
+        let changes: Vec<_> = before
+            .drift(&after, &Thresholds::default())
+            .into_iter()
+            .map(|drift| (drift.column, drift.change))
+            .collect();
+        let column = |name: &str| -> Vec<&Change> {
+            changes
+                .iter()
+                .filter(|(c, _)| c == name)
+                .map(|(_, change)| change)
+                .collect()
+        };
+        assert_eq!(column("note"), [&Change::Removed]);
+        assert_eq!(column("churned"), [&Change::Added]);
+        assert!(matches!(
+            column("plan")[..],
+            [
+                Change::DType { .. },
+                Change::Semantic {
+                    before: Semantic::Categorical,
+                    after: Semantic::Numeric
+                }
+            ]
+        ));
+        let spend = column("spend");
+        assert!(spend.contains(&&Change::NullRate {
+            before: 0.25,
+            after: 0.625
+        }));
+        assert!(spend
+            .iter()
+            .any(|c| matches!(c, Change::Mean { shift, .. } if *shift > 20.0)));
+        assert!(spend
+            .iter()
+            .any(|c| matches!(c, Change::Distribution { .. })));
+        assert!(column("email").is_empty());
+    }
+
+    #[test]
+    fn flags_a_change_of_shape_with_the_same_mean() {
+        let uniform: Vec<f64> = (0..=100).map(f64::from).collect();
+        let split: Vec<f64> = (0..=100)
+            .map(|i| {
+                if i < 50 {
+                    0.0
+                } else if i > 50 {
+                    100.0
+                } else {
+                    50.0
+                }
+            })
+            .collect();
+        let before = Profile::of(&df!("x" => uniform).unwrap()).unwrap();
+        let after = Profile::of(&df!("x" => split).unwrap()).unwrap();
+
+        let drift = before.drift(&after, &Thresholds::default());
+        assert_eq!(drift.len(), 2);
+        assert!(matches!(
+            drift[0].change,
+            Change::Distinct {
+                before: 101,
+                after: 3
+            }
+        ));
+        let Change::Distribution { psi } = drift[1].change else {
+            panic!("{drift:?}");
+        };
+        assert!(psi > 1.0, "{psi}");
+        assert_eq!(
+            drift[1].to_string(),
+            format!("`x`: distribution shifted (PSI {psi:.2})")
+        );
+    }
+}
@@ -0,0 +1,238 @@
+use std::fmt::Write;
+
+use super::{ColumnProfile, NumericStats, Profile};
+
+/// Widest histogram bar, in characters
+const BAR: usize = 30;
+
+impl Profile {
+    /// A summary table, then a section per column with its quantiles, histogram and
+    /// most common values
+    pub fn to_markdown(&self) -> String {
+        let mut out = String::new();
+        let title = self.source.as_deref().unwrap_or("frame");
+        writeln!(out, "# Profile of `{title}`\n").unwrap();
+        writeln!(out, "{} rows, {} columns\n", self.rows, self.columns.len()).unwrap();
+        writeln!(
+            out,
+            "| column | type | semantic | nulls | distinct | min | max | mean |"
+        )
+        .unwrap();
+        writeln!(out, "|---|---|---|---:|---:|---|---|---:|").unwrap();
+        for column in &self.columns {
+            let [nulls, distinct, min, max, mean] = self.summary(column);
+            writeln!(
+                out,
+                "| `{}` | {} | {} | {nulls} | {distinct} | {} | {} | {mean} |",
+                column.name,
+                column.dtype,
+                column.semantic,
+                cell(&min),
+                cell(&max),
+            )
+            .unwrap();
+        }
+
+        for column in &self.columns {
+            writeln!(out, "\n## `{}`\n", column.name).unwrap();
+            if let Some(numeric) = &column.numeric {
+                writeln!(out, "{}\n", quantiles(numeric)).unwrap();
+                writeln!(out, "| bin | count | |\n|---|---:|---|").unwrap();
+                let widest = widest(numeric);
+                for bin in &numeric.histogram {
+                    writeln!(
+                        out,
+                        "| {} – {} | {} | {} |",
+                        number(bin.start),
+                        number(bin.end),
+                        bin.count,
+                        bar(bin.count, widest)
+                    )
+                    .unwrap();
+                }
+                out.push('\n');
+            }
+            if column.top.is_empty() {
+                writeln!(out, "no values").unwrap();
+            } else {
+                writeln!(out, "| value | count |\n|---|---:|").unwrap();
+                for top in &column.top {
+                    writeln!(out, "| {} | {} |", cell(&top.value), top.count).unwrap();
+                }
+            }
+        }
+        out
+    }
+
+    /// The same as [`Profile::to_markdown`], as a standalone page
+    pub fn to_html(&self) -> String {
+        let mut out = String::new();
+        let title = escape(self.source.as_deref().unwrap_or("frame"));
+        writeln!(
+            out,
+            "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">"
+        )
+        .unwrap();
+        writeln!(out, "<title>Profile of {title}</title>").unwrap();
+        writeln!(
+            out,
+            "<style>\
+             body {{ font-family: sans-serif; }} \
+             table {{ border-collapse: collapse; margin: 0.5em 0; }} \
+             td, th {{ border: 1px solid #ccc; padding: 2px 8px; text-align: left; }} \
+             .n {{ text-align: right; }} \
+             .bar {{ background: #4a90d9; height: 0.8em; }}\
+             </style>"
+        )
+        .unwrap();
+        writeln!(
+            out,
+            "</head>\n<body>\n<h1>Profile of <code>{title}</code></h1>"
+        )
+        .unwrap();
+        writeln!(
+            out,
+            "<p>{} rows, {} columns</p>",
+            self.rows,
+            self.columns.len()
+        )
+        .unwrap();
+        writeln!(out, "<table>\n<tr><th>column</th><th>type</th><th>semantic</th><th>nulls</th><th>distinct</th><th>min</th><th>max</th><th>mean</th></tr>").unwrap();
+        for column in &self.columns {
+            let [nulls, distinct, min, max, mean] = self.summary(column);
+            writeln!(
+                out,
+                "<tr><td><code>{}</code></td><td>{}</td><td>{}</td><td class=\"n\">{nulls}</td><td class=\"n\">{distinct}</td><td>{}</td><td>{}</td><td class=\"n\">{mean}</td></tr>",
+                escape(&column.name),
+                escape(&column.dtype),
+                column.semantic,
+                escape(&min),
+                escape(&max),
+            )
+            .unwrap();
+        }
+        writeln!(out, "</table>").unwrap();
+
+        for column in &self.columns {
+            writeln!(
+                out,
+                "<section>\n<h2><code>{}</code></h2>",
+                escape(&column.name)
+            )
+            .unwrap();
+            if let Some(numeric) = &column.numeric {
+                writeln!(out, "<p>{}</p>\n<table>", quantiles(numeric)).unwrap();
+                let widest = widest(numeric);
+                for bin in &numeric.histogram {
+                    let width = (bin.count * 100).checked_div(widest).unwrap_or(0);
+                    writeln!(
+                        out,
+                        "<tr><td>{} – {}</td><td class=\"n\">{}</td><td style=\"width: 12em\"><div class=\"bar\" style=\"width: {width}%\"></div></td></tr>",
+                        number(bin.start),
+                        number(bin.end),
+                        bin.count
+                    )
+                    .unwrap();
+                }
+                writeln!(out, "</table>").unwrap();
+            }
+            if !column.top.is_empty() {
+                writeln!(out, "<table>\n<tr><th>value</th><th>count</th></tr>").unwrap();
+                for top in &column.top {
+                    writeln!(
+                        out,
+                        "<tr><td>{}</td><td class=\"n\">{}</td></tr>",
+                        escape(&top.value),
+                        top.count
+                    )
+                    .unwrap();
+                }
+                writeln!(out, "</table>").unwrap();
+            }
+            writeln!(out, "</section>").unwrap();
+        }
+        writeln!(out, "</body>\n</html>").unwrap();
+        out
+    }
+
+    /// Everything, pretty printed; [`Profile::from_json`] reads it back
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("a profile is plain data")
+    }
+
+    /// nulls, distinct, min, max and mean as table cells
+    fn summary(&self, column: &ColumnProfile) -> [String; 5] {
+        let nulls = if column.nulls == 0 {
+            "0".to_string()
+        } else {
+            format!(
+                "{} ({:.1}%)",
+                column.nulls,
+                column.null_rate(self.rows) * 100.0
+            )
+        };
+        [
+            nulls,
+            column.distinct.to_string(),
+            column.min.clone().unwrap_or_default(),
+            column.max.clone().unwrap_or_default(),
+            column
+                .numeric
+                .as_ref()
+                .map(|numeric| number(numeric.mean))
+                .unwrap_or_default(),
+        ]
+    }
+}
+
+fn quantiles(numeric: &NumericStats) -> String {
+    let mut line = numeric
+        .quantiles
+        .iter()
+        .map(|quantile| format!("p{} {}", number(quantile.q * 100.0), number(quantile.value)))
+        .collect::<Vec<_>>()
+        .join(", ");
+    if let Some(std) = numeric.std {
+        line.push_str(&format!(", std {}", number(std)));
+    }
+    line
+}
+
+fn widest(numeric: &NumericStats) -> usize {
+    numeric
+        .histogram
+        .iter()
+        .map(|bin| bin.count)
+        .max()
+        .unwrap_or(0)
+}
+
+fn bar(count: usize, widest: usize) -> String {
+    if widest == 0 {
+        return String::new();
+    }
+    "█".repeat((count * BAR).div_ceil(widest))
+}
+
+/// Whole numbers without a fraction, the rest to 4 places at most
+fn number(value: f64) -> String {
+    if value.fract() == 0.0 && value.abs() < 1e15 {
+        format!("{value:.0}")
+    } else {
+        let fixed = format!("{value:.4}");
+        fixed.trim_end_matches('0').to_string()
+    }
+}
+
+/// Pipes would end the cell, newlines the row
+fn cell(value: &str) -> String {
+    value.replace('|', "\\|").replace('\n', " ")
+}
+
+fn escape(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
@@ -0,0 +1,134 @@
+use std::fmt;
+
+use chrono::{NaiveDate, NaiveDateTime};
+use polars::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// What a column looks like it holds, beyond its polars type
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Semantic {
+    /// nothing but nulls
+    Empty,
+    /// unique, never null, and named like an id or made of codes like `A-1001`
+    Id,
+    Email,
+    /// `date` columns, and strings that all parse as dates
+    Date,
+    Datetime,
+    Boolean,
+    /// a few values, each repeated
+    Categorical,
+    Numeric,
+    Text,
+}
+
+/// Formats strings are tried against - ISO dates are parsed when a CSV is read
+const DATE_FORMATS: [&str; 5] = ["%Y-%m-%d", "%d/%m/%Y", "%m/%d/%Y", "%Y/%m/%d", "%d.%m.%Y"];
+const DATETIME_FORMATS: [&str; 3] = [
+    "%Y-%m-%dT%H:%M:%S",
+    "%Y-%m-%d %H:%M:%S",
+    "%Y-%m-%dT%H:%M:%S%.f",
+];
+
+/// At most this many distinct values to be categorical
+const CATEGORIES: usize = 20;
+
+impl Semantic {
+    /// `distinct` doesn't count null
+    pub(super) fn infer(series: &Series, distinct: usize) -> PolarsResult<Semantic> {
+        let nulls = series.null_count();
+        let values = series.len() - nulls;
+        if values == 0 {
+            return Ok(Semantic::Empty);
+        }
+        let unique = nulls == 0 && distinct == values && values > 1;
+        let categorical = distinct <= CATEGORIES && values >= 2 * distinct;
+
+        Ok(match series.dtype() {
+            DataType::Boolean => Semantic::Boolean,
+            DataType::Date => Semantic::Date,
+            DataType::Datetime(_, _) => Semantic::Datetime,
+            dtype if dtype.is_integer() && unique && named_like_an_id(series.name()) => {
+                Semantic::Id
+            }
+            dtype if dtype.is_numeric() => Semantic::Numeric,
+            DataType::String => {
+                let strings = series.str()?;
+                let all = |test: fn(&str) -> bool| strings.into_iter().flatten().all(test);
+                if all(is_email) {
+                    Semantic::Email
+                } else if all(is_date) {
+                    Semantic::Date
+                } else if all(is_datetime) {
+                    Semantic::Datetime
+                } else if unique && (named_like_an_id(series.name()) || all(is_code)) {
+                    Semantic::Id
+                } else if categorical {
+                    Semantic::Categorical
+                } else {
+                    Semantic::Text
+                }
+            }
+            _ => Semantic::Text,
+        })
+    }
+}
+
+impl fmt::Display for Semantic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Semantic::Empty => "empty",
+            Semantic::Id => "id",
+            Semantic::Email => "email",
+            Semantic::Date => "date",
+            Semantic::Datetime => "datetime",
+            Semantic::Boolean => "boolean",
+            Semantic::Categorical => "categorical",
+            Semantic::Numeric => "numeric",
+            Semantic::Text => "text",
+        };
+        f.write_str(name)
+    }
+}
+
+/// `id`, `customer_id`, `customerId`, `uuid`
+fn named_like_an_id(name: &str) -> bool {
+    let lower = name.to_ascii_lowercase();
+    ["id", "uuid", "guid", "key"].contains(&lower.as_str())
+        || ["_id", "-id", "_uuid", "_key"]
+            .iter()
+            .any(|suffix| lower.ends_with(suffix))
+        || name.ends_with("Id")
+}
+
+fn is_email(value: &str) -> bool {
+    let Some((local, domain)) = value.split_once('@') else {
+        return false;
+    };
+    !local.is_empty()
+        && !domain.contains('@')
+        && domain.contains('.')
+        && !domain.starts_with('.')
+        && !domain.ends_with('.')
+        && !value.contains(char::is_whitespace)
+}
+
+fn is_date(value: &str) -> bool {
+    DATE_FORMATS
+        .iter()
+        .any(|format| NaiveDate::parse_from_str(value, format).is_ok())
+}
+
+fn is_datetime(value: &str) -> bool {
+    DATETIME_FORMATS
+        .iter()
+        .any(|format| NaiveDateTime::parse_from_str(value, format).is_ok())
+}
+
+/// One token with a digit in it - `A-1001`, `f47ac10b-58cc-4372-a567-0e02b2c3d479`
+fn is_code(value: &str) -> bool {
+    !value.is_empty()
+        && !value.contains(char::is_whitespace)
+        && value.contains(|c: char| c.is_ascii_digit())
+}
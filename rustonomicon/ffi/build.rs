@@ -10,6 +10,8 @@ fn main() {
 
 	let out = Config::new("snappy")
 		.define("CMAKE_VERBOSE_MAKEFILE", "ON")
+		.define("SNAPPY_BUILD_TESTS", "OFF")
+		.define("SNAPPY_BUILD_BENCHMARKS", "OFF")
 		.build_target("snappy")
 		.build();
 
@@ -20,9 +22,11 @@ fn main() {
 	let target_env = env::var("CARGO_CFG_TARGET_ENV").expect("CARGO_CFG_TARGET_ENV is set by cargo.");
 
 	fs::copy(src.join("snappy.h"), build.join("snappy.h")).unwrap();
-    println!("cargo:rustc-link-lib=dylib=stdc++");
 	println!("cargo:rustc-link-search=native={}", build.display());
 	println!("cargo:rustc-link-lib=static=snappy");
+	// after snappy, or the linker has dropped it by the time snappy needs
+	// `operator delete` and friends
+	println!("cargo:rustc-link-lib=dylib=stdc++");
 	println!("cargo:include={}", build.display());
     println!("{}----{}", target_os, target_env);
 
@@ -0,0 +1,491 @@
+//! The snappy [framing format] as `Read`/`Write` adapters: a stream
+//! identifier, then chunks of at most 64 KiB of input, each compressed on its
+//! own and checksummed with a masked CRC-32C.
+//!
+//! [framing format]: https://github.com/google/snappy/blob/main/framing_format.txt
+
+use std::fmt;
+use std::io::{self, Read, Write};
+
+use crate::crc32c::masked_crc32c;
+use crate::snappy::{self, max_compressed_length, Error};
+
+/// The first chunk of every stream.
+const STREAM_IDENTIFIER: &[u8] = b"\xff\x06\x00\x00sNaPpY";
+/// The most data one chunk may hold once uncompressed.
+const MAX_BLOCK: usize = 65536;
+/// The type byte, then the length of the rest as 24-bit little endian.
+const HEADER: usize = 4;
+const CHECKSUM: usize = 4;
+
+const COMPRESSED: u8 = 0x00;
+const UNCOMPRESSED: u8 = 0x01;
+const STREAM_ID: u8 = 0xff;
+
+/// Why a framed stream couldn't be read. Turned into an [`io::Error`] of kind
+/// `InvalidData`, or `UnexpectedEof` for [`Truncated`](FrameError::Truncated).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FrameError {
+    /// The first chunk isn't a stream identifier.
+    MissingStreamIdentifier,
+    /// A stream identifier chunk that doesn't say `sNaPpY`.
+    BadStreamIdentifier,
+    /// One of the chunk types reserved as unskippable (`0x02..=0x7f`).
+    UnskippableChunk(u8),
+    /// A data chunk too short to hold its checksum.
+    ChunkTooShort(usize),
+    /// A data chunk that holds more than 64 KiB once uncompressed.
+    ChunkTooLarge(usize),
+    /// A chunk's checksum doesn't match its uncompressed data.
+    ChecksumMismatch { expected: u32, actual: u32 },
+    /// A compressed chunk snappy wouldn't uncompress.
+    Snappy(Error),
+    /// The stream ends in the middle of a chunk.
+    Truncated,
+}
+
+impl fmt::Display for FrameError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FrameError::MissingStreamIdentifier => f.write_str("missing stream identifier"),
+            FrameError::BadStreamIdentifier => f.write_str("bad stream identifier"),
+            FrameError::UnskippableChunk(ty) => write!(f, "unskippable chunk type {ty:#04x}"),
+            FrameError::ChunkTooShort(len) => {
+                write!(f, "{len} byte chunk has no room for a checksum")
+            }
+            FrameError::ChunkTooLarge(len) => {
+                write!(f, "chunk holds {len} bytes, more than {MAX_BLOCK}")
+            }
+            FrameError::ChecksumMismatch { expected, actual } => {
+                write!(f, "checksum {actual:#010x} doesn't match {expected:#010x}")
+            }
+            FrameError::Snappy(err) => write!(f, "corrupt chunk: {err}"),
+            FrameError::Truncated => f.write_str("stream ends in the middle of a chunk"),
+        }
+    }
+}
+
+impl std::error::Error for FrameError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            FrameError::Snappy(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<FrameError> for io::Error {
+    fn from(err: FrameError) -> io::Error {
+        let kind = match err {
+            FrameError::Truncated => io::ErrorKind::UnexpectedEof,
+            _ => io::ErrorKind::InvalidData,
+        };
+        io::Error::new(kind, err)
+    }
+}
+
+/// Compresses everything written to it into a framed stream on `W`.
+///
+/// Data is buffered a chunk at a time, so call [`finish`](FrameEncoder::finish)
+/// (or at least `flush`) when done; dropping it flushes too but loses any error.
+pub struct FrameEncoder<W: Write> {
+    /// `None` only once `finish` has taken it.
+    inner: Option<W>,
+    /// Input not yet written out.
+    buf: Vec<u8>,
+    /// Where chunks are put together.
+    chunk: Vec<u8>,
+    wrote_identifier: bool,
+}
+
+impl<W: Write> FrameEncoder<W> {
+    pub fn new(inner: W) -> Self {
+        FrameEncoder {
+            inner: Some(inner),
+            buf: Vec::with_capacity(MAX_BLOCK),
+            chunk: vec![0; HEADER + CHECKSUM + max_compressed_length(MAX_BLOCK)],
+            wrote_identifier: false,
+        }
+    }
+
+    pub fn get_ref(&self) -> &W {
+        self.inner.as_ref().unwrap()
+    }
+
+    /// Writing to it directly will most likely corrupt the stream.
+    pub fn get_mut(&mut self) -> &mut W {
+        self.inner.as_mut().unwrap()
+    }
+
+    /// Writes out what's buffered and hands back the writer. Even a stream
+    /// nothing was written to gets its identifier.
+    pub fn finish(mut self) -> io::Result<W> {
+        self.flush()?;
+        Ok(self.inner.take().unwrap())
+    }
+
+    fn write_chunk(&mut self) -> io::Result<()> {
+        let inner = self.inner.as_mut().unwrap();
+        if !self.wrote_identifier {
+            inner.write_all(STREAM_IDENTIFIER)?;
+            self.wrote_identifier = true;
+        }
+        if self.buf.is_empty() {
+            return Ok(());
+        }
+
+        let checksum = masked_crc32c(&self.buf);
+        let compressed = snappy::compress_into(&self.buf, &mut self.chunk[HEADER + CHECKSUM..])
+            .expect("sized by snappy_max_compressed_length");
+        // only worth it if it saves at least an eighth
+        let (ty, data) = if compressed < self.buf.len() - self.buf.len() / 8 {
+            (COMPRESSED, &self.chunk[HEADER + CHECKSUM..][..compressed])
+        } else {
+            (UNCOMPRESSED, &self.buf[..])
+        };
+        let len = (CHECKSUM + data.len()) as u32;
+
+        let mut head = [0; HEADER + CHECKSUM];
+        head[..HEADER].copy_from_slice(&(len << 8 | ty as u32).to_le_bytes());
+        head[HEADER..].copy_from_slice(&checksum.to_le_bytes());
+        inner.write_all(&head)?;
+        inner.write_all(data)?;
+        self.buf.clear();
+        Ok(())
+    }
+}
+
+impl<W: Write> Write for FrameEncoder<W> {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        // out before taking more, so a failed write has taken nothing
+        if self.buf.len() == MAX_BLOCK {
+            self.write_chunk()?;
+        }
+        let n = data.len().min(MAX_BLOCK - self.buf.len());
+        self.buf.extend_from_slice(&data[..n]);
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.write_chunk()?;
+        self.get_mut().flush()
+    }
+}
+
+impl<W: Write> Drop for FrameEncoder<W> {
+    fn drop(&mut self) {
+        if self.inner.is_some() {
+            let _ = self.write_chunk();
+        }
+    }
+}
+
+impl<W: Write + fmt::Debug> fmt::Debug for FrameEncoder<W> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FrameEncoder")
+            .field("inner", &self.inner)
+            .field("buffered", &self.buf.len())
+            .finish()
+    }
+}
+
+/// Reads the uncompressed data back out of a framed stream on `R`, checking
+/// every chunk's checksum. Streams that were concatenated read as one.
+pub struct FrameDecoder<R: Read> {
+    inner: R,
+    /// The chunk being read.
+    chunk: Vec<u8>,
+    /// Its uncompressed data, and how much of it has been read.
+    buf: Vec<u8>,
+    pos: usize,
+    read_identifier: bool,
+}
+
+impl<R: Read> FrameDecoder<R> {
+    pub fn new(inner: R) -> Self {
+        FrameDecoder {
+            inner,
+            chunk: Vec::new(),
+            buf: Vec::with_capacity(MAX_BLOCK),
+            pos: 0,
+            read_identifier: false,
+        }
+    }
+
+    pub fn get_ref(&self) -> &R {
+        &self.inner
+    }
+
+    /// Anything still in the current chunk is lost with the decoder.
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+
+    /// Fills `buf` with the next data chunk, saying whether there was one.
+    fn next_chunk(&mut self) -> io::Result<bool> {
+        loop {
+            let Some((ty, len)) = self.read_header()? else {
+                return Ok(false);
+            };
+            if ty != STREAM_ID && !self.read_identifier {
+                return Err(FrameError::MissingStreamIdentifier.into());
+            }
+            match ty {
+                STREAM_ID => {
+                    self.read_chunk(len)?;
+                    if self.chunk != STREAM_IDENTIFIER[HEADER..] {
+                        return Err(FrameError::BadStreamIdentifier.into());
+                    }
+                    self.read_identifier = true;
+                }
+                COMPRESSED | UNCOMPRESSED => {
+                    if len < CHECKSUM {
+                        return Err(FrameError::ChunkTooShort(len).into());
+                    }
+                    self.read_chunk(len)?;
+                    let (checksum, data) = self.chunk.split_at(CHECKSUM);
+                    let expected = u32::from_le_bytes(checksum.try_into().unwrap());
+
+                    self.buf.clear();
+                    self.pos = 0;
+                    if ty == COMPRESSED {
+                        let n = snappy::uncompressed_length(data).map_err(FrameError::Snappy)?;
+                        if n > MAX_BLOCK {
+                            return Err(FrameError::ChunkTooLarge(n).into());
+                        }
+                        self.buf.resize(n, 0);
+                        let n = snappy::uncompress_into(data, &mut self.buf)
+                            .map_err(FrameError::Snappy)?;
+                        self.buf.truncate(n);
+                    } else {
+                        if data.len() > MAX_BLOCK {
+                            return Err(FrameError::ChunkTooLarge(data.len()).into());
+                        }
+                        self.buf.extend_from_slice(data);
+                    }
+
+                    let actual = masked_crc32c(&self.buf);
+                    if actual != expected {
+                        return Err(FrameError::ChecksumMismatch { expected, actual }.into());
+                    }
+                    return Ok(true);
+                }
+                0x02..=0x7f => return Err(FrameError::UnskippableChunk(ty).into()),
+                // padding and the skippable ones
+                _ => {
+                    let skipped =
+                        io::copy(&mut (&mut self.inner).take(len as u64), &mut io::sink())?;
+                    if skipped < len as u64 {
+                        return Err(FrameError::Truncated.into());
+                    }
+                }
+            }
+        }
+    }
+
+    /// The next chunk's type and length, or `None` at the end of the stream.
+    fn read_header(&mut self) -> io::Result<Option<(u8, usize)>> {
+        let mut header = [0; HEADER];
+        let mut read = 0;
+        while read < HEADER {
+            match self.inner.read(&mut header[read..]) {
+                Ok(0) if read == 0 => return Ok(None),
+                Ok(0) => return Err(FrameError::Truncated.into()),
+                Ok(n) => read += n,
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+                Err(err) => return Err(err),
+            }
+        }
+        let header = u32::from_le_bytes(header);
+        Ok(Some((header as u8, (header >> 8) as usize)))
+    }
+
+    fn read_chunk(&mut self, len: usize) -> io::Result<()> {
+        self.chunk.resize(len, 0);
+        self.inner
+            .read_exact(&mut self.chunk)
+            .map_err(|err| match err.kind() {
+                io::ErrorKind::UnexpectedEof => FrameError::Truncated.into(),
+                _ => err,
+            })
+    }
+}
+
+impl<R: Read> Read for FrameDecoder<R> {
+    fn read(&mut self, out: &mut [u8]) -> io::Result<usize> {
+        while self.pos == self.buf.len() {
+            if out.is_empty() || !self.next_chunk()? {
+                return Ok(0);
+            }
+        }
+        let n = out.len().min(self.buf.len() - self.pos);
+        out[..n].copy_from_slice(&self.buf[self.pos..][..n]);
+        self.pos += n;
+        Ok(n)
+    }
+}
+
+impl<R: Read + fmt::Debug> fmt::Debug for FrameDecoder<R> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FrameDecoder")
+            .field("inner", &self.inner)
+            .field("buffered", &(self.buf.len() - self.pos))
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    fn encode(data: &[u8]) -> Vec<u8> {
+        let mut encoder = FrameEncoder::new(Vec::new());
+        encoder.write_all(data).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    fn decode(framed: &[u8]) -> io::Result<Vec<u8>> {
+        let mut out = Vec::new();
+        FrameDecoder::new(framed).read_to_end(&mut out)?;
+        Ok(out)
+    }
+
+    fn frame_error(err: io::Error) -> FrameError {
+        err.into_inner()
+            .unwrap()
+            .downcast::<FrameError>()
+            .map(|err| *err)
+            .unwrap()
+    }
+
+    /// Every chunk type in the stream, in order.
+    fn chunk_types(mut framed: &[u8]) -> Vec<u8> {
+        let mut types = Vec::new();
+        while !framed.is_empty() {
+            let header = u32::from_le_bytes(framed[..HEADER].try_into().unwrap());
+            types.push(header as u8);
+            framed = &framed[HEADER + (header >> 8) as usize..];
+        }
+        types
+    }
+
+    #[test]
+    fn empty_stream_is_just_the_identifier() {
+        assert_eq!(encode(b""), STREAM_IDENTIFIER);
+        assert_eq!(decode(STREAM_IDENTIFIER).unwrap(), b"");
+        assert_eq!(decode(b"").unwrap(), b"");
+    }
+
+    #[test]
+    fn chunks_compress_only_when_it_pays() {
+        let text = b"the quick brown fox jumps over the lazy dog. ".repeat(3000);
+        let framed = encode(&text);
+        assert!(framed.len() < text.len() / 4);
+        assert_eq!(
+            chunk_types(&framed),
+            [STREAM_ID, COMPRESSED, COMPRESSED, COMPRESSED]
+        );
+        assert_eq!(decode(&framed).unwrap(), text);
+
+        // an xorshift's output doesn't compress
+        let mut x = 0x2545_f491_4f6c_dd1du64;
+        let noise: Vec<u8> = (0..MAX_BLOCK + 10)
+            .map(|_| {
+                x ^= x << 13;
+                x ^= x >> 7;
+                x ^= x << 17;
+                x as u8
+            })
+            .collect();
+        let framed = encode(&noise);
+        assert_eq!(
+            chunk_types(&framed),
+            [STREAM_ID, UNCOMPRESSED, UNCOMPRESSED]
+        );
+        assert_eq!(decode(&framed).unwrap(), noise);
+    }
+
+    #[test]
+    fn skips_padding_and_skippable_chunks_and_reads_concatenated_streams() {
+        let mut framed = encode(b"hello ");
+        framed.extend_from_slice(b"\xfe\x03\x00\x00\x00\x00\x00");
+        framed.extend_from_slice(b"\x80\x00\x00\x00");
+        framed.extend(encode(b"world"));
+        assert_eq!(decode(&framed).unwrap(), b"hello world");
+    }
+
+    #[test]
+    fn rejects_corrupt_streams() {
+        let framed = encode(b"hello world");
+        let err = |framed: &[u8]| frame_error(decode(framed).unwrap_err());
+
+        assert_eq!(
+            err(&framed[STREAM_IDENTIFIER.len()..]),
+            FrameError::MissingStreamIdentifier
+        );
+        let mut bad = framed.clone();
+        bad[4] = b'S';
+        assert_eq!(err(&bad), FrameError::BadStreamIdentifier);
+
+        let mut bad = framed.clone();
+        *bad.last_mut().unwrap() ^= 1;
+        assert!(matches!(err(&bad), FrameError::ChecksumMismatch { .. }));
+
+        let mut bad = STREAM_IDENTIFIER.to_vec();
+        bad.extend_from_slice(b"\x02\x00\x00\x00");
+        assert_eq!(err(&bad), FrameError::UnskippableChunk(0x02));
+
+        let mut bad = STREAM_IDENTIFIER.to_vec();
+        bad.extend_from_slice(b"\x01\x03\x00\x00abc");
+        assert_eq!(err(&bad), FrameError::ChunkTooShort(3));
+
+        let truncated = decode(&framed[..framed.len() - 1]).unwrap_err();
+        assert_eq!(truncated.kind(), io::ErrorKind::UnexpectedEof);
+        assert_eq!(frame_error(truncated), FrameError::Truncated);
+    }
+
+    #[test]
+    fn interoperates_with_the_snap_crate() {
+        let text = b"snappy, but framed. ".repeat(5000);
+
+        let mut out = Vec::new();
+        snap::read::FrameDecoder::new(&encode(&text)[..])
+            .read_to_end(&mut out)
+            .unwrap();
+        assert_eq!(out, text);
+
+        let mut theirs = snap::write::FrameEncoder::new(Vec::new());
+        theirs.write_all(&text).unwrap();
+        assert_eq!(decode(&theirs.into_inner().unwrap()).unwrap(), text);
+    }
+
+    proptest! {
+        // each case is a few chunks' worth
+        #![proptest_config(ProptestConfig::with_cases(16))]
+
+        #[test]
+        fn round_trips_however_it_is_split(
+            data in prop::collection::vec(prop_oneof![Just(b'a'), any::<u8>()], 0..150_000),
+            write_size in 1usize..100_000,
+            read_size in 1usize..100_000,
+        ) {
+            let mut encoder = FrameEncoder::new(Vec::new());
+            for piece in data.chunks(write_size) {
+                encoder.write_all(piece).unwrap();
+            }
+            let framed = encoder.finish().unwrap();
+
+            let mut decoder = FrameDecoder::new(&framed[..]);
+            let mut out = Vec::new();
+            let mut buf = vec![0; read_size];
+            loop {
+                match decoder.read(&mut buf).unwrap() {
+                    0 => break,
+                    n => out.extend_from_slice(&buf[..n]),
+                }
+            }
+            prop_assert_eq!(out, data);
+        }
+    }
+}
@@ -1,72 +1,33 @@
 #![allow(dead_code)]
 
-mod call_c{
+mod call_c {
     use libc::{c_int, size_t};
 
     #[link(name = "snappy")]
-    extern {
-        fn snappy_compress(input: *const u8,
+    extern "C" {
+        pub fn snappy_compress(input: *const u8,
                         input_length: size_t,
                         compressed: *mut u8,
                         compressed_length: *mut size_t) -> c_int;
-        fn snappy_uncompress(compressed: *const u8,
+        pub fn snappy_uncompress(compressed: *const u8,
                             compressed_length: size_t,
                             uncompressed: *mut u8,
                             uncompressed_length: *mut size_t) -> c_int;
         pub fn snappy_max_compressed_length(source_length: size_t) -> size_t;
-        fn snappy_uncompressed_length(compressed: *const u8,
+        pub fn snappy_uncompressed_length(compressed: *const u8,
                                     compressed_length: size_t,
                                     result: *mut size_t) -> c_int;
-        fn snappy_validate_compressed_buffer(compressed: *const u8,
+        pub fn snappy_validate_compressed_buffer(compressed: *const u8,
                                             compressed_length: size_t) -> c_int;
     }
+}
 
-    pub fn validate_compressed_buffer(src: &[u8]) -> bool {
-        unsafe {
-            snappy_validate_compressed_buffer(src.as_ptr(), src.len() as size_t) == 0
-        }
-    }
-
-    pub fn compress(src: &[u8]) -> Vec<u8> {
-        unsafe {
-            let srclen = src.len() as size_t;
-            let psrc = src.as_ptr();
-
-            let mut dstlen = snappy_max_compressed_length(srclen);
-            let mut dst = Vec::with_capacity(dstlen as usize);
-            let pdst = dst.as_mut_ptr(); // mut pointer to Vec data
-            // snappy_compress(
-            // input: *const u8,
-            // input_length: size_t, 
-            // compressed: *mut u8, 
-            // compressed_length: *mut size_t
-            // ) -> c_int;
-            snappy_compress(psrc, srclen, pdst, &mut dstlen);
-            dst.set_len(dstlen as usize);
-            dst
-        }
-    }
-
-    pub fn uncompress(src: &[u8]) -> Option<Vec<u8>> {
-        unsafe {
-            let srclen = src.len() as size_t;
-            let psrc = src.as_ptr();
-
-            let mut dstlen: size_t = 0;
-            snappy_uncompressed_length(psrc, srclen, &mut dstlen);
-
-            let mut dst = Vec::with_capacity(dstlen as usize);
-            let pdst = dst.as_mut_ptr();
+mod crc32c;
+pub mod frame;
+pub mod snappy;
 
-            if snappy_uncompress(psrc, srclen, pdst, &mut dstlen) == 0 {
-                dst.set_len(dstlen as usize);
-                Some(dst)
-            } else {
-                None // SNAPPY_INVALID_INPUT
-            }
-        }
-    }
-}
+pub use frame::{FrameDecoder, FrameEncoder, FrameError};
+pub use snappy::{compress, uncompress, validate_compressed_buffer, Error};
 
 
 
@@ -100,31 +61,49 @@ mod tests {
         
     }
 
-    // TODO: these tests fail due to linker errors: "undefined reference to `operator delete(void*)'""
-    // #[test]
-    // fn valid() {
-    //     let d = vec![0xde, 0xad, 0xd0, 0x0d];
-    //     let c: &[u8] = &compress(&d);
-    //     assert!(validate_compressed_buffer(c));
-    //     assert!(uncompress(c) == Some(d));
-    // }
-
-    // #[test]
-    // fn invalid() {
-    //     let d = vec![0, 0, 0, 0];
-    //     assert!(!validate_compressed_buffer(&d));
-    //     assert!(uncompress(&d).is_none());
-    // }
-
-    // #[test]
-    // fn empty() {
-    //     let d = vec![];
-    //     assert!(!validate_compressed_buffer(&d));
-    //     assert!(uncompress(&d).is_none());
-    //     let c = compress(&d);
-    //     assert!(validate_compressed_buffer(&c));
-    //     assert!(uncompress(&c) == Some(d));
-    // }
+    #[test]
+    fn valid() {
+        let d = vec![0xde, 0xad, 0xd0, 0x0d];
+        let c: &[u8] = &compress(&d);
+        assert!(validate_compressed_buffer(c));
+        assert!(uncompress(c) == Ok(d));
+    }
+
+    #[test]
+    fn invalid() {
+        let d = vec![0, 0, 0, 0];
+        assert!(!validate_compressed_buffer(&d));
+        assert_eq!(uncompress(&d), Err(Error::InvalidInput));
+    }
+
+    #[test]
+    fn lying_header() {
+        // a varint claiming 4 GiB, then a single literal byte
+        let d = [0xff, 0xff, 0xff, 0xff, 0x0f, 0x00, 0x61];
+        assert_eq!(snappy::uncompressed_length(&d), Ok(0xffff_ffff));
+        assert!(!validate_compressed_buffer(&d));
+        assert_eq!(uncompress(&d), Err(Error::InvalidInput));
+    }
+
+    #[test]
+    fn empty() {
+        let d = vec![];
+        assert!(!validate_compressed_buffer(&d));
+        assert!(uncompress(&d).is_err());
+        let c = compress(&d);
+        assert!(validate_compressed_buffer(&c));
+        assert!(uncompress(&c) == Ok(d));
+    }
+
+    proptest::proptest! {
+        #[test]
+        fn round_trips(d in proptest::collection::vec(0..4u8, 0..10_000)) {
+            let c = compress(&d);
+            proptest::prop_assert!(c.len() <= snappy::max_compressed_length(d.len()));
+            proptest::prop_assert!(validate_compressed_buffer(&c));
+            proptest::prop_assert_eq!(uncompress(&c), Ok(d));
+        }
+    }
 
     #[test]
     fn call_readline() {
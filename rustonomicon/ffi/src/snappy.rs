@@ -0,0 +1,121 @@
+//! Safe wrappers around the snappy C API, for the raw (unframed) format.
+
+use std::fmt;
+
+use libc::{c_int, size_t};
+
+use crate::call_c;
+
+// snappy_status from snappy-c.h
+const SNAPPY_OK: c_int = 0;
+const SNAPPY_INVALID_INPUT: c_int = 1;
+const SNAPPY_BUFFER_TOO_SMALL: c_int = 2;
+
+/// What snappy said went wrong.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    /// The input isn't valid snappy-compressed data.
+    InvalidInput,
+    /// The output buffer is too small for the result.
+    BufferTooSmall,
+    /// A status `snappy-c.h` doesn't define.
+    Unknown(c_int),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::InvalidInput => f.write_str("invalid snappy-compressed data"),
+            Error::BufferTooSmall => f.write_str("output buffer too small"),
+            Error::Unknown(status) => write!(f, "unknown snappy status {status}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+fn check(status: c_int) -> Result<(), Error> {
+    match status {
+        SNAPPY_OK => Ok(()),
+        SNAPPY_INVALID_INPUT => Err(Error::InvalidInput),
+        SNAPPY_BUFFER_TOO_SMALL => Err(Error::BufferTooSmall),
+        status => Err(Error::Unknown(status)),
+    }
+}
+
+/// The most `compress` can turn `len` bytes into.
+pub fn max_compressed_length(len: usize) -> usize {
+    unsafe { call_c::snappy_max_compressed_length(len as size_t) as usize }
+}
+
+pub fn compress(src: &[u8]) -> Vec<u8> {
+    let mut dst = vec![0; max_compressed_length(src.len())];
+    let len = compress_into(src, &mut dst).expect("sized by snappy_max_compressed_length");
+    dst.truncate(len);
+    dst
+}
+
+/// Compresses `src` into the front of `dst`, returning how much of it was
+/// used. `dst` needs to be [`max_compressed_length`] long.
+pub fn compress_into(src: &[u8], dst: &mut [u8]) -> Result<usize, Error> {
+    let mut dstlen = dst.len() as size_t;
+    let status = unsafe {
+        call_c::snappy_compress(
+            src.as_ptr(),
+            src.len() as size_t,
+            dst.as_mut_ptr(),
+            &mut dstlen,
+        )
+    };
+    check(status)?;
+    Ok(dstlen as usize)
+}
+
+/// The length `src` uncompresses to, read from its header.
+pub fn uncompressed_length(src: &[u8]) -> Result<usize, Error> {
+    let mut result: size_t = 0;
+    let status = unsafe {
+        call_c::snappy_uncompressed_length(src.as_ptr(), src.len() as size_t, &mut result)
+    };
+    check(status)?;
+    Ok(result as usize)
+}
+
+/// Uncompresses `src` into a buffer of the length its header claims. The header
+/// is only believed once snappy has walked the whole of `src` and found it
+/// consistent - a few bytes claiming gigabytes are invalid input, not an
+/// allocation. A stream that does hold what it claims can only expand so far
+/// (a 3-byte copy makes at most 64), so the buffer stays in proportion to `src`.
+pub fn uncompress(src: &[u8]) -> Result<Vec<u8>, Error> {
+    if !validate_compressed_buffer(src) {
+        return Err(Error::InvalidInput);
+    }
+    let mut dst = vec![0; uncompressed_length(src)?];
+    let len = uncompress_into(src, &mut dst)?;
+    dst.truncate(len);
+    Ok(dst)
+}
+
+/// Uncompresses `src` into the front of `dst`, returning how much of it was
+/// used.
+pub fn uncompress_into(src: &[u8], dst: &mut [u8]) -> Result<usize, Error> {
+    let mut dstlen = dst.len() as size_t;
+    let status = unsafe {
+        call_c::snappy_uncompress(
+            src.as_ptr(),
+            src.len() as size_t,
+            dst.as_mut_ptr(),
+            &mut dstlen,
+        )
+    };
+    check(status)?;
+    Ok(dstlen as usize)
+}
+
+/// Whether `src` is well-formed and uncompresses to the length its header says -
+/// checked without writing the output anywhere.
+pub fn validate_compressed_buffer(src: &[u8]) -> bool {
+    let status =
+        unsafe { call_c::snappy_validate_compressed_buffer(src.as_ptr(), src.len() as size_t) };
+    status == SNAPPY_OK
+}
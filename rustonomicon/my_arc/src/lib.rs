@@ -1,70 +1,320 @@
+//! The Rustonomicon's `Arc`, with `Weak` references, clone-on-write and
+//! unsized contents.
+//!
+//! Built with `--cfg loom` the atomics come from loom, so that
+//! `tests/loom.rs` can try every interleaving of racing clones, drops and
+//! upgrades.
+
+use std::alloc::{self, Layout};
+use std::fmt;
 use std::marker::PhantomData;
+use std::mem::{self, ManuallyDrop};
 use std::ops::Deref;
-use std::ptr::NonNull;
-use std::sync::atomic::{self, AtomicUsize, Ordering};
+use std::ptr::{self, NonNull};
+
+#[cfg(loom)]
+use loom::{
+    hint,
+    sync::atomic::{self, AtomicUsize, Ordering},
+};
+#[cfg(not(loom))]
+use std::{
+    hint,
+    sync::atomic::{self, AtomicUsize, Ordering},
+};
+
+/// Past this many references we'd rather abort than risk the count
+/// overflowing, like `std::sync::Arc`.
+const MAX_REFCOUNT: usize = isize::MAX as usize;
 
-pub struct Arc<T> {
+/// What `weak` is set to while `get_mut` checks that there are no `Weak`s.
+const LOCKED: usize = usize::MAX;
+
+pub struct Arc<T: ?Sized> {
     ptr: NonNull<ArcInner<T>>,
     phantom: PhantomData<ArcInner<T>>,
 }
 
-pub struct ArcInner<T> {
-    rc: AtomicUsize,
+/// A reference that doesn't keep the data alive, only the allocation.
+pub struct Weak<T: ?Sized> {
+    // dangling, with the address `usize::MAX`, when made by `Weak::new`
+    ptr: NonNull<ArcInner<T>>,
+}
+
+// `repr(C)` so the counts come first for every `T`, which lets
+// `allocate_for_slice` work out where the data of an `ArcInner<[T]>` goes.
+#[repr(C)]
+pub struct ArcInner<T: ?Sized> {
+    /// The number of `Arc`s.
+    strong: AtomicUsize,
+    /// The number of `Weak`s, plus one held by all the `Arc`s together.
+    weak: AtomicUsize,
+    /// What the allocation was made with. The last `Weak` frees it after the
+    /// data has been dropped, when there's no longer a value to measure.
+    layout: Layout,
     data: T,
 }
 
+/// The counts of an `ArcInner` whose data may already have been dropped.
+struct WeakInner<'a> {
+    strong: &'a AtomicUsize,
+    weak: &'a AtomicUsize,
+}
+
+unsafe impl<T: ?Sized + Sync + Send> Send for Arc<T> {}
+unsafe impl<T: ?Sized + Sync + Send> Sync for Arc<T> {}
+unsafe impl<T: ?Sized + Sync + Send> Send for Weak<T> {}
+unsafe impl<T: ?Sized + Sync + Send> Sync for Weak<T> {}
+
 impl<T> Arc<T> {
     pub fn new(data: T) -> Arc<T> {
         // We start the reference count at 1, as that first reference is the
         // current pointer.
         let boxed = Box::new(ArcInner {
-            rc: AtomicUsize::new(1),
+            strong: AtomicUsize::new(1),
+            weak: AtomicUsize::new(1),
+            layout: Layout::new::<ArcInner<T>>(),
             data,
         });
+        // It is okay to call `.unwrap()` here as we get a pointer from
+        // `Box::into_raw` which is guaranteed to not be null.
+        unsafe { Arc::from_inner(NonNull::new(Box::into_raw(boxed)).unwrap()) }
+    }
+
+    /// The data, if this is the only `Arc`. `Weak`s left behind can no longer
+    /// upgrade.
+    pub fn try_unwrap(this: Arc<T>) -> Result<T, Arc<T>> {
+        if this
+            .inner()
+            .strong
+            .compare_exchange(1, 0, Ordering::Relaxed, Ordering::Relaxed)
+            .is_err()
+        {
+            return Err(this);
+        }
+        // see `Drop`: the other `Arc`s' uses of the data happen before this
+        atomic::fence(Ordering::Acquire);
+
+        let this = ManuallyDrop::new(this);
+        let data = unsafe { ptr::read(&this.inner().data) };
+        // the strong references' weak one, which frees the memory if it's the last
+        drop(Weak { ptr: this.ptr });
+        Ok(data)
+    }
+}
+
+impl<T: ?Sized> Arc<T> {
+    /// # Safety
+    ///
+    /// `ptr` must point to a live `ArcInner` whose strong count includes
+    /// the `Arc` being made.
+    unsafe fn from_inner(ptr: NonNull<ArcInner<T>>) -> Arc<T> {
         Arc {
-            // It is okay to call `.unwrap()` here as we get a pointer from
-            // `Box::into_raw` which is guaranteed to not be null.
-            ptr: NonNull::new(Box::into_raw(boxed)).unwrap(),
+            ptr,
             phantom: PhantomData,
         }
     }
+
+    fn inner(&self) -> &ArcInner<T> {
+        // This is safe as long as there is an `Arc`, which keeps the inner alive.
+        unsafe { self.ptr.as_ref() }
+    }
+
+    pub fn downgrade(this: &Arc<T>) -> Weak<T> {
+        let inner = this.inner();
+        let mut weak = inner.weak.load(Ordering::Relaxed);
+        loop {
+            // `get_mut` is checking for weak references, wait for it
+            if weak == LOCKED {
+                hint::spin_loop();
+                weak = inner.weak.load(Ordering::Relaxed);
+                continue;
+            }
+            if weak > MAX_REFCOUNT {
+                std::process::abort();
+            }
+            // Acquire pairs with the Release that unlocks in `is_unique`
+            match inner.weak.compare_exchange_weak(
+                weak,
+                weak + 1,
+                Ordering::Acquire,
+                Ordering::Relaxed,
+            ) {
+                Ok(_) => return Weak { ptr: this.ptr },
+                Err(current) => weak = current,
+            }
+        }
+    }
+
+    /// How many `Arc`s share the data. Another thread may change it any time.
+    pub fn strong_count(this: &Arc<T>) -> usize {
+        this.inner().strong.load(Ordering::Relaxed)
+    }
+
+    /// How many `Weak`s point at the data. Another thread may change it any time.
+    pub fn weak_count(this: &Arc<T>) -> usize {
+        let weak = this.inner().weak.load(Ordering::Relaxed);
+        // `get_mut` only locks when there is a single `Arc` and no `Weak`
+        if weak == LOCKED {
+            0
+        } else {
+            weak - 1
+        }
+    }
+
+    /// Whether both point at the same allocation.
+    pub fn ptr_eq(this: &Arc<T>, other: &Arc<T>) -> bool {
+        ptr::addr_eq(this.ptr.as_ptr(), other.ptr.as_ptr())
+    }
+
+    /// Whether this is the only reference of either kind.
+    fn is_unique(&mut self) -> bool {
+        // Lock the weak count, so a `Weak` can't be made from another `Arc`
+        // (which would have to be ours, hence `&mut self`) or upgraded while
+        // we look. Acquire pairs with the Release in `Weak`'s `Drop`, so
+        // every use of a `Weak` that's gone happens before what we do next.
+        if self
+            .inner()
+            .weak
+            .compare_exchange(1, LOCKED, Ordering::Acquire, Ordering::Relaxed)
+            .is_err()
+        {
+            return false;
+        }
+        // Acquire pairs with the Release in `Arc`'s `Drop`, for the same reason.
+        let unique = self.inner().strong.load(Ordering::Acquire) == 1;
+        self.inner().weak.store(1, Ordering::Release);
+        unique
+    }
+
+    /// The data, if no other `Arc` or `Weak` can get at it.
+    pub fn get_mut(this: &mut Arc<T>) -> Option<&mut T> {
+        if this.is_unique() {
+            // nobody else can read or write the data
+            Some(unsafe { &mut (*this.ptr.as_ptr()).data })
+        } else {
+            None
+        }
+    }
+}
+
+impl<T: Clone> Arc<T> {
+    /// The data, cloned into an `Arc` of its own first if another `Arc`
+    /// shares it. If only `Weak`s do, the data moves instead and the `Weak`s
+    /// are left unable to upgrade.
+    pub fn make_mut(this: &mut Arc<T>) -> &mut T {
+        let inner = this.inner();
+        // Acquire, so the other `Arc`s' writes before they dropped are seen
+        if inner
+            .strong
+            .compare_exchange(1, 0, Ordering::Acquire, Ordering::Relaxed)
+            .is_err()
+        {
+            // others share it: clone the data and leave them to it
+            *this = Arc::new((**this).clone());
+        } else if inner.weak.load(Ordering::Relaxed) != 1 {
+            // only `Weak`s are left, and with the strong count at zero they
+            // can't upgrade any more: move the data out to a fresh allocation
+            let weak = Weak { ptr: this.ptr };
+            unsafe {
+                let data = ptr::read(&inner.data);
+                ptr::write(this, Arc::new(data));
+            }
+            // gives up the strong references' weak one
+            drop(weak);
+        } else {
+            // we were the only one after all; put the count back
+            inner.strong.store(1, Ordering::Release);
+        }
+        // either way `this` is unique now
+        unsafe { &mut (*this.ptr.as_ptr()).data }
+    }
+}
+
+impl<T> Arc<[T]> {
+    /// An `ArcInner<[T]>` with room for `len` items and both counts at 1.
+    fn allocate_for_slice(len: usize) -> NonNull<ArcInner<[T]>> {
+        let (layout, data_offset) = Layout::new::<ArcInner<()>>()
+            .extend(Layout::array::<T>(len).expect("capacity overflow"))
+            .expect("capacity overflow");
+        let layout = layout.pad_to_align();
+        let mem = unsafe { alloc::alloc(layout) };
+        if mem.is_null() {
+            alloc::handle_alloc_error(layout);
+        }
+        // `ArcInner<[T]>` is `repr(C)`, so its data starts where `extend` said
+        debug_assert_eq!(data_offset, mem::offset_of!(ArcInner<[T; 0]>, data));
+        let inner = ptr::slice_from_raw_parts_mut(mem.cast::<T>(), len) as *mut ArcInner<[T]>;
+        unsafe {
+            ptr::write(&raw mut (*inner).strong, AtomicUsize::new(1));
+            ptr::write(&raw mut (*inner).weak, AtomicUsize::new(1));
+            ptr::write(&raw mut (*inner).layout, layout);
+            NonNull::new_unchecked(inner)
+        }
+    }
+}
+
+impl<T> From<Vec<T>> for Arc<[T]> {
+    fn from(mut vec: Vec<T>) -> Arc<[T]> {
+        let inner = Arc::allocate_for_slice(vec.len());
+        unsafe {
+            let data = &raw mut (*inner.as_ptr()).data;
+            ptr::copy_nonoverlapping(vec.as_ptr(), data.cast::<T>(), vec.len());
+            // the items moved, `vec` only frees its buffer
+            vec.set_len(0);
+            Arc::from_inner(inner)
+        }
+    }
 }
 
-unsafe impl<T: Sync + Send> Send for Arc<T> {}
-unsafe impl<T: Sync + Send> Sync for Arc<T> {}
+impl<T: Clone> From<&[T]> for Arc<[T]> {
+    fn from(slice: &[T]) -> Arc<[T]> {
+        // going through a `Vec` drops what was cloned if a clone panics
+        Arc::from(slice.to_vec())
+    }
+}
+
+impl From<&str> for Arc<str> {
+    fn from(s: &str) -> Arc<str> {
+        let bytes: Arc<[u8]> = Arc::from(s.as_bytes());
+        let bytes = ManuallyDrop::new(bytes);
+        // `str` and `[u8]` share a layout, and the bytes are UTF-8
+        unsafe { Arc::from_inner(NonNull::new_unchecked(bytes.ptr.as_ptr() as *mut ArcInner<str>)) }
+    }
+}
+
+impl From<String> for Arc<str> {
+    fn from(s: String) -> Arc<str> {
+        Arc::from(s.as_str())
+    }
+}
 
-impl<T> Deref for Arc<T> {
+impl<T: ?Sized> Deref for Arc<T> {
     type Target = T;
 
     fn deref(&self) -> &T {
-        let inner = unsafe { self.ptr.as_ref() };
-        &inner.data
+        &self.inner().data
     }
 }
 
-impl<T> Clone for Arc<T> {
+impl<T: ?Sized> Clone for Arc<T> {
     fn clone(&self) -> Arc<T> {
-        let inner = unsafe { self.ptr.as_ref() };
         // Using a relaxed ordering is alright here as we don't need any atomic
         // synchronization here as we're not modifying or accessing the inner
         // data.
-        let old_rc = inner.rc.fetch_add(1, Ordering::Relaxed);
+        let old_rc = self.inner().strong.fetch_add(1, Ordering::Relaxed);
 
-        if old_rc >= isize::MAX as usize {
+        if old_rc >= MAX_REFCOUNT {
             std::process::abort();
         }
 
-        Self {
-            ptr: self.ptr,
-            phantom: PhantomData,
-        }
+        unsafe { Arc::from_inner(self.ptr) }
     }
 }
 
-impl<T> Drop for Arc<T> {
+impl<T: ?Sized> Drop for Arc<T> {
     fn drop(&mut self) {
-        let inner = unsafe { self.ptr.as_ref() };
-        if inner.rc.fetch_sub(1, Ordering::Release) != 1 {
+        if self.inner().strong.fetch_sub(1, Ordering::Release) != 1 {
             return;
         }
         // This fence is needed to prevent reordering of the use and deletion
@@ -72,6 +322,282 @@ impl<T> Drop for Arc<T> {
         atomic::fence(Ordering::Acquire);
         // This is safe as we know we have the last pointer to the `ArcInner`
         // and that its pointer is valid.
-        unsafe { let _ = Box::from_raw(self.ptr.as_ptr()); }
+        unsafe { ptr::drop_in_place(&mut (*self.ptr.as_ptr()).data) };
+        // the strong references' weak one, which frees the memory if it's the last
+        drop(Weak { ptr: self.ptr });
+    }
+}
+
+impl<T: ?Sized + fmt::Debug> fmt::Debug for Arc<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+
+impl<T: ?Sized + fmt::Display> fmt::Display for Arc<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&**self, f)
+    }
+}
+
+impl<T: ?Sized + PartialEq> PartialEq for Arc<T> {
+    fn eq(&self, other: &Arc<T>) -> bool {
+        **self == **other
+    }
+}
+
+impl<T: ?Sized + Eq> Eq for Arc<T> {}
+
+impl<T> Weak<T> {
+    /// A `Weak` that never upgrades, without allocating.
+    pub fn new() -> Weak<T> {
+        Weak {
+            ptr: NonNull::new(ptr::without_provenance_mut(usize::MAX)).unwrap(),
+        }
+    }
+}
+
+impl<T> Default for Weak<T> {
+    fn default() -> Weak<T> {
+        Weak::new()
+    }
+}
+
+impl<T: ?Sized> Weak<T> {
+    /// The counts, unless this came from `Weak::new`.
+    fn inner(&self) -> Option<WeakInner<'_>> {
+        if self.ptr.as_ptr().cast::<()>().addr() == usize::MAX {
+            None
+        } else {
+            // the weak count keeps the allocation alive, though the data
+            // may have been dropped - so no reference to the whole `ArcInner`
+            let inner = self.ptr.as_ptr();
+            unsafe {
+                Some(WeakInner {
+                    strong: &(*inner).strong,
+                    weak: &(*inner).weak,
+                })
+            }
+        }
+    }
+
+    /// An `Arc`, if the data is still alive.
+    pub fn upgrade(&self) -> Option<Arc<T>> {
+        let inner = self.inner()?;
+        let mut strong = inner.strong.load(Ordering::Relaxed);
+        loop {
+            // once at zero, the data is gone for good
+            if strong == 0 {
+                return None;
+            }
+            if strong > MAX_REFCOUNT {
+                std::process::abort();
+            }
+            // Acquire pairs with the Release in `make_mut`'s put-back
+            match inner.strong.compare_exchange_weak(
+                strong,
+                strong + 1,
+                Ordering::Acquire,
+                Ordering::Relaxed,
+            ) {
+                Ok(_) => return Some(unsafe { Arc::from_inner(self.ptr) }),
+                Err(current) => strong = current,
+            }
+        }
+    }
+
+    pub fn strong_count(&self) -> usize {
+        self.inner()
+            .map_or(0, |inner| inner.strong.load(Ordering::Relaxed))
+    }
+
+    /// How many `Weak`s point at the data, 0 once it's gone.
+    pub fn weak_count(&self) -> usize {
+        let Some(inner) = self.inner() else {
+            return 0;
+        };
+        let weak = inner.weak.load(Ordering::Relaxed);
+        let strong = inner.strong.load(Ordering::Relaxed);
+        if strong == 0 {
+            0
+        } else {
+            // the `Arc`s' one doesn't count
+            weak - 1
+        }
+    }
+
+    pub fn ptr_eq(&self, other: &Weak<T>) -> bool {
+        ptr::addr_eq(self.ptr.as_ptr(), other.ptr.as_ptr())
+    }
+}
+
+impl<T: ?Sized> Clone for Weak<T> {
+    fn clone(&self) -> Weak<T> {
+        if let Some(inner) = self.inner() {
+            // no need to wait for `get_mut`'s lock: it only locks when there
+            // are no `Weak`s, and this is one
+            if inner.weak.fetch_add(1, Ordering::Relaxed) >= MAX_REFCOUNT {
+                std::process::abort();
+            }
+        }
+        Weak { ptr: self.ptr }
+    }
+}
+
+impl<T: ?Sized> Drop for Weak<T> {
+    fn drop(&mut self) {
+        let Some(inner) = self.inner() else {
+            return;
+        };
+        if inner.weak.fetch_sub(1, Ordering::Release) != 1 {
+            return;
+        }
+        atomic::fence(Ordering::Acquire);
+        // The data has been dropped by now, so the layout comes from the header
+        // rather than from measuring it.
+        unsafe {
+            let layout = ptr::read(&raw const (*self.ptr.as_ptr()).layout);
+            // the counts are plain atomics under std, but loom's need dropping
+            ptr::drop_in_place(&raw mut (*self.ptr.as_ptr()).strong);
+            ptr::drop_in_place(&raw mut (*self.ptr.as_ptr()).weak);
+            alloc::dealloc(self.ptr.as_ptr().cast(), layout);
+        }
+    }
+}
+
+impl<T: ?Sized> fmt::Debug for Weak<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("(Weak)")
+    }
+}
+
+#[cfg(all(test, not(loom)))]
+mod tests {
+    use super::*;
+    use std::sync::atomic::AtomicUsize;
+    use std::thread;
+
+    /// Counts its drops.
+    #[derive(Debug)]
+    struct Tracked<'a>(&'a AtomicUsize);
+
+    impl Drop for Tracked<'_> {
+        fn drop(&mut self) {
+            self.0.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    #[test]
+    fn counts_clones_and_weaks() {
+        let drops = AtomicUsize::new(0);
+        let a = Arc::new(Tracked(&drops));
+        let b = a.clone();
+        let weak = Arc::downgrade(&a);
+        assert_eq!((Arc::strong_count(&a), Arc::weak_count(&a)), (2, 1));
+        assert_eq!((weak.strong_count(), weak.weak_count()), (2, 1));
+        assert!(Arc::ptr_eq(&a, &b));
+        assert!(!Arc::ptr_eq(&a, &Arc::new(Tracked(&drops))));
+        assert_eq!(drops.load(Ordering::Relaxed), 1);
+
+        drop(a);
+        let upgraded = weak.upgrade().unwrap();
+        assert_eq!(Arc::strong_count(&upgraded), 2);
+        drop((b, upgraded));
+        assert_eq!(drops.load(Ordering::Relaxed), 2);
+        assert!(weak.upgrade().is_none());
+        assert_eq!((weak.strong_count(), weak.weak_count()), (0, 0));
+    }
+
+    #[test]
+    fn new_weaks_never_upgrade() {
+        let weak = Weak::<String>::new();
+        assert!(weak.upgrade().is_none());
+        assert_eq!((weak.strong_count(), weak.weak_count()), (0, 0));
+        assert!(weak.ptr_eq(&weak.clone()));
+    }
+
+    #[test]
+    fn get_mut_only_when_unique() {
+        let mut a = Arc::new(1);
+        *Arc::get_mut(&mut a).unwrap() += 1;
+        let b = a.clone();
+        assert!(Arc::get_mut(&mut a).is_none());
+        drop(b);
+        let weak = Arc::downgrade(&a);
+        assert!(Arc::get_mut(&mut a).is_none());
+        drop(weak);
+        assert_eq!(Arc::get_mut(&mut a), Some(&mut 2));
+    }
+
+    #[test]
+    fn make_mut_clones_only_when_shared() {
+        let mut a = Arc::new(vec![1]);
+        let before = a.as_ptr();
+        Arc::make_mut(&mut a).push(2);
+        assert_eq!(a.as_ptr(), before);
+
+        let b = a.clone();
+        Arc::make_mut(&mut a).push(3);
+        assert_eq!((&*a, &*b), (&vec![1, 2, 3], &vec![1, 2]));
+        assert!(!Arc::ptr_eq(&a, &b));
+
+        // only a `Weak` left: the data moves and the `Weak` is cut off
+        let weak = Arc::downgrade(&a);
+        Arc::make_mut(&mut a).push(4);
+        assert!(weak.upgrade().is_none());
+        assert_eq!(*a, [1, 2, 3, 4]);
+        assert_eq!(Arc::weak_count(&a), 0);
+    }
+
+    #[test]
+    fn try_unwrap_needs_the_last_arc() {
+        let a = Arc::new(String::from("only"));
+        let b = a.clone();
+        let a = Arc::try_unwrap(a).unwrap_err();
+        drop(b);
+        let weak = Arc::downgrade(&a);
+        assert_eq!(Arc::try_unwrap(a).unwrap(), "only");
+        assert!(weak.upgrade().is_none());
+    }
+
+    #[test]
+    fn holds_slices_and_strs() {
+        let drops = AtomicUsize::new(0);
+        let items: Arc<[Tracked]> = Arc::from((0..3).map(|_| Tracked(&drops)).collect::<Vec<_>>());
+        assert_eq!(items.len(), 3);
+        let weak = Arc::downgrade(&items);
+        drop(items.clone());
+        drop(items);
+        assert_eq!(drops.load(Ordering::Relaxed), 3);
+        drop(weak);
+
+        let empty: Arc<[u64]> = Arc::from(&[][..]);
+        assert!(empty.is_empty());
+        let s: Arc<str> = Arc::from("hello");
+        assert_eq!(&*s, "hello");
+        assert_eq!(s, Arc::from(String::from("hello")));
+        assert_eq!(format!("{s} {s:?}"), "hello \"hello\"");
+    }
+
+    #[test]
+    fn shared_across_threads() {
+        let a = Arc::new(AtomicUsize::new(0));
+        let weak = Arc::downgrade(&a);
+        let threads: Vec<_> = (0..8)
+            .map(|_| {
+                let a = a.clone();
+                let weak = weak.clone();
+                thread::spawn(move || {
+                    for _ in 0..1000 {
+                        let b = a.clone();
+                        b.fetch_add(1, Ordering::Relaxed);
+                        drop(weak.upgrade().unwrap());
+                    }
+                })
+            })
+            .collect();
+        threads.into_iter().for_each(|t| t.join().unwrap());
+        assert_eq!(a.load(Ordering::Relaxed), 8000);
+        assert_eq!((Arc::strong_count(&a), Arc::weak_count(&a)), (1, 1));
     }
 }
@@ -0,0 +1,114 @@
+//! Every interleaving of racing clones, drops and upgrades, courtesy of loom:
+//!
+//! ```sh
+//! RUSTFLAGS="--cfg loom" cargo test --release --test loom
+//! ```
+#![cfg(loom)]
+
+use loom::sync::atomic::{AtomicUsize, Ordering};
+use loom::thread;
+use my_arc::{Arc, Weak};
+
+/// Counts its drops in a counter loom can see.
+struct Tracked(loom::sync::Arc<AtomicUsize>);
+
+impl Drop for Tracked {
+    fn drop(&mut self) {
+        self.0.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+fn tracked() -> (Arc<Tracked>, loom::sync::Arc<AtomicUsize>) {
+    let drops = loom::sync::Arc::new(AtomicUsize::new(0));
+    (Arc::new(Tracked(drops.clone())), drops)
+}
+
+#[test]
+fn racing_clones_and_drops_drop_the_data_once() {
+    loom::model(|| {
+        let (a, drops) = tracked();
+        let b = a.clone();
+        let t = thread::spawn(move || {
+            let c = b.clone();
+            drop(b);
+            drop(c);
+        });
+        drop(a.clone());
+        drop(a);
+        t.join().unwrap();
+        assert_eq!(drops.load(Ordering::Relaxed), 1);
+    });
+}
+
+#[test]
+fn upgrades_race_the_last_drop() {
+    loom::model(|| {
+        let (a, drops) = tracked();
+        let weak = Arc::downgrade(&a);
+        let t = thread::spawn(move || match weak.upgrade() {
+            // if it got one, the data stays alive as long as it's held
+            Some(a) => {
+                assert_eq!(a.0.load(Ordering::Relaxed), 0);
+                true
+            }
+            None => false,
+        });
+        drop(a);
+        let upgraded = t.join().unwrap();
+        assert_eq!(drops.load(Ordering::Relaxed), 1, "upgraded: {upgraded}");
+    });
+}
+
+#[test]
+fn weak_and_strong_drops_free_the_memory_once() {
+    loom::model(|| {
+        let (a, drops) = tracked();
+        let weak = Arc::downgrade(&a);
+        let other: Weak<Tracked> = weak.clone();
+        let t = thread::spawn(move || drop(other));
+        drop(weak);
+        drop(a);
+        t.join().unwrap();
+        assert_eq!(drops.load(Ordering::Relaxed), 1);
+    });
+}
+
+#[test]
+fn get_mut_never_overlaps_an_upgrade() {
+    loom::model(|| {
+        let mut a = Arc::new(AtomicUsize::new(0));
+        let weak = Arc::downgrade(&a);
+        let t = thread::spawn(move || {
+            if let Some(a) = weak.upgrade() {
+                a.fetch_add(1, Ordering::Relaxed);
+            }
+        });
+        // unique only once the other thread's `Weak` and anything it
+        // upgraded to are gone, and then its write must be visible
+        if let Some(value) = Arc::get_mut(&mut a) {
+            let seen = value.load(Ordering::Relaxed);
+            value.store(seen + 10, Ordering::Relaxed);
+        }
+        t.join().unwrap();
+        let value = a.load(Ordering::Relaxed);
+        assert!([0, 1, 10, 11].contains(&value), "{value}");
+    });
+}
+
+#[test]
+fn make_mut_and_try_unwrap_race_a_drop() {
+    loom::model(|| {
+        let mut a = Arc::new(vec![1]);
+        let b = a.clone();
+        let t = thread::spawn(move || {
+            // the one that's left gets the data, or nobody does
+            Arc::try_unwrap(b).ok()
+        });
+        Arc::make_mut(&mut a).push(2);
+        assert_eq!(*a, [1, 2]);
+        if let Some(other) = t.join().unwrap() {
+            assert_eq!(other, [1]);
+        }
+        assert_eq!(Arc::try_unwrap(a).unwrap(), [1, 2]);
+    });
+}
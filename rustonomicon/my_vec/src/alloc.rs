@@ -0,0 +1,277 @@
+//! A stable stand-in for the unstable `std::alloc::Allocator`, the global heap
+//! behind it, and an allocator that keeps count for tests.
+
+use std::alloc::{self, Layout};
+use std::cell::Cell;
+use std::fmt;
+use std::ptr::{self, NonNull};
+use std::rc::Rc;
+
+/// The allocator couldn't hand out the memory asked for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AllocError;
+
+impl fmt::Display for AllocError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("memory allocation failed")
+    }
+}
+
+impl std::error::Error for AllocError {}
+
+/// Hands out and takes back blocks of memory, like `std::alloc::Allocator`.
+///
+/// # Safety
+///
+/// A block returned by `allocate`, `grow` or `shrink` must stay valid, and
+/// not overlap any other, until it is passed to `deallocate`, `grow` or
+/// `shrink`. Clones of an allocator must be able to free each other's blocks.
+pub unsafe trait Allocator {
+    /// A block that fits `layout`. `Vec` never asks for zero bytes.
+    fn allocate(&self, layout: Layout) -> Result<NonNull<u8>, AllocError>;
+
+    /// # Safety
+    ///
+    /// `ptr` must have come from this allocator, with `layout`.
+    unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout);
+
+    /// Moves the block at `ptr` into one that fits `new_layout`, keeping its
+    /// contents.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must have come from this allocator, with `old_layout`, and
+    /// `new_layout` may not be smaller.
+    unsafe fn grow(
+        &self,
+        ptr: NonNull<u8>,
+        old_layout: Layout,
+        new_layout: Layout,
+    ) -> Result<NonNull<u8>, AllocError> {
+        let new_ptr = self.allocate(new_layout)?;
+        ptr::copy_nonoverlapping(ptr.as_ptr(), new_ptr.as_ptr(), old_layout.size());
+        self.deallocate(ptr, old_layout);
+        Ok(new_ptr)
+    }
+
+    /// Moves the block at `ptr` into one that fits `new_layout`, keeping as
+    /// much of its contents as fit.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must have come from this allocator, with `old_layout`, and
+    /// `new_layout` may not be bigger or zero-sized.
+    unsafe fn shrink(
+        &self,
+        ptr: NonNull<u8>,
+        old_layout: Layout,
+        new_layout: Layout,
+    ) -> Result<NonNull<u8>, AllocError> {
+        let new_ptr = self.allocate(new_layout)?;
+        ptr::copy_nonoverlapping(ptr.as_ptr(), new_ptr.as_ptr(), new_layout.size());
+        self.deallocate(ptr, old_layout);
+        Ok(new_ptr)
+    }
+}
+
+unsafe impl<A: Allocator + ?Sized> Allocator for &A {
+    fn allocate(&self, layout: Layout) -> Result<NonNull<u8>, AllocError> {
+        (**self).allocate(layout)
+    }
+
+    unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+        (**self).deallocate(ptr, layout)
+    }
+
+    unsafe fn grow(
+        &self,
+        ptr: NonNull<u8>,
+        old_layout: Layout,
+        new_layout: Layout,
+    ) -> Result<NonNull<u8>, AllocError> {
+        (**self).grow(ptr, old_layout, new_layout)
+    }
+
+    unsafe fn shrink(
+        &self,
+        ptr: NonNull<u8>,
+        old_layout: Layout,
+        new_layout: Layout,
+    ) -> Result<NonNull<u8>, AllocError> {
+        (**self).shrink(ptr, old_layout, new_layout)
+    }
+}
+
+/// The global heap: `alloc::alloc`, `alloc::realloc` and `alloc::dealloc`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Global;
+
+unsafe impl Allocator for Global {
+    fn allocate(&self, layout: Layout) -> Result<NonNull<u8>, AllocError> {
+        if layout.size() == 0 {
+            // nothing to allocate, any aligned address will do
+            return Ok(NonNull::new(ptr::without_provenance_mut(layout.align())).unwrap());
+        }
+        NonNull::new(unsafe { alloc::alloc(layout) }).ok_or(AllocError)
+    }
+
+    unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+        if layout.size() != 0 {
+            alloc::dealloc(ptr.as_ptr(), layout)
+        }
+    }
+
+    unsafe fn grow(
+        &self,
+        ptr: NonNull<u8>,
+        old_layout: Layout,
+        new_layout: Layout,
+    ) -> Result<NonNull<u8>, AllocError> {
+        if old_layout.size() == 0 || old_layout.align() != new_layout.align() {
+            return realloc_by_copying(self, ptr, old_layout, new_layout);
+        }
+        NonNull::new(alloc::realloc(ptr.as_ptr(), old_layout, new_layout.size())).ok_or(AllocError)
+    }
+
+    unsafe fn shrink(
+        &self,
+        ptr: NonNull<u8>,
+        old_layout: Layout,
+        new_layout: Layout,
+    ) -> Result<NonNull<u8>, AllocError> {
+        if old_layout.align() != new_layout.align() {
+            return realloc_by_copying(self, ptr, old_layout, new_layout);
+        }
+        NonNull::new(alloc::realloc(ptr.as_ptr(), old_layout, new_layout.size())).ok_or(AllocError)
+    }
+}
+
+/// For what `realloc` can't do: start from nothing, or change the alignment.
+unsafe fn realloc_by_copying(
+    alloc: &impl Allocator,
+    ptr: NonNull<u8>,
+    old_layout: Layout,
+    new_layout: Layout,
+) -> Result<NonNull<u8>, AllocError> {
+    let new_ptr = alloc.allocate(new_layout)?;
+    let size = old_layout.size().min(new_layout.size());
+    ptr::copy_nonoverlapping(ptr.as_ptr(), new_ptr.as_ptr(), size);
+    alloc.deallocate(ptr, old_layout);
+    Ok(new_ptr)
+}
+
+/// What a [`Counting`] allocator has done so far.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Counts {
+    pub allocations: usize,
+    pub deallocations: usize,
+    pub grows: usize,
+    pub shrinks: usize,
+    /// Bytes handed out and not yet given back.
+    pub live_bytes: usize,
+}
+
+/// Passes everything on to `A`, counting as it goes. Clones share the counts,
+/// so a test can keep one to look at while a `Vec` owns another.
+///
+/// It can also be told to fail after a number of requests, to see what a
+/// `Vec` does when it runs out of memory.
+#[derive(Debug, Clone, Default)]
+pub struct Counting<A = Global> {
+    inner: A,
+    counts: Rc<Cell<Counts>>,
+    fail_after: Rc<Cell<Option<usize>>>,
+}
+
+impl Counting {
+    pub fn new() -> Counting {
+        Counting::default()
+    }
+}
+
+impl<A> Counting<A> {
+    pub fn wrapping(inner: A) -> Counting<A> {
+        Counting {
+            inner,
+            counts: Rc::default(),
+            fail_after: Rc::default(),
+        }
+    }
+
+    pub fn counts(&self) -> Counts {
+        self.counts.get()
+    }
+
+    /// Lets `requests` more allocations, grows or shrinks through, then
+    /// fails the rest. `None` lifts the limit.
+    pub fn fail_after(&self, requests: Option<usize>) {
+        self.fail_after.set(requests);
+    }
+
+    fn request(&self) -> Result<(), AllocError> {
+        match self.fail_after.get() {
+            Some(0) => Err(AllocError),
+            Some(n) => {
+                self.fail_after.set(Some(n - 1));
+                Ok(())
+            }
+            None => Ok(()),
+        }
+    }
+
+    fn count(&self, f: impl FnOnce(&mut Counts)) {
+        let mut counts = self.counts.get();
+        f(&mut counts);
+        self.counts.set(counts);
+    }
+}
+
+unsafe impl<A: Allocator> Allocator for Counting<A> {
+    fn allocate(&self, layout: Layout) -> Result<NonNull<u8>, AllocError> {
+        self.request()?;
+        let ptr = self.inner.allocate(layout)?;
+        self.count(|counts| {
+            counts.allocations += 1;
+            counts.live_bytes += layout.size();
+        });
+        Ok(ptr)
+    }
+
+    unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+        self.inner.deallocate(ptr, layout);
+        self.count(|counts| {
+            counts.deallocations += 1;
+            counts.live_bytes -= layout.size();
+        });
+    }
+
+    unsafe fn grow(
+        &self,
+        ptr: NonNull<u8>,
+        old_layout: Layout,
+        new_layout: Layout,
+    ) -> Result<NonNull<u8>, AllocError> {
+        self.request()?;
+        let ptr = self.inner.grow(ptr, old_layout, new_layout)?;
+        self.count(|counts| {
+            counts.grows += 1;
+            counts.live_bytes += new_layout.size() - old_layout.size();
+        });
+        Ok(ptr)
+    }
+
+    unsafe fn shrink(
+        &self,
+        ptr: NonNull<u8>,
+        old_layout: Layout,
+        new_layout: Layout,
+    ) -> Result<NonNull<u8>, AllocError> {
+        self.request()?;
+        let ptr = self.inner.shrink(ptr, old_layout, new_layout)?;
+        self.count(|counts| {
+            counts.shrinks += 1;
+            counts.live_bytes -= old_layout.size() - new_layout.size();
+        });
+        Ok(ptr)
+    }
+}
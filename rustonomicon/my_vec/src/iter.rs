@@ -0,0 +1,211 @@
+//! The iterators that move items out of a `Vec`.
+
+use std::fmt;
+use std::iter::FusedIterator;
+use std::ptr;
+
+use crate::alloc::{Allocator, Global};
+use crate::raw::{handle_reserve, RawValIter, RawVec};
+use crate::Vec;
+
+pub struct IntoIter<T, A: Allocator = Global> {
+    pub(crate) _buf: RawVec<T, A>, // we don't actually care about this. Just need it to live.
+    pub(crate) iter: RawValIter<T>,
+}
+
+impl<T, A: Allocator> IntoIter<T, A> {
+    /// The items not yet yielded.
+    pub fn as_slice(&self) -> &[T] {
+        self.iter.as_slice()
+    }
+}
+
+impl<T: fmt::Debug, A: Allocator> fmt::Debug for IntoIter<T, A> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("IntoIter").field(&self.as_slice()).finish()
+    }
+}
+
+impl<T, A: Allocator> Iterator for IntoIter<T, A> {
+    type Item = T;
+    fn next(&mut self) -> Option<T> {
+        self.iter.next()
+    }
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.iter.size_hint()
+    }
+}
+
+impl<T, A: Allocator> DoubleEndedIterator for IntoIter<T, A> {
+    fn next_back(&mut self) -> Option<T> {
+        self.iter.next_back()
+    }
+}
+
+impl<T, A: Allocator> ExactSizeIterator for IntoIter<T, A> {}
+impl<T, A: Allocator> FusedIterator for IntoIter<T, A> {}
+
+impl<T, A: Allocator> Drop for IntoIter<T, A> {
+    fn drop(&mut self) {
+        // the buffer is freed afterwards even if an item panics
+        self.iter.drop_remaining();
+    }
+}
+
+/// Created by [`Vec::drain`]. Whatever is left of the range when it's dropped
+/// is dropped with it, and the items after the range move up to close the gap.
+pub struct Drain<'a, T: 'a, A: Allocator + 'a = Global> {
+    /// Its `len` is the start of the range for as long as the `Drain` lives,
+    /// so forgetting the `Drain` merely leaks the tail.
+    pub(crate) vec: &'a mut Vec<T, A>,
+    pub(crate) iter: RawValIter<T>,
+    pub(crate) tail_start: usize,
+    pub(crate) tail_len: usize,
+}
+
+impl<T, A: Allocator> Drain<'_, T, A> {
+    /// The items not yet yielded.
+    pub fn as_slice(&self) -> &[T] {
+        self.iter.as_slice()
+    }
+
+    /// Writes items from `replace_with` into the gap left by the drained
+    /// range, saying whether it was filled.
+    fn fill(&mut self, replace_with: &mut impl Iterator<Item = T>) -> bool {
+        while self.vec.len < self.tail_start {
+            let Some(item) = replace_with.next() else {
+                return false;
+            };
+            unsafe { ptr::write(self.vec.ptr().add(self.vec.len), item) };
+            self.vec.len += 1;
+        }
+        true
+    }
+
+    /// Moves the tail `additional` places further back.
+    fn move_tail(&mut self, additional: usize) {
+        let used = self.tail_start + self.tail_len;
+        handle_reserve(self.vec.buf.try_reserve(used, additional));
+        unsafe {
+            let src = self.vec.ptr().add(self.tail_start);
+            ptr::copy(src, src.add(additional), self.tail_len);
+        }
+        self.tail_start += additional;
+    }
+}
+
+impl<T: fmt::Debug, A: Allocator> fmt::Debug for Drain<'_, T, A> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("Drain").field(&self.as_slice()).finish()
+    }
+}
+
+impl<T, A: Allocator> Iterator for Drain<'_, T, A> {
+    type Item = T;
+    fn next(&mut self) -> Option<T> {
+        self.iter.next()
+    }
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.iter.size_hint()
+    }
+}
+
+impl<T, A: Allocator> DoubleEndedIterator for Drain<'_, T, A> {
+    fn next_back(&mut self) -> Option<T> {
+        self.iter.next_back()
+    }
+}
+
+impl<T, A: Allocator> ExactSizeIterator for Drain<'_, T, A> {}
+impl<T, A: Allocator> FusedIterator for Drain<'_, T, A> {}
+
+impl<T, A: Allocator> Drop for Drain<'_, T, A> {
+    fn drop(&mut self) {
+        /// Closes the gap even if dropping what's left of the range panics.
+        struct MoveTail<'r, 'a, T, A: Allocator>(&'r mut Drain<'a, T, A>);
+
+        impl<T, A: Allocator> Drop for MoveTail<'_, '_, T, A> {
+            fn drop(&mut self) {
+                let drain = &mut *self.0;
+                let start = drain.vec.len;
+                if drain.tail_start != start {
+                    unsafe {
+                        let ptr = drain.vec.ptr();
+                        ptr::copy(ptr.add(drain.tail_start), ptr.add(start), drain.tail_len);
+                    }
+                }
+                drain.vec.len = start + drain.tail_len;
+            }
+        }
+
+        let guard = MoveTail(self);
+        guard.0.iter.drop_remaining();
+    }
+}
+
+/// Created by [`Vec::splice`]. Yields the removed items; the replacements go
+/// in when it's dropped.
+pub struct Splice<'a, I: Iterator + 'a, A: Allocator + 'a = Global> {
+    pub(crate) drain: Drain<'a, I::Item, A>,
+    pub(crate) replace_with: I,
+}
+
+impl<I: Iterator, A: Allocator> fmt::Debug for Splice<'_, I, A>
+where
+    I::Item: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("Splice")
+            .field(&self.drain.as_slice())
+            .finish()
+    }
+}
+
+impl<I: Iterator, A: Allocator> Iterator for Splice<'_, I, A> {
+    type Item = I::Item;
+    fn next(&mut self) -> Option<I::Item> {
+        self.drain.next()
+    }
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.drain.size_hint()
+    }
+}
+
+impl<I: Iterator, A: Allocator> DoubleEndedIterator for Splice<'_, I, A> {
+    fn next_back(&mut self) -> Option<I::Item> {
+        self.drain.next_back()
+    }
+}
+
+impl<I: Iterator, A: Allocator> ExactSizeIterator for Splice<'_, I, A> {}
+
+impl<I: Iterator, A: Allocator> Drop for Splice<'_, I, A> {
+    fn drop(&mut self) {
+        self.drain.iter.drop_remaining();
+
+        if self.drain.tail_len == 0 {
+            self.drain.vec.extend(self.replace_with.by_ref());
+            return;
+        }
+        if !self.drain.fill(&mut self.replace_with) {
+            return;
+        }
+        // more to come: make room for as many as it promises
+        let (lower, _) = self.replace_with.size_hint();
+        if lower > 0 {
+            self.drain.move_tail(lower);
+            if !self.drain.fill(&mut self.replace_with) {
+                return;
+            }
+        }
+        // and for whatever it didn't promise
+        let mut rest: Vec<I::Item> = self.replace_with.by_ref().collect();
+        if !rest.is_empty() {
+            self.drain.move_tail(rest.len());
+            let mut rest = rest.drain(..);
+            let filled = self.drain.fill(&mut rest);
+            debug_assert!(filled);
+        }
+        // dropping the `Drain` moves the tail back up against the last one
+    }
+}
@@ -1,108 +1,142 @@
-use std::alloc::{self, Layout};
-use std::marker::PhantomData;
+//! The Rustonomicon's `Vec`, grown to the everyday half of `std::vec::Vec`'s
+//! API, with its memory coming from an [`Allocator`] of your choosing.
+
+use std::borrow::{Borrow, BorrowMut};
+use std::cmp::Ordering;
+use std::fmt;
+use std::hash::{Hash, Hasher};
 use std::mem;
-use std::ops::{Deref, DerefMut};
-use std::ptr::{self, NonNull};
+use std::ops::{Bound, Deref, DerefMut, RangeBounds};
+use std::ptr;
+
+pub mod alloc;
+mod iter;
+mod raw;
+
+pub use alloc::{AllocError, Allocator, Counting, Counts, Global};
+pub use iter::{Drain, IntoIter, Splice};
+pub use raw::TryReserveError;
+
+use raw::{handle_reserve, RawValIter, RawVec};
 
-struct RawVec<T> {
-    ptr: NonNull<T>,
-    cap: usize,
+pub struct Vec<T, A: Allocator = Global> {
+    buf: RawVec<T, A>,
+    len: usize,
 }
 
-unsafe impl<T: Send> Send for RawVec<T> {}
-unsafe impl<T: Sync> Sync for RawVec<T> {}
+impl<T> Vec<T> {
+    pub fn new() -> Self {
+        Vec::new_in(Global)
+    }
+
+    pub fn with_capacity(capacity: usize) -> Self {
+        Vec::with_capacity_in(capacity, Global)
+    }
+}
 
-impl<T> RawVec<T> {
-    fn new() -> Self {
-        // !0 is usize::MAX. This branch should be stripped at compile time.
-        let cap = if mem::size_of::<T>() == 0 { !0 } else { 0 };
+impl<T, A: Allocator> Vec<T, A> {
+    fn ptr(&self) -> *mut T {
+        self.buf.ptr.as_ptr()
+    }
 
-        // `NonNull::dangling()` doubles as "unallocated" and "zero-sized allocation"
-        RawVec {
-            ptr: NonNull::dangling(),
-            cap,
+    fn cap(&self) -> usize {
+        self.buf.cap
+    }
+
+    pub fn new_in(alloc: A) -> Self {
+        Vec {
+            buf: RawVec::new_in(alloc),
+            len: 0,
         }
     }
 
-    fn grow(&mut self) {
-        // since we set the capacity to usize::MAX when T has size 0,
-        // getting to here necessarily means the Vec is overfull.
-        assert!(mem::size_of::<T>() != 0, "capacity overflow");
+    /// # Panics
+    ///
+    /// If `capacity` items would take more than `isize::MAX` bytes.
+    pub fn with_capacity_in(capacity: usize, alloc: A) -> Self {
+        Vec {
+            buf: RawVec::with_capacity_in(capacity, alloc),
+            len: 0,
+        }
+    }
 
-        let (new_cap, new_layout) = if self.cap == 0 {
-            (1, Layout::array::<T>(1).unwrap())
-        } else {
-            // This can't overflow because we ensure self.cap <= isize::MAX.
-            let new_cap = 2 * self.cap;
-
-            // `Layout::array` checks that the number of bytes is <= usize::MAX,
-            // but this is redundant since old_layout.size() <= isize::MAX,
-            // so the `unwrap` should never fail.
-            let new_layout = Layout::array::<T>(new_cap).unwrap();
-            (new_cap, new_layout)
-        };
+    pub fn allocator(&self) -> &A {
+        &self.buf.alloc
+    }
 
-        // Ensure that the new allocation doesn't exceed `isize::MAX` bytes.
-        assert!(
-            new_layout.size() <= isize::MAX as usize,
-            "Allocation too large"
-        );
+    /// How many items fit before the buffer has to grow; `usize::MAX` for
+    /// zero-sized types, which never need one.
+    pub fn capacity(&self) -> usize {
+        self.cap()
+    }
 
-        let new_ptr = if self.cap == 0 {
-            unsafe { alloc::alloc(new_layout) }
-        } else {
-            let old_layout = Layout::array::<T>(self.cap).unwrap();
-            let old_ptr = self.ptr.as_ptr() as *mut u8;
-            unsafe { alloc::realloc(old_ptr, old_layout, new_layout.size()) }
-        };
+    pub fn len(&self) -> usize {
+        self.len
+    }
 
-        // If allocation fails, `new_ptr` will be null, in which case we abort.
-        self.ptr = match NonNull::new(new_ptr as *mut T) {
-            Some(p) => p,
-            None => alloc::handle_alloc_error(new_layout),
-        };
-        self.cap = new_cap;
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
     }
-}
 
-impl<T> Drop for RawVec<T> {
-    fn drop(&mut self) {
-        let elem_size = mem::size_of::<T>();
+    pub fn as_ptr(&self) -> *const T {
+        self.ptr()
+    }
 
-        if self.cap != 0 && elem_size != 0 {
-            unsafe {
-                alloc::dealloc(
-                    self.ptr.as_ptr() as *mut u8,
-                    Layout::array::<T>(self.cap).unwrap(),
-                );
-            }
-        }
+    pub fn as_mut_ptr(&mut self) -> *mut T {
+        self.ptr()
     }
-}
 
-pub struct Vec<T> {
-    buf: RawVec<T>,
-    len: usize,
-}
+    pub fn as_slice(&self) -> &[T] {
+        self
+    }
 
-impl<T> Vec<T> {
-    fn ptr(&self) -> *mut T {
-        self.buf.ptr.as_ptr()
+    pub fn as_mut_slice(&mut self) -> &mut [T] {
+        self
     }
 
-    fn cap(&self) -> usize {
-        self.buf.cap
+    /// Makes room for at least `additional` more items, growing the buffer
+    /// more than that to keep pushes cheap.
+    ///
+    /// # Panics
+    ///
+    /// If the capacity would take more than `isize::MAX` bytes.
+    pub fn reserve(&mut self, additional: usize) {
+        handle_reserve(self.try_reserve(additional));
     }
 
-    pub fn new() -> Self {
-        Vec {
-            buf: RawVec::new(),
-            len: 0,
-        }
+    /// Makes room for exactly `additional` more items.
+    ///
+    /// # Panics
+    ///
+    /// If the capacity would take more than `isize::MAX` bytes.
+    pub fn reserve_exact(&mut self, additional: usize) {
+        handle_reserve(self.try_reserve_exact(additional));
+    }
+
+    /// [`reserve`](Vec::reserve), reporting failure instead of panicking or
+    /// aborting.
+    pub fn try_reserve(&mut self, additional: usize) -> Result<(), TryReserveError> {
+        self.buf.try_reserve(self.len, additional)
     }
+
+    pub fn try_reserve_exact(&mut self, additional: usize) -> Result<(), TryReserveError> {
+        self.buf.try_reserve_exact(self.len, additional)
+    }
+
+    /// Gives back the capacity the items don't need.
+    pub fn shrink_to_fit(&mut self) {
+        self.buf.shrink_to(self.len);
+    }
+
+    /// Gives back the capacity past `min_capacity`, or past what the items
+    /// need if that's more.
+    pub fn shrink_to(&mut self, min_capacity: usize) {
+        self.buf.shrink_to(self.len.max(min_capacity));
+    }
+
     pub fn push(&mut self, elem: T) {
         if self.len == self.cap() {
-            self.buf.grow();
+            self.reserve(1);
         }
 
         unsafe {
@@ -125,7 +159,7 @@ impl<T> Vec<T> {
     pub fn insert(&mut self, index: usize, elem: T) {
         assert!(index <= self.len, "index out of bounds");
         if self.len == self.cap() {
-            self.buf.grow();
+            self.reserve(1);
         }
 
         unsafe {
@@ -156,174 +190,812 @@ impl<T> Vec<T> {
         }
     }
 
-    pub fn drain(&mut self) -> Drain<T> {
-        let iter = unsafe { RawValIter::new(&self) };
+    /// Removes the item at `index`, putting the last one in its place.
+    pub fn swap_remove(&mut self, index: usize) -> T {
+        assert!(index < self.len, "index out of bounds");
 
-        // this is a mem::forget safety thing. If Drain is forgotten, we just
-        // leak the whole Vec's contents. Also we need to do this *eventually*
-        // anyway, so why not do it now?
+        self.len -= 1;
+
+        unsafe {
+            let result = ptr::read(self.ptr().add(index));
+            ptr::copy(self.ptr().add(self.len), self.ptr().add(index), 1);
+            result
+        }
+    }
+
+    /// Drops the items from `len` on, keeping the capacity.
+    pub fn truncate(&mut self, len: usize) {
+        if len >= self.len {
+            return;
+        }
+        let tail = ptr::slice_from_raw_parts_mut(unsafe { self.ptr().add(len) }, self.len - len);
+        // shorten first: if an item panics on drop, the slice's drop glue
+        // still drops the others and we don't drop any of them twice
+        self.len = len;
+        unsafe { ptr::drop_in_place(tail) };
+    }
+
+    pub fn clear(&mut self) {
+        self.truncate(0);
+    }
+
+    pub fn resize_with(&mut self, new_len: usize, mut f: impl FnMut() -> T) {
+        if new_len <= self.len {
+            self.truncate(new_len);
+            return;
+        }
+        self.reserve(new_len - self.len);
+        while self.len < new_len {
+            self.push(f());
+        }
+    }
+
+    /// Keeps the items `f` says yes to, in order.
+    pub fn retain(&mut self, mut f: impl FnMut(&T) -> bool) {
+        self.retain_mut(|elem| f(elem));
+    }
+
+    pub fn retain_mut(&mut self, mut f: impl FnMut(&mut T) -> bool) {
+        let original_len = self.len;
+        // if `f` or a drop panics, the guard puts the survivors and the
+        // unchecked items back together
+        struct Guard<'a, T, A: Allocator> {
+            vec: &'a mut Vec<T, A>,
+            checked: usize,
+            removed: usize,
+            original_len: usize,
+        }
+
+        impl<T, A: Allocator> Drop for Guard<'_, T, A> {
+            fn drop(&mut self) {
+                if self.removed > 0 {
+                    unsafe {
+                        let ptr = self.vec.ptr();
+                        ptr::copy(
+                            ptr.add(self.checked),
+                            ptr.add(self.checked - self.removed),
+                            self.original_len - self.checked,
+                        );
+                    }
+                }
+                self.vec.len = self.original_len - self.removed;
+            }
+        }
+
+        // nothing is visible through the `Vec` while items are moving
         self.len = 0;
+        let mut guard = Guard {
+            vec: self,
+            checked: 0,
+            removed: 0,
+            original_len,
+        };
+        while guard.checked < original_len {
+            let ptr = guard.vec.ptr();
+            let current = unsafe { &mut *ptr.add(guard.checked) };
+            if f(current) {
+                if guard.removed > 0 {
+                    unsafe {
+                        ptr::copy_nonoverlapping(current, ptr.add(guard.checked - guard.removed), 1)
+                    };
+                }
+                guard.checked += 1;
+            } else {
+                guard.checked += 1;
+                guard.removed += 1;
+                unsafe { ptr::drop_in_place(current) };
+            }
+        }
+    }
+
+    /// Removes all but the first of each run of items `same_bucket` calls the
+    /// same. It's given the item in question first, then the one kept before it.
+    pub fn dedup_by(&mut self, mut same_bucket: impl FnMut(&mut T, &mut T) -> bool) {
+        let len = self.len;
+        if len <= 1 {
+            return;
+        }
+        // like `retain_mut`: `read` is the next item to look at, everything
+        // before `write` is kept
+        struct Guard<'a, T, A: Allocator> {
+            vec: &'a mut Vec<T, A>,
+            read: usize,
+            write: usize,
+            len: usize,
+        }
+
+        impl<T, A: Allocator> Drop for Guard<'_, T, A> {
+            fn drop(&mut self) {
+                unsafe {
+                    let ptr = self.vec.ptr();
+                    ptr::copy(
+                        ptr.add(self.read),
+                        ptr.add(self.write),
+                        self.len - self.read,
+                    );
+                }
+                self.vec.len = self.write + self.len - self.read;
+            }
+        }
+
+        self.len = 0;
+        let mut guard = Guard {
+            vec: self,
+            read: 1,
+            write: 1,
+            len,
+        };
+        let ptr = guard.vec.ptr();
+        while guard.read < len {
+            unsafe {
+                let current = ptr.add(guard.read);
+                let kept = ptr.add(guard.write - 1);
+                if same_bucket(&mut *current, &mut *kept) {
+                    guard.read += 1;
+                    ptr::drop_in_place(current);
+                } else {
+                    ptr::copy(current, ptr.add(guard.write), 1);
+                    guard.write += 1;
+                    guard.read += 1;
+                }
+            }
+        }
+    }
+
+    pub fn dedup_by_key<K: PartialEq>(&mut self, mut key: impl FnMut(&mut T) -> K) {
+        self.dedup_by(|a, b| key(a) == key(b))
+    }
+
+    /// Removes consecutive repeats.
+    pub fn dedup(&mut self)
+    where
+        T: PartialEq,
+    {
+        self.dedup_by(|a, b| a == b)
+    }
+
+    /// Moves all of `other`'s items onto the end of this one.
+    pub fn append<B: Allocator>(&mut self, other: &mut Vec<T, B>) {
+        let count = other.len;
+        self.reserve(count);
+        unsafe {
+            ptr::copy_nonoverlapping(other.ptr(), self.ptr().add(self.len), count);
+        }
+        other.len = 0;
+        self.len += count;
+    }
+
+    /// Splits off the items from `at` on, into a `Vec` of their own.
+    pub fn split_off(&mut self, at: usize) -> Vec<T, A>
+    where
+        A: Clone,
+    {
+        assert!(at <= self.len, "index out of bounds");
+        let count = self.len - at;
+        let mut other = Vec::with_capacity_in(count, self.allocator().clone());
+        unsafe {
+            ptr::copy_nonoverlapping(self.ptr().add(at), other.ptr(), count);
+        }
+        self.len = at;
+        other.len = count;
+        other
+    }
+
+    /// Removes the items in `range`, yielding them one at a time.
+    ///
+    /// # Panics
+    ///
+    /// If `range` doesn't fit in the `Vec`.
+    pub fn drain(&mut self, range: impl RangeBounds<usize>) -> Drain<'_, T, A> {
+        let (start, end) = bounds(range, self.len);
+        let len = self.len;
+        let iter = unsafe { RawValIter::new(self.ptr().add(start), end - start) };
+
+        // this is a mem::forget safety thing. If Drain is forgotten, we just
+        // leak the tail and whatever is left of the range.
+        self.len = start;
 
         Drain {
+            vec: self,
             iter,
-            vec: PhantomData,
+            tail_start: end,
+            tail_len: len - end,
+        }
+    }
+
+    /// Replaces the items in `range` by those of `replace_with`, yielding the
+    /// ones it removes. The replacements go in when the `Splice` is dropped.
+    ///
+    /// # Panics
+    ///
+    /// If `range` doesn't fit in the `Vec`.
+    pub fn splice<I: IntoIterator<Item = T>>(
+        &mut self,
+        range: impl RangeBounds<usize>,
+        replace_with: I,
+    ) -> Splice<'_, I::IntoIter, A> {
+        Splice {
+            drain: self.drain(range),
+            replace_with: replace_with.into_iter(),
+        }
+    }
+}
+
+impl<T: Clone, A: Allocator> Vec<T, A> {
+    pub fn extend_from_slice(&mut self, other: &[T]) {
+        self.reserve(other.len());
+        for elem in other {
+            self.push(elem.clone());
         }
     }
+
+    pub fn resize(&mut self, new_len: usize, value: T) {
+        self.resize_with(new_len, || value.clone())
+    }
+}
+
+/// `range` as a start and an end within `len`.
+fn bounds(range: impl RangeBounds<usize>, len: usize) -> (usize, usize) {
+    let start = match range.start_bound() {
+        Bound::Included(&start) => start,
+        Bound::Excluded(&start) => start.checked_add(1).expect("range start overflows"),
+        Bound::Unbounded => 0,
+    };
+    let end = match range.end_bound() {
+        Bound::Included(&end) => end.checked_add(1).expect("range end overflows"),
+        Bound::Excluded(&end) => end,
+        Bound::Unbounded => len,
+    };
+    assert!(start <= end, "range starts at {start} but ends at {end}");
+    assert!(end <= len, "range end {end} out of bounds for length {len}");
+    (start, end)
 }
 
-impl<T> Drop for Vec<T> {
+impl<T, A: Allocator> Drop for Vec<T, A> {
     fn drop(&mut self) {
-        while let Some(_) = self.pop() {}
+        // if an item panics, the slice's drop glue still drops the others
+        unsafe { ptr::drop_in_place(ptr::slice_from_raw_parts_mut(self.ptr(), self.len)) }
         // deallocation is handled by RawVec
     }
 }
 
-impl<T> Deref for Vec<T> {
+impl<T, A: Allocator> Deref for Vec<T, A> {
     type Target = [T];
     fn deref(&self) -> &[T] {
         unsafe { std::slice::from_raw_parts(self.ptr(), self.len) }
     }
 }
 
-impl<T> DerefMut for Vec<T> {
+impl<T, A: Allocator> DerefMut for Vec<T, A> {
     fn deref_mut(&mut self) -> &mut [T] {
         unsafe { std::slice::from_raw_parts_mut(self.ptr(), self.len) }
     }
 }
 
-impl<T> IntoIterator for Vec<T> {
+impl<T, A: Allocator> IntoIterator for Vec<T, A> {
     type Item = T;
-    type IntoIter = IntoIter<T>;
-    fn into_iter(self) -> IntoIter<T> {
-        let (iter, buf) = unsafe {
-            (RawValIter::new(&self), ptr::read(&self.buf))
-        };
+    type IntoIter = IntoIter<T, A>;
+    fn into_iter(self) -> IntoIter<T, A> {
+        let (iter, buf) = unsafe { (RawValIter::new(self.ptr(), self.len), ptr::read(&self.buf)) };
 
         mem::forget(self);
 
-        IntoIter {
-            iter,
-            _buf: buf,
-        }
+        IntoIter { iter, _buf: buf }
     }
 }
 
-struct RawValIter<T> {
-    start: *const T,
-    end: *const T,
+impl<'a, T, A: Allocator> IntoIterator for &'a Vec<T, A> {
+    type Item = &'a T;
+    type IntoIter = std::slice::Iter<'a, T>;
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
 }
 
-impl<T> RawValIter<T> {
-    unsafe fn new(slice: &[T]) -> Self {
-        RawValIter {
-            start: slice.as_ptr(),
-            end: if mem::size_of::<T>() == 0 {
-                ((slice.as_ptr() as usize) + slice.len()) as *const _
-            } else if slice.len() == 0 {
-                slice.as_ptr()
-            } else {
-                slice.as_ptr().add(slice.len())
-            },
-        }
+impl<'a, T, A: Allocator> IntoIterator for &'a mut Vec<T, A> {
+    type Item = &'a mut T;
+    type IntoIter = std::slice::IterMut<'a, T>;
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter_mut()
     }
 }
 
-impl<T> Iterator for RawValIter<T> {
-    type Item = T;
-    fn next(&mut self) -> Option<T> {
-        if self.start == self.end {
-            None
-        } else {
-            unsafe {
-                if mem::size_of::<T>() == 0 {
-                    self.start = (self.start as usize + 1) as *const _;
-                    Some(ptr::read(NonNull::<T>::dangling().as_ptr()))
-                } else {
-                    let old_ptr = self.start;
-                    self.start = self.start.offset(1);
-                    Some(ptr::read(old_ptr))
-                }
-            }
+impl<T, A: Allocator> Extend<T> for Vec<T, A> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        let iter = iter.into_iter();
+        self.reserve(iter.size_hint().0);
+        for elem in iter {
+            self.push(elem);
         }
     }
+}
 
-    fn size_hint(&self) -> (usize, Option<usize>) {
-        let elem_size = mem::size_of::<T>();
-        let len = (self.end as usize - self.start as usize)
-                  / if elem_size == 0 { 1 } else { elem_size };
-        (len, Some(len))
+impl<'a, T: Copy + 'a, A: Allocator> Extend<&'a T> for Vec<T, A> {
+    fn extend<I: IntoIterator<Item = &'a T>>(&mut self, iter: I) {
+        self.extend(iter.into_iter().copied())
     }
 }
 
-impl<T> DoubleEndedIterator for RawValIter<T> {
-    fn next_back(&mut self) -> Option<T> {
-        if self.start == self.end {
-            None
-        } else {
-            unsafe {
-                if mem::size_of::<T>() == 0 {
-                    self.end = (self.end as usize - 1) as *const _;
-                    Some(ptr::read(NonNull::<T>::dangling().as_ptr()))
-                } else {
-                    self.end = self.end.offset(-1);
-                    Some(ptr::read(self.end))
-                }
-            }
-        }
+impl<T> FromIterator<T> for Vec<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let mut vec = Vec::new();
+        vec.extend(iter);
+        vec
     }
 }
 
-pub struct IntoIter<T> {
-    _buf: RawVec<T>, // we don't actually care about this. Just need it to live.
-    iter: RawValIter<T>,
+impl<T> Default for Vec<T> {
+    fn default() -> Self {
+        Vec::new()
+    }
 }
 
-impl<T> Iterator for IntoIter<T> {
-    type Item = T;
-    fn next(&mut self) -> Option<T> {
-        self.iter.next()
+impl<T: Clone, A: Allocator + Clone> Clone for Vec<T, A> {
+    fn clone(&self) -> Self {
+        let mut vec = Vec::with_capacity_in(self.len, self.allocator().clone());
+        vec.extend_from_slice(self);
+        vec
     }
-    fn size_hint(&self) -> (usize, Option<usize>) {
-        self.iter.size_hint()
+}
+
+impl<T: fmt::Debug, A: Allocator> fmt::Debug for Vec<T, A> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
     }
 }
 
-impl<T> DoubleEndedIterator for IntoIter<T> {
-    fn next_back(&mut self) -> Option<T> {
-        self.iter.next_back()
+impl<T: Clone> From<&[T]> for Vec<T> {
+    fn from(slice: &[T]) -> Self {
+        let mut vec = Vec::with_capacity(slice.len());
+        vec.extend_from_slice(slice);
+        vec
     }
 }
 
-impl<T> Drop for IntoIter<T> {
-    fn drop(&mut self) {
-        for _ in &mut *self {}
+impl<T, const N: usize> From<[T; N]> for Vec<T> {
+    fn from(array: [T; N]) -> Self {
+        Vec::from_iter(array)
     }
 }
 
-pub struct Drain<'a, T: 'a> {
-    vec: PhantomData<&'a mut Vec<T>>,
-    iter: RawValIter<T>,
+impl<T, A: Allocator, B: Allocator, U> PartialEq<Vec<U, B>> for Vec<T, A>
+where
+    T: PartialEq<U>,
+{
+    fn eq(&self, other: &Vec<U, B>) -> bool {
+        self[..] == other[..]
+    }
 }
 
-impl<'a, T> Iterator for Drain<'a, T> {
-    type Item = T;
-    fn next(&mut self) -> Option<T> {
-        self.iter.next()
+impl<T: PartialEq<U>, A: Allocator, U> PartialEq<[U]> for Vec<T, A> {
+    fn eq(&self, other: &[U]) -> bool {
+        self[..] == *other
     }
-    fn size_hint(&self) -> (usize, Option<usize>) {
-        self.iter.size_hint()
+}
+
+impl<T: PartialEq<U>, A: Allocator, U> PartialEq<&[U]> for Vec<T, A> {
+    fn eq(&self, other: &&[U]) -> bool {
+        self[..] == **other
     }
 }
 
-impl<'a, T> DoubleEndedIterator for Drain<'a, T> {
-    fn next_back(&mut self) -> Option<T> {
-        self.iter.next_back()
+impl<T: PartialEq<U>, A: Allocator, U, const N: usize> PartialEq<[U; N]> for Vec<T, A> {
+    fn eq(&self, other: &[U; N]) -> bool {
+        self[..] == other[..]
     }
 }
 
-impl<'a, T> Drop for Drain<'a, T> {
-    fn drop(&mut self) {
-        // pre-drain the iter
-        for _ in &mut *self {}
+impl<T: Eq, A: Allocator> Eq for Vec<T, A> {}
+
+impl<T: PartialOrd, A: Allocator> PartialOrd for Vec<T, A> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        (**self).partial_cmp(&**other)
+    }
+}
+
+impl<T: Ord, A: Allocator> Ord for Vec<T, A> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (**self).cmp(&**other)
+    }
+}
+
+impl<T: Hash, A: Allocator> Hash for Vec<T, A> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        (**self).hash(state)
+    }
+}
+
+impl<T, A: Allocator> AsRef<[T]> for Vec<T, A> {
+    fn as_ref(&self) -> &[T] {
+        self
+    }
+}
+
+impl<T, A: Allocator> AsMut<[T]> for Vec<T, A> {
+    fn as_mut(&mut self) -> &mut [T] {
+        self
+    }
+}
+
+impl<T, A: Allocator> Borrow<[T]> for Vec<T, A> {
+    fn borrow(&self) -> &[T] {
+        self
+    }
+}
+
+impl<T, A: Allocator> BorrowMut<[T]> for Vec<T, A> {
+    fn borrow_mut(&mut self) -> &mut [T] {
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+    use std::panic::{self, AssertUnwindSafe};
+    use std::rc::Rc;
+
+    /// Small enough for Miri, big enough to grow a few times.
+    const MANY: usize = if cfg!(miri) { 50 } else { 1000 };
+
+    /// Counts its drops, and panics in one if told to.
+    #[derive(Debug, Clone)]
+    struct Tracked {
+        id: usize,
+        drops: Rc<Cell<usize>>,
+        panics: bool,
+    }
+
+    impl Tracked {
+        fn many(n: usize, drops: &Rc<Cell<usize>>, alloc: Counting) -> Vec<Tracked, Counting> {
+            let mut vec = Vec::new_in(alloc);
+            vec.extend((0..n).map(|id| Tracked {
+                id,
+                drops: Rc::clone(drops),
+                panics: false,
+            }));
+            vec
+        }
+    }
+
+    impl Drop for Tracked {
+        fn drop(&mut self) {
+            self.drops.set(self.drops.get() + 1);
+            if self.panics {
+                panic!("{} panicked", self.id);
+            }
+        }
+    }
+
+    fn ids<A: Allocator>(vec: &Vec<Tracked, A>) -> std::vec::Vec<usize> {
+        vec.iter().map(|tracked| tracked.id).collect()
+    }
+
+    fn quietly<R>(f: impl FnOnce() -> R) -> std::thread::Result<R> {
+        panic::catch_unwind(AssertUnwindSafe(f))
+    }
+
+    #[test]
+    fn pushes_pops_inserts_and_removes() {
+        let mut vec = Vec::new();
+        for i in 0..MANY {
+            vec.push(i);
+        }
+        assert_eq!(vec.len(), MANY);
+        assert_eq!(vec.pop(), Some(MANY - 1));
+        vec.insert(0, 42);
+        assert_eq!(vec[..3], [42, 0, 1]);
+        assert_eq!(vec.remove(1), 0);
+        assert_eq!(vec.swap_remove(0), 42);
+        assert_eq!(vec[0], MANY - 2);
+        assert_eq!(vec.len(), MANY - 2);
+        vec.clear();
+        assert!(vec.is_empty());
+        assert_eq!(vec.pop(), None);
+    }
+
+    #[test]
+    fn capacity_comes_from_the_allocator_and_goes_back() {
+        let alloc = Counting::new();
+        {
+            let mut vec = Vec::with_capacity_in(10, alloc.clone());
+            assert_eq!(vec.capacity(), 10);
+            assert_eq!(alloc.counts().allocations, 1);
+            assert_eq!(alloc.counts().live_bytes, 10 * mem::size_of::<u64>());
+
+            vec.extend(0..10u64);
+            assert_eq!(alloc.counts().grows, 0);
+            vec.push(10);
+            assert_eq!(alloc.counts().grows, 1);
+            assert!(vec.capacity() >= 20);
+
+            vec.reserve_exact(100);
+            assert_eq!(vec.capacity(), 111);
+            vec.shrink_to(50);
+            assert_eq!(vec.capacity(), 50);
+            vec.shrink_to_fit();
+            assert_eq!(vec.capacity(), 11);
+            assert_eq!(alloc.counts().shrinks, 2);
+            assert_eq!(alloc.counts().live_bytes, 11 * mem::size_of::<u64>());
+
+            vec.clear();
+            vec.shrink_to_fit();
+            assert_eq!(vec.capacity(), 0);
+            assert_eq!(alloc.counts().live_bytes, 0);
+            vec.push(1);
+        }
+        let counts = alloc.counts();
+        assert_eq!(counts.allocations, counts.deallocations);
+        assert_eq!(counts.live_bytes, 0);
+    }
+
+    #[test]
+    fn failed_reserves_leave_the_vec_alone() {
+        let alloc = Counting::new();
+        let mut vec = Vec::new_in(alloc.clone());
+        vec.extend_from_slice(&[1u32, 2, 3]);
+        assert_eq!(
+            vec.try_reserve(usize::MAX),
+            Err(TryReserveError::CapacityOverflow)
+        );
+        assert_eq!(
+            vec.try_reserve_exact(isize::MAX as usize),
+            Err(TryReserveError::CapacityOverflow)
+        );
+        alloc.fail_after(Some(0));
+        let capacity = vec.capacity();
+        assert!(matches!(
+            vec.try_reserve(100),
+            Err(TryReserveError::AllocError { .. })
+        ));
+        assert_eq!(vec.capacity(), capacity);
+        assert_eq!(vec, [1, 2, 3]);
+        alloc.fail_after(None);
+        assert_eq!(vec.try_reserve(100), Ok(()));
+    }
+
+    #[test]
+    #[should_panic(expected = "capacity overflow")]
+    fn reserving_too_much_panics() {
+        Vec::<u16>::new().reserve(usize::MAX / 2);
+    }
+
+    #[test]
+    fn truncates_resizes_and_extends() {
+        let mut vec: Vec<i32> = Vec::new();
+        vec.resize(5, 7);
+        assert_eq!(vec, [7; 5]);
+        vec.truncate(2);
+        vec.extend([1, 2].iter());
+        vec.extend_from_slice(&[3]);
+        assert_eq!(vec, [7, 7, 1, 2, 3]);
+        vec.resize_with(6, Default::default);
+        assert_eq!(vec, [7, 7, 1, 2, 3, 0]);
+        vec.resize(1, 0);
+        assert_eq!(vec, [7]);
+        vec.truncate(10);
+        assert_eq!(vec, [7]);
+    }
+
+    #[test]
+    fn retains_and_dedups() {
+        let drops = Rc::new(Cell::new(0));
+        let mut vec = Tracked::many(10, &drops, Counting::new());
+        vec.retain(|tracked| tracked.id % 3 == 0);
+        assert_eq!(ids(&vec), [0, 3, 6, 9]);
+        assert_eq!(drops.get(), 6);
+
+        let mut vec = Vec::from([1, 1, 2, 3, 3, 3, 1, 4, 4]);
+        vec.dedup();
+        assert_eq!(vec, [1, 2, 3, 1, 4]);
+        vec.dedup_by_key(|n| *n / 2);
+        assert_eq!(vec, [1, 2, 1, 4]);
+        let mut words = Vec::from(["a", "A", "b", "B", "b"]);
+        words.dedup_by(|a, b| a.eq_ignore_ascii_case(b));
+        assert_eq!(words, ["a", "b"]);
+    }
+
+    #[test]
+    fn drains_a_range() {
+        let drops = Rc::new(Cell::new(0));
+        let mut vec = Tracked::many(10, &drops, Counting::new());
+        let mut drain = vec.drain(2..6);
+        assert_eq!(drain.len(), 4);
+        assert_eq!(drain.next().unwrap().id, 2);
+        assert_eq!(drain.next_back().unwrap().id, 5);
+        drop(drain);
+        assert_eq!(drops.get(), 4);
+        assert_eq!(ids(&vec), [0, 1, 6, 7, 8, 9]);
+
+        assert_eq!(vec.drain(..).count(), 6);
+        assert!(vec.is_empty());
+        assert_eq!(drops.get(), 10);
+    }
+
+    #[test]
+    fn forgetting_a_drain_leaks_but_stays_sound() {
+        let mut vec = Vec::from([1, 2, 3, 4]);
+        mem::forget(vec.drain(1..3));
+        assert_eq!(vec, [1]);
+        vec.push(5);
+        assert_eq!(vec, [1, 5]);
+    }
+
+    #[test]
+    #[should_panic(expected = "out of bounds")]
+    fn draining_past_the_end_panics() {
+        Vec::from([1, 2]).drain(1..3);
+    }
+
+    #[test]
+    fn splices_in_more_fewer_or_unknown_numbers() {
+        let mut vec = Vec::from([0, 1, 2, 3, 4]);
+        let removed: std::vec::Vec<_> = vec.splice(1..3, [10, 11, 12, 13]).collect();
+        assert_eq!(removed, [1, 2]);
+        assert_eq!(vec, [0, 10, 11, 12, 13, 3, 4]);
+
+        vec.splice(1..5, [20]);
+        assert_eq!(vec, [0, 20, 3, 4]);
+
+        // `filter` promises nothing, so the rest goes through a buffer
+        vec.splice(..1, (0..10).filter(|n| n % 3 == 0));
+        assert_eq!(vec, [0, 3, 6, 9, 20, 3, 4]);
+
+        vec.splice(5.., []);
+        assert_eq!(vec, [0, 3, 6, 9, 20]);
+        vec.splice(5.., [1, 2]);
+        assert_eq!(vec, [0, 3, 6, 9, 20, 1, 2]);
+    }
+
+    #[test]
+    fn splits_appends_and_iterates() {
+        let alloc = Counting::new();
+        let mut vec = Vec::new_in(alloc.clone());
+        vec.extend(0..6);
+        let mut tail = vec.split_off(4);
+        assert_eq!(vec, [0, 1, 2, 3]);
+        assert_eq!(tail, [4, 5]);
+        vec.append(&mut tail);
+        assert!(tail.is_empty());
+        assert_eq!(vec, [0, 1, 2, 3, 4, 5]);
+
+        for n in &mut vec {
+            *n *= 2;
+        }
+        assert_eq!((&vec).into_iter().sum::<i32>(), 30);
+        let mut iter = vec.clone().into_iter();
+        assert_eq!(iter.next(), Some(0));
+        assert_eq!(iter.next_back(), Some(10));
+        assert_eq!(iter.as_slice(), [2, 4, 6, 8]);
+        drop(iter);
+        drop((vec, tail));
+        assert_eq!(alloc.counts().live_bytes, 0);
+    }
+
+    #[test]
+    fn compares_clones_and_prints_like_a_slice() {
+        let vec: Vec<char> = "hello".chars().collect();
+        let clone = vec.clone();
+        assert_eq!(vec, clone);
+        assert_eq!(vec, ['h', 'e', 'l', 'l', 'o']);
+        assert_eq!(vec, &['h', 'e', 'l', 'l', 'o'][..]);
+        let later = Vec::from(['w']);
+        assert!(vec < later);
+        assert_eq!(vec.cmp(&clone), Ordering::Equal);
+        assert_eq!(format!("{vec:?}"), "['h', 'e', 'l', 'l', 'o']");
+        let mut iter = vec.into_iter();
+        iter.next();
+        assert_eq!(format!("{iter:?}"), "IntoIter(['e', 'l', 'l', 'o'])");
+    }
+
+    #[test]
+    fn zero_sized_types_never_allocate() {
+        let alloc = Counting::new();
+        let mut vec = Vec::new_in(alloc.clone());
+        assert_eq!(vec.capacity(), usize::MAX);
+        for _ in 0..MANY {
+            vec.push(());
+        }
+        vec.insert(3, ());
+        vec.remove(0);
+        vec.reserve(100);
+        vec.shrink_to_fit();
+        assert_eq!(vec.len(), MANY);
+        assert_eq!(vec.drain(..10).count(), 10);
+        vec.retain(|_| false);
+        vec.resize(7, ());
+        vec.dedup();
+        assert_eq!(vec.len(), 1);
+        vec.splice(.., [(), (), ()]);
+        let mut iter = vec.into_iter();
+        assert_eq!(iter.len(), 3);
+        assert_eq!(iter.next_back(), Some(()));
+        assert_eq!(iter.count(), 2);
+        assert_eq!(alloc.counts(), Counts::default());
+    }
+
+    #[test]
+    fn zero_sized_types_are_dropped_once_each() {
+        struct Zst;
+        thread_local!(static DROPS: Cell<usize> = const { Cell::new(0) });
+        impl Drop for Zst {
+            fn drop(&mut self) {
+                DROPS.with(|drops| drops.set(drops.get() + 1));
+            }
+        }
+
+        let mut vec = Vec::new();
+        vec.extend((0..10).map(|_| Zst));
+        drop(vec.drain(2..5));
+        assert_eq!(DROPS.get(), 3);
+        let mut iter = vec.into_iter();
+        iter.next();
+        drop(iter);
+        assert_eq!(DROPS.get(), 10);
+    }
+
+    #[test]
+    fn a_panicking_drop_still_drops_the_rest_and_frees_the_buffer() {
+        let alloc = Counting::new();
+        let drops = Rc::new(Cell::new(0));
+        let mut vec = Tracked::many(5, &drops, alloc.clone());
+        vec[1].panics = true;
+        assert!(quietly(|| drop(vec)).is_err());
+        assert_eq!(drops.get(), 5);
+        assert_eq!(alloc.counts().live_bytes, 0);
+
+        let drops = Rc::new(Cell::new(0));
+        let mut vec = Tracked::many(5, &drops, alloc.clone());
+        vec[3].panics = true;
+        assert!(quietly(|| vec.truncate(1)).is_err());
+        assert_eq!(drops.get(), 4);
+        assert_eq!(ids(&vec), [0]);
+        drop(vec);
+
+        let drops = Rc::new(Cell::new(0));
+        let mut vec = Tracked::many(5, &drops, alloc.clone());
+        vec[2].panics = true;
+        let mut iter = vec.into_iter();
+        iter.next();
+        assert!(quietly(|| drop(iter)).is_err());
+        assert_eq!(drops.get(), 5);
+        assert_eq!(alloc.counts().live_bytes, 0);
+    }
+
+    #[test]
+    fn a_panicking_drain_still_closes_the_gap() {
+        let drops = Rc::new(Cell::new(0));
+        let mut vec = Tracked::many(6, &drops, Counting::new());
+        vec[2].panics = true;
+        assert!(quietly(|| drop(vec.drain(1..4))).is_err());
+        assert_eq!(drops.get(), 3);
+        assert_eq!(ids(&vec), [0, 4, 5]);
+
+        vec[1].panics = true;
+        assert!(quietly(|| drop(vec.splice(1..2, None))).is_err());
+        assert_eq!(ids(&vec), [0, 5]);
+    }
+
+    #[test]
+    fn a_panicking_retain_keeps_what_it_has_not_looked_at() {
+        let drops = Rc::new(Cell::new(0));
+        let mut vec = Tracked::many(6, &drops, Counting::new());
+        let result = quietly(|| {
+            vec.retain(|tracked| match tracked.id {
+                3 => panic!("can't decide"),
+                id => id % 2 == 0,
+            })
+        });
+        assert!(result.is_err());
+        assert_eq!(ids(&vec), [0, 2, 3, 4, 5]);
+        assert_eq!(drops.get(), 1);
+
+        vec[2].panics = true;
+        let result = quietly(|| vec.dedup_by(|a, _| a.id == 3 || a.id == 4));
+        assert!(result.is_err());
+        // 3 was dropped, and panicked, 4 wasn't looked at
+        assert_eq!(ids(&vec), [0, 2, 4, 5]);
+        assert_eq!(drops.get(), 2);
     }
 }
@@ -0,0 +1,250 @@
+//! The buffer under a `Vec`, and the by-value iterator its iterators share.
+
+use std::alloc::{self, Layout};
+use std::fmt;
+use std::mem;
+use std::ptr::{self, NonNull};
+
+use crate::alloc::{Allocator, Global};
+
+/// Why [`Vec::try_reserve`](crate::Vec::try_reserve) failed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TryReserveError {
+    /// The capacity asked for doesn't fit in `isize::MAX` bytes.
+    CapacityOverflow,
+    /// The allocator said no.
+    AllocError { layout: Layout },
+}
+
+impl fmt::Display for TryReserveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TryReserveError::CapacityOverflow => f.write_str("capacity overflow"),
+            TryReserveError::AllocError { layout } => {
+                write!(f, "failed to allocate {} bytes", layout.size())
+            }
+        }
+    }
+}
+
+impl std::error::Error for TryReserveError {}
+
+/// Turns a failed reserve into the panic or abort the infallible methods promise.
+pub(crate) fn handle_reserve(result: Result<(), TryReserveError>) {
+    match result {
+        Ok(()) => {}
+        Err(TryReserveError::CapacityOverflow) => panic!("capacity overflow"),
+        Err(TryReserveError::AllocError { layout }) => alloc::handle_alloc_error(layout),
+    }
+}
+
+pub(crate) struct RawVec<T, A: Allocator = Global> {
+    pub(crate) ptr: NonNull<T>,
+    pub(crate) cap: usize,
+    pub(crate) alloc: A,
+}
+
+unsafe impl<T: Send, A: Allocator + Send> Send for RawVec<T, A> {}
+unsafe impl<T: Sync, A: Allocator + Sync> Sync for RawVec<T, A> {}
+
+impl<T, A: Allocator> RawVec<T, A> {
+    const IS_ZST: bool = mem::size_of::<T>() == 0;
+
+    pub(crate) fn new_in(alloc: A) -> Self {
+        // !0 is usize::MAX. This branch should be stripped at compile time.
+        let cap = if Self::IS_ZST { !0 } else { 0 };
+
+        // `NonNull::dangling()` doubles as "unallocated" and "zero-sized allocation"
+        RawVec {
+            ptr: NonNull::dangling(),
+            cap,
+            alloc,
+        }
+    }
+
+    pub(crate) fn with_capacity_in(cap: usize, alloc: A) -> Self {
+        let mut buf = RawVec::new_in(alloc);
+        handle_reserve(buf.try_reserve_exact(0, cap));
+        buf
+    }
+
+    /// Makes room for `additional` more than `len`, at least doubling the
+    /// capacity when it has to grow.
+    pub(crate) fn try_reserve(
+        &mut self,
+        len: usize,
+        additional: usize,
+    ) -> Result<(), TryReserveError> {
+        if self.cap - len >= additional {
+            return Ok(());
+        }
+        // since we set the capacity to usize::MAX when T has size 0,
+        // getting to here necessarily means the Vec is overfull.
+        let required = len
+            .checked_add(additional)
+            .ok_or(TryReserveError::CapacityOverflow)?;
+        // This can't overflow because we ensure self.cap <= isize::MAX.
+        let new_cap = required.max(2 * self.cap).max(4);
+        self.grow_to(new_cap)
+    }
+
+    /// Makes room for exactly `additional` more than `len`.
+    pub(crate) fn try_reserve_exact(
+        &mut self,
+        len: usize,
+        additional: usize,
+    ) -> Result<(), TryReserveError> {
+        if self.cap - len >= additional {
+            return Ok(());
+        }
+        let required = len
+            .checked_add(additional)
+            .ok_or(TryReserveError::CapacityOverflow)?;
+        self.grow_to(required)
+    }
+
+    fn grow_to(&mut self, new_cap: usize) -> Result<(), TryReserveError> {
+        // `Layout::array` checks that the number of bytes is <= isize::MAX
+        let new_layout =
+            Layout::array::<T>(new_cap).map_err(|_| TryReserveError::CapacityOverflow)?;
+
+        let new_ptr = if self.cap == 0 {
+            self.alloc.allocate(new_layout)
+        } else {
+            let old_layout = Layout::array::<T>(self.cap).unwrap();
+            unsafe { self.alloc.grow(self.ptr.cast(), old_layout, new_layout) }
+        };
+
+        self.ptr = new_ptr
+            .map_err(|_| TryReserveError::AllocError { layout: new_layout })?
+            .cast();
+        self.cap = new_cap;
+        Ok(())
+    }
+
+    /// Gives back all the capacity past `cap`.
+    pub(crate) fn shrink_to(&mut self, cap: usize) {
+        if Self::IS_ZST || cap >= self.cap {
+            return;
+        }
+        let old_layout = Layout::array::<T>(self.cap).unwrap();
+        if cap == 0 {
+            unsafe { self.alloc.deallocate(self.ptr.cast(), old_layout) };
+            self.ptr = NonNull::dangling();
+        } else {
+            let new_layout = Layout::array::<T>(cap).unwrap();
+            let new_ptr = unsafe { self.alloc.shrink(self.ptr.cast(), old_layout, new_layout) };
+            self.ptr = match new_ptr {
+                Ok(ptr) => ptr.cast(),
+                Err(_) => alloc::handle_alloc_error(new_layout),
+            };
+        }
+        self.cap = cap;
+    }
+}
+
+impl<T, A: Allocator> Drop for RawVec<T, A> {
+    fn drop(&mut self) {
+        if self.cap != 0 && !Self::IS_ZST {
+            unsafe {
+                self.alloc
+                    .deallocate(self.ptr.cast(), Layout::array::<T>(self.cap).unwrap());
+            }
+        }
+    }
+}
+
+pub(crate) struct RawValIter<T> {
+    start: *const T,
+    end: *const T,
+}
+
+impl<T> RawValIter<T> {
+    /// # Safety
+    ///
+    /// The iterator reads the `len` items at `ptr` out, so they mustn't be
+    /// used again, and it must not outlive the memory behind them.
+    pub(crate) unsafe fn new(ptr: *mut T, len: usize) -> Self {
+        RawValIter {
+            start: ptr,
+            // for zero-sized types the pointers only count
+            end: if mem::size_of::<T>() == 0 {
+                ptr.wrapping_byte_add(len)
+            } else if len == 0 {
+                ptr
+            } else {
+                ptr.add(len)
+            },
+        }
+    }
+
+    fn len(&self) -> usize {
+        let elem_size = mem::size_of::<T>();
+        (self.end as usize - self.start as usize) / if elem_size == 0 { 1 } else { elem_size }
+    }
+
+    /// The items not yet read, which are still owned by the iterator.
+    fn remaining(&self) -> *mut [T] {
+        let start = if mem::size_of::<T>() == 0 {
+            NonNull::dangling().as_ptr()
+        } else {
+            self.start as *mut T
+        };
+        ptr::slice_from_raw_parts_mut(start, self.len())
+    }
+
+    pub(crate) fn as_slice(&self) -> &[T] {
+        unsafe { &*self.remaining() }
+    }
+
+    /// Drops the items not yet read. If one of them panics the rest are
+    /// still dropped.
+    pub(crate) fn drop_remaining(&mut self) {
+        let remaining = self.remaining();
+        self.start = self.end;
+        unsafe { ptr::drop_in_place(remaining) }
+    }
+}
+
+impl<T> Iterator for RawValIter<T> {
+    type Item = T;
+    fn next(&mut self) -> Option<T> {
+        if self.start == self.end {
+            None
+        } else {
+            unsafe {
+                if mem::size_of::<T>() == 0 {
+                    self.start = self.start.wrapping_byte_add(1);
+                    Some(ptr::read(NonNull::<T>::dangling().as_ptr()))
+                } else {
+                    let old_ptr = self.start;
+                    self.start = self.start.offset(1);
+                    Some(ptr::read(old_ptr))
+                }
+            }
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.len();
+        (len, Some(len))
+    }
+}
+
+impl<T> DoubleEndedIterator for RawValIter<T> {
+    fn next_back(&mut self) -> Option<T> {
+        if self.start == self.end {
+            None
+        } else {
+            unsafe {
+                if mem::size_of::<T>() == 0 {
+                    self.end = self.end.wrapping_byte_sub(1);
+                    Some(ptr::read(NonNull::<T>::dangling().as_ptr()))
+                } else {
+                    self.end = self.end.offset(-1);
+                    Some(ptr::read(self.end))
+                }
+            }
+        }
+    }
+}
@@ -1,12 +1,16 @@
 //! `SeaORM` Entity. Generated by sea-orm-codegen 0.12.15
 
+use async_graphql::dataloader::DataLoader;
 use async_graphql::{ComplexObject, Context, SimpleObject};
+use rocket::serde::Serialize;
 use sea_orm::entity::prelude::*;
 
-use crate::{chef, Chef};
+use crate::chef;
+use crate::schema::ChefsByBakery;
 
-#[derive(Clone, Debug, PartialEq, DeriveEntityModel, SimpleObject)]
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, SimpleObject, Serialize)]
 #[graphql(complex, name = "Bakery")]
+#[serde(crate = "rocket::serde")]
 #[sea_orm(table_name = "bakery")]
 pub struct Model {
     #[sea_orm(primary_key)]
@@ -18,10 +22,11 @@ pub struct Model {
 
 #[ComplexObject]
 impl Model {
-    async fn chefs(&self, ctx: &Context<'_>) -> Result<Vec<chef::Model>, DbErr> {
-        let db = ctx.data::<DatabaseConnection>().unwrap();
+    // Batched with the other bakeries' chefs in the same response
+    async fn chefs(&self, ctx: &Context<'_>) -> async_graphql::Result<Vec<chef::Model>> {
+        let chefs = ctx.data::<DataLoader<ChefsByBakery>>()?;
 
-        self.find_related(Chef).all(db).await
+        Ok(chefs.load_one(self.id).await?.unwrap_or_default())
     }
 }
 
@@ -1,9 +1,12 @@
 //! `SeaORM` Entity. Generated by sea-orm-codegen 0.12.15
 
 use async_graphql::SimpleObject;
+use rocket::serde::Serialize;
 use sea_orm::entity::prelude::*;
 
-#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq, SimpleObject)]
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq, SimpleObject, Serialize)]
+#[graphql(name = "Chef")]
+#[serde(crate = "rocket::serde")]
 #[sea_orm(table_name = "chef")]
 pub struct Model {
     #[sea_orm(primary_key)]
@@ -0,0 +1,4 @@
+//! The schema lives in the `migration` crate, so `sea-orm-cli migrate` and the server
+//! run the same migrations.
+
+pub use migration::{Migrator, MigratorTrait};
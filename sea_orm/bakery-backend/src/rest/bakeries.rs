@@ -0,0 +1,142 @@
+use rocket::response::status::{Created, NoContent};
+use rocket::serde::json::{self, Json};
+use rocket::serde::Deserialize;
+use rocket::State;
+use sea_orm::*;
+
+use super::problem::Problem;
+use super::validate::{self, validate};
+use crate::database_access::{self, BakeryDeletion};
+use crate::entities::{prelude::*, *};
+use crate::schema::{Changes, MutationType};
+use crate::Db;
+
+/// A bakery as it's created
+#[derive(Deserialize)]
+#[serde(crate = "rocket::serde", deny_unknown_fields)]
+pub(crate) struct NewBakery {
+    name: String,
+    #[serde(default)]
+    profit_margin: f64,
+}
+
+/// Fields of a bakery to change; those left out stay as they are
+#[derive(Deserialize)]
+#[serde(crate = "rocket::serde", deny_unknown_fields)]
+pub(crate) struct BakeryChanges {
+    name: Option<String>,
+    profit_margin: Option<f64>,
+}
+
+#[rocket::get("/bakeries")]
+async fn list(db: &State<Db>) -> Result<Json<Vec<bakery::Model>>, Problem> {
+    let bakeries = Bakery::find()
+        .order_by_asc(bakery::Column::Id)
+        .all(db.as_ref())
+        .await?;
+    Ok(Json(bakeries))
+}
+
+#[rocket::get("/bakeries/<id>")]
+async fn show(db: &State<Db>, id: i32) -> Result<Json<bakery::Model>, Problem> {
+    Bakery::find_by_id(id)
+        .one(db.as_ref())
+        .await?
+        .map(Json)
+        .ok_or_else(|| Problem::not_found("bakery", id))
+}
+
+#[rocket::post("/bakeries", data = "<body>")]
+async fn create(
+    db: &State<Db>,
+    changes: &State<Changes>,
+    body: Result<Json<NewBakery>, json::Error<'_>>,
+) -> Result<Created<Json<bakery::Model>>, Problem> {
+    let NewBakery {
+        name,
+        profit_margin,
+    } = body?.into_inner();
+    validate([
+        validate::name(&name),
+        validate::profit_margin(profit_margin),
+    ])?;
+
+    let bakery = bakery::ActiveModel {
+        name: ActiveValue::Set(name),
+        profit_margin: ActiveValue::Set(profit_margin),
+        ..Default::default()
+    }
+    .insert(db.as_ref())
+    .await?;
+    changes.bakery(MutationType::Created, bakery.id, Some(&bakery));
+    Ok(Created::new(rocket::uri!(show(bakery.id)).to_string()).body(Json(bakery)))
+}
+
+#[rocket::patch("/bakeries/<id>", data = "<body>")]
+async fn update(
+    db: &State<Db>,
+    changes: &State<Changes>,
+    id: i32,
+    body: Result<Json<BakeryChanges>, json::Error<'_>>,
+) -> Result<Json<bakery::Model>, Problem> {
+    let BakeryChanges {
+        name,
+        profit_margin,
+    } = body?.into_inner();
+    validate([
+        name.as_deref().and_then(validate::name),
+        profit_margin.and_then(validate::profit_margin),
+    ])?;
+
+    let db = db.as_ref();
+    let mut bakery = Bakery::find_by_id(id)
+        .one(db)
+        .await?
+        .ok_or_else(|| Problem::not_found("bakery", id))?
+        .into_active_model();
+    if let Some(name) = name {
+        bakery.name = ActiveValue::Set(name);
+    }
+    if let Some(profit_margin) = profit_margin {
+        bakery.profit_margin = ActiveValue::Set(profit_margin);
+    }
+    let bakery = bakery.update(db).await?;
+    changes.bakery(MutationType::Updated, id, Some(&bakery));
+    Ok(Json(bakery))
+}
+
+/// Only a bakery without chefs - they have to move or go first
+#[rocket::delete("/bakeries/<id>")]
+async fn remove(db: &State<Db>, changes: &State<Changes>, id: i32) -> Result<NoContent, Problem> {
+    match database_access::delete_bakery(db, id).await? {
+        BakeryDeletion::Deleted => {}
+        BakeryDeletion::NotFound => return Err(Problem::not_found("bakery", id)),
+        BakeryDeletion::HasChefs(chefs) => {
+            return Err(Problem::new(
+                rocket::http::Status::Conflict,
+                format!("bakery {id} still has {chefs} chefs"),
+            ))
+        }
+    }
+    changes.bakery(MutationType::Deleted, id, None);
+    Ok(NoContent)
+}
+
+#[rocket::get("/bakeries/<id>/chefs")]
+async fn chefs(db: &State<Db>, id: i32) -> Result<Json<Vec<chef::Model>>, Problem> {
+    let db = db.as_ref();
+    let bakery = Bakery::find_by_id(id)
+        .one(db)
+        .await?
+        .ok_or_else(|| Problem::not_found("bakery", id))?;
+    let chefs = bakery
+        .find_related(Chef)
+        .order_by_asc(chef::Column::Id)
+        .all(db)
+        .await?;
+    Ok(Json(chefs))
+}
+
+pub(crate) fn routes() -> Vec<rocket::Route> {
+    rocket::routes![list, show, create, update, remove, chefs]
+}
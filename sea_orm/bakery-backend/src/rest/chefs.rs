@@ -0,0 +1,140 @@
+use rocket::response::status::{Created, NoContent};
+use rocket::serde::json::{self, Json};
+use rocket::serde::{Deserialize, Deserializer};
+use rocket::State;
+use sea_orm::entity::prelude::Json as JsonValue;
+use sea_orm::*;
+
+use super::problem::Problem;
+use super::validate::{self, validate};
+use crate::entities::{prelude::*, *};
+use crate::schema::{Changes, MutationType};
+use crate::Db;
+
+/// A chef as they're hired
+#[derive(Deserialize)]
+#[serde(crate = "rocket::serde", deny_unknown_fields)]
+pub(crate) struct NewChef {
+    name: String,
+    bakery_id: i32,
+    #[serde(default)]
+    contact_details: Option<JsonValue>,
+}
+
+/// Fields of a chef to change; those left out stay as they are, and
+/// `"contact_details": null` clears them
+#[derive(Deserialize)]
+#[serde(crate = "rocket::serde", deny_unknown_fields)]
+pub(crate) struct ChefChanges {
+    name: Option<String>,
+    bakery_id: Option<i32>,
+    #[serde(default, deserialize_with = "present")]
+    contact_details: Option<JsonValue>,
+}
+
+/// `Some` whenever the field is there, `null` or not
+fn present<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<JsonValue>, D::Error> {
+    JsonValue::deserialize(deserializer).map(Some)
+}
+
+#[rocket::get("/chefs")]
+async fn list(db: &State<Db>) -> Result<Json<Vec<chef::Model>>, Problem> {
+    let chefs = Chef::find()
+        .order_by_asc(chef::Column::Id)
+        .all(db.as_ref())
+        .await?;
+    Ok(Json(chefs))
+}
+
+#[rocket::get("/chefs/<id>")]
+async fn show(db: &State<Db>, id: i32) -> Result<Json<chef::Model>, Problem> {
+    Chef::find_by_id(id)
+        .one(db.as_ref())
+        .await?
+        .map(Json)
+        .ok_or_else(|| Problem::not_found("chef", id))
+}
+
+#[rocket::post("/chefs", data = "<body>")]
+async fn create(
+    db: &State<Db>,
+    changes: &State<Changes>,
+    body: Result<Json<NewChef>, json::Error<'_>>,
+) -> Result<Created<Json<chef::Model>>, Problem> {
+    let NewChef {
+        name,
+        bakery_id,
+        contact_details,
+    } = body?.into_inner();
+    let db = db.as_ref();
+    validate([
+        validate::name(&name),
+        validate::bakery_exists(db, bakery_id).await?,
+    ])?;
+
+    let chef = chef::ActiveModel {
+        name: ActiveValue::Set(name),
+        bakery_id: ActiveValue::Set(bakery_id),
+        contact_details: ActiveValue::Set(contact_details),
+        ..Default::default()
+    }
+    .insert(db)
+    .await?;
+    changes.chef(MutationType::Created, chef.id, chef.bakery_id, Some(&chef));
+    Ok(Created::new(rocket::uri!(show(chef.id)).to_string()).body(Json(chef)))
+}
+
+#[rocket::patch("/chefs/<id>", data = "<body>")]
+async fn update(
+    db: &State<Db>,
+    changes: &State<Changes>,
+    id: i32,
+    body: Result<Json<ChefChanges>, json::Error<'_>>,
+) -> Result<Json<chef::Model>, Problem> {
+    let ChefChanges {
+        name,
+        bakery_id,
+        contact_details,
+    } = body?.into_inner();
+    let db = db.as_ref();
+    let moved_to = match bakery_id {
+        Some(bakery_id) => validate::bakery_exists(db, bakery_id).await?,
+        None => None,
+    };
+    validate([name.as_deref().and_then(validate::name), moved_to])?;
+
+    let mut chef = Chef::find_by_id(id)
+        .one(db)
+        .await?
+        .ok_or_else(|| Problem::not_found("chef", id))?
+        .into_active_model();
+    if let Some(name) = name {
+        chef.name = ActiveValue::Set(name);
+    }
+    if let Some(bakery_id) = bakery_id {
+        chef.bakery_id = ActiveValue::Set(bakery_id);
+    }
+    if let Some(details) = contact_details {
+        chef.contact_details = ActiveValue::Set((!details.is_null()).then_some(details));
+    }
+    let chef = chef.update(db).await?;
+    changes.chef(MutationType::Updated, id, chef.bakery_id, Some(&chef));
+    Ok(Json(chef))
+}
+
+#[rocket::delete("/chefs/<id>")]
+async fn remove(db: &State<Db>, changes: &State<Changes>, id: i32) -> Result<NoContent, Problem> {
+    let db = db.as_ref();
+    let chef = Chef::find_by_id(id)
+        .one(db)
+        .await?
+        .ok_or_else(|| Problem::not_found("chef", id))?;
+    let bakery_id = chef.bakery_id;
+    chef.delete(db).await?;
+    changes.chef(MutationType::Deleted, id, bakery_id, None);
+    Ok(NoContent)
+}
+
+pub(crate) fn routes() -> Vec<rocket::Route> {
+    rocket::routes![list, show, create, update, remove]
+}
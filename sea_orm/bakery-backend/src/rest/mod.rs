@@ -0,0 +1,16 @@
+//! Bakeries and chefs as REST resources. Bodies are validated before anything touches
+//! the database, and every error - the routes' own, the database's and Rocket's - is
+//! answered with problem details.
+
+mod bakeries;
+mod chefs;
+mod problem;
+pub(crate) mod validate;
+
+pub(crate) use problem::{catch, FieldError, Problem};
+
+pub(crate) fn routes() -> Vec<rocket::Route> {
+    let mut routes = bakeries::routes();
+    routes.extend(chefs::routes());
+    routes
+}
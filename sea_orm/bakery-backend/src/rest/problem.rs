@@ -0,0 +1,187 @@
+use rocket::http::{ContentType, Status};
+use rocket::request::Request;
+use rocket::response::{self, Responder, Response};
+use rocket::serde::json::{self, Json};
+use rocket::serde::Serialize;
+use sea_orm::{DbErr, SqlErr};
+
+/// An error as RFC 9457 problem details, served as `application/problem+json`
+#[derive(Debug, Serialize)]
+#[serde(crate = "rocket::serde")]
+pub(crate) struct Problem {
+    title: String,
+    status: u16,
+    detail: String,
+    /// what's wrong with each field of the request body, for a 422
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    errors: Vec<FieldError>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(crate = "rocket::serde")]
+pub(crate) struct FieldError {
+    field: &'static str,
+    message: String,
+}
+
+impl FieldError {
+    pub(crate) fn new(field: &'static str, message: impl Into<String>) -> FieldError {
+        FieldError {
+            field,
+            message: message.into(),
+        }
+    }
+
+    pub(crate) fn field(&self) -> &'static str {
+        self.field
+    }
+
+    pub(crate) fn message(&self) -> &str {
+        &self.message
+    }
+}
+
+impl Problem {
+    pub(crate) fn new(status: Status, detail: impl Into<String>) -> Problem {
+        Problem {
+            title: status.reason_lossy().to_owned(),
+            status: status.code,
+            detail: detail.into(),
+            errors: vec![],
+        }
+    }
+
+    pub(crate) fn not_found(what: &str, id: i32) -> Problem {
+        Problem::new(Status::NotFound, format!("no {what} with id {id}"))
+    }
+
+    pub(crate) fn status(&self) -> u16 {
+        self.status
+    }
+
+    pub(crate) fn detail(&self) -> &str {
+        &self.detail
+    }
+
+    pub(crate) fn invalid(errors: Vec<FieldError>) -> Problem {
+        Problem {
+            errors,
+            ..Problem::new(Status::UnprocessableEntity, "the request body is invalid")
+        }
+    }
+}
+
+impl From<DbErr> for Problem {
+    fn from(err: DbErr) -> Problem {
+        match err.sql_err() {
+            // the database's own words name tables and constraints - they're for the log
+            Some(SqlErr::UniqueConstraintViolation(_)) => {
+                rocket::warn!("database: {}", err);
+                return Problem::new(Status::Conflict, "a record like that already exists");
+            }
+            Some(SqlErr::ForeignKeyConstraintViolation(_)) => {
+                rocket::warn!("database: {}", err);
+                return Problem::new(
+                    Status::Conflict,
+                    "the request refers to a record that doesn't exist, or one still referred to",
+                );
+            }
+            _ => {}
+        }
+        match err {
+            DbErr::RecordNotFound(detail) => Problem::new(Status::NotFound, detail),
+            DbErr::RecordNotUpdated => Problem::new(Status::NotFound, "nothing there to update"),
+            DbErr::AttrNotSet(field) => {
+                Problem::new(Status::UnprocessableEntity, format!("{field} is required"))
+            }
+            DbErr::Type(detail) | DbErr::Json(detail) => {
+                Problem::new(Status::UnprocessableEntity, detail)
+            }
+            err => {
+                // the details are for the log, not the client
+                rocket::error!("database: {}", err);
+                Problem::new(
+                    Status::InternalServerError,
+                    "the database couldn't handle the request",
+                )
+            }
+        }
+    }
+}
+
+/// A body that isn't JSON is a bad request; JSON that isn't the right shape is
+/// unprocessable
+impl From<json::Error<'_>> for Problem {
+    fn from(err: json::Error<'_>) -> Problem {
+        match err {
+            json::Error::Io(err) => Problem::new(Status::BadRequest, err.to_string()),
+            json::Error::Parse(_, err) if err.is_data() => {
+                Problem::new(Status::UnprocessableEntity, err.to_string())
+            }
+            json::Error::Parse(_, err) => Problem::new(Status::BadRequest, err.to_string()),
+        }
+    }
+}
+
+impl<'r> Responder<'r, 'static> for Problem {
+    fn respond_to(self, request: &'r Request<'_>) -> response::Result<'static> {
+        let status = Status::new(self.status);
+        Response::build_from(Json(self).respond_to(request)?)
+            .status(status)
+            .header(ContentType::new("application", "problem+json"))
+            .ok()
+    }
+}
+
+/// Whatever Rocket fails on before a route answers - no route, a guard that refused -
+/// as problem details too
+#[rocket::catch(default)]
+pub(crate) fn catch(status: Status, request: &Request<'_>) -> Problem {
+    let detail = if status == Status::NotFound {
+        format!("nothing at {} {}", request.method(), request.uri())
+    } else {
+        status.reason_lossy().to_owned()
+    };
+    Problem::new(status, detail)
+}
+
+#[cfg(test)]
+mod tests {
+    use sea_orm::{ActiveModelTrait, ActiveValue};
+
+    use super::*;
+    use crate::config::DatabaseConfig;
+    use crate::database_access;
+    use crate::entities::chef;
+
+    #[test]
+    fn maps_database_errors_to_statuses() {
+        let status = |err: DbErr| Problem::from(err).status;
+        assert_eq!(status(DbErr::RecordNotFound("bakery".into())), 404);
+        assert_eq!(status(DbErr::RecordNotUpdated), 404);
+        assert_eq!(status(DbErr::Type("not a number".into())), 422);
+        assert_eq!(status(DbErr::AttrNotSet("name".into())), 422);
+        assert_eq!(status(DbErr::Custom("out of disk".into())), 500);
+
+        let hidden = Problem::from(DbErr::Custom("password=hunter2".into()));
+        assert!(!hidden.detail.contains("hunter2"));
+    }
+
+    #[rocket::async_test]
+    async fn maps_constraint_violations_to_conflicts() {
+        let figment = rocket::Config::figment().merge(("database.url", "sqlite::memory:"));
+        let config = DatabaseConfig::from_figment(&figment).unwrap();
+        let db = database_access::get_db_connection(&config).await.unwrap();
+        let err = chef::ActiveModel {
+            name: ActiveValue::Set("Nobody's".to_owned()),
+            bakery_id: ActiveValue::Set(404),
+            ..Default::default()
+        }
+        .insert(&db)
+        .await
+        .unwrap_err();
+        let problem = Problem::from(err);
+        assert_eq!(problem.status, 409);
+        assert!(!problem.detail.to_lowercase().contains("foreign key"));
+    }
+}
@@ -0,0 +1,61 @@
+use std::ops::RangeInclusive;
+
+use sea_orm::{DatabaseConnection, DbErr, EntityTrait};
+
+use super::problem::{FieldError, Problem};
+use crate::entities::prelude::Bakery;
+
+/// The widest a `string()` column is on MySQL and Postgres
+const NAME_LENGTH: usize = 255;
+
+/// As a percentage - a loss is negative
+pub(crate) const PROFIT_MARGIN: RangeInclusive<f64> = -100.0..=100.0;
+
+/// 422 with every error found, if there were any
+pub(crate) fn validate(
+    errors: impl IntoIterator<Item = Option<FieldError>>,
+) -> Result<(), Problem> {
+    let errors: Vec<_> = errors.into_iter().flatten().collect();
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(Problem::invalid(errors))
+    }
+}
+
+pub(crate) fn name(name: &str) -> Option<FieldError> {
+    if name.trim().is_empty() {
+        Some(FieldError::new("name", "must not be blank"))
+    } else if name.chars().count() > NAME_LENGTH {
+        Some(FieldError::new(
+            "name",
+            format!("must be at most {NAME_LENGTH} characters"),
+        ))
+    } else {
+        None
+    }
+}
+
+pub(crate) fn profit_margin(margin: f64) -> Option<FieldError> {
+    (!PROFIT_MARGIN.contains(&margin)).then(|| {
+        FieldError::new(
+            "profit_margin",
+            format!(
+                "must be between {} and {}",
+                PROFIT_MARGIN.start(),
+                PROFIT_MARGIN.end()
+            ),
+        )
+    })
+}
+
+/// A chef has to work somewhere that exists
+pub(crate) async fn bakery_exists(
+    db: &DatabaseConnection,
+    id: i32,
+) -> Result<Option<FieldError>, DbErr> {
+    let found = Bakery::find_by_id(id).one(db).await?;
+    Ok(found
+        .is_none()
+        .then(|| FieldError::new("bakery_id", format!("no bakery with id {id}"))))
+}
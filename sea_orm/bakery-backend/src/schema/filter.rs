@@ -0,0 +1,111 @@
+use async_graphql::{Enum, InputObject};
+use sea_orm::*;
+
+use super::SortDirection;
+use crate::entities::*;
+
+/// Bakeries matching every field given
+#[derive(InputObject, Default)]
+pub(crate) struct BakeryFilter {
+    /// part of the name, matched case-sensitively
+    name_contains: Option<String>,
+    min_profit_margin: Option<f64>,
+    max_profit_margin: Option<f64>,
+}
+
+impl BakeryFilter {
+    pub(crate) fn condition(self) -> Condition {
+        Condition::all()
+            .add_option(
+                self.name_contains
+                    .map(|name| bakery::Column::Name.contains(name)),
+            )
+            .add_option(
+                self.min_profit_margin
+                    .map(|min| bakery::Column::ProfitMargin.gte(min)),
+            )
+            .add_option(
+                self.max_profit_margin
+                    .map(|max| bakery::Column::ProfitMargin.lte(max)),
+            )
+    }
+}
+
+#[derive(Enum, Copy, Clone, Eq, PartialEq, Default)]
+pub(crate) enum BakerySortField {
+    #[default]
+    Id,
+    Name,
+    ProfitMargin,
+}
+
+impl BakerySortField {
+    /// The column sorted by before the id, if any
+    pub(crate) fn column(self) -> Option<bakery::Column> {
+        match self {
+            BakerySortField::Id => None,
+            BakerySortField::Name => Some(bakery::Column::Name),
+            BakerySortField::ProfitMargin => Some(bakery::Column::ProfitMargin),
+        }
+    }
+}
+
+/// Ties are broken by id, in the same direction
+#[derive(InputObject, Default)]
+pub(crate) struct BakeryOrder {
+    pub(crate) field: BakerySortField,
+    #[graphql(default)]
+    pub(crate) direction: SortDirection,
+}
+
+/// Chefs matching every field given
+#[derive(InputObject, Default)]
+pub(crate) struct ChefFilter {
+    /// part of the name, matched case-sensitively
+    name_contains: Option<String>,
+    bakery_id: Option<i32>,
+    has_contact_details: Option<bool>,
+}
+
+impl ChefFilter {
+    pub(crate) fn condition(self) -> Condition {
+        Condition::all()
+            .add_option(
+                self.name_contains
+                    .map(|name| chef::Column::Name.contains(name)),
+            )
+            .add_option(self.bakery_id.map(|id| chef::Column::BakeryId.eq(id)))
+            .add_option(self.has_contact_details.map(|has| {
+                if has {
+                    chef::Column::ContactDetails.is_not_null()
+                } else {
+                    chef::Column::ContactDetails.is_null()
+                }
+            }))
+    }
+}
+
+#[derive(Enum, Copy, Clone, Eq, PartialEq, Default)]
+pub(crate) enum ChefSortField {
+    #[default]
+    Id,
+    Name,
+}
+
+impl ChefSortField {
+    /// The column sorted by before the id, if any
+    pub(crate) fn column(self) -> Option<chef::Column> {
+        match self {
+            ChefSortField::Id => None,
+            ChefSortField::Name => Some(chef::Column::Name),
+        }
+    }
+}
+
+/// Ties are broken by id, in the same direction
+#[derive(InputObject, Default)]
+pub(crate) struct ChefOrder {
+    pub(crate) field: ChefSortField,
+    #[graphql(default)]
+    pub(crate) direction: SortDirection,
+}
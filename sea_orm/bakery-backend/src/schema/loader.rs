@@ -0,0 +1,96 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use async_graphql::dataloader::Loader;
+use sea_orm::*;
+
+use crate::entities::{prelude::*, *};
+use crate::Db;
+
+/// Each bakery's chefs, by bakery id. The `chefs` of every bakery in a response are
+/// fetched with one query rather than one per bakery.
+pub(crate) struct ChefsByBakery {
+    db: Db,
+}
+
+impl ChefsByBakery {
+    pub(crate) fn new(db: Db) -> Self {
+        ChefsByBakery { db }
+    }
+}
+
+impl Loader<i32> for ChefsByBakery {
+    type Value = Vec<chef::Model>;
+    type Error = Arc<DbErr>;
+
+    async fn load(&self, keys: &[i32]) -> Result<HashMap<i32, Self::Value>, Self::Error> {
+        let chefs = Chef::find()
+            .filter(chef::Column::BakeryId.is_in(keys.iter().copied()))
+            .order_by_asc(chef::Column::Id)
+            .all(self.db.as_ref())
+            .await
+            .map_err(Arc::new)?;
+        let mut by_bakery: HashMap<i32, Self::Value> = HashMap::new();
+        for chef in chefs {
+            by_bakery.entry(chef.bakery_id).or_default().push(chef);
+        }
+        Ok(by_bakery)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::*;
+    use crate::config::DatabaseConfig;
+    use crate::database_access;
+
+    #[rocket::async_test]
+    async fn loads_every_bakerys_chefs_in_one_query() {
+        let figment = rocket::Config::figment().merge(("database.url", "sqlite::memory:"));
+        let config = DatabaseConfig::from_figment(&figment).unwrap();
+        let mut db = database_access::get_db_connection(&config).await.unwrap();
+        for name in ["Rye", "Spelt", "Emmer"] {
+            let bakery = bakery::ActiveModel {
+                name: ActiveValue::Set(name.to_owned()),
+                profit_margin: ActiveValue::Set(0.0),
+                ..Default::default()
+            }
+            .insert(&db)
+            .await
+            .unwrap();
+            for chef in ["Ana", "Ben"] {
+                chef::ActiveModel {
+                    name: ActiveValue::Set(format!("{chef} of {name}")),
+                    bakery_id: ActiveValue::Set(bakery.id),
+                    ..Default::default()
+                }
+                .insert(&db)
+                .await
+                .unwrap();
+            }
+        }
+
+        let statements = Arc::new(AtomicUsize::new(0));
+        let counter = statements.clone();
+        db.set_metric_callback(move |_| {
+            counter.fetch_add(1, Ordering::SeqCst);
+        });
+        let schema = crate::schema::build(Arc::new(db), Default::default());
+        let response = schema
+            .execute("{ bakeries { nodes { name chefs { name } } } }")
+            .await;
+        assert!(response.errors.is_empty(), "{:?}", response.errors);
+
+        let data = response.data.into_json().unwrap();
+        let nodes = data["bakeries"]["nodes"].as_array().unwrap();
+        assert_eq!(nodes.len(), 3);
+        assert_eq!(
+            nodes[2]["chefs"],
+            rocket::serde::json::json!([{ "name": "Ana of Emmer" }, { "name": "Ben of Emmer" }])
+        );
+        // the bakeries, then all their chefs
+        assert_eq!(statements.load(Ordering::SeqCst), 2);
+    }
+}
@@ -0,0 +1,339 @@
+use crate::database_access::{self, BakeryDeletion};
+use crate::entities::{prelude::*, *};
+use crate::rest::{validate, FieldError, Problem};
+use crate::Db;
+use async_graphql::connection::{Connection, OpaqueCursor};
+use async_graphql::dataloader::DataLoader;
+use async_graphql::{Context, ErrorExtensions, InputObject, MaybeUndefined, Object, Schema};
+use sea_orm::entity::prelude::Json;
+use sea_orm::*;
+
+mod filter;
+mod loader;
+mod pagination;
+mod subscription;
+
+pub(crate) use filter::*;
+pub(crate) use loader::ChefsByBakery;
+pub(crate) use pagination::{Cursor, SortDirection};
+pub(crate) use subscription::*;
+
+pub(crate) struct QueryRoot;
+pub(crate) struct MutationRoot;
+
+pub(crate) type SchemaType = Schema<QueryRoot, MutationRoot, SubscriptionRoot>;
+
+/// The schema over the shared pool, with a chef loader batching `Bakery.chefs` and the
+/// channel mutations publish their changes to
+pub(crate) fn build(db: Db, changes: Changes) -> SchemaType {
+    Schema::build(QueryRoot, MutationRoot, SubscriptionRoot)
+        .data(DataLoader::new(
+            ChefsByBakery::new(db.clone()),
+            rocket::tokio::spawn,
+        ))
+        .data(changes)
+        .data(db)
+        .finish()
+}
+
+/// The pool, or an error for the client if the schema was built without one
+pub(crate) fn db<'a>(ctx: &Context<'a>) -> async_graphql::Result<&'a DatabaseConnection> {
+    Ok(ctx.data::<Db>()?.as_ref())
+}
+
+/// The checks the REST API makes on a body, as one error naming every argument at fault
+fn check(errors: impl IntoIterator<Item = Option<FieldError>>) -> async_graphql::Result<()> {
+    let errors: Vec<FieldError> = errors.into_iter().flatten().collect();
+    if errors.is_empty() {
+        return Ok(());
+    }
+    let message = errors
+        .iter()
+        .map(|error| format!("{} {}", argument(error.field()), error.message()))
+        .collect::<Vec<_>>()
+        .join(", ");
+    Err(async_graphql::Error::new(message)
+        .extend_with(|_, extensions| extensions.set("code", "INVALID")))
+}
+
+/// `profit_margin` as GraphQL spells it, `profitMargin`
+fn argument(field: &str) -> String {
+    let mut words = field.split('_');
+    let first = words.next().unwrap_or_default().to_owned();
+    words.fold(first, |mut name, word| {
+        let mut chars = word.chars();
+        name.extend(chars.next().map(|c| c.to_ascii_uppercase()));
+        name.push_str(chars.as_str());
+        name
+    })
+}
+
+/// A database error worded as the REST API words it - the database's own message names
+/// tables and constraints, so that only goes to the log
+fn database(err: DbErr) -> async_graphql::Error {
+    let problem = Problem::from(err);
+    let code = match problem.status() {
+        404 => "NOT_FOUND",
+        409 => "CONFLICT",
+        422 => "INVALID",
+        _ => "INTERNAL",
+    };
+    async_graphql::Error::new(problem.detail())
+        .extend_with(|_, extensions| extensions.set("code", code))
+}
+
+fn not_found(what: &str, id: i32) -> async_graphql::Error {
+    async_graphql::Error::new(format!("no {what} with id {id}"))
+        .extend_with(|_, extensions| extensions.set("code", "NOT_FOUND"))
+}
+
+#[Object]
+impl QueryRoot {
+    async fn hello(&self) -> String {
+        "Hello GraphQL".to_owned()
+    }
+
+    /// Bakeries a page at a time, by id unless `orderBy` says otherwise
+    #[allow(clippy::too_many_arguments)]
+    async fn bakeries(
+        &self,
+        ctx: &Context<'_>,
+        filter: Option<BakeryFilter>,
+        order_by: Option<BakeryOrder>,
+        after: Option<String>,
+        before: Option<String>,
+        first: Option<i32>,
+        last: Option<i32>,
+    ) -> async_graphql::Result<Connection<OpaqueCursor<Cursor>, bakery::Model>> {
+        let db = db(ctx)?;
+        let order = order_by.unwrap_or_default();
+        let select = Bakery::find().filter(filter.unwrap_or_default().condition());
+        pagination::paginate(
+            db,
+            select,
+            bakery::Column::Id,
+            order.field.column(),
+            order.direction,
+            (after, before, first, last),
+        )
+        .await
+    }
+
+    // For finding one bakery by id
+    async fn bakery(
+        &self,
+        ctx: &Context<'_>,
+        id: i32,
+    ) -> async_graphql::Result<Option<bakery::Model>> {
+        Bakery::find_by_id(id).one(db(ctx)?).await.map_err(database)
+    }
+
+    /// Chefs a page at a time, by id unless `orderBy` says otherwise
+    #[allow(clippy::too_many_arguments)]
+    async fn chefs(
+        &self,
+        ctx: &Context<'_>,
+        filter: Option<ChefFilter>,
+        order_by: Option<ChefOrder>,
+        after: Option<String>,
+        before: Option<String>,
+        first: Option<i32>,
+        last: Option<i32>,
+    ) -> async_graphql::Result<Connection<OpaqueCursor<Cursor>, chef::Model>> {
+        let db = db(ctx)?;
+        let order = order_by.unwrap_or_default();
+        let select = Chef::find().filter(filter.unwrap_or_default().condition());
+        pagination::paginate(
+            db,
+            select,
+            chef::Column::Id,
+            order.field.column(),
+            order.direction,
+            (after, before, first, last),
+        )
+        .await
+    }
+
+    // For finding one chef by id
+    async fn chef(&self, ctx: &Context<'_>, id: i32) -> async_graphql::Result<Option<chef::Model>> {
+        Chef::find_by_id(id).one(db(ctx)?).await.map_err(database)
+    }
+}
+
+/// Fields of a bakery to change; those left out stay as they are
+#[derive(InputObject)]
+pub(crate) struct UpdateBakery {
+    name: Option<String>,
+    profit_margin: Option<f64>,
+}
+
+/// Fields of a chef to change; those left out stay as they are, and
+/// `contactDetails: null` clears them
+#[derive(InputObject)]
+pub(crate) struct UpdateChef {
+    name: Option<String>,
+    contact_details: MaybeUndefined<Json>,
+    bakery_id: Option<i32>,
+}
+
+#[Object]
+impl MutationRoot {
+    // For inserting a bakery
+    async fn add_bakery(
+        &self,
+        ctx: &Context<'_>,
+        name: String,
+        #[graphql(default)] profit_margin: f64,
+    ) -> async_graphql::Result<bakery::Model> {
+        check([
+            validate::name(&name),
+            validate::profit_margin(profit_margin),
+        ])?;
+        let bakery = bakery::ActiveModel {
+            name: ActiveValue::Set(name),
+            profit_margin: ActiveValue::Set(profit_margin),
+            ..Default::default()
+        }
+        .insert(db(ctx)?)
+        .await
+        .map_err(database)?;
+        ctx.data::<Changes>()?
+            .bakery(MutationType::Created, bakery.id, Some(&bakery));
+        Ok(bakery)
+    }
+
+    async fn update_bakery(
+        &self,
+        ctx: &Context<'_>,
+        id: i32,
+        input: UpdateBakery,
+    ) -> async_graphql::Result<bakery::Model> {
+        check([
+            input.name.as_deref().and_then(validate::name),
+            input.profit_margin.and_then(validate::profit_margin),
+        ])?;
+        let db = db(ctx)?;
+        let mut bakery = Bakery::find_by_id(id)
+            .one(db)
+            .await
+            .map_err(database)?
+            .ok_or_else(|| not_found("bakery", id))?
+            .into_active_model();
+        if let Some(name) = input.name {
+            bakery.name = ActiveValue::Set(name);
+        }
+        if let Some(profit_margin) = input.profit_margin {
+            bakery.profit_margin = ActiveValue::Set(profit_margin);
+        }
+        let bakery = bakery.update(db).await.map_err(database)?;
+        ctx.data::<Changes>()?
+            .bakery(MutationType::Updated, id, Some(&bakery));
+        Ok(bakery)
+    }
+
+    /// Whether there was a bakery to delete. One with chefs stays until they've moved
+    /// or gone.
+    async fn delete_bakery(&self, ctx: &Context<'_>, id: i32) -> async_graphql::Result<bool> {
+        match database_access::delete_bakery(db(ctx)?, id)
+            .await
+            .map_err(database)?
+        {
+            BakeryDeletion::Deleted => {
+                ctx.data::<Changes>()?
+                    .bakery(MutationType::Deleted, id, None);
+                Ok(true)
+            }
+            BakeryDeletion::NotFound => Ok(false),
+            BakeryDeletion::HasChefs(chefs) => Err(async_graphql::Error::new(format!(
+                "bakery {id} still has {chefs} chefs"
+            ))
+            .extend_with(|_, extensions| extensions.set("code", "CONFLICT"))),
+        }
+    }
+
+    // For inserting a chef
+    async fn add_chef(
+        &self,
+        ctx: &Context<'_>,
+        name: String,
+        bakery_id: i32,
+        contact_details: Option<Json>,
+    ) -> async_graphql::Result<chef::Model> {
+        let db = db(ctx)?;
+        let bakery = validate::bakery_exists(db, bakery_id)
+            .await
+            .map_err(database)?;
+        check([validate::name(&name), bakery])?;
+        let chef = chef::ActiveModel {
+            name: ActiveValue::Set(name),
+            bakery_id: ActiveValue::Set(bakery_id),
+            contact_details: ActiveValue::Set(contact_details),
+            ..Default::default()
+        }
+        .insert(db)
+        .await
+        .map_err(database)?;
+        ctx.data::<Changes>()?
+            .chef(MutationType::Created, chef.id, chef.bakery_id, Some(&chef));
+        Ok(chef)
+    }
+
+    async fn update_chef(
+        &self,
+        ctx: &Context<'_>,
+        id: i32,
+        input: UpdateChef,
+    ) -> async_graphql::Result<chef::Model> {
+        let db = db(ctx)?;
+        let moved_to = match input.bakery_id {
+            Some(bakery_id) => validate::bakery_exists(db, bakery_id)
+                .await
+                .map_err(database)?,
+            None => None,
+        };
+        check([input.name.as_deref().and_then(validate::name), moved_to])?;
+        let mut chef = Chef::find_by_id(id)
+            .one(db)
+            .await
+            .map_err(database)?
+            .ok_or_else(|| not_found("chef", id))?
+            .into_active_model();
+        if let Some(name) = input.name {
+            chef.name = ActiveValue::Set(name);
+        }
+        match input.contact_details {
+            MaybeUndefined::Undefined => {}
+            MaybeUndefined::Null => chef.contact_details = ActiveValue::Set(None),
+            MaybeUndefined::Value(details) => {
+                chef.contact_details = ActiveValue::Set(Some(details))
+            }
+        }
+        if let Some(bakery_id) = input.bakery_id {
+            chef.bakery_id = ActiveValue::Set(bakery_id);
+        }
+        let chef = chef.update(db).await.map_err(database)?;
+        ctx.data::<Changes>()?
+            .chef(MutationType::Updated, id, chef.bakery_id, Some(&chef));
+        Ok(chef)
+    }
+
+    /// Whether there was a chef to delete
+    async fn delete_chef(&self, ctx: &Context<'_>, id: i32) -> async_graphql::Result<bool> {
+        let db = db(ctx)?;
+        let Some(chef) = Chef::find_by_id(id).one(db).await.map_err(database)? else {
+            return Ok(false);
+        };
+        let deleted = chef
+            .clone()
+            .delete(db)
+            .await
+            .map_err(database)?
+            .rows_affected
+            > 0;
+        if deleted {
+            ctx.data::<Changes>()?
+                .chef(MutationType::Deleted, id, chef.bakery_id, None);
+        }
+        Ok(deleted)
+    }
+}
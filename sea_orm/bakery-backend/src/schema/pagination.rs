@@ -0,0 +1,173 @@
+//! Relay-style connections, paged by keyset: a cursor holds the sort key and id of its
+//! row, and the next page is whatever sorts after them - so rows added or removed
+//! elsewhere don't shift a page the way an offset would.
+
+use async_graphql::connection::{query, Connection, Edge, OpaqueCursor};
+use async_graphql::{Enum, OutputType};
+use rocket::serde::{Deserialize, Serialize};
+use sea_orm::sea_query::Value;
+use sea_orm::*;
+
+#[derive(Enum, Copy, Clone, Eq, PartialEq, Default)]
+pub(crate) enum SortDirection {
+    #[default]
+    Asc,
+    Desc,
+}
+
+impl SortDirection {
+    fn reverse(self) -> SortDirection {
+        match self {
+            SortDirection::Asc => SortDirection::Desc,
+            SortDirection::Desc => SortDirection::Asc,
+        }
+    }
+}
+
+impl From<SortDirection> for Order {
+    fn from(direction: SortDirection) -> Order {
+        match direction {
+            SortDirection::Asc => Order::Asc,
+            SortDirection::Desc => Order::Desc,
+        }
+    }
+}
+
+/// Where a row sits in its ordering - opaque to clients
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub(crate) struct Cursor {
+    id: i32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    key: Option<Key>,
+}
+
+/// The value of the column sorted by
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "rocket::serde", untagged)]
+enum Key {
+    Number(f64),
+    Text(String),
+}
+
+impl Key {
+    fn of(value: Value) -> async_graphql::Result<Key> {
+        match value {
+            Value::String(Some(text)) => Ok(Key::Text(*text)),
+            Value::Double(Some(number)) => Ok(Key::Number(number)),
+            Value::Float(Some(number)) => Ok(Key::Number(number.into())),
+            Value::Int(Some(number)) => Ok(Key::Number(number.into())),
+            value => Err(format!("can't page by {value:?}").into()),
+        }
+    }
+}
+
+impl From<Key> for Value {
+    fn from(key: Key) -> Value {
+        match key {
+            Key::Number(number) => number.into(),
+            Key::Text(text) => text.into(),
+        }
+    }
+}
+
+/// Whatever `first`, `after`, `last` and `before` pick from `select`, sorted by `key`
+/// then `id`. `first` and `last` together take the last of the first.
+pub(crate) async fn paginate<E>(
+    db: &DatabaseConnection,
+    select: Select<E>,
+    id: E::Column,
+    key: Option<E::Column>,
+    direction: SortDirection,
+    (after, before, first, last): (Option<String>, Option<String>, Option<i32>, Option<i32>),
+) -> async_graphql::Result<Connection<OpaqueCursor<Cursor>, E::Model>>
+where
+    E: EntityTrait,
+    E::Model: OutputType,
+{
+    query(
+        after,
+        before,
+        first,
+        last,
+        |after: Option<OpaqueCursor<Cursor>>, before: Option<OpaqueCursor<Cursor>>, first, last| async move {
+            let mut select = select;
+            let (has_after, has_before) = (after.is_some(), before.is_some());
+            if let Some(OpaqueCursor(after)) = after {
+                select = select.filter(beyond(after, id, key, direction));
+            }
+            if let Some(OpaqueCursor(before)) = before {
+                select = select.filter(beyond(before, id, key, direction.reverse()));
+            }
+
+            // with only `last`, read backwards from the end and flip the rows round
+            let backwards = first.is_none() && last.is_some();
+            let order = if backwards { direction.reverse() } else { direction };
+            if let Some(key) = key {
+                select = select.order_by(key, order.into());
+            }
+            select = select.order_by(id, order.into());
+            let limit = if backwards { last } else { first };
+            if let Some(limit) = limit {
+                // one more to see if there's another page
+                select = select.limit(limit as u64 + 1);
+            }
+
+            let mut rows = select.all(db).await.map_err(super::database)?;
+            let more = limit.is_some_and(|limit| rows.len() > limit);
+            if let Some(limit) = limit {
+                rows.truncate(limit);
+            }
+            let (mut has_previous, has_next) = if backwards {
+                rows.reverse();
+                (more, has_before)
+            } else {
+                (has_after, more)
+            };
+            if let (Some(_), Some(last)) = (first, last) {
+                if rows.len() > last {
+                    rows.drain(..rows.len() - last);
+                    has_previous = true;
+                }
+            }
+
+            let mut connection = Connection::new(has_previous, has_next);
+            for row in rows {
+                let cursor = Cursor {
+                    id: match row.get(id) {
+                        Value::Int(Some(id)) => id,
+                        value => return Err(format!("can't page by {value:?}").into()),
+                    },
+                    key: key.map(|key| Key::of(row.get(key))).transpose()?,
+                };
+                connection.edges.push(Edge::new(OpaqueCursor(cursor), row));
+            }
+            Ok::<_, async_graphql::Error>(connection)
+        },
+    )
+    .await
+}
+
+/// Rows sorting after `cursor` when going in `direction`
+fn beyond<C: ColumnTrait>(
+    cursor: Cursor,
+    id: C,
+    key: Option<C>,
+    direction: SortDirection,
+) -> Condition {
+    let past = |column: C, value: Value| match direction {
+        SortDirection::Asc => column.gt(value),
+        SortDirection::Desc => column.lt(value),
+    };
+    match (key, cursor.key) {
+        (Some(key), Some(value)) => {
+            let value = Value::from(value);
+            Condition::any().add(past(key, value.clone())).add(
+                Condition::all()
+                    .add(key.eq(value))
+                    .add(past(id, cursor.id.into())),
+            )
+        }
+        _ => Condition::all().add(past(id, cursor.id.into())),
+    }
+}
@@ -0,0 +1,133 @@
+use async_graphql::{Context, Enum, SimpleObject, Subscription};
+use futures::{future, stream, Stream, StreamExt};
+use rocket::tokio::sync::broadcast::{self, error::RecvError};
+
+use crate::entities::*;
+
+/// Changes a subscriber hasn't read yet before it starts missing some
+const BACKLOG: usize = 256;
+
+#[derive(Enum, Copy, Clone, Eq, PartialEq, Debug)]
+pub(crate) enum MutationType {
+    Created,
+    Updated,
+    Deleted,
+}
+
+#[derive(SimpleObject, Clone, Debug)]
+pub(crate) struct BakeryChanged {
+    mutation_type: MutationType,
+    id: i32,
+    /// As it is now - null once deleted
+    bakery: Option<bakery::Model>,
+}
+
+#[derive(SimpleObject, Clone, Debug)]
+pub(crate) struct ChefChanged {
+    mutation_type: MutationType,
+    id: i32,
+    bakery_id: i32,
+    /// As it is now - null once deleted
+    chef: Option<chef::Model>,
+}
+
+#[derive(Clone, Debug)]
+enum Change {
+    Bakery(BakeryChanged),
+    Chef(ChefChanged),
+}
+
+/// What the mutations and REST routes have changed, for every subscription to hear
+#[derive(Clone)]
+pub(crate) struct Changes(broadcast::Sender<Change>);
+
+impl Default for Changes {
+    fn default() -> Self {
+        Changes(broadcast::channel(BACKLOG).0)
+    }
+}
+
+impl Changes {
+    pub(crate) fn bakery(
+        &self,
+        mutation_type: MutationType,
+        id: i32,
+        bakery: Option<&bakery::Model>,
+    ) {
+        // no subscribers, no one to tell
+        let _ = self.0.send(Change::Bakery(BakeryChanged {
+            mutation_type,
+            id,
+            bakery: bakery.cloned(),
+        }));
+    }
+
+    pub(crate) fn chef(
+        &self,
+        mutation_type: MutationType,
+        id: i32,
+        bakery_id: i32,
+        chef: Option<&chef::Model>,
+    ) {
+        let _ = self.0.send(Change::Chef(ChefChanged {
+            mutation_type,
+            id,
+            bakery_id,
+            chef: chef.cloned(),
+        }));
+    }
+
+    /// Everything from now on. A subscriber too slow to keep up skips what it missed.
+    fn subscribe(&self) -> impl Stream<Item = Change> {
+        stream::unfold(self.0.subscribe(), |mut changes| async move {
+            loop {
+                match changes.recv().await {
+                    Ok(change) => return Some((change, changes)),
+                    Err(RecvError::Lagged(_)) => continue,
+                    Err(RecvError::Closed) => return None,
+                }
+            }
+        })
+    }
+}
+
+pub(crate) struct SubscriptionRoot;
+
+#[Subscription]
+impl SubscriptionRoot {
+    /// Bakeries as they're created, updated and deleted, or just the changes of `mutationType`
+    async fn bakery_changed(
+        &self,
+        ctx: &Context<'_>,
+        mutation_type: Option<MutationType>,
+    ) -> async_graphql::Result<impl Stream<Item = BakeryChanged>> {
+        let changes = ctx.data::<Changes>()?.subscribe();
+        Ok(changes.filter_map(move |change| {
+            future::ready(match change {
+                Change::Bakery(changed)
+                    if mutation_type.is_none_or(|wanted| wanted == changed.mutation_type) =>
+                {
+                    Some(changed)
+                }
+                _ => None,
+            })
+        }))
+    }
+
+    /// Chefs as they're created, updated and deleted, or just those of `bakeryId`
+    async fn chef_changed(
+        &self,
+        ctx: &Context<'_>,
+        bakery_id: Option<i32>,
+    ) -> async_graphql::Result<impl Stream<Item = ChefChanged>> {
+        let changes = ctx.data::<Changes>()?.subscribe();
+        Ok(changes.filter_map(move |change| {
+            future::ready(match change {
+                Change::Chef(changed) if bakery_id.is_none_or(|id| id == changed.bakery_id) => {
+                    Some(changed)
+                }
+                _ => None,
+            })
+        }))
+    }
+}
@@ -0,0 +1,119 @@
+//! GraphQL subscriptions over WebSocket, speaking `graphql-transport-ws` or the older
+//! `graphql-ws`, whichever the client asks for. Rocket upgrades the connection and
+//! tungstenite frames the messages on it.
+
+use std::io;
+use std::pin::Pin;
+use std::str::FromStr;
+
+use async_graphql::http::{WebSocket, WebSocketProtocols, WsMessage};
+use futures::{future, SinkExt, StreamExt};
+use rocket::data::{IoHandler, IoStream};
+use rocket::http::Status;
+use rocket::request::{FromRequest, Outcome, Request};
+use rocket::response::{self, Responder, Response};
+use tokio_tungstenite::tungstenite::handshake::derive_accept_key;
+use tokio_tungstenite::tungstenite::protocol::frame::CloseFrame;
+use tokio_tungstenite::tungstenite::protocol::Role;
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::WebSocketStream;
+
+use crate::schema::SchemaType;
+
+/// A request to upgrade to a WebSocket
+pub(crate) struct Upgrade {
+    key: String,
+    protocol: WebSocketProtocols,
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for Upgrade {
+    type Error = &'static str;
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let headers = request.headers();
+        let upgrading = headers
+            .get("Upgrade")
+            .any(|value| value.eq_ignore_ascii_case("websocket"));
+        let Some(key) = headers.get_one("Sec-WebSocket-Key").filter(|_| upgrading) else {
+            return Outcome::Error((Status::UpgradeRequired, "expected a WebSocket upgrade"));
+        };
+        // the first protocol offered that we speak
+        let protocol = headers
+            .get("Sec-WebSocket-Protocol")
+            .flat_map(|value| value.split(','))
+            .find_map(|protocol| WebSocketProtocols::from_str(protocol.trim()).ok())
+            .unwrap_or(WebSocketProtocols::GraphQLWS);
+        Outcome::Success(Upgrade {
+            key: key.to_owned(),
+            protocol,
+        })
+    }
+}
+
+/// Switches the connection over to WebSocket, then answers subscriptions on it
+pub(crate) struct Subscriptions {
+    pub(crate) upgrade: Upgrade,
+    pub(crate) schema: SchemaType,
+}
+
+impl<'r> Responder<'r, 'static> for Subscriptions {
+    fn respond_to(self, _: &'r Request<'_>) -> response::Result<'static> {
+        let Subscriptions { upgrade, schema } = self;
+        Response::build()
+            .raw_header(
+                "Sec-WebSocket-Accept",
+                derive_accept_key(upgrade.key.as_bytes()),
+            )
+            .raw_header(
+                "Sec-WebSocket-Protocol",
+                upgrade.protocol.sec_websocket_protocol(),
+            )
+            .upgrade(
+                "websocket",
+                Session {
+                    schema,
+                    protocol: upgrade.protocol,
+                },
+            )
+            .ok()
+    }
+}
+
+struct Session {
+    schema: SchemaType,
+    protocol: WebSocketProtocols,
+}
+
+#[rocket::async_trait]
+impl IoHandler for Session {
+    async fn io(self: Pin<Box<Self>>, io: IoStream) -> io::Result<()> {
+        let Session { schema, protocol } = *Pin::into_inner(self);
+        let (mut sink, stream) = WebSocketStream::from_raw_socket(io, Role::Server, None)
+            .await
+            .split();
+        // a read error ends the session the same as a close
+        let incoming = stream
+            .take_while(|message| future::ready(message.is_ok()))
+            .filter_map(|message| {
+                future::ready(match message {
+                    Ok(Message::Text(text)) => Some(text.into_bytes()),
+                    Ok(Message::Binary(bytes)) => Some(bytes),
+                    _ => None,
+                })
+            });
+
+        let mut outgoing = WebSocket::new(schema, incoming, protocol);
+        while let Some(message) = outgoing.next().await {
+            let message = match message {
+                WsMessage::Text(text) => Message::Text(text),
+                WsMessage::Close(code, reason) => Message::Close(Some(CloseFrame {
+                    code: code.into(),
+                    reason: reason.into(),
+                })),
+            };
+            sink.send(message).await.map_err(io::Error::other)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,465 @@
+//! The GraphQL API against in-memory SQLite: mutations, paging, filtering and
+//! subscriptions over a real WebSocket
+
+use std::net::TcpListener;
+use std::time::Duration;
+
+use bakery_backend::rocket;
+use futures::{SinkExt, StreamExt};
+use rocket::figment::Figment;
+use rocket::http::{ContentType, Status};
+use rocket::local::asynchronous::Client;
+use rocket::serde::json::{serde_json, Value};
+use rocket::tokio::net::TcpStream;
+use rocket::tokio::time::{sleep, timeout};
+use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::{MaybeTlsStream, WebSocketStream};
+
+fn figment() -> Figment {
+    rocket::Config::figment()
+        .merge(("database.url", "sqlite::memory:"))
+        .merge(("database.seed", false))
+}
+
+async fn client() -> Client {
+    Client::tracked(rocket(figment())).await.unwrap()
+}
+
+async fn execute(client: &Client, query: &str) -> Value {
+    let response = client
+        .post("/graphql")
+        .header(ContentType::JSON)
+        .body(serde_json::json!({ "query": query }).to_string())
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::Ok);
+    response.into_json().await.unwrap()
+}
+
+async fn graphql(client: &Client, query: &str) -> Value {
+    let body = execute(client, query).await;
+    assert_eq!(body.get("errors"), None, "{body}");
+    body["data"].clone()
+}
+
+/// Adds bakeries with these names and margins, returning their ids
+async fn bakeries(client: &Client, bakeries: &[(&str, f64)]) -> Vec<i64> {
+    let mut ids = vec![];
+    for (name, margin) in bakeries {
+        let data = graphql(
+            client,
+            &format!(
+                r#"mutation {{ addBakery(name: "{name}", profitMargin: {margin}) {{ id }} }}"#
+            ),
+        )
+        .await;
+        ids.push(data["addBakery"]["id"].as_i64().unwrap());
+    }
+    ids
+}
+
+fn names(connection: &Value) -> Vec<&str> {
+    connection["nodes"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|node| node["name"].as_str().unwrap())
+        .collect()
+}
+
+#[rocket::async_test]
+async fn updates_and_deletes() {
+    let client = client().await;
+    let [id] = bakeries(&client, &[("Crumbs", 0.1)]).await[..] else {
+        unreachable!()
+    };
+    let chef = graphql(
+        &client,
+        &format!(
+            r#"mutation {{ addChef(name: "Pat", bakeryId: {id}, contactDetails: {{ phone: "555" }}) {{ id contactDetails }} }}"#
+        ),
+    )
+    .await;
+    assert_eq!(
+        chef["addChef"]["contactDetails"],
+        serde_json::json!({ "phone": "555" })
+    );
+    let chef_id = chef["addChef"]["id"].as_i64().unwrap();
+
+    let data = graphql(
+        &client,
+        &format!(
+            r#"mutation {{ updateBakery(id: {id}, input: {{ profitMargin: 0.25 }}) {{ name profitMargin }} }}"#
+        ),
+    )
+    .await;
+    assert_eq!(
+        data,
+        serde_json::json!({ "updateBakery": { "name": "Crumbs", "profitMargin": 0.25 } })
+    );
+
+    let data = graphql(
+        &client,
+        &format!(
+            r#"mutation {{ updateChef(id: {chef_id}, input: {{ name: "Sam", contactDetails: null }}) {{ name contactDetails bakeryId }} }}"#
+        ),
+    )
+    .await;
+    assert_eq!(
+        data,
+        serde_json::json!({ "updateChef": { "name": "Sam", "contactDetails": null, "bakeryId": id } })
+    );
+
+    let body = execute(
+        &client,
+        r#"mutation { updateBakery(id: 999, input: { name: "Nowhere" }) { id } }"#,
+    )
+    .await;
+    assert_eq!(body["errors"][0]["message"], "no bakery with id 999");
+    assert_eq!(body["errors"][0]["extensions"]["code"], "NOT_FOUND");
+
+    // a bakery with chefs stays until they've gone, as over REST
+    let body = execute(&client, &format!("mutation {{ deleteBakery(id: {id}) }}")).await;
+    assert_eq!(
+        body["errors"][0]["message"],
+        format!("bakery {id} still has 1 chefs")
+    );
+    assert_eq!(body["errors"][0]["extensions"]["code"], "CONFLICT");
+    let data = graphql(
+        &client,
+        &format!("mutation {{ deleteChef(id: {chef_id}) deleteBakery(id: {id}) }}"),
+    )
+    .await;
+    assert_eq!(
+        data,
+        serde_json::json!({ "deleteChef": true, "deleteBakery": true })
+    );
+    let data = graphql(
+        &client,
+        &format!("{{ bakery(id: {id}) {{ id }} chef(id: {chef_id}) {{ id }} }}"),
+    )
+    .await;
+    assert_eq!(data, serde_json::json!({ "bakery": null, "chef": null }));
+    let data = graphql(
+        &client,
+        &format!("mutation {{ deleteBakery(id: {id}) deleteChef(id: {chef_id}) }}"),
+    )
+    .await;
+    assert_eq!(
+        data,
+        serde_json::json!({ "deleteBakery": false, "deleteChef": false })
+    );
+}
+
+#[rocket::async_test]
+async fn validates_like_the_rest_api() {
+    let client = client().await;
+    let body = execute(
+        &client,
+        r#"mutation { addBakery(name: " ", profitMargin: 150) { id } }"#,
+    )
+    .await;
+    assert_eq!(
+        body["errors"][0]["message"],
+        "name must not be blank, profitMargin must be between -100 and 100"
+    );
+    assert_eq!(body["errors"][0]["extensions"]["code"], "INVALID");
+
+    let id = bakeries(&client, &[("Crumbs", 10.0)]).await[0];
+    let long = "x".repeat(256);
+    for input in [
+        r#"{ profitMargin: -100.5 }"#.to_owned(),
+        format!(r#"{{ name: "{long}" }}"#),
+    ] {
+        let body = execute(
+            &client,
+            &format!("mutation {{ updateBakery(id: {id}, input: {input}) {{ id }} }}"),
+        )
+        .await;
+        assert_eq!(body["errors"][0]["extensions"]["code"], "INVALID", "{body}");
+    }
+    let body = execute(
+        &client,
+        &format!(r#"mutation {{ addChef(name: "", bakeryId: {id}) {{ id }} }}"#),
+    )
+    .await;
+    assert_eq!(body["errors"][0]["message"], "name must not be blank");
+
+    // a chef has to work at a bakery that exists, rather than trip the foreign key
+    let missing = id + 1;
+    for mutation in [
+        format!(r#"addChef(name: "Pat", bakeryId: {missing})"#),
+        format!(r#"updateChef(id: 1, input: {{ bakeryId: {missing} }})"#),
+    ] {
+        let body = execute(&client, &format!("mutation {{ {mutation} {{ id }} }}")).await;
+        assert_eq!(
+            body["errors"][0]["message"],
+            format!("bakeryId no bakery with id {missing}")
+        );
+        assert_eq!(body["errors"][0]["extensions"]["code"], "INVALID");
+    }
+
+    // nothing got through
+    let data = graphql(
+        &client,
+        "{ bakeries { nodes { name profitMargin chefs { name } } } }",
+    )
+    .await;
+    assert_eq!(
+        data,
+        serde_json::json!({ "bakeries": { "nodes": [
+            { "name": "Crumbs", "profitMargin": 10.0, "chefs": [] }
+        ] } })
+    );
+}
+
+#[rocket::async_test]
+async fn pages_forwards_and_backwards() {
+    let client = client().await;
+    bakeries(
+        &client,
+        &[("A", 0.1), ("B", 0.2), ("C", 0.3), ("D", 0.4), ("E", 0.5)],
+    )
+    .await;
+    let page = |arguments: &str| {
+        format!(
+            "{{ bakeries({arguments}) {{ nodes {{ name }} pageInfo {{ hasPreviousPage hasNextPage startCursor endCursor }} }} }}"
+        )
+    };
+
+    let first = graphql(&client, &page("first: 2")).await["bakeries"].clone();
+    assert_eq!(names(&first), ["A", "B"]);
+    assert_eq!(first["pageInfo"]["hasPreviousPage"], false);
+    assert_eq!(first["pageInfo"]["hasNextPage"], true);
+
+    let end = first["pageInfo"]["endCursor"].as_str().unwrap();
+    let second =
+        graphql(&client, &page(&format!(r#"first: 2, after: "{end}""#))).await["bakeries"].clone();
+    assert_eq!(names(&second), ["C", "D"]);
+    assert_eq!(second["pageInfo"]["hasPreviousPage"], true);
+    assert_eq!(second["pageInfo"]["hasNextPage"], true);
+
+    let end = second["pageInfo"]["endCursor"].as_str().unwrap();
+    let third =
+        graphql(&client, &page(&format!(r#"first: 2, after: "{end}""#))).await["bakeries"].clone();
+    assert_eq!(names(&third), ["E"]);
+    assert_eq!(third["pageInfo"]["hasNextPage"], false);
+
+    let last = graphql(&client, &page("last: 2")).await["bakeries"].clone();
+    assert_eq!(names(&last), ["D", "E"]);
+    assert_eq!(last["pageInfo"]["hasPreviousPage"], true);
+    assert_eq!(last["pageInfo"]["hasNextPage"], false);
+
+    let start = last["pageInfo"]["startCursor"].as_str().unwrap();
+    let before = graphql(&client, &page(&format!(r#"last: 2, before: "{start}""#))).await
+        ["bakeries"]
+        .clone();
+    assert_eq!(names(&before), ["B", "C"]);
+    assert_eq!(before["pageInfo"]["hasNextPage"], true);
+
+    let body = execute(&client, &page(r#"first: 2, after: "not a cursor""#)).await;
+    assert!(body["errors"][0]["message"].is_string(), "{body}");
+}
+
+#[rocket::async_test]
+async fn filters_and_sorts() {
+    let client = client().await;
+    let ids = bakeries(
+        &client,
+        &[
+            ("Rye House", 0.3),
+            ("Spelt", 0.1),
+            ("Rye & Co", 0.2),
+            ("Sourdough", 0.2),
+        ],
+    )
+    .await;
+    for (name, bakery) in [("Ana", ids[0]), ("Ben", ids[0]), ("Cy", ids[3])] {
+        graphql(
+            &client,
+            &format!(r#"mutation {{ addChef(name: "{name}", bakeryId: {bakery}) {{ id }} }}"#),
+        )
+        .await;
+    }
+
+    let data = graphql(
+        &client,
+        r#"{ bakeries(filter: { nameContains: "Rye" }) { nodes { name } } }"#,
+    )
+    .await;
+    assert_eq!(names(&data["bakeries"]), ["Rye House", "Rye & Co"]);
+
+    let data = graphql(
+        &client,
+        "{ bakeries(filter: { minProfitMargin: 0.15, maxProfitMargin: 0.25 }) { nodes { name } } }",
+    )
+    .await;
+    assert_eq!(names(&data["bakeries"]), ["Rye & Co", "Sourdough"]);
+
+    // ties on the margin are broken by id, descending too
+    let sorted = "{ bakeries(first: 2, orderBy: { field: PROFIT_MARGIN, direction: DESC }) { nodes { name } pageInfo { endCursor } } }";
+    let data = graphql(&client, sorted).await;
+    assert_eq!(names(&data["bakeries"]), ["Rye House", "Sourdough"]);
+    let end = data["bakeries"]["pageInfo"]["endCursor"].as_str().unwrap();
+    let data = graphql(
+        &client,
+        &format!(
+            r#"{{ bakeries(after: "{end}", orderBy: {{ field: PROFIT_MARGIN, direction: DESC }}) {{ nodes {{ name }} }} }}"#
+        ),
+    )
+    .await;
+    assert_eq!(names(&data["bakeries"]), ["Rye & Co", "Spelt"]);
+
+    let data = graphql(
+        &client,
+        "{ bakeries(orderBy: { field: NAME }) { nodes { name } } }",
+    )
+    .await;
+    assert_eq!(
+        names(&data["bakeries"]),
+        ["Rye & Co", "Rye House", "Sourdough", "Spelt"]
+    );
+
+    let data = graphql(
+        &client,
+        &format!(
+            "{{ chefs(filter: {{ bakeryId: {} }}, orderBy: {{ field: NAME, direction: DESC }}) {{ nodes {{ name }} }} }}",
+            ids[0]
+        ),
+    )
+    .await;
+    assert_eq!(names(&data["chefs"]), ["Ben", "Ana"]);
+}
+
+#[rocket::async_test]
+async fn only_upgrades_websockets() {
+    let client = client().await;
+    let response = client.get("/graphql/ws").dispatch().await;
+    assert_eq!(response.status(), Status::UpgradeRequired);
+}
+
+/// A free port to launch on
+fn port() -> u16 {
+    TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port()
+}
+
+type Socket = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+async fn send(socket: &mut Socket, message: Value) {
+    socket
+        .send(Message::Text(message.to_string()))
+        .await
+        .unwrap();
+}
+
+async fn receive(socket: &mut Socket) -> Value {
+    loop {
+        let message = timeout(Duration::from_secs(5), socket.next())
+            .await
+            .expect("a message in time")
+            .unwrap()
+            .unwrap();
+        if let Message::Text(text) = message {
+            return serde_json::from_str(&text).unwrap();
+        }
+    }
+}
+
+#[rocket::async_test]
+async fn pushes_changes_over_a_websocket() {
+    let port = port();
+    let server = rocket(figment().merge(("port", port)))
+        .ignite()
+        .await
+        .unwrap();
+    let shutdown = server.shutdown();
+    rocket::tokio::spawn(server.launch());
+
+    let mut request = format!("ws://127.0.0.1:{port}/graphql/ws")
+        .into_client_request()
+        .unwrap();
+    request.headers_mut().insert(
+        "Sec-WebSocket-Protocol",
+        "graphql-transport-ws".parse().unwrap(),
+    );
+    let mut socket = None;
+    for _ in 0..50 {
+        match tokio_tungstenite::connect_async(request.clone()).await {
+            Ok((connected, response)) => {
+                assert_eq!(
+                    response.headers()["Sec-WebSocket-Protocol"],
+                    "graphql-transport-ws"
+                );
+                socket = Some(connected);
+                break;
+            }
+            // not listening yet
+            Err(_) => sleep(Duration::from_millis(20)).await,
+        }
+    }
+    let mut socket = socket.expect("the server to start");
+
+    send(
+        &mut socket,
+        serde_json::json!({ "type": "connection_init" }),
+    )
+    .await;
+    assert_eq!(receive(&mut socket).await["type"], "connection_ack");
+    send(
+        &mut socket,
+        serde_json::json!({
+            "id": "changes",
+            "type": "subscribe",
+            "payload": {
+                "query": "subscription { bakeryChanged { mutationType id bakery { name } } }"
+            }
+        }),
+    )
+    .await;
+    // let the subscription start listening before anything changes
+    sleep(Duration::from_millis(200)).await;
+
+    // operations on one socket run side by side, so each mutation finishes first
+    let mut changes = vec![];
+    for mutation in [
+        r#"mutation { addBakery(name: "Live") { id } }"#,
+        r#"mutation { updateBakery(id: 1, input: { name: "Still live" }) { id } }"#,
+        "mutation { deleteBakery(id: 1) }",
+    ] {
+        send(
+            &mut socket,
+            serde_json::json!({ "id": mutation, "type": "subscribe", "payload": { "query": mutation } }),
+        )
+        .await;
+        loop {
+            let message = receive(&mut socket).await;
+            if message["id"] == "changes" {
+                assert_eq!(message["type"], "next", "{message}");
+                changes.push(message["payload"]["data"]["bakeryChanged"].clone());
+            } else if message["type"] == "complete" {
+                break;
+            } else {
+                assert_eq!(message["payload"].get("errors"), None, "{message}");
+            }
+        }
+    }
+    while changes.len() < 3 {
+        changes.push(receive(&mut socket).await["payload"]["data"]["bakeryChanged"].clone());
+    }
+    assert_eq!(
+        changes,
+        [
+            serde_json::json!({ "mutationType": "CREATED", "id": 1, "bakery": { "name": "Live" } }),
+            serde_json::json!({ "mutationType": "UPDATED", "id": 1, "bakery": { "name": "Still live" } }),
+            serde_json::json!({ "mutationType": "DELETED", "id": 1, "bakery": null }),
+        ]
+    );
+
+    socket.close(None).await.unwrap();
+    shutdown.notify();
+}
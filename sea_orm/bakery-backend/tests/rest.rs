@@ -0,0 +1,269 @@
+//! The REST resources against in-memory SQLite, through Rocket's local client
+
+use bakery_backend::rocket;
+use rocket::http::{ContentType, Status};
+use rocket::local::asynchronous::{Client, LocalResponse};
+use rocket::serde::json::{serde_json, Value};
+
+async fn client() -> Client {
+    let figment = rocket::Config::figment()
+        .merge(("database.url", "sqlite::memory:"))
+        .merge(("database.seed", false));
+    Client::tracked(rocket(figment)).await.unwrap()
+}
+
+/// The status, and the body if there is one
+async fn call(client: &Client, method: &str, uri: &str, body: Option<Value>) -> (Status, Value) {
+    let mut request = client.req(method.parse().unwrap(), uri.to_owned());
+    if let Some(body) = body {
+        request = request.header(ContentType::JSON).body(body.to_string());
+    }
+    let response = request.dispatch().await;
+    let status = response.status();
+    let body = match response.into_string().await {
+        Some(body) if !body.is_empty() => serde_json::from_str(&body).unwrap(),
+        _ => Value::Null,
+    };
+    (status, body)
+}
+
+/// Asserts a problem-details response with `status`, returning its body
+async fn problem(response: LocalResponse<'_>, status: Status) -> Value {
+    assert_eq!(response.status(), status);
+    assert_eq!(
+        response.content_type(),
+        Some(ContentType::new("application", "problem+json"))
+    );
+    let body: Value = response.into_json().await.unwrap();
+    assert_eq!(body["status"], status.code);
+    assert_eq!(body["title"], status.reason_lossy());
+    body
+}
+
+#[rocket::async_test]
+async fn creates_reads_updates_and_deletes_bakeries() {
+    let client = client().await;
+    let response = client
+        .post("/bakeries")
+        .header(ContentType::JSON)
+        .body(r#"{ "name": "Crumbs", "profit_margin": 12.5 }"#)
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::Created);
+    assert_eq!(response.headers().get_one("Location"), Some("/bakeries/1"));
+    let created: Value = response.into_json().await.unwrap();
+    assert_eq!(
+        created,
+        serde_json::json!({ "id": 1, "name": "Crumbs", "profit_margin": 12.5 })
+    );
+    call(
+        &client,
+        "POST",
+        "/bakeries",
+        Some(serde_json::json!({ "name": "Loaf" })),
+    )
+    .await;
+
+    let (status, list) = call(&client, "GET", "/bakeries", None).await;
+    assert_eq!(status, Status::Ok);
+    assert_eq!(
+        list,
+        serde_json::json!([
+            { "id": 1, "name": "Crumbs", "profit_margin": 12.5 },
+            { "id": 2, "name": "Loaf", "profit_margin": 0.0 }
+        ])
+    );
+
+    let (status, updated) = call(
+        &client,
+        "PATCH",
+        "/bakeries/2",
+        Some(serde_json::json!({ "profit_margin": -3.0 })),
+    )
+    .await;
+    assert_eq!(status, Status::Ok);
+    assert_eq!(
+        updated,
+        serde_json::json!({ "id": 2, "name": "Loaf", "profit_margin": -3.0 })
+    );
+    assert_eq!(call(&client, "GET", "/bakeries/2", None).await.1, updated);
+
+    let (status, body) = call(&client, "DELETE", "/bakeries/2", None).await;
+    assert_eq!((status, body), (Status::NoContent, Value::Null));
+    let body = problem(client.get("/bakeries/2").dispatch().await, Status::NotFound).await;
+    assert_eq!(body["detail"], "no bakery with id 2");
+    problem(
+        client.delete("/bakeries/2").dispatch().await,
+        Status::NotFound,
+    )
+    .await;
+    let response = client
+        .patch("/bakeries/2")
+        .header(ContentType::JSON)
+        .body(r#"{ "name": "Gone" }"#)
+        .dispatch()
+        .await;
+    problem(response, Status::NotFound).await;
+}
+
+#[rocket::async_test]
+async fn creates_reads_updates_and_deletes_chefs() {
+    let client = client().await;
+    for name in ["Crumbs", "Loaf"] {
+        call(
+            &client,
+            "POST",
+            "/bakeries",
+            Some(serde_json::json!({ "name": name })),
+        )
+        .await;
+    }
+    let (status, chef) = call(
+        &client,
+        "POST",
+        "/chefs",
+        Some(serde_json::json!({ "name": "Pat", "bakery_id": 1, "contact_details": { "phone": "555" } })),
+    )
+    .await;
+    assert_eq!(status, Status::Created);
+    assert_eq!(
+        chef,
+        serde_json::json!({ "id": 1, "name": "Pat", "contact_details": { "phone": "555" }, "bakery_id": 1 })
+    );
+    assert_eq!(
+        call(&client, "GET", "/bakeries/1/chefs", None).await.1,
+        serde_json::json!([chef])
+    );
+
+    // left out, the contact details stay; null, they go
+    let (_, moved) = call(
+        &client,
+        "PATCH",
+        "/chefs/1",
+        Some(serde_json::json!({ "bakery_id": 2 })),
+    )
+    .await;
+    assert_eq!(
+        moved["contact_details"],
+        serde_json::json!({ "phone": "555" })
+    );
+    assert_eq!(moved["bakery_id"], 2);
+    let (_, cleared) = call(
+        &client,
+        "PATCH",
+        "/chefs/1",
+        Some(serde_json::json!({ "name": "Sam", "contact_details": null })),
+    )
+    .await;
+    assert_eq!(
+        cleared,
+        serde_json::json!({ "id": 1, "name": "Sam", "contact_details": null, "bakery_id": 2 })
+    );
+    assert_eq!(
+        call(&client, "GET", "/bakeries/1/chefs", None).await.1,
+        serde_json::json!([])
+    );
+    assert_eq!(
+        call(&client, "GET", "/chefs", None).await.1,
+        serde_json::json!([cleared])
+    );
+
+    // a bakery with chefs stays until they've gone
+    let body = problem(
+        client.delete("/bakeries/2").dispatch().await,
+        Status::Conflict,
+    )
+    .await;
+    assert_eq!(body["detail"], "bakery 2 still has 1 chefs");
+    let (status, _) = call(&client, "DELETE", "/chefs/1", None).await;
+    assert_eq!(status, Status::NoContent);
+    let (status, _) = call(&client, "DELETE", "/bakeries/2", None).await;
+    assert_eq!(status, Status::NoContent);
+    problem(client.get("/chefs/1").dispatch().await, Status::NotFound).await;
+    problem(
+        client.get("/bakeries/2/chefs").dispatch().await,
+        Status::NotFound,
+    )
+    .await;
+}
+
+#[rocket::async_test]
+async fn rejects_invalid_bodies() {
+    let client = client().await;
+    let post = |uri: &'static str, body: &'static str| {
+        client
+            .post(uri)
+            .header(ContentType::JSON)
+            .body(body)
+            .dispatch()
+    };
+
+    let body = problem(
+        post("/bakeries", r#"{ "name": "  ", "profit_margin": 250 }"#).await,
+        Status::UnprocessableEntity,
+    )
+    .await;
+    assert_eq!(
+        body["errors"],
+        serde_json::json!([
+            { "field": "name", "message": "must not be blank" },
+            { "field": "profit_margin", "message": "must be between -100 and 100" }
+        ])
+    );
+    let long = format!(r#"{{ "name": "{}" }}"#, "x".repeat(256));
+    let response = client
+        .post("/bakeries")
+        .header(ContentType::JSON)
+        .body(long)
+        .dispatch()
+        .await;
+    let body = problem(response, Status::UnprocessableEntity).await;
+    assert_eq!(
+        body["errors"][0]["message"],
+        "must be at most 255 characters"
+    );
+
+    // not JSON at all, then JSON of the wrong shape
+    problem(post("/bakeries", "{ name: ").await, Status::BadRequest).await;
+    problem(
+        post("/bakeries", r#"{ "name": 7 }"#).await,
+        Status::UnprocessableEntity,
+    )
+    .await;
+    problem(
+        post("/bakeries", r#"{ "name": "Crumbs", "owner": "me" }"#).await,
+        Status::UnprocessableEntity,
+    )
+    .await;
+
+    let body = problem(
+        post("/chefs", r#"{ "name": "", "bakery_id": 9 }"#).await,
+        Status::UnprocessableEntity,
+    )
+    .await;
+    assert_eq!(
+        body["errors"],
+        serde_json::json!([
+            { "field": "name", "message": "must not be blank" },
+            { "field": "bakery_id", "message": "no bakery with id 9" }
+        ])
+    );
+    // nothing was written
+    assert_eq!(
+        call(&client, "GET", "/bakeries", None).await.1,
+        serde_json::json!([])
+    );
+}
+
+#[rocket::async_test]
+async fn answers_what_no_route_handles_with_problems() {
+    let client = client().await;
+    let body = problem(client.get("/pastries").dispatch().await, Status::NotFound).await;
+    assert_eq!(body["detail"], "nothing at GET /pastries");
+    // an id that isn't a number fails the route's guard
+    problem(
+        client.get("/bakeries/first").dispatch().await,
+        Status::UnprocessableEntity,
+    )
+    .await;
+}
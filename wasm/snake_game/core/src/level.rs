@@ -0,0 +1,352 @@
+use std::fmt;
+use std::str::FromStr;
+
+/// frames per second of the first level when it doesn't set its own `speed`
+pub const BASE_FPS: u32 = 3;
+
+/// A square board: walls kill, portals teleport to their twin, spawns place the snakes.
+///
+/// Text format - optional `key = value` header lines followed by the grid:
+/// ```text
+/// name = Garden
+/// speed = 4
+/// goal = 5
+/// #######
+/// #..1.a#
+/// #.....#
+/// #..#..#
+/// #.....#
+/// #a.2..#
+/// #######
+/// ```
+/// `.` empty, `#` wall, `1`/`2` spawn of player one (heading right) / two (heading left),
+/// a letter marks a portal and must appear exactly twice.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Level {
+    pub name: String,
+    pub width: usize,
+    /// frames per second - defaults to speeding up with every level of a [`Campaign`]
+    pub speed: Option<u32>,
+    /// points that clear the level - without one it is played until the board is full
+    pub goal: Option<usize>,
+    pub walls: Vec<usize>,
+    pub portals: Vec<(usize, usize)>,
+    pub spawns: Vec<usize>,
+}
+
+impl Level {
+    /// The classic board: no walls, no portals, no goal
+    pub fn open(width: usize, spawn: usize) -> Level {
+        let row = spawn / width;
+        let other = ((row + width / 2) % width) * width + spawn % width;
+        Level {
+            name: String::from("Classic"),
+            width,
+            speed: None,
+            goal: None,
+            walls: vec![],
+            portals: vec![],
+            spawns: vec![spawn, other],
+        }
+    }
+
+    pub fn size(&self) -> usize {
+        self.width * self.width
+    }
+
+    pub fn is_wall(&self, idx: usize) -> bool {
+        self.walls.contains(&idx)
+    }
+
+    /// where entering `idx` takes you - `None` if it isn't a portal
+    pub fn portal_exit(&self, idx: usize) -> Option<usize> {
+        self.portals.iter().find_map(|&(a, b)| match idx {
+            _ if idx == a => Some(b),
+            _ if idx == b => Some(a),
+            _ => None,
+        })
+    }
+
+    /// cells a snake or reward can occupy
+    pub fn is_free(&self, idx: usize) -> bool {
+        !self.is_wall(idx) && self.portal_exit(idx).is_none()
+    }
+
+    fn cell_char(&self, idx: usize) -> char {
+        if self.is_wall(idx) {
+            return '#';
+        }
+        if let Some(n) = self.spawns.iter().position(|&s| s == idx) {
+            return char::from(b'1' + n as u8);
+        }
+        match self.portals.iter().position(|&(a, b)| a == idx || b == idx) {
+            Some(n) => char::from(b'a' + n as u8),
+            None => '.',
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum LevelError {
+    InvalidHeader(String),
+    NotSquare { rows: usize, width: usize },
+    UnknownCell { cell: char, row: usize, col: usize },
+    UnpairedPortal(char),
+    MissingSpawn(usize),
+    NoPlayers,
+}
+
+impl fmt::Display for LevelError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LevelError::InvalidHeader(line) => write!(f, "invalid level header: `{line}`"),
+            LevelError::NotSquare { rows, width } => {
+                write!(f, "level must be square: {rows} rows of {width} cells")
+            }
+            LevelError::UnknownCell { cell, row, col } => {
+                write!(f, "unknown cell `{cell}` at row {row}, column {col}")
+            }
+            LevelError::UnpairedPortal(p) => write!(f, "portal `{p}` must appear exactly twice"),
+            LevelError::MissingSpawn(player) => write!(f, "no spawn for player {}", player + 1),
+            LevelError::NoPlayers => write!(f, "a game needs at least one player"),
+        }
+    }
+}
+
+impl std::error::Error for LevelError {}
+
+impl FromStr for Level {
+    type Err = LevelError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut level = Level {
+            name: String::from("Untitled"),
+            width: 0,
+            speed: None,
+            goal: None,
+            walls: vec![],
+            portals: vec![],
+            spawns: vec![],
+        };
+        let lines = s.lines().map(str::trim).filter(|l| !l.is_empty());
+        let (header, grid): (Vec<&str>, Vec<&str>) = lines.partition(|l| l.contains('='));
+
+        for line in header {
+            let invalid = || LevelError::InvalidHeader(line.to_string());
+            let (key, value) = line.split_once('=').ok_or_else(invalid)?;
+            let value = value.trim();
+            match key.trim() {
+                "name" => level.name = value.to_string(),
+                "speed" => level.speed = Some(value.parse().map_err(|_| invalid())?),
+                "goal" => level.goal = Some(value.parse().map_err(|_| invalid())?),
+                _ => return Err(invalid()),
+            }
+        }
+
+        level.width = grid.len();
+        let mut spawns = [None; 2];
+        let mut portals: Vec<(char, Vec<usize>)> = vec![];
+        for (row, line) in grid.iter().enumerate() {
+            if line.chars().count() != level.width {
+                return Err(LevelError::NotSquare {
+                    rows: grid.len(),
+                    width: line.chars().count(),
+                });
+            }
+            for (col, cell) in line.chars().enumerate() {
+                let idx = row * level.width + col;
+                match cell {
+                    '.' => {}
+                    '#' => level.walls.push(idx),
+                    '1' | '2' => spawns[cell as usize - '1' as usize] = Some(idx),
+                    'a'..='z' => match portals.iter_mut().find(|(p, _)| *p == cell) {
+                        Some((_, cells)) => cells.push(idx),
+                        None => portals.push((cell, vec![idx])),
+                    },
+                    _ => return Err(LevelError::UnknownCell { cell, row, col }),
+                }
+            }
+        }
+
+        portals.sort_by_key(|(p, _)| *p);
+        for (p, cells) in portals {
+            match cells[..] {
+                [a, b] => level.portals.push((a, b)),
+                _ => return Err(LevelError::UnpairedPortal(p)),
+            }
+        }
+        level.spawns = match spawns {
+            [Some(one), Some(two)] => vec![one, two],
+            [Some(one), None] => vec![one],
+            [None, _] => return Err(LevelError::MissingSpawn(0)),
+        };
+        Ok(level)
+    }
+}
+
+impl fmt::Display for Level {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "name = {}", self.name)?;
+        if let Some(speed) = self.speed {
+            writeln!(f, "speed = {speed}")?;
+        }
+        if let Some(goal) = self.goal {
+            writeln!(f, "goal = {goal}")?;
+        }
+        for row in 0..self.width {
+            let line: String = (0..self.width)
+                .map(|col| self.cell_char(row * self.width + col))
+                .collect();
+            writeln!(f, "{line}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Parses several levels separated by `---` lines
+pub fn parse_levels(s: &str) -> Result<Vec<Level>, LevelError> {
+    let mut levels = vec![];
+    let mut current = String::new();
+    for line in s.lines() {
+        if line.trim() == "---" {
+            levels.push(current.parse()?);
+            current.clear();
+        } else {
+            current.push_str(line);
+            current.push('\n');
+        }
+    }
+    if !current.trim().is_empty() {
+        levels.push(current.parse()?);
+    }
+    Ok(levels)
+}
+
+/// An ordered run of levels, each one faster than the last
+#[derive(Clone, Debug)]
+pub struct Campaign {
+    levels: Vec<Level>,
+    current: usize,
+}
+
+impl Campaign {
+    pub fn new(levels: Vec<Level>) -> Campaign {
+        assert!(!levels.is_empty(), "a campaign needs at least one level");
+        Campaign { levels, current: 0 }
+    }
+
+    pub fn level(&self) -> &Level {
+        &self.levels[self.current]
+    }
+
+    /// zero based index of the current level
+    pub fn index(&self) -> usize {
+        self.current
+    }
+
+    pub fn fps(&self) -> u32 {
+        self.level().speed.unwrap_or(BASE_FPS + self.current as u32)
+    }
+
+    /// moves on to the next level - `false` once the last level is done
+    pub fn advance(&mut self) -> bool {
+        if self.current + 1 < self.levels.len() {
+            self.current += 1;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GARDEN: &str = "name = Garden
+speed = 4
+goal = 5
+#######
+#..1.a#
+#.....#
+#..#..#
+#.....#
+#a.2..#
+#######
+";
+
+    #[test]
+    fn parses_and_displays() {
+        let level: Level = GARDEN.parse().unwrap();
+        assert_eq!(level.name, "Garden");
+        assert_eq!(level.width, 7);
+        assert_eq!(level.speed, Some(4));
+        assert_eq!(level.goal, Some(5));
+        assert_eq!(level.spawns, vec![10, 38]);
+        assert_eq!(level.portals, vec![(12, 36)]);
+        assert!(level.is_wall(24));
+        assert_eq!(level.portal_exit(36), Some(12));
+        assert!(!level.is_free(12));
+
+        assert_eq!(level.to_string(), GARDEN);
+    }
+
+    #[test]
+    fn open_level_round_trips() {
+        let level = Level::open(6, 14);
+        assert_eq!(level.spawns, vec![14, 32]);
+        assert_eq!(level.to_string().parse::<Level>(), Ok(level));
+    }
+
+    #[test]
+    fn rejects_bad_levels() {
+        assert_eq!(
+            "size = 3\n.1.\n...\n...".parse::<Level>(),
+            Err(LevelError::InvalidHeader("size = 3".to_string()))
+        );
+        assert_eq!(
+            ".1.\n..\n...".parse::<Level>(),
+            Err(LevelError::NotSquare { rows: 3, width: 2 })
+        );
+        assert_eq!(
+            ".1.\n.?.\n...".parse::<Level>(),
+            Err(LevelError::UnknownCell { cell: '?', row: 1, col: 1 })
+        );
+        assert_eq!(
+            ".1.\n.a.\n...".parse::<Level>(),
+            Err(LevelError::UnpairedPortal('a'))
+        );
+        assert_eq!("...\n...\n..2".parse::<Level>(), Err(LevelError::MissingSpawn(0)));
+    }
+
+    /// snakes start three cells long, trailing away from the way they head
+    fn starts_clear(level: &Level) -> bool {
+        let game = crate::Game::from_level(level.clone(), 2, crate::SeededRng::new(1)).unwrap();
+        (0..2).all(|p| game.player_cells(p).iter().all(|cell| level.is_free(cell.0)))
+    }
+
+    #[test]
+    fn shipped_levels_parse() {
+        let levels = parse_levels(include_str!("../../www/levels.txt")).unwrap();
+        assert_eq!(levels.len(), 3);
+        assert!(levels.iter().all(|l| l.spawns.len() == 2));
+        assert!(levels.iter().all(starts_clear));
+        assert!(starts_clear(&GARDEN.parse().unwrap()));
+    }
+
+    #[test]
+    fn campaign_speeds_up() {
+        let levels = parse_levels(&format!("1..\n...\n...\n---\n{GARDEN}---\n.1\n..")).unwrap();
+        assert_eq!(levels.len(), 3);
+
+        let mut campaign = Campaign::new(levels);
+        assert_eq!(campaign.fps(), BASE_FPS);
+        assert!(campaign.advance());
+        // Garden sets its own speed
+        assert_eq!(campaign.fps(), 4);
+        assert!(campaign.advance());
+        assert_eq!(campaign.fps(), BASE_FPS + 2);
+        assert!(!campaign.advance());
+        assert_eq!(campaign.index(), 2);
+    }
+}
@@ -0,0 +1,691 @@
+//! Platform independent snake game logic - no wasm, no JS.
+//! Randomness is injected through [`Rng`] so games can be seeded, replayed and tested natively.
+
+use std::str::FromStr;
+
+mod level;
+mod replay;
+mod rng;
+mod scores;
+
+pub use level::{parse_levels, Campaign, Level, LevelError, BASE_FPS};
+pub use replay::{Input, Replay, ReplayError};
+pub use rng::{Rng, SeededRng};
+pub use scores::{HighScore, HighScores, HIGH_SCORE_CAPACITY};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum GameStatus {
+    Won,
+    Lost,
+    Played,
+    /// every snake died on the same step
+    Draw,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Direction {
+    Up,
+    Right,
+    Down,
+    Left,
+}
+
+impl FromStr for Direction {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "Up" => Ok(Direction::Up),
+            "Right" => Ok(Direction::Right),
+            "Down" => Ok(Direction::Down),
+            "Left" => Ok(Direction::Left),
+            _ => Err(format!("unknown direction: {s}")),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SnakeCell(pub usize);
+
+struct Snake {
+    body: Vec<SnakeCell>,
+    direction: Direction,
+    next_cell: Option<SnakeCell>,
+    alive: bool,
+    points: usize,
+}
+
+impl Snake {
+    // the tail trails behind the head, wrapping around within the row
+    fn new(spawn_index: usize, size: usize, width: usize, direction: Direction) -> Snake {
+        let row_start = spawn_index - spawn_index % width;
+        let col = spawn_index % width;
+        let body = (0..size)
+            .map(|i| match direction {
+                Direction::Left => (col + i) % width,
+                _ => (col + width * size - i) % width,
+            })
+            .map(|col| SnakeCell(row_start + col))
+            .collect();
+
+        Snake {
+            body,
+            direction,
+            next_cell: None,
+            alive: true,
+            points: 0,
+        }
+    }
+
+    fn head(&self) -> SnakeCell {
+        self.body[0]
+    }
+}
+
+pub struct Game<R: Rng = SeededRng> {
+    level: Level,
+    snakes: Vec<Snake>,
+    reward_cell: Option<usize>,
+    status: Option<GameStatus>,
+    winner: Option<usize>,
+    rng: R,
+    tick: u64,
+    inputs: Vec<Input>,
+}
+
+impl Game<SeededRng> {
+    /// A deterministic game: the same seed and inputs always play out the same way
+    pub fn with_seed(width: usize, snake_idx: usize, seed: u64) -> Game<SeededRng> {
+        Game::new(width, snake_idx, SeededRng::new(seed))
+    }
+}
+
+impl<R: Rng> Game<R> {
+    /// single player on the classic open board
+    pub fn new(width: usize, snake_idx: usize, rng: R) -> Game<R> {
+        Game::from_level(Level::open(width, snake_idx), 1, rng)
+            .expect("open levels have a spawn for both players")
+    }
+
+    pub fn from_level(level: Level, players: usize, mut rng: R) -> Result<Game<R>, LevelError> {
+        if players == 0 {
+            return Err(LevelError::NoPlayers);
+        }
+        if let Some(missing) = (0..players).find(|&p| p >= level.spawns.len()) {
+            return Err(LevelError::MissingSpawn(missing));
+        }
+        let directions = [Direction::Right, Direction::Left];
+        let snakes: Vec<Snake> = (0..players)
+            .map(|p| Snake::new(level.spawns[p], 3, level.width, directions[p % 2]))
+            .collect();
+
+        Ok(Game {
+            reward_cell: gen_reward_cell(&mut rng, &level, &snakes),
+            level,
+            snakes,
+            status: None,
+            winner: None,
+            rng,
+            tick: 0,
+            inputs: vec![],
+        })
+    }
+
+    pub fn level(&self) -> &Level {
+        &self.level
+    }
+
+    pub fn players(&self) -> usize {
+        self.snakes.len()
+    }
+
+    pub fn points(&self) -> usize {
+        self.player_points(0)
+    }
+
+    /// 0 for a player who isn't in the game
+    pub fn player_points(&self, player: usize) -> usize {
+        self.snakes.get(player).map_or(0, |snake| snake.points)
+    }
+
+    pub fn width(&self) -> usize {
+        self.level.width
+    }
+
+    pub fn reward_cell(&self) -> Option<usize> {
+        self.reward_cell
+    }
+
+    /// the first player's - every game has one
+    pub fn snake_head_idx(&self) -> usize {
+        self.snakes[0].head().0
+    }
+
+    pub fn snake_cells(&self) -> &[SnakeCell] {
+        self.player_cells(0)
+    }
+
+    /// empty for a player who isn't in the game
+    pub fn player_cells(&self, player: usize) -> &[SnakeCell] {
+        self.snakes.get(player).map_or(&[], |snake| &snake.body)
+    }
+
+    /// the first player's - every game has one
+    pub fn snake_length(&self) -> usize {
+        self.snakes[0].body.len()
+    }
+
+    pub fn game_status(&self) -> Option<GameStatus> {
+        self.status
+    }
+
+    /// the player who won - the only one in single player games
+    pub fn winner(&self) -> Option<usize> {
+        self.winner
+    }
+
+    /// number of steps taken since the game started
+    pub fn tick(&self) -> u64 {
+        self.tick
+    }
+
+    /// The seed and input log so far - only seeded games can be replayed
+    pub fn replay(&self) -> Option<Replay> {
+        Some(Replay {
+            seed: self.rng.seed()?,
+            players: self.players(),
+            steps: self.tick,
+            inputs: self.inputs.clone(),
+            level: self.level.clone(),
+        })
+    }
+
+    pub fn change_snake_dir(&mut self, direction: Direction) {
+        self.change_player_dir(0, direction);
+    }
+
+    /// ignored for a player who isn't in the game
+    pub fn change_player_dir(&mut self, player: usize, direction: Direction) {
+        let Some(snake) = self.snakes.get(player) else {
+            return;
+        };
+        let next_cell = self.gen_next_snake_cell(snake, direction);
+        let snake = &mut self.snakes[player];
+        // can't turn back into its own neck
+        if snake.body[1] == next_cell {
+            return;
+        }
+        snake.next_cell = Some(next_cell);
+        snake.direction = direction;
+        self.inputs.push(Input {
+            tick: self.tick,
+            player,
+            direction,
+        });
+    }
+
+    pub fn start_game(&mut self) {
+        self.status = Some(GameStatus::Played);
+    }
+
+    pub fn step(&mut self) {
+        if self.status != Some(GameStatus::Played) {
+            return;
+        }
+        self.tick += 1;
+
+        // move every snake first so collisions see where everyone ended up
+        for p in 0..self.snakes.len() {
+            let direction = self.snakes[p].direction;
+            let head = match self.snakes[p].next_cell.take() {
+                Some(cell) => cell,
+                None => self.gen_next_snake_cell(&self.snakes[p], direction),
+            };
+            let body = &mut self.snakes[p].body;
+            body.rotate_right(1);
+            body[0] = head;
+        }
+
+        let crashed: Vec<bool> = (0..self.snakes.len()).map(|p| self.crashed(p)).collect();
+        for (snake, crashed) in self.snakes.iter_mut().zip(crashed) {
+            snake.alive = !crashed;
+        }
+
+        for p in 0..self.snakes.len() {
+            if self.snakes[p].alive && self.reward_cell == Some(self.snakes[p].head().0) {
+                self.eat_reward(p);
+            }
+        }
+
+        self.update_status();
+    }
+
+    fn crashed(&self, player: usize) -> bool {
+        let head = self.snakes[player].head();
+        self.level.is_wall(head.0)
+            || self.snakes[player].body[1..].contains(&head)
+            || self
+                .snakes
+                .iter()
+                .enumerate()
+                .any(|(p, other)| p != player && other.body.contains(&head))
+    }
+
+    fn eat_reward(&mut self, player: usize) {
+        let occupied: usize = self.snakes.iter().map(|s| s.body.len()).sum();
+        let capacity = (0..self.level.size()).filter(|&i| self.level.is_free(i)).count();
+        if occupied < capacity {
+            self.snakes[player].points += 1;
+            self.reward_cell = gen_reward_cell(&mut self.rng, &self.level, &self.snakes);
+        } else {
+            // nowhere left to put a reward - the board is full
+            self.reward_cell = None;
+            self.winner.get_or_insert(player);
+        }
+        let snake = &mut self.snakes[player];
+        snake.body.push(snake.body[1]);
+        if self.level.goal.is_some_and(|goal| snake.points >= goal) {
+            self.winner.get_or_insert(player);
+        }
+    }
+
+    fn update_status(&mut self) {
+        let alive: Vec<usize> = (0..self.snakes.len()).filter(|&p| self.snakes[p].alive).collect();
+        let status = match (self.snakes.len(), alive.as_slice()) {
+            (_, []) if self.snakes.len() > 1 => GameStatus::Draw,
+            (_, []) => GameStatus::Lost,
+            (1, _) if self.winner.is_some() => GameStatus::Won,
+            (1, _) => return,
+            // last one standing
+            (_, [survivor]) => {
+                self.winner.get_or_insert(*survivor);
+                GameStatus::Won
+            }
+            _ if self.winner.is_some() => GameStatus::Won,
+            _ => return,
+        };
+        self.status = Some(status);
+    }
+
+    fn gen_next_snake_cell(&self, snake: &Snake, direction: Direction) -> SnakeCell {
+        let width = self.level.width;
+        let idx = snake.head().0;
+        let (row, col) = (idx / width, idx % width);
+        let (row, col) = match direction {
+            Direction::Right => (row, (col + 1) % width),
+            Direction::Left => (row, (col + width - 1) % width),
+            Direction::Up => ((row + width - 1) % width, col),
+            Direction::Down => ((row + 1) % width, col),
+        };
+        let next = row * width + col;
+        // stepping onto a portal comes out of its twin
+        SnakeCell(self.level.portal_exit(next).unwrap_or(next))
+    }
+}
+
+fn gen_reward_cell(rng: &mut impl Rng, level: &Level, snakes: &[Snake]) -> Option<usize> {
+    let taken = |idx: usize| !level.is_free(idx) || snakes.iter().any(|s| s.body.contains(&SnakeCell(idx)));
+    if (0..level.size()).all(taken) {
+        return None;
+    }
+    loop {
+        let reward_cell = rng.gen_below(level.size());
+        if !taken(reward_cell) {
+            return Some(reward_cell);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// always hands out the queued values, then repeats the last one
+    struct FixedRng(Vec<usize>);
+
+    impl Rng for FixedRng {
+        fn gen_below(&mut self, max: usize) -> usize {
+            let value = if self.0.len() > 1 { self.0.remove(0) } else { self.0[0] };
+            value % max
+        }
+    }
+
+    fn cells(game: &Game<impl Rng>) -> Vec<usize> {
+        game.snake_cells().iter().map(|c| c.0).collect()
+    }
+
+    #[test]
+    fn spawns_trailing_left() {
+        let game = Game::with_seed(5, 12, 1);
+        assert_eq!(cells(&game), vec![12, 11, 10]);
+
+        // tail wraps within the row rather than spilling into the previous one
+        let game = Game::with_seed(5, 6, 1);
+        assert_eq!(cells(&game), vec![6, 5, 9]);
+    }
+
+    #[test]
+    fn wraps_around_every_edge() {
+        let mut game = Game::with_seed(5, 14, 1);
+        game.start_game();
+        game.step();
+        assert_eq!(game.snake_head_idx(), 10);
+
+        game.change_snake_dir(Direction::Down);
+        game.step();
+        game.step();
+        game.step();
+        assert_eq!(game.snake_head_idx(), 0);
+
+        game.change_snake_dir(Direction::Left);
+        game.step();
+        assert_eq!(game.snake_head_idx(), 4);
+
+        game.change_snake_dir(Direction::Up);
+        game.step();
+        assert_eq!(game.snake_head_idx(), 24);
+    }
+
+    #[test]
+    fn ignores_turning_back() {
+        let mut game = Game::with_seed(5, 12, 1);
+        game.start_game();
+        game.change_snake_dir(Direction::Left);
+        game.step();
+        assert_eq!(game.snake_head_idx(), 13);
+        assert!(game.replay().unwrap().inputs.is_empty());
+    }
+
+    #[test]
+    fn collides_with_own_body() {
+        let mut game = Game::with_seed(5, 12, 1);
+        // curled up: heading right with the body looping round below the head
+        game.snakes[0].body = [12, 11, 16, 17, 18, 13].into_iter().map(SnakeCell).collect();
+        game.start_game();
+
+        game.change_snake_dir(Direction::Down);
+        game.step();
+        assert_eq!(game.snake_head_idx(), 17);
+        assert_eq!(game.game_status(), Some(GameStatus::Lost));
+
+        // a lost game no longer moves
+        game.step();
+        assert_eq!(game.snake_head_idx(), 17);
+    }
+
+    #[test]
+    fn reward_never_spawns_on_snake() {
+        // the first two picks land on the snake and are rejected
+        let game = Game::new(5, 12, FixedRng(vec![12, 11, 7]));
+        assert_eq!(game.reward_cell(), Some(7));
+    }
+
+    #[test]
+    fn eating_reward_grows_snake_and_respawns() {
+        let mut game = Game::new(5, 12, FixedRng(vec![13, 20]));
+        game.start_game();
+        game.step();
+
+        assert_eq!(game.points(), 1);
+        assert_eq!(game.snake_length(), 4);
+        assert_eq!(cells(&game), vec![13, 12, 11, 12]);
+        assert_eq!(game.reward_cell(), Some(20));
+    }
+
+    #[test]
+    fn filling_the_board_wins() {
+        let mut game = Game::with_seed(2, 1, 1);
+        // just ate - the duplicated tail cell moves out next step
+        game.snakes[0].body = [1, 0, 2, 2].into_iter().map(SnakeCell).collect();
+        game.reward_cell = Some(3);
+        game.start_game();
+
+        game.change_snake_dir(Direction::Down);
+        game.step();
+        assert_eq!(game.game_status(), Some(GameStatus::Won));
+        assert_eq!(game.reward_cell(), None);
+    }
+
+    #[test]
+    fn same_seed_same_rewards() {
+        let rewards = |seed| -> Vec<Option<usize>> {
+            (0..10).map(|w| Game::with_seed(8 + w, 4, seed).reward_cell()).collect()
+        };
+        assert_eq!(rewards(42), rewards(42));
+        assert_ne!(rewards(42), rewards(43));
+    }
+
+    #[test]
+    fn replay_round_trip() {
+        let mut game = Game::with_seed(8, 10, 7);
+        game.start_game();
+        let moves = [
+            (3, Direction::Down),
+            (5, Direction::Left),
+            (9, Direction::Up),
+            (12, Direction::Right),
+        ];
+        for tick in 0..20 {
+            for (_, dir) in moves.iter().filter(|(t, _)| *t == tick) {
+                game.change_snake_dir(*dir);
+            }
+            game.step();
+        }
+
+        let text = game.replay().unwrap().to_string();
+        let replay: Replay = text.parse().unwrap();
+        assert_eq!(replay.inputs.len(), moves.len());
+
+        let replayed = replay.run().unwrap();
+        assert_eq!(cells(&replayed), cells(&game));
+        assert_eq!(replayed.reward_cell(), game.reward_cell());
+        assert_eq!(replayed.points(), game.points());
+        assert_eq!(replayed.game_status(), game.game_status());
+    }
+
+    #[test]
+    fn unseeded_games_cannot_replay() {
+        let game = Game::new(5, 12, FixedRng(vec![0]));
+        assert!(game.replay().is_none());
+    }
+
+    #[test]
+    fn rejects_bad_replays() {
+        assert_eq!("seed 1".parse::<Replay>(), Err(ReplayError::MissingHeader));
+        assert_eq!(
+            "snake-replay 2\nseed 1\nsteps 2".parse::<Replay>(),
+            Err(ReplayError::MissingField("players"))
+        );
+        assert_eq!(
+            "snake-replay 2\nseed 1\ninput 2 0 Sideways".parse::<Replay>(),
+            Err(ReplayError::InvalidLine(3, "input 2 0 Sideways".to_string()))
+        );
+        // a player the game doesn't have
+        assert_eq!(
+            "snake-replay 2\nseed 1\nplayers 1\nsteps 2\ninput 1 1 Up\nlevel\n1.\n..".parse::<Replay>(),
+            Err(ReplayError::InvalidLine(5, "input 1 1 Up".to_string()))
+        );
+        // inputs going back in time
+        assert_eq!(
+            "snake-replay 2\nseed 1\ninput 4 0 Up\ninput 4 0 Left\ninput 2 0 Down".parse::<Replay>(),
+            Err(ReplayError::InvalidLine(5, "input 2 0 Down".to_string()))
+        );
+        assert_eq!(
+            "snake-replay 2\nseed 1\nplayers 0".parse::<Replay>(),
+            Err(ReplayError::InvalidLine(3, "players 0".to_string()))
+        );
+        assert_eq!(
+            "snake-replay 2\nseed 1\nlevel\n..\n.".parse::<Replay>(),
+            Err(ReplayError::Level(LevelError::NotSquare { rows: 2, width: 1 }))
+        );
+    }
+
+    fn level(text: &str) -> Level {
+        text.parse().unwrap()
+    }
+
+    #[test]
+    fn walls_kill() {
+        let level = level(
+            "
+            .....
+            ..1.#
+            .....
+            .....
+            .....",
+        );
+        let mut game = Game::from_level(level, 1, SeededRng::new(1)).unwrap();
+        game.start_game();
+        game.step();
+        assert_eq!(game.game_status(), Some(GameStatus::Played));
+        game.step();
+        assert_eq!(game.snake_head_idx(), 9);
+        assert_eq!(game.game_status(), Some(GameStatus::Lost));
+    }
+
+    #[test]
+    fn portals_teleport() {
+        let level = level(
+            "
+            .....
+            .1a..
+            .....
+            .....
+            ...a.",
+        );
+        let mut game = Game::from_level(level, 1, SeededRng::new(1)).unwrap();
+        game.start_game();
+        game.step();
+        // stepped onto the portal at 7, came out of its twin at 23
+        assert_eq!(game.snake_head_idx(), 23);
+        game.step();
+        assert_eq!(game.snake_head_idx(), 24);
+    }
+
+    #[test]
+    fn reward_avoids_walls_and_portals() {
+        let level = level(
+            "
+            #a.
+            .1.
+            #a#",
+        );
+        // the snake covers the middle row, so the top right corner is the only free cell
+        let game = Game::from_level(level, 1, FixedRng(vec![0, 1, 6, 7, 8, 4, 2])).unwrap();
+        assert_eq!(cells(&game), vec![4, 3, 5]);
+        assert_eq!(game.reward_cell(), Some(2));
+    }
+
+    #[test]
+    fn reaching_goal_wins() {
+        let level = level(
+            "
+            goal = 1
+            ...
+            .1.
+            ...",
+        );
+        let mut game = Game::from_level(level, 1, FixedRng(vec![0])).unwrap();
+        game.reward_cell = Some(5);
+        game.start_game();
+        game.step();
+        assert_eq!(game.points(), 1);
+        assert_eq!(game.game_status(), Some(GameStatus::Won));
+        assert_eq!(game.winner(), Some(0));
+    }
+
+    #[test]
+    fn missing_second_spawn() {
+        let level = level(".1\n..");
+        assert_eq!(
+            Game::from_level(level, 2, SeededRng::new(1)).err(),
+            Some(LevelError::MissingSpawn(1))
+        );
+        assert_eq!(
+            Game::from_level(Level::open(4, 5), 0, SeededRng::new(1)).err(),
+            Some(LevelError::NoPlayers)
+        );
+    }
+
+    #[test]
+    fn two_players_spawn_facing_each_other() {
+        let level = level(
+            "
+            .........
+            ..1...2..
+            .........
+            .........
+            .........
+            .........
+            .........
+            .........
+            .........",
+        );
+        let game = Game::from_level(level, 2, SeededRng::new(1)).unwrap();
+        assert_eq!(game.players(), 2);
+        assert_eq!(cells(&game), vec![11, 10, 9]);
+        let two: Vec<usize> = game.player_cells(1).iter().map(|c| c.0).collect();
+        assert_eq!(two, vec![15, 16, 17]);
+        // there's no third player to ask about
+        assert!(game.player_cells(2).is_empty());
+        assert_eq!(game.player_points(2), 0);
+        let mut game = game;
+        game.change_player_dir(2, Direction::Up);
+        assert_eq!(game.replay().unwrap().inputs, vec![]);
+    }
+
+    #[test]
+    fn running_into_other_snake_loses() {
+        let mut game = Game::from_level(Level::open(6, 14), 2, SeededRng::new(3)).unwrap();
+        game.snakes[0].body = [14, 13, 12].into_iter().map(SnakeCell).collect();
+        game.snakes[1].body = [3, 4, 5].into_iter().map(SnakeCell).collect();
+        game.snakes[1].direction = Direction::Left;
+        game.start_game();
+
+        // player two turns down into the path of player one's body
+        game.change_player_dir(1, Direction::Down);
+        game.step();
+        assert_eq!(game.game_status(), Some(GameStatus::Played));
+        game.step();
+        assert_eq!(game.player_cells(1)[0], SnakeCell(15));
+        assert_eq!(game.game_status(), Some(GameStatus::Won));
+        assert_eq!(game.winner(), Some(0));
+    }
+
+    #[test]
+    fn head_on_collision_is_a_draw() {
+        let mut game = Game::from_level(Level::open(6, 14), 2, SeededRng::new(3)).unwrap();
+        game.snakes[0].body = [13, 12, 11].into_iter().map(SnakeCell).collect();
+        game.snakes[1].body = [15, 16, 17].into_iter().map(SnakeCell).collect();
+        game.snakes[1].direction = Direction::Left;
+        game.start_game();
+
+        game.step();
+        assert_eq!(game.game_status(), Some(GameStatus::Draw));
+        assert_eq!(game.winner(), None);
+    }
+
+    #[test]
+    fn two_player_replay_round_trip() {
+        let mut game = Game::from_level(Level::open(9, 40), 2, SeededRng::new(11)).unwrap();
+        game.start_game();
+        for tick in 0..15 {
+            match tick {
+                2 => game.change_player_dir(1, Direction::Up),
+                4 => game.change_player_dir(0, Direction::Down),
+                6 => game.change_player_dir(1, Direction::Left),
+                _ => {}
+            }
+            game.step();
+        }
+
+        let replay: Replay = game.replay().unwrap().to_string().parse().unwrap();
+        assert_eq!(replay.players, 2);
+        let replayed = replay.run().unwrap();
+        for p in 0..2 {
+            assert_eq!(replayed.player_cells(p), game.player_cells(p));
+            assert_eq!(replayed.player_points(p), game.player_points(p));
+        }
+        assert_eq!(replayed.game_status(), game.game_status());
+    }
+}
@@ -0,0 +1,154 @@
+use std::fmt;
+use std::str::FromStr;
+
+use crate::{Direction, Game, Level, LevelError, SeededRng};
+
+/// A direction change accepted at a given tick (number of steps already taken)
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Input {
+    pub tick: u64,
+    pub player: usize,
+    pub direction: Direction,
+}
+
+/// Everything needed to re-run a game exactly: the seed, the level and the input log.
+///
+/// Text format, one record per line, with the level (see [`Level`]) last:
+/// ```text
+/// snake-replay 2
+/// seed 42
+/// players 1
+/// steps 25
+/// input 3 0 Up
+/// input 7 0 Left
+/// level
+/// name = Classic
+/// .....
+/// ...
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct Replay {
+    pub seed: u64,
+    pub players: usize,
+    pub steps: u64,
+    pub inputs: Vec<Input>,
+    pub level: Level,
+}
+
+impl Replay {
+    /// Re-plays the recorded game and returns it in its final state
+    pub fn run(&self) -> Result<Game<SeededRng>, LevelError> {
+        let mut game = Game::from_level(self.level.clone(), self.players, SeededRng::new(self.seed))?;
+        game.start_game();
+        let mut inputs = self.inputs.iter().peekable();
+        for tick in 0..self.steps {
+            while let Some(input) = inputs.next_if(|input| input.tick == tick) {
+                game.change_player_dir(input.player, input.direction);
+            }
+            game.step();
+        }
+        Ok(game)
+    }
+}
+
+const HEADER: &str = "snake-replay 2";
+
+impl fmt::Display for Replay {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{HEADER}")?;
+        writeln!(f, "seed {}", self.seed)?;
+        writeln!(f, "players {}", self.players)?;
+        writeln!(f, "steps {}", self.steps)?;
+        for input in &self.inputs {
+            writeln!(f, "input {} {} {:?}", input.tick, input.player, input.direction)?;
+        }
+        writeln!(f, "level")?;
+        write!(f, "{}", self.level)
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum ReplayError {
+    MissingHeader,
+    MissingField(&'static str),
+    InvalidLine(usize, String),
+    Level(LevelError),
+}
+
+impl fmt::Display for ReplayError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReplayError::MissingHeader => write!(f, "not a replay: expected `{HEADER}`"),
+            ReplayError::MissingField(field) => write!(f, "replay has no `{field}` line"),
+            ReplayError::InvalidLine(n, line) => write!(f, "invalid replay line {n}: `{line}`"),
+            ReplayError::Level(e) => write!(f, "invalid replay level: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for ReplayError {}
+
+impl FromStr for Replay {
+    type Err = ReplayError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut lines = s.lines().map(str::trim).enumerate().filter(|(_, l)| !l.is_empty());
+        match lines.next() {
+            Some((_, HEADER)) => {}
+            _ => return Err(ReplayError::MissingHeader),
+        }
+
+        let (mut seed, mut players, mut steps) = (None, None, None);
+        let mut inputs = vec![];
+        // `players` may come after the inputs, so they're checked against it at the end
+        let mut input_lines = vec![];
+        let mut level = None;
+        while let Some((n, line)) = lines.next() {
+            let invalid = || ReplayError::InvalidLine(n + 1, line.to_string());
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            match fields.as_slice() {
+                ["seed", v] => seed = Some(v.parse().map_err(|_| invalid())?),
+                ["players", v] => match v.parse() {
+                    Ok(0) | Err(_) => return Err(invalid()),
+                    Ok(n) => players = Some(n),
+                },
+                ["steps", v] => steps = Some(v.parse().map_err(|_| invalid())?),
+                ["input", tick, player, dir] => {
+                    let tick = tick.parse().map_err(|_| invalid())?;
+                    // `run` plays the log in order, so one going back in time
+                    // would hold up every input after it
+                    if inputs.last().is_some_and(|last: &Input| tick < last.tick) {
+                        return Err(invalid());
+                    }
+                    inputs.push(Input {
+                        tick,
+                        player: player.parse().map_err(|_| invalid())?,
+                        direction: dir.parse().map_err(|_| invalid())?,
+                    });
+                    input_lines.push((n + 1, line));
+                }
+                ["level"] => {
+                    // everything after `level` belongs to it
+                    let text: Vec<&str> = lines.by_ref().map(|(_, l)| l).collect();
+                    level = Some(text.join("\n").parse().map_err(ReplayError::Level)?);
+                }
+                _ => return Err(invalid()),
+            }
+        }
+
+        let seed = seed.ok_or(ReplayError::MissingField("seed"))?;
+        let players = players.ok_or(ReplayError::MissingField("players"))?;
+        let stranger = inputs.iter().zip(input_lines).find(|(input, _)| input.player >= players);
+        if let Some((_, (n, line))) = stranger {
+            return Err(ReplayError::InvalidLine(n, line.to_string()));
+        }
+
+        Ok(Replay {
+            seed,
+            players,
+            steps: steps.ok_or(ReplayError::MissingField("steps"))?,
+            inputs,
+            level: level.ok_or(ReplayError::MissingField("level"))?,
+        })
+    }
+}
@@ -0,0 +1,52 @@
+/// Source of randomness for the game - injectable so the core stays deterministic under test
+pub trait Rng {
+    /// returns a value in `0..max`
+    fn gen_below(&mut self, max: usize) -> usize;
+
+    /// the seed this source started from - `None` if it can't be reproduced
+    fn seed(&self) -> Option<u64> {
+        None
+    }
+}
+
+impl<R: Rng + ?Sized> Rng for Box<R> {
+    fn gen_below(&mut self, max: usize) -> usize {
+        (**self).gen_below(max)
+    }
+
+    fn seed(&self) -> Option<u64> {
+        (**self).seed()
+    }
+}
+
+/// SplitMix64 - tiny, fast and good enough for placing rewards.
+/// The same seed always yields the same sequence, on every platform.
+#[derive(Clone, Debug)]
+pub struct SeededRng {
+    seed: u64,
+    state: u64,
+}
+
+impl SeededRng {
+    pub fn new(seed: u64) -> SeededRng {
+        SeededRng { seed, state: seed }
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+}
+
+impl Rng for SeededRng {
+    fn gen_below(&mut self, max: usize) -> usize {
+        (self.next_u64() % max as u64) as usize
+    }
+
+    fn seed(&self) -> Option<u64> {
+        Some(self.seed)
+    }
+}
@@ -0,0 +1,130 @@
+use std::fmt;
+use std::str::FromStr;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct HighScore {
+    pub name: String,
+    pub points: usize,
+    /// one based level the game ended on
+    pub level: usize,
+}
+
+/// Best scores first, capped at `capacity` entries.
+/// Stored as one `points<TAB>level<TAB>name` line per entry.
+#[derive(Clone, Debug, PartialEq)]
+pub struct HighScores {
+    entries: Vec<HighScore>,
+    capacity: usize,
+}
+
+pub const HIGH_SCORE_CAPACITY: usize = 10;
+
+impl Default for HighScores {
+    fn default() -> Self {
+        HighScores {
+            entries: vec![],
+            capacity: HIGH_SCORE_CAPACITY,
+        }
+    }
+}
+
+impl HighScores {
+    pub fn entries(&self) -> &[HighScore] {
+        &self.entries
+    }
+
+    /// would `points` make it onto the table?
+    pub fn qualifies(&self, points: usize) -> bool {
+        points > 0
+            && (self.entries.len() < self.capacity
+                || self.entries.last().is_some_and(|last| points > last.points))
+    }
+
+    /// Adds the score if it qualifies and returns its zero based rank.
+    /// Ties keep the older entry in front.
+    pub fn submit(&mut self, name: &str, points: usize, level: usize) -> Option<usize> {
+        if !self.qualifies(points) {
+            return None;
+        }
+        let rank = self.entries.partition_point(|e| e.points >= points);
+        self.entries.insert(
+            rank,
+            HighScore {
+                name: name.trim().to_string(),
+                points,
+                level,
+            },
+        );
+        self.entries.truncate(self.capacity);
+        Some(rank)
+    }
+}
+
+impl fmt::Display for HighScores {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for e in &self.entries {
+            writeln!(f, "{}\t{}\t{}", e.points, e.level, e.name)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for HighScores {
+    type Err = std::convert::Infallible;
+
+    /// Lenient - storage can be edited by hand, so malformed lines are dropped
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut scores = HighScores::default();
+        for line in s.lines() {
+            let mut fields = line.splitn(3, '\t');
+            let (Some(points), Some(level), Some(name)) = (fields.next(), fields.next(), fields.next()) else {
+                continue;
+            };
+            if let (Ok(points), Ok(level)) = (points.parse(), level.parse()) {
+                scores.submit(name, points, level);
+            }
+        }
+        Ok(scores)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_best_scores_in_order() {
+        let mut scores = HighScores::default();
+        assert_eq!(scores.submit("ann", 5, 1), Some(0));
+        assert_eq!(scores.submit("bob", 9, 2), Some(0));
+        assert_eq!(scores.submit("cid", 5, 1), Some(2));
+        assert_eq!(scores.submit("nil", 0, 1), None);
+
+        let names: Vec<&str> = scores.entries().iter().map(|e| e.name.as_str()).collect();
+        assert_eq!(names, vec!["bob", "ann", "cid"]);
+    }
+
+    #[test]
+    fn capped_at_capacity() {
+        let mut scores = HighScores::default();
+        for points in 1..=HIGH_SCORE_CAPACITY + 2 {
+            scores.submit("p", points, 1);
+        }
+        assert_eq!(scores.entries().len(), HIGH_SCORE_CAPACITY);
+        assert!(!scores.qualifies(3));
+        assert_eq!(scores.submit("low", 2, 1), None);
+        assert_eq!(scores.entries().last().unwrap().points, 3);
+    }
+
+    #[test]
+    fn storage_round_trip_skips_garbage() {
+        let mut scores = HighScores::default();
+        scores.submit("ann lee", 7, 3);
+        scores.submit("bob", 4, 1);
+        let stored = scores.to_string();
+        assert_eq!(stored, "7\t3\tann lee\n4\t1\tbob\n");
+
+        let loaded: HighScores = format!("{stored}garbage\nx\t1\tcid\n").parse().unwrap();
+        assert_eq!(loaded, scores);
+    }
+}
@@ -20,6 +20,23 @@ extern {
     fn rnd(max: usize) -> usize;
 }
 
+#[wasm_bindgen(module = "/www/utils/storage.js")]
+extern {
+    fn load_high_scores() -> String;
+    fn save_high_scores(scores: &str);
+}
+
+// the game logic lives in snake_core - this crate only binds it to JS
+use snake_core::{parse_levels, Campaign, Game, HighScores, Level, LevelError, SeededRng};
+
+/// Randomness from the browser's Math.random
+struct JsRng;
+
+impl snake_core::Rng for JsRng {
+    fn gen_below(&mut self, max: usize) -> usize {
+        rnd(max)
+    }
+}
 
 #[wasm_bindgen]
 #[derive(Clone, Copy)]
@@ -27,6 +44,18 @@ pub enum GameStatus {
     Won,
     Lost,
     Played,
+    Draw,
+}
+
+impl From<snake_core::GameStatus> for GameStatus {
+    fn from(status: snake_core::GameStatus) -> Self {
+        match status {
+            snake_core::GameStatus::Won => GameStatus::Won,
+            snake_core::GameStatus::Lost => GameStatus::Lost,
+            snake_core::GameStatus::Played => GameStatus::Played,
+            snake_core::GameStatus::Draw => GameStatus::Draw,
+        }
+    }
 }
 
 #[wasm_bindgen]
@@ -38,238 +67,229 @@ pub enum Direction {
     Left
 }
 
-#[derive(Clone, Copy, PartialEq)]
-pub struct SnakeCell(usize);
-
-struct Snake {
-    body: Vec<SnakeCell>,
-    direction: Direction,
-}
-
-impl Snake {
-    fn new(spawn_index: usize, size: usize) -> Snake {
-        let mut body = vec!();
-
-        for i in 0..size {
-            body.push(SnakeCell(spawn_index - i));
-        }
-
-        Snake {
-            body,
-            direction: Direction::Right,
+impl From<Direction> for snake_core::Direction {
+    fn from(direction: Direction) -> Self {
+        match direction {
+            Direction::Up => snake_core::Direction::Up,
+            Direction::Right => snake_core::Direction::Right,
+            Direction::Down => snake_core::Direction::Down,
+            Direction::Left => snake_core::Direction::Left,
         }
     }
 }
 
+#[wasm_bindgen(getter_with_clone)]
+pub struct HighScore {
+    pub name: String,
+    pub points: usize,
+    pub level: usize,
+}
 
 #[wasm_bindgen]
 pub struct World {
-    width: usize,
-    size: usize,
-    snake: Snake,
-    next_cell: Option<SnakeCell>,
-    reward_cell: Option<usize>,
-    status: Option<GameStatus>,
-    points: usize,
+    game: Game<Box<dyn snake_core::Rng>>,
+    campaign: Campaign,
+    players: usize,
+    seed: Option<u64>,
+    // points scored on levels already cleared
+    banked_points: usize,
 }
 
+impl World {
+    fn build(campaign: Campaign, players: usize, seed: Option<u64>) -> Result<World, LevelError> {
+        let game = World::new_game(campaign.level(), players, seed, campaign.index())?;
+        Ok(World {
+            game,
+            campaign,
+            players,
+            seed,
+            banked_points: 0,
+        })
+    }
+
+    fn new_game(
+        level: &Level,
+        players: usize,
+        seed: Option<u64>,
+        level_idx: usize,
+    ) -> Result<Game<Box<dyn snake_core::Rng>>, LevelError> {
+        // every level of a seeded campaign gets its own, still reproducible, seed
+        let rng: Box<dyn snake_core::Rng> = match seed {
+            Some(seed) => Box::new(SeededRng::new(seed.wrapping_add(level_idx as u64))),
+            None => Box::new(JsRng),
+        };
+        Game::from_level(level.clone(), players, rng)
+    }
+}
 
 #[wasm_bindgen]
 impl World {
+    /// single player on the classic open board
     pub fn new(width: usize, snake_idx: usize) -> World {
-        let snake = Snake::new(snake_idx, 3);
-        let size = width * width;
-       
-        World {
-            width,
-            size,
-            reward_cell: World::gen_reward_cell(size, &snake.body),
-            snake,
-            next_cell: None,
-            status: None,
-            points: 0,
-        }
+        let campaign = Campaign::new(vec![Level::open(width, snake_idx)]);
+        World::build(campaign, 1, None).expect("open levels are always playable")
     }
 
-    pub fn points(&self) -> usize {
-        self.points
+    /// A reproducible game - the same seed and key presses always play out the same way
+    pub fn with_seed(width: usize, snake_idx: usize, seed: u64) -> World {
+        let campaign = Campaign::new(vec![Level::open(width, snake_idx)]);
+        World::build(campaign, 1, Some(seed)).expect("open levels are always playable")
     }
 
-    fn gen_reward_cell(max: usize, snake_body: &Vec<SnakeCell>) -> Option<usize> {
-        let mut reward_cell;
+    /// A campaign of levels in the snake_core level text format, separated by `---` lines
+    pub fn with_levels(levels: &str, players: usize, seed: Option<u64>) -> Result<World, JsError> {
+        if players == 0 {
+            return Err(JsError::new("a game needs at least one player"));
+        }
+        let levels = parse_levels(levels)?;
+        if levels.is_empty() {
+            return Err(JsError::new("no levels found"));
+        }
+        Ok(World::build(Campaign::new(levels), players, seed)?)
+    }
 
-        loop {
-          reward_cell = rnd(max);
-          if !snake_body.contains(&SnakeCell(reward_cell)) { break; }
+    /// Moves on once the current level is won - `false` if there is no next level
+    pub fn next_level(&mut self) -> Result<bool, JsError> {
+        if self.game.game_status() != Some(snake_core::GameStatus::Won) || !self.campaign.advance() {
+            return Ok(false);
         }
+        self.banked_points += self.game.points();
+        self.game = World::new_game(self.campaign.level(), self.players, self.seed, self.campaign.index())?;
+        Ok(true)
+    }
 
-        Some(reward_cell)
+    pub fn level_name(&self) -> String {
+        self.campaign.level().name.clone()
     }
 
-    pub fn width(&self) -> usize {
-        self.width
+    /// one based, for display
+    pub fn level_number(&self) -> usize {
+        self.campaign.index() + 1
     }
 
-    pub fn reward_cell(&self) -> Option<usize> {
-        self.reward_cell
+    /// frames per second - rises with every level
+    pub fn fps(&self) -> u32 {
+        self.campaign.fps()
     }
 
-    pub fn snake_head_idx(&self) -> usize {
-        self.snake.body[0].0
+    pub fn players(&self) -> usize {
+        self.players
     }
 
-    pub fn game_status(&self) -> Option<GameStatus> {
-        self.status
+    /// player one's points across the whole campaign
+    pub fn points(&self) -> usize {
+        self.banked_points + self.game.points()
     }
 
-    pub fn game_status_text(&self) -> String {
-        match self.status {
-            Some(GameStatus::Won) => String::from("You have won!"),
-            Some(GameStatus::Lost) => String::from("You have lost!"),
-            Some(GameStatus::Played) => String::from("Playing"),
-            None => String::from("No Status"),
-        }
+    /// points on the current level
+    pub fn player_points(&self, player: usize) -> usize {
+        self.game.player_points(player)
     }
 
-    pub fn change_snake_dir(&mut self, direction: Direction) {
-        let next_cell = self.gen_next_snake_cell(&direction);
-        if self.snake.body[1].0 == next_cell.0 { return; }
-        self.next_cell = Some(next_cell);
-        self.snake.direction = direction;
+    pub fn width(&self) -> usize {
+        self.game.width()
     }
 
-    pub fn snake_length(&self) -> usize {
-        self.snake.body.len()
+    pub fn reward_cell(&self) -> Option<usize> {
+        self.game.reward_cell()
     }
 
-    // cannot return a reference to JS because of borring rules
-    // pub fn snake_cells(&self) -> &Vec<SnakeCell> {
-    //     &self.snake.body
-    // }
+    pub fn walls(&self) -> Vec<usize> {
+        self.game.level().walls.clone()
+    }
 
-    // *const is raw pointer
-    // borrowing rules doesn't apply to it
-    pub fn snake_cells(&self) -> *const SnakeCell {
-        self.snake.body.as_ptr()
+    /// portal pairs, flattened: `[a0, b0, a1, b1, ...]`
+    pub fn portals(&self) -> Vec<usize> {
+        self.game.level().portals.iter().flat_map(|&(a, b)| [a, b]).collect()
     }
 
+    pub fn snake_head_idx(&self) -> usize {
+        self.game.snake_head_idx()
+    }
 
+    pub fn game_status(&self) -> Option<GameStatus> {
+        self.game.game_status().map(GameStatus::from)
+    }
 
-    // pub fn update(&mut self) {
-    //     let snake_idx = self.snake_head_idx();
+    pub fn winner(&self) -> Option<usize> {
+        self.game.winner()
+    }
 
-    //     let (row, col) = self.index_to_cell(snake_idx);
-    //     let (row, col) = match self.snake.direction {
-    //         Direction::Right => {
-    //             (row, (col + 1) % self.width)
-    //         },
-    //         Direction::Left => {
-    //             (row, (col - 1) % self.width)
-    //         },
-    //         Direction::Up => {
-    //             ((row - 1) % self.width, col)
-    //         },
-    //         Direction::Down => {
-    //             ((row +1) % self.width, col)
-    //         },
-    //     };
+    pub fn game_status_text(&self) -> String {
+        match (self.game_status(), self.players) {
+            (Some(GameStatus::Won), 1) => String::from("You have won!"),
+            (Some(GameStatus::Won), _) => match self.winner() {
+                Some(player) => format!("Player {} has won!", player + 1),
+                None => String::from("Game over"),
+            },
+            (Some(GameStatus::Lost), _) => String::from("You have lost!"),
+            (Some(GameStatus::Draw), _) => String::from("It's a draw!"),
+            (Some(GameStatus::Played), _) => String::from("Playing"),
+            (None, _) => String::from("No Status"),
+        }
+    }
 
-    //     let next_idx = self.cell_to_index(row, col);
-    //     self.set_snake_head(next_idx);
-    // }
+    pub fn change_snake_dir(&mut self, direction: Direction) {
+        self.game.change_snake_dir(direction.into());
+    }
 
-    // fn set_snake_head(&mut self, idx: usize) {
-    //     self.snake.body[0].0 = idx;
-    // }
+    pub fn change_player_dir(&mut self, player: usize, direction: Direction) {
+        if player < self.players {
+            self.game.change_player_dir(player, direction.into());
+        }
+    }
 
-    // fn index_to_cell(&self, idx: usize) -> (usize, usize) {
-    //     (idx / self.width, idx % self.width)
-    // }
+    pub fn snake_length(&self) -> usize {
+        self.game.snake_length()
+    }
 
-    // fn cell_to_index(&self, row: usize, col: usize) -> usize {
-    //     (row * self.width) + col
-    // }
+    /// A copy of the player's body, head first - arrives in JS as a `Uint32Array`
+    /// so nothing can read stale wasm memory once the snake grows
+    pub fn snake_cells(&self, player: usize) -> Vec<usize> {
+        self.game.player_cells(player).iter().map(|cell| cell.0).collect()
+    }
 
-    pub fn step(&mut self) {
-        match self.status {
-            Some(GameStatus::Played) => {
-                let temp = self.snake.body.clone();
-        
-                match self.next_cell {
-                    Some(cell) => {
-                        self.snake.body[0] = cell;
-                        self.next_cell = None;
-                    },
-                    None => {
-                        self.snake.body[0] = self.gen_next_snake_cell(&self.snake.direction);
-                    }
-                }
-        
-                for i in 1..self.snake_length() {
-                    self.snake.body[i] = SnakeCell(temp[i - 1].0);
-                }
-
-                if self.snake.body[1..self.snake_length()].contains(&self.snake.body[0]) {
-                    self.status = Some(GameStatus::Lost)
-                }
-        
-                if self.reward_cell == Some(self.snake_head_idx()) {
-                    if self.snake_length() < self.size {
-                        self.points += 1;
-                        self.reward_cell = World::gen_reward_cell(self.size, &self.snake.body);
-                    } else {
-                        self.reward_cell = None;
-                        self.status = Some(GameStatus::Won)
-                    }
-                    self.snake.body.push(SnakeCell(self.snake.body[1].0));
-                }
+    /// seed + input log of a seeded game, in the snake_core replay text format
+    pub fn replay_log(&self) -> Option<String> {
+        self.game.replay().map(|replay| replay.to_string())
+    }
 
-            },
-            _ => {}
-        }
+    pub fn step(&mut self) {
+        self.game.step();
     }
 
     pub fn start_game(&mut self) {
-        self.status = Some(GameStatus::Played);
+        self.game.start_game();
     }
 
-    fn gen_next_snake_cell(&self, direction: &Direction) -> SnakeCell {
-        let snake_idx = self.snake_head_idx();
-        let row = snake_idx / self.width;
+    /// the table kept in localStorage, best first
+    pub fn high_scores() -> Vec<HighScore> {
+        World::load_scores()
+            .entries()
+            .iter()
+            .map(|e| HighScore {
+                name: e.name.clone(),
+                points: e.points,
+                level: e.level,
+            })
+            .collect()
+    }
 
-        return match direction {
-            Direction::Right => {
-                let threshold = (row + 1) * self.width;
-                if snake_idx + 1 == threshold {
-                    SnakeCell(threshold - self.width)
-                } else {
-                    SnakeCell(snake_idx + 1)
-                }
-            },
-            Direction::Left => {
-                let threshold = row * self.width;
-                if snake_idx == threshold {
-                    SnakeCell(threshold + (self.width - 1))
-                } else {
-                    SnakeCell(snake_idx - 1)
-                }
-            },
-            Direction::Up => {
-                let threshold = snake_idx - (row * self.width);
-                if snake_idx == threshold {
-                    SnakeCell((self.size - self.width) + threshold)
-                } else {
-                    SnakeCell(snake_idx - self.width)
-                }
-            },
-            Direction::Down => {
-                let threshold = snake_idx + ((self.width - row) * self.width);
-                if snake_idx + self.width == threshold {
-                    SnakeCell(threshold - ((row + 1) * self.width))
-                } else {
-                    SnakeCell(snake_idx + self.width)
-                }
-            },
-        };
+    pub fn qualifies_for_high_score(&self) -> bool {
+        World::load_scores().qualifies(self.points())
+    }
+
+    /// Records player one's campaign points - returns the zero based rank if they made the table
+    pub fn submit_high_score(&self, name: &str) -> Option<usize> {
+        let mut scores = World::load_scores();
+        let rank = scores.submit(name, self.points(), self.level_number())?;
+        save_high_scores(&scores.to_string());
+        Some(rank)
+    }
+}
+
+impl World {
+    fn load_scores() -> HighScores {
+        load_high_scores().parse().unwrap_or_default()
     }
 }
@@ -1,144 +1,190 @@
 import init, { World, Direction, GameStatus } from "snake_game";
-import { rnd } from "./utils/rnd";
 
-init().then(wasm => {
+init().then(_ => {
   const CELL_SIZE = 50;
-  const WORLD_WIDTH = 5;
-  const snakeSpawnIdx = rnd(WORLD_WIDTH * WORLD_WIDTH);
-
-  const world = World.new(WORLD_WIDTH, snakeSpawnIdx);
-  const worldWidth = world.width();
+  // ?players=2 for local two player mode: arrows for player 1, WASD for player 2
+  const PLAYERS = new URLSearchParams(location.search).get("players") === "2" ? 2 : 1;
+  const SNAKE_COLORS = [["#7878db", "#000000"], ["#db7878", "#555555"]];
 
   const points = document.getElementById("points");
+  const level = document.getElementById("level");
   const gameStatus = document.getElementById("game-status");
   const gameControlBtn = document.getElementById("game-control-btn");
+  const highScores = document.getElementById("high-scores");
   const canvas = <HTMLCanvasElement> document.getElementById("snake-canvas");
   const ctx = canvas.getContext("2d");
 
-  canvas.height = worldWidth * CELL_SIZE;
-  canvas.width = worldWidth * CELL_SIZE;
+  fetch("levels.txt")
+    .then(response => response.text())
+    .then(levels => start(World.with_levels(levels, PLAYERS)));
+
+  function start(world: World) {
+    const resize = () => {
+      canvas.height = world.width() * CELL_SIZE;
+      canvas.width = world.width() * CELL_SIZE;
+    };
+    resize();
+
+    gameControlBtn.addEventListener("click", _ => {
+      const status = world.game_status();
+
+      if (status === undefined) {
+        gameControlBtn.textContent = "Playing..."
+        world.start_game();
+        play();
+      } else if (status == GameStatus.Won && world.next_level()) {
+        resize();
+        gameControlBtn.textContent = "Playing..."
+        world.start_game();
+        play();
+      } else {
+        if (status == GameStatus.Won) {
+          // cleared the last level
+          recordHighScore();
+        }
+        location.reload();
+      }
+    })
+
+    const keys: { [code: string]: [number, Direction] } = {
+      ArrowUp: [0, Direction.Up],
+      ArrowRight: [0, Direction.Right],
+      ArrowDown: [0, Direction.Down],
+      ArrowLeft: [0, Direction.Left],
+      KeyW: [1, Direction.Up],
+      KeyD: [1, Direction.Right],
+      KeyS: [1, Direction.Down],
+      KeyA: [1, Direction.Left],
+    };
+
+    document.addEventListener("keydown", e => {
+      const key = keys[e.code];
+      if (key !== undefined) {
+        world.change_player_dir(key[0], key[1]);
+      }
+    })
 
-  gameControlBtn.addEventListener("click", _ => {
-    const status = world.game_status();
+    function drawWorld() {
+      const worldWidth = world.width();
+      ctx.beginPath();
 
-    if (status === undefined) {
-      gameControlBtn.textContent = "Playing..."
-      world.start_game();
-      play();
-    } else  {
-      location.reload();
-    }
-  })
-  
-  document.addEventListener("keydown", e => {
-    switch(e.code) {
-      case "ArrowUp":
-        world.change_snake_dir(Direction.Up);
-        break;
-      case "ArrowRight":
-        world.change_snake_dir(Direction.Right);
-        break;
-      case "ArrowDown":
-        world.change_snake_dir(Direction.Down);
-        break;
-      case "ArrowLeft":
-        world.change_snake_dir(Direction.Left);        
-        break;
-    }
-  })
+      for (let x = 0; x < worldWidth + 1; x++) {
+        ctx.moveTo(CELL_SIZE * x, 0);
+        ctx.lineTo(CELL_SIZE * x, worldWidth * CELL_SIZE)
+      }
 
-  function drawWorld() {
-    ctx.beginPath();
+      for (let y = 0; y < worldWidth + 1; y++) {
+        ctx.moveTo(0, CELL_SIZE * y);
+        ctx.lineTo(worldWidth * CELL_SIZE, CELL_SIZE * y)
+      }
 
-    for (let x = 0; x < worldWidth + 1; x++) {
-      ctx.moveTo(CELL_SIZE * x, 0);
-      ctx.lineTo(CELL_SIZE * x, worldWidth * CELL_SIZE)
+      ctx.stroke();
     }
 
-    for (let y = 0; y < worldWidth + 1; y++) {
-      ctx.moveTo(0, CELL_SIZE * y);
-      ctx.lineTo(worldWidth * CELL_SIZE, CELL_SIZE * y)
+    function fillCell(idx: number, color: string) {
+      const worldWidth = world.width();
+      const col = idx % worldWidth;
+      const row = Math.floor(idx / worldWidth);
+
+      ctx.fillStyle = color;
+      ctx.beginPath();
+      ctx.fillRect(
+        col * CELL_SIZE,
+        row * CELL_SIZE,
+        CELL_SIZE,
+        CELL_SIZE
+      );
+      ctx.stroke();
     }
 
-    ctx.stroke();
-  }
+    function drawLevel() {
+      world.walls().forEach(idx => fillCell(idx, "#8b5a2b"));
+      world.portals().forEach(idx => fillCell(idx, "#9b30ff"));
+    }
 
-  function drawReward() {
-    const idx = world.reward_cell();
-    const col = idx % worldWidth;
-    const row = Math.floor(idx / worldWidth);
-
-    ctx.beginPath();
-    ctx.fillStyle = "#FF0000";
-    ctx.fillRect(
-      col * CELL_SIZE,
-      row * CELL_SIZE,
-      CELL_SIZE,
-      CELL_SIZE
-    );
-    ctx.stroke();
-  }
+    function drawReward() {
+      const idx = world.reward_cell();
+      if (idx !== undefined) {
+        fillCell(idx, "#FF0000");
+      }
+    }
 
-  function drawSnake() {
-    const snakeCells = new Uint32Array(
-      wasm.memory.buffer,
-      world.snake_cells(),
-      world.snake_length()
-    )
-
-    snakeCells
-      .slice()
-      .reverse()
-      .forEach((cellIdx, i) => {
-        const col = cellIdx % worldWidth;
-        const row = Math.floor(cellIdx / worldWidth);
-
-        ctx.fillStyle = i === snakeCells.length - 1 ? "#7878db" : "#000000";
-        ctx.beginPath();
-        ctx.fillRect(
-          col * CELL_SIZE,
-          row * CELL_SIZE,
-          CELL_SIZE,
-          CELL_SIZE
-        );
-    })
-    ctx.stroke();
-  }
+    function drawSnakes() {
+      for (let player = 0; player < world.players(); player++) {
+        const [headColor, bodyColor] = SNAKE_COLORS[player];
+        const snakeCells = world.snake_cells(player);
+
+        snakeCells
+          .slice()
+          .reverse()
+          .forEach((cellIdx, i) => {
+            fillCell(cellIdx, i === snakeCells.length - 1 ? headColor : bodyColor);
+          });
+      }
+    }
 
-  function drawGameStatus() {
-    const status = world.game_status();
-    gameStatus.textContent = world.game_status_text();
-    points.textContent = world.points().toString();
+    function drawGameStatus() {
+      const status = world.game_status();
+      gameStatus.textContent = world.game_status_text();
+      level.textContent = `${world.level_number()} - ${world.level_name()}`;
+      points.textContent = PLAYERS === 1
+        ? world.points().toString()
+        : `${world.player_points(0)} : ${world.player_points(1)}`;
+
+      if (status == GameStatus.Won) {
+        gameControlBtn.textContent = "Next Level";
+      } else if (status == GameStatus.Lost || status == GameStatus.Draw) {
+        gameControlBtn.textContent = "Re-Play";
+      }
+    }
 
-    if (status == GameStatus.Won || status == GameStatus.Lost) {
-      gameControlBtn.textContent = "Re-Play";
+    function drawHighScores() {
+      highScores.innerHTML = "";
+      World.high_scores().forEach(score => {
+        const entry = document.createElement("li");
+        entry.textContent = `${score.points} - ${score.name} (level ${score.level})`;
+        highScores.appendChild(entry);
+      });
     }
-  }
-  
-  function paint() {
-    drawWorld();
-    drawSnake();
-    drawReward();
-    drawGameStatus();
-  }
 
-  function play() {
-    const status = world.game_status();
-    if (status == GameStatus.Won || status == GameStatus.Lost) {
-      gameControlBtn.textContent = "Re-Play";
-      return;
+    function recordHighScore() {
+      if (PLAYERS === 1 && world.qualifies_for_high_score()) {
+        const name = prompt("New high score! Your name:");
+        if (name) {
+          world.submit_high_score(name);
+          drawHighScores();
+        }
+      }
     }
-    const fps = 3;
-    setTimeout(() => {
-      console.log("Playing!");
-      ctx.clearRect(0, 0, canvas.width, canvas.height);
-      world.step();
-      paint();
-      // the method takes a callback to invoked before the next repaint
-      requestAnimationFrame(play)
-    }, 1000 / fps)
-  }
 
-  paint();
+    function paint() {
+      drawWorld();
+      drawLevel();
+      drawSnakes();
+      drawReward();
+      drawGameStatus();
+    }
 
+    function play() {
+      const status = world.game_status();
+      if (status == GameStatus.Lost || status == GameStatus.Draw) {
+        recordHighScore();
+        return;
+      }
+      if (status == GameStatus.Won) {
+        return;
+      }
+      setTimeout(() => {
+        console.log("Playing!");
+        ctx.clearRect(0, 0, canvas.width, canvas.height);
+        world.step();
+        paint();
+        // the method takes a callback to invoked before the next repaint
+        requestAnimationFrame(play)
+      }, 1000 / world.fps())
+    }
+
+    paint();
+    drawHighScores();
+  }
 });
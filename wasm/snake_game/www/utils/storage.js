@@ -0,0 +1,9 @@
+const HIGH_SCORES_KEY = "snake_game.high_scores";
+
+export function load_high_scores() {
+    return localStorage.getItem(HIGH_SCORES_KEY) || "";
+}
+
+export function save_high_scores(scores) {
+    localStorage.setItem(HIGH_SCORES_KEY, scores);
+}
@@ -23,7 +23,8 @@ module.exports = {
   plugins: [
     new CopyWebpackPlugin({
       patterns: [
-        { from: "./index.html", to: "./"}
+        { from: "./index.html", to: "./"},
+        { from: "./levels.txt", to: "./"}
       ]
     })
   ]